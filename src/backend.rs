@@ -0,0 +1,256 @@
+use std::future::Future;
+use std::ops::Range;
+use std::sync::Arc;
+
+use anyhow::{anyhow, ensure, Result};
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use futures_util::future::try_join;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use tracing::{info, warn};
+
+use crate::sse::SseCustomer;
+use crate::{fetch_range, head, plan, read_buffer_size, validate_plan, EditError, ModifyStats, ObjectStat, Part, PartOp, RangeKind, Session, Source, Upload, DEFAULT_CONCURRENCY};
+
+/// The object store requests an edit is made of: a multipart upload whose parts are either
+/// copied server-side from a range of the original object or uploaded.
+///
+/// [`modify_with_backend`] plans and runs edits on top of these alone, so a store with the
+/// same server-side block copy, e.g. the GCS XML API or Azure block blobs, only needs these
+/// implemented to reuse the planning. [`Session`] implements it with `aws_sdk_s3`.
+pub trait ObjectStoreBackend: Send + Sync {
+    /// Length and ETag of `key`, `None` if it doesn't exist.
+    fn head(&self, key: &str) -> impl Future<Output = Result<Option<ObjectStat>>> + Send;
+
+    /// The bytes of `range` of `key`, which must be within the object.
+    fn read_range(&self, key: &str, range: Range<i64>) -> impl Future<Output = Result<Bytes>> + Send;
+
+    /// Start an upload replacing `key`, returning its id. The new object should keep the
+    /// headers of the one it replaces, as far as the store has them.
+    fn create_upload(&self, key: &str) -> impl Future<Output = Result<String>> + Send;
+
+    /// Make part `part_num` of the upload a server-side copy of `range` of `key` as it is now,
+    /// returning the part's ETag. With `etag` the copy fails with [`EditError::Conflict`]
+    /// unless `key` still has that ETag.
+    fn copy_part(&self, key: &str, upload_id: &str, part_num: i32, range: Range<i64>, etag: Option<&str>) -> impl Future<Output = Result<String>> + Send;
+
+    /// Upload `data` as part `part_num` of the upload, returning the part's ETag.
+    fn upload_part(&self, key: &str, upload_id: &str, part_num: i32, data: Bytes) -> impl Future<Output = Result<String>> + Send;
+
+    /// Replace `key` with the `(part number, ETag)` parts, returning the new object's ETag.
+    fn complete(&self, key: &str, upload_id: &str, parts: Vec<(i32, String)>) -> impl Future<Output = Result<Option<String>>> + Send;
+
+    /// Drop the upload and its parts, leaving `key` as it was.
+    fn abort(&self, key: &str, upload_id: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Most part copies to run at once.
+    fn max_concurrency(&self) -> usize {
+        DEFAULT_CONCURRENCY
+    }
+}
+
+impl ObjectStoreBackend for Session {
+    async fn head(&self, key: &str) -> Result<Option<ObjectStat>> {
+        head(&self.client, &self.config, key).await
+    }
+
+    async fn read_range(&self, key: &str, range: Range<i64>) -> Result<Bytes> {
+        let data = fetch_range(
+            self.client.clone(),
+            self.config.bucket.clone(),
+            key.to_string(),
+            SseCustomer::new(&self.config),
+            read_buffer_size(&self.config),
+            range.start as u64,
+            range.end as u64,
+        ).await?;
+        Ok(Bytes::from(data))
+    }
+
+    /// Starts a multipart upload as the session's own edits do, in the headers, storage class
+    /// and encryption of `key` and with `checksum_algorithm`, tracked by the session until
+    /// completed or aborted.
+    async fn create_upload(&self, key: &str) -> Result<String> {
+        let obj = self.head_source(key.into()).await?;
+        let create = self.rewrite_headers(key, Some(&obj))?.create_request(&self.client);
+
+        let upload = Upload::begin(self, key, create).await?;
+        let upload_id = upload.upload_id().to_string();
+        self.backend_uploads.lock().unwrap().insert(upload_id.clone(), Arc::new(upload));
+        Ok(upload_id)
+    }
+
+    async fn copy_part(&self, key: &str, upload_id: &str, part_num: i32, range: Range<i64>, etag: Option<&str>) -> Result<String> {
+        let upload = self.backend_upload(key, upload_id)?;
+        let src = Source { etag, ..Source::from(key) };
+        upload.copy_part(part_num, src, range.start, range.end - range.start).await
+    }
+
+    async fn upload_part(&self, key: &str, upload_id: &str, part_num: i32, data: Bytes) -> Result<String> {
+        let upload = self.backend_upload(key, upload_id)?;
+        upload.upload_part(part_num, ByteStream::from(data)).await
+    }
+
+    async fn complete(&self, key: &str, upload_id: &str, parts: Vec<(i32, String)>) -> Result<Option<String>> {
+        let mut upload = self.take_backend_upload(key, upload_id)?;
+        upload.add_parts(parts);
+        Ok(upload.finish(None).await?.etag)
+    }
+
+    /// Aborts the upload unless its completion failed, which settled it already.
+    async fn abort(&self, key: &str, upload_id: &str) -> Result<()> {
+        match self.take_backend_upload(key, upload_id) {
+            Ok(upload) => upload.abort().await,
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn max_concurrency(&self) -> usize {
+        self.config.max_concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1)
+    }
+}
+
+impl Session {
+    /// The upload `upload_id` of `key` started by [`ObjectStoreBackend::create_upload`].
+    fn backend_upload(&self, key: &str, upload_id: &str) -> Result<Arc<Upload>> {
+        self.backend_uploads.lock()
+            .unwrap()
+            .get(upload_id)
+            .filter(|upload| upload.key() == key)
+            .cloned()
+            .ok_or_else(|| anyhow!("{} has no upload {}", key, upload_id))
+    }
+
+    /// [`Session::backend_upload`], no longer tracked, once no part is in flight.
+    fn take_backend_upload(&self, key: &str, upload_id: &str) -> Result<Upload> {
+        let mut uploads = self.backend_uploads.lock().unwrap();
+        ensure!(uploads.get(upload_id).is_some_and(|upload| upload.key() == key), "{} has no upload {}", key, upload_id);
+
+        let upload = uploads.remove(upload_id).unwrap();
+        Arc::try_unwrap(upload).map_err(|upload| {
+            uploads.insert(upload_id.to_string(), upload);
+            anyhow!("{} upload {} has parts in flight", key, upload_id)
+        })
+    }
+}
+
+/// Apply `parts` to `key` through `backend`, as [`Session::modify_many`] does through S3:
+/// laid out with [`plan`] in parts of about `part_size` bytes, the unedited ranges copied
+/// server-side and the edited ones uploaded, padded with the original bytes around them.
+/// Copies run up to [`ObjectStoreBackend::max_concurrency`] at once and require the ETag the
+/// object had when planned; uploads, which hold a part in memory each, go one after another
+/// next to them. The upload is aborted if any part fails.
+///
+/// Only the offsets and data of `parts` are used, not the expectations of e.g.
+/// [`Part::with_expected_object_len`]. A [`Session`] backend writes the object as its own
+/// edits do, in the headers of the original and with its checksum and retry settings.
+pub async fn modify_with_backend<B: ObjectStoreBackend>(backend: &B, key: &str, parts: Vec<Part>, part_size: i64) -> Result<ModifyStats> {
+    let stat = backend.head(key)
+        .await?
+        .ok_or_else(|| anyhow::Error::new(EditError::ObjectNotFound { key: key.to_string() }))?;
+    let obj_len = stat.len;
+
+    let mut edits = parts.into_iter()
+        .filter_map(|part| part.data.map(|data| (part.index..part.index + data.len() as i64, data)))
+        .filter(|(range, _)| !range.is_empty())
+        .collect::<Vec<_>>();
+    edits.sort_by_key(|(range, _)| range.start);
+
+    for pair in edits.windows(2) {
+        let (a, b) = (&pair[0].0, &pair[1].0);
+        ensure!(a.end <= b.start, "{} edit {}-{} overlaps edit {}-{}", key, a.start, a.end, b.start, b.end);
+    }
+
+    let mut new_len = obj_len;
+
+    if let (Some((first, _)), Some((last, _))) = (edits.first(), edits.last()) {
+        ensure!(first.start >= 0, "{} edit starts at negative offset {}", key, first.start);
+        ensure!(last.start <= obj_len, EditError::RangeOutOfBounds { key: key.to_string(), start: last.start, end: last.end, len: obj_len });
+        new_len = std::cmp::max(obj_len, last.end);
+    }
+
+    let ranges = edits.iter().map(|(range, _)| range.clone()).collect::<Vec<_>>();
+    let ops = plan(new_len, &ranges, part_size);
+    validate_plan(&ops, new_len)?;
+
+    let upload_id = backend.create_upload(key).await?;
+    info!(key, upload_id, parts = ops.len(), "backend upload");
+
+    let mut stats = ModifyStats { upload_id: Some(upload_id.clone()), ..ModifyStats::default() };
+
+    for op in &ops {
+        match op {
+            PartOp::Upload(range) => {
+                stats.ranges.push((RangeKind::Uploaded, range.start, range.end));
+                stats.bytes_read += (std::cmp::min(range.end, obj_len) - range.start).max(0) as u64;
+            }
+            PartOp::Copy(range) => stats.ranges.push((RangeKind::Copied, range.start, range.end)),
+        }
+    }
+
+    let res = async {
+        let (upload_id, etag) = (upload_id.as_str(), stat.etag.as_deref());
+
+        let copies = ops.iter()
+            .zip(1..)
+            .filter_map(|(op, part_num)| match op {
+                PartOp::Copy(range) => Some((part_num, range.clone())),
+                PartOp::Upload(_) => None,
+            })
+            .map(|(part_num, range)| async move {
+                let etag = backend.copy_part(key, upload_id, part_num, range, etag).await?;
+                Result::<_, anyhow::Error>::Ok((part_num, etag))
+            });
+        let copies = stream::iter(copies)
+            .buffer_unordered(backend.max_concurrency().max(1))
+            .try_collect::<Vec<_>>();
+
+        let uploads = async {
+            let mut etags = Vec::new();
+
+            for (op, part_num) in ops.iter().zip(1..) {
+                let PartOp::Upload(range) = op else {
+                    continue;
+                };
+
+                let original = range.start..std::cmp::min(range.end, obj_len);
+                let mut data = match original.is_empty() {
+                    true => Vec::new(),
+                    false => backend.read_range(key, original).await?.to_vec(),
+                };
+                data.resize((range.end - range.start) as usize, 0);
+
+                for (edit, bytes) in &edits {
+                    let start = std::cmp::max(edit.start, range.start);
+                    let end = std::cmp::min(edit.end, range.end);
+
+                    if start < end {
+                        data[(start - range.start) as usize..(end - range.start) as usize]
+                            .copy_from_slice(&bytes[(start - edit.start) as usize..(end - edit.start) as usize]);
+                    }
+                }
+                etags.push((part_num, backend.upload_part(key, upload_id, part_num, Bytes::from(data)).await?));
+            }
+            Result::<_, anyhow::Error>::Ok(etags)
+        };
+
+        let (copied, uploaded) = try_join(copies, uploads).await?;
+        let mut etags = copied.into_iter().chain(uploaded).collect::<Vec<_>>();
+        etags.sort_by_key(|(part_num, _)| *part_num);
+
+        backend.complete(key, upload_id, etags).await
+    }.await;
+
+    match res {
+        Ok(etag) => {
+            stats.etag = etag;
+            Ok(stats)
+        }
+        Err(e) => {
+            if let Err(abort_err) = backend.abort(key, &upload_id).await {
+                warn!(key, upload_id, "failed to abort upload: {}", abort_err);
+            }
+            Err(e)
+        }
+    }
+}
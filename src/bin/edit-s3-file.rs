@@ -0,0 +1,190 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
+use edit_s3_file::{ModifyStats, Part, PartOp, PartSource, S3Config, Session, PATH_ENV};
+
+/// Edit S3 objects in place: patch, truncate or append without downloading the whole object.
+#[derive(Parser)]
+#[command(name = "edit-s3-file", version)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// The JSON config file of the library, or the settings to build one from.
+#[derive(Args)]
+struct ConfigArgs {
+    /// JSON config file, as read from S3_STORE_CONFIG by the library.
+    #[arg(long, global = true, env = PATH_ENV)]
+    config: Option<PathBuf>,
+    /// Endpoint URL, used instead of the config file.
+    #[arg(long, global = true, requires = "bucket")]
+    endpoint: Option<String>,
+    #[arg(long, global = true, requires = "endpoint")]
+    bucket: Option<String>,
+    #[arg(long, global = true)]
+    region: Option<String>,
+    /// Access key, with --secret-key.
+    #[arg(long, global = true, env = "AWS_ACCESS_KEY_ID", requires = "secret_key")]
+    access_key: Option<String>,
+    #[arg(long, global = true, env = "AWS_SECRET_ACCESS_KEY", hide_env_values = true)]
+    secret_key: Option<String>,
+    /// AWS profile to take credentials from without --access-key.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Overwrite the bytes at --offset with the contents of --input, extending the object if
+    /// they run past its end.
+    Write {
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        offset: i64,
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Cut the object to its first --len bytes.
+    Truncate {
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        len: i64,
+    },
+    /// Add the contents of --input to the end of the object.
+    Append {
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Apply the edits of the patch file --input, as written by `encode_patch`.
+    Patch {
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Make the object a copy of --input, uploading only the blocks that differ.
+    Sync {
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Print the parts and cost a write would take, without writing anything.
+    Plan {
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        offset: i64,
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Abort multipart uploads started more than --older-than-secs ago.
+    AbortUploads {
+        /// Only uploads of keys under this prefix.
+        #[arg(long)]
+        prefix: Option<String>,
+        #[arg(long, default_value_t = 24 * 60 * 60)]
+        older_than_secs: u64,
+    },
+}
+
+impl ConfigArgs {
+    fn session(self) -> Result<Session> {
+        let config = match (self.endpoint, self.bucket, self.config) {
+            (Some(endpoint), Some(bucket), _) => {
+                let mut builder = S3Config::builder().endpoint(endpoint).bucket(bucket);
+
+                if let Some(region) = self.region {
+                    builder = builder.region(region);
+                }
+                if let (Some(access_key), Some(secret_key)) = (self.access_key, self.secret_key) {
+                    builder = builder.credentials(access_key, secret_key);
+                }
+                if let Some(profile) = self.profile {
+                    builder = builder.profile(profile);
+                }
+                builder.build()?
+            }
+            (_, _, Some(path)) => serde_json::from_reader(std::fs::File::open(path)?)?,
+            _ => return Err(anyhow!("either --config (or {}) or --endpoint and --bucket are needed", PATH_ENV)),
+        };
+        Session::new(config)
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let session = cli.config.session()?;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    rt.block_on(run(&session, cli.command))
+}
+
+async fn run(session: &Session, command: Command) -> Result<()> {
+    match command {
+        Command::Write { key, offset, input } => {
+            let stats = session.modify_from(&key, offset, PartSource::file(input)?).await?;
+            print_stats(&stats);
+        }
+        Command::Truncate { key, len } => {
+            let stats = session.truncate(&key, len).await?;
+            print_stats(&stats);
+        }
+        Command::Append { key, input } => {
+            let stats = session.append(&key, std::fs::read(input)?).await?;
+            print_stats(&stats);
+        }
+        Command::Patch { key, input } => {
+            let stats = session.apply_patch(&key, &std::fs::read(input)?).await?;
+            print_stats(&stats);
+        }
+        Command::Sync { key, input } => {
+            let stats = session.sync_from_file(&key, input).await?;
+            print_stats(&stats);
+        }
+        Command::Plan { key, offset, input } => {
+            let plan = session.plan_modify(&key, vec![Part::new(offset, std::fs::read(input)?)]).await?;
+            println!("{}: {} -> {} bytes, {} parts", plan.key(), plan.object_len(), plan.new_len(), plan.ops().len());
+
+            for (i, op) in plan.ops().iter().enumerate() {
+                let (kind, range) = match op {
+                    PartOp::Copy(range) => ("copy", range),
+                    PartOp::Upload(range) => ("upload", range),
+                };
+                println!("{:>5} {:<6} bytes={}-{}", i + 1, kind, range.start, range.end - 1);
+            }
+            println!("{:?}", plan.cost());
+        }
+        Command::AbortUploads { prefix, older_than_secs } => {
+            let aborted = session.abort_stale_uploads(prefix.as_deref(), Duration::from_secs(older_than_secs)).await?;
+
+            for upload_id in &aborted {
+                println!("aborted {}", upload_id);
+            }
+            println!("{} uploads aborted", aborted.len());
+        }
+    }
+    Ok(())
+}
+
+fn print_stats(stats: &ModifyStats) {
+    println!(
+        "{} bytes copied, {} uploaded, {} read, etag {}",
+        stats.bytes_copied(),
+        stats.bytes_uploaded(),
+        stats.bytes_read,
+        stats.etag.as_deref().unwrap_or("-"),
+    );
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, ensure, Result};
+
+use crate::sse::SseCustomer;
+use crate::{fetch_range, read_buffer_size, session_for, Session, DEFAULT_CHUNK_CACHE_BYTES, DEFAULT_READ_WINDOW};
+
+/// Random access to an object through a cache of fixed-size windows, for reading scattered
+/// offsets of a large object without downloading it.
+///
+/// [`ChunkedObject::read_at`] fetches the windows it touches with ranged `get_object` calls
+/// of `window` bytes ([`DEFAULT_READ_WINDOW`] unless set with [`ChunkedObject::with_window`])
+/// and keeps them for later reads. The cache holds up to `chunk_cache_bytes` of the config
+/// ([`DEFAULT_CHUNK_CACHE_BYTES`] if not set), at least one window; once full, the least
+/// recently read window is evicted. The object's length is taken when it is opened, so
+/// later changes to the object may be seen only partly.
+pub struct ChunkedObject {
+    session: Session,
+    key: String,
+    len: u64,
+    window: u64,
+    cache_bytes: u64,
+    // least recently read first
+    cache: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl ChunkedObject {
+    /// Open `key` on the global session, see [`Session::chunked`] for other sessions.
+    /// `key` may be an `s3://bucket/key` URI.
+    pub async fn open(key: &str) -> Result<Self> {
+        let (session, key) = session_for(key)?;
+        Self::from_session(&session, key).await
+    }
+
+    pub(crate) async fn from_session(session: &Session, key: &str) -> Result<Self> {
+        let stat = session.stat(key)
+            .await?
+            .ok_or_else(|| anyhow!("{} not found", key))?;
+
+        Ok(ChunkedObject {
+            session: session.clone(),
+            key: key.to_string(),
+            len: stat.len as u64,
+            window: DEFAULT_READ_WINDOW as u64,
+            cache_bytes: session.config.chunk_cache_bytes.unwrap_or(DEFAULT_CHUNK_CACHE_BYTES),
+            cache: VecDeque::new(),
+        })
+    }
+
+    /// Use windows of `window` bytes, dropping what was cached with the previous size.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1) as u64;
+        self.cache.clear();
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The `len` bytes at `offset`, which must be within the object.
+    pub async fn read_at(&mut self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let end = offset.checked_add(len as u64).filter(|&end| end <= self.len);
+        let end = end.ok_or_else(|| anyhow!("{} range {}+{} is outside the {} byte object", self.key, offset, len, self.len))?;
+
+        let mut out = Vec::with_capacity(len);
+        let mut pos = offset;
+
+        while pos < end {
+            let index = pos / self.window;
+            let window_start = index * self.window;
+            let data = self.cached_window(index).await?;
+
+            let from = (pos - window_start) as usize;
+            let to = (std::cmp::min(end, window_start + data.len() as u64) - window_start) as usize;
+
+            // the object got shorter since it was opened
+            if from >= to {
+                break;
+            }
+
+            out.extend_from_slice(&data[from..to]);
+            pos = window_start + to as u64;
+        }
+
+        ensure!(out.len() == len, "{} is shorter than the {} bytes it had when opened", self.key, self.len);
+        Ok(out)
+    }
+
+    // window `index`, from the cache or fetched into it
+    async fn cached_window(&mut self, index: u64) -> Result<&[u8]> {
+        if let Some(i) = self.cache.iter().position(|(cached, _)| *cached == index) {
+            let entry = self.cache.remove(i).unwrap();
+            self.cache.push_back(entry);
+        } else {
+            let start = index * self.window;
+            let end = std::cmp::min(start + self.window, self.len);
+
+            let data = fetch_range(
+                self.session.client.clone(),
+                self.session.config.bucket.clone(),
+                self.key.clone(),
+                SseCustomer::new(&self.session.config),
+                read_buffer_size(&self.session.config),
+                start,
+                end,
+            ).await?;
+
+            let capacity = std::cmp::max(self.cache_bytes / self.window, 1) as usize;
+
+            while self.cache.len() >= capacity {
+                self.cache.pop_front();
+            }
+            self.cache.push_back((index, data));
+        }
+
+        Ok(&self.cache.back().unwrap().1)
+    }
+}
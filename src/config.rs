@@ -0,0 +1,529 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, ensure, Result};
+
+use crate::sse::check_sse;
+use crate::{BucketType, CompatMode, MetadataDirective, PartChecksum, PartSize, S3Config, SseMode, TempKeyStrategy, MAX_PART_SIZE, MIN_PART_SIZE};
+
+/// Fluent construction of an [`S3Config`], see [`S3Config::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct S3ConfigBuilder {
+    endpoint: Option<String>,
+    failover_endpoints: Vec<String>,
+    bucket: Option<String>,
+    region: Option<String>,
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    profile: Option<String>,
+    force_path_style: bool,
+    part_size: PartSize,
+    max_memory_bytes: Option<u64>,
+    chunk_cache_bytes: Option<u64>,
+    restore_archived: bool,
+    compute_final_checksum: bool,
+    atomic_edits: bool,
+    temp_key_strategy: TempKeyStrategy,
+    insecure: bool,
+    max_concurrency: Option<usize>,
+    max_read_bytes: Option<u64>,
+    read_buffer_size: Option<usize>,
+    preserve_timestamps: bool,
+    metadata_directive: MetadataDirective,
+    replace_metadata: HashMap<String, String>,
+    read_max_retries: Option<u32>,
+    write_max_retries: Option<u32>,
+    retry_initial_backoff_ms: Option<u64>,
+    retry_max_backoff_ms: Option<u64>,
+    force_single_put: bool,
+    preserve_acl: bool,
+    preserve_tags: bool,
+    pad_to_part: Option<bool>,
+    preserve_part_layout: bool,
+    if_none_match: bool,
+    max_bytes_per_sec: Option<u64>,
+    sse_customer_key: Option<String>,
+    sse: Option<SseMode>,
+    storage_class: Option<String>,
+    upload_token: Option<String>,
+    compat: CompatMode,
+    bucket_type: Option<BucketType>,
+    requester_pays: bool,
+    log_plan: bool,
+    stream_checksums: bool,
+    stat_cache_ttl_secs: Option<u64>,
+    stat_cache_size: Option<usize>,
+    adaptive_part_size: bool,
+    coalesce_gap_bytes: Option<i64>,
+    connect_timeout_secs: Option<u64>,
+    request_timeout_secs: Option<u64>,
+    read_timeout_secs: Option<u64>,
+    ca_cert_path: Option<String>,
+    verify_edits: bool,
+    checksum_algorithm: Option<PartChecksum>,
+    edit_lock: bool,
+    lock_wait_secs: Option<u64>,
+    lock_ttl_secs: Option<u64>,
+    propagate_object_lock: bool,
+}
+
+impl S3Config {
+    pub fn builder() -> S3ConfigBuilder {
+        S3ConfigBuilder::default()
+    }
+}
+
+/// The defaults of [`S3Config::builder`], with an empty endpoint and bucket.
+impl Default for S3Config {
+    fn default() -> Self {
+        S3ConfigBuilder::default().unchecked()
+    }
+}
+
+impl S3ConfigBuilder {
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    pub fn failover_endpoints(mut self, failover_endpoints: Vec<String>) -> Self {
+        self.failover_endpoints = failover_endpoints;
+        self
+    }
+
+    pub fn bucket(mut self, bucket: impl Into<String>) -> Self {
+        self.bucket = Some(bucket.into());
+        self
+    }
+
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    pub fn credentials(mut self, access_key: impl Into<String>, secret_key: impl Into<String>) -> Self {
+        self.access_key = Some(access_key.into());
+        self.secret_key = Some(secret_key.into());
+        self
+    }
+
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    pub fn insecure(mut self, insecure: bool) -> Self {
+        self.insecure = insecure;
+        self
+    }
+
+    pub fn force_path_style(mut self, force_path_style: bool) -> Self {
+        self.force_path_style = force_path_style;
+        self
+    }
+
+    pub fn part_size(mut self, part_size: PartSize) -> Self {
+        self.part_size = part_size;
+        self
+    }
+
+    pub fn max_memory_bytes(mut self, max_memory_bytes: u64) -> Self {
+        self.max_memory_bytes = Some(max_memory_bytes);
+        self
+    }
+
+    pub fn chunk_cache_bytes(mut self, chunk_cache_bytes: u64) -> Self {
+        self.chunk_cache_bytes = Some(chunk_cache_bytes);
+        self
+    }
+
+    pub fn restore_archived(mut self, restore_archived: bool) -> Self {
+        self.restore_archived = restore_archived;
+        self
+    }
+
+    pub fn compute_final_checksum(mut self, compute_final_checksum: bool) -> Self {
+        self.compute_final_checksum = compute_final_checksum;
+        self
+    }
+
+    pub fn atomic_edits(mut self, atomic_edits: bool) -> Self {
+        self.atomic_edits = atomic_edits;
+        self
+    }
+
+    pub fn temp_key_strategy(mut self, temp_key_strategy: TempKeyStrategy) -> Self {
+        self.temp_key_strategy = temp_key_strategy;
+        self
+    }
+
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+
+    pub fn max_read_bytes(mut self, max_read_bytes: u64) -> Self {
+        self.max_read_bytes = Some(max_read_bytes);
+        self
+    }
+
+    pub fn read_buffer_size(mut self, read_buffer_size: usize) -> Self {
+        self.read_buffer_size = Some(read_buffer_size);
+        self
+    }
+
+    pub fn preserve_timestamps(mut self, preserve_timestamps: bool) -> Self {
+        self.preserve_timestamps = preserve_timestamps;
+        self
+    }
+
+    pub fn metadata_directive(mut self, metadata_directive: MetadataDirective) -> Self {
+        self.metadata_directive = metadata_directive;
+        self
+    }
+
+    /// Set [`MetadataDirective::Replace`] with `metadata` as the only user metadata.
+    pub fn replace_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata_directive = MetadataDirective::Replace;
+        self.replace_metadata = metadata;
+        self
+    }
+
+    pub fn read_max_retries(mut self, read_max_retries: u32) -> Self {
+        self.read_max_retries = Some(read_max_retries);
+        self
+    }
+
+    pub fn write_max_retries(mut self, write_max_retries: u32) -> Self {
+        self.write_max_retries = Some(write_max_retries);
+        self
+    }
+
+    pub fn retry_initial_backoff_ms(mut self, retry_initial_backoff_ms: u64) -> Self {
+        self.retry_initial_backoff_ms = Some(retry_initial_backoff_ms);
+        self
+    }
+
+    pub fn retry_max_backoff_ms(mut self, retry_max_backoff_ms: u64) -> Self {
+        self.retry_max_backoff_ms = Some(retry_max_backoff_ms);
+        self
+    }
+
+    pub fn force_single_put(mut self, force_single_put: bool) -> Self {
+        self.force_single_put = force_single_put;
+        self
+    }
+
+    pub fn preserve_acl(mut self, preserve_acl: bool) -> Self {
+        self.preserve_acl = preserve_acl;
+        self
+    }
+
+    pub fn preserve_tags(mut self, preserve_tags: bool) -> Self {
+        self.preserve_tags = preserve_tags;
+        self
+    }
+
+    pub fn pad_to_part(mut self, pad_to_part: bool) -> Self {
+        self.pad_to_part = Some(pad_to_part);
+        self
+    }
+
+    pub fn preserve_part_layout(mut self, preserve_part_layout: bool) -> Self {
+        self.preserve_part_layout = preserve_part_layout;
+        self
+    }
+
+    pub fn if_none_match(mut self, if_none_match: bool) -> Self {
+        self.if_none_match = if_none_match;
+        self
+    }
+
+    pub fn max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        self.max_bytes_per_sec = Some(max_bytes_per_sec);
+        self
+    }
+
+    /// Base64 256-bit SSE-C key, see [`S3Config::sse_customer_key`].
+    pub fn sse_customer_key(mut self, sse_customer_key: impl Into<String>) -> Self {
+        self.sse_customer_key = Some(sse_customer_key.into());
+        self
+    }
+
+    pub fn sse(mut self, sse: SseMode) -> Self {
+        self.sse = Some(sse);
+        self
+    }
+
+    pub fn storage_class(mut self, storage_class: impl Into<String>) -> Self {
+        self.storage_class = Some(storage_class.into());
+        self
+    }
+
+    pub fn upload_token(mut self, upload_token: impl Into<String>) -> Self {
+        self.upload_token = Some(upload_token.into());
+        self
+    }
+
+    pub fn compat(mut self, compat: CompatMode) -> Self {
+        self.compat = compat;
+        self
+    }
+
+    pub fn bucket_type(mut self, bucket_type: BucketType) -> Self {
+        self.bucket_type = Some(bucket_type);
+        self
+    }
+
+    pub fn requester_pays(mut self, requester_pays: bool) -> Self {
+        self.requester_pays = requester_pays;
+        self
+    }
+
+    pub fn log_plan(mut self, log_plan: bool) -> Self {
+        self.log_plan = log_plan;
+        self
+    }
+
+    pub fn stream_checksums(mut self, stream_checksums: bool) -> Self {
+        self.stream_checksums = stream_checksums;
+        self
+    }
+
+    pub fn stat_cache_ttl_secs(mut self, stat_cache_ttl_secs: u64) -> Self {
+        self.stat_cache_ttl_secs = Some(stat_cache_ttl_secs);
+        self
+    }
+
+    pub fn stat_cache_size(mut self, stat_cache_size: usize) -> Self {
+        self.stat_cache_size = Some(stat_cache_size);
+        self
+    }
+
+    pub fn adaptive_part_size(mut self, adaptive_part_size: bool) -> Self {
+        self.adaptive_part_size = adaptive_part_size;
+        self
+    }
+
+    pub fn coalesce_gap_bytes(mut self, coalesce_gap_bytes: i64) -> Self {
+        self.coalesce_gap_bytes = Some(coalesce_gap_bytes);
+        self
+    }
+
+    pub fn connect_timeout_secs(mut self, connect_timeout_secs: u64) -> Self {
+        self.connect_timeout_secs = Some(connect_timeout_secs);
+        self
+    }
+
+    pub fn request_timeout_secs(mut self, request_timeout_secs: u64) -> Self {
+        self.request_timeout_secs = Some(request_timeout_secs);
+        self
+    }
+
+    pub fn read_timeout_secs(mut self, read_timeout_secs: u64) -> Self {
+        self.read_timeout_secs = Some(read_timeout_secs);
+        self
+    }
+
+    pub fn ca_cert_path(mut self, ca_cert_path: impl Into<String>) -> Self {
+        self.ca_cert_path = Some(ca_cert_path.into());
+        self
+    }
+
+    pub fn verify_edits(mut self, verify_edits: bool) -> Self {
+        self.verify_edits = verify_edits;
+        self
+    }
+
+    pub fn checksum_algorithm(mut self, checksum_algorithm: PartChecksum) -> Self {
+        self.checksum_algorithm = Some(checksum_algorithm);
+        self
+    }
+
+    pub fn edit_lock(mut self, edit_lock: bool) -> Self {
+        self.edit_lock = edit_lock;
+        self
+    }
+
+    pub fn lock_wait_secs(mut self, lock_wait_secs: u64) -> Self {
+        self.lock_wait_secs = Some(lock_wait_secs);
+        self
+    }
+
+    pub fn lock_ttl_secs(mut self, lock_ttl_secs: u64) -> Self {
+        self.lock_ttl_secs = Some(lock_ttl_secs);
+        self
+    }
+
+    pub fn propagate_object_lock(mut self, propagate_object_lock: bool) -> Self {
+        self.propagate_object_lock = propagate_object_lock;
+        self
+    }
+
+    /// Fails if endpoint or bucket is missing, an endpoint isn't an http(s) URL, a fixed
+    /// part size is outside what S3 accepts, or an SSE-C key isn't a base64 256-bit key or comes with `sse`.
+    /// An endpoint without a scheme gets `https://`, or `http://` if `insecure` is set.
+    /// The settings `compat` needs are turned on in the result.
+    pub fn build(self) -> Result<S3Config> {
+        let endpoint = self.endpoint.clone().ok_or_else(|| anyhow!("S3 config is missing endpoint"))?;
+        let bucket = self.bucket.clone().ok_or_else(|| anyhow!("S3 config is missing bucket"))?;
+
+        let endpoint = normalize_endpoint(&endpoint, self.insecure)?;
+        let failover_endpoints = self.failover_endpoints.iter()
+            .map(|endpoint| normalize_endpoint(endpoint, self.insecure))
+            .collect::<Result<Vec<_>>>()?;
+        check_part_size(self.part_size)?;
+        check_sse(self.sse_customer_key.as_deref(), self.sse.as_ref())?;
+
+        let mut config = S3Config { endpoint, failover_endpoints, bucket, ..self.unchecked() };
+
+        config.compat.apply(&mut config);
+        check_bucket(&config.bucket, config.force_path_style)?;
+        check_bucket_type(&config)?;
+        Ok(config)
+    }
+
+    /// The config as set, without the checks and normalizing of [`S3ConfigBuilder::build`].
+    fn unchecked(self) -> S3Config {
+        S3Config {
+            endpoint: self.endpoint.unwrap_or_default(),
+            failover_endpoints: self.failover_endpoints,
+            bucket: self.bucket.unwrap_or_default(),
+            region: self.region,
+            access_key: self.access_key,
+            secret_key: self.secret_key,
+            profile: self.profile,
+            force_path_style: self.force_path_style,
+            part_size: self.part_size,
+            max_memory_bytes: self.max_memory_bytes,
+            chunk_cache_bytes: self.chunk_cache_bytes,
+            restore_archived: self.restore_archived,
+            compute_final_checksum: self.compute_final_checksum,
+            atomic_edits: self.atomic_edits,
+            temp_key_strategy: self.temp_key_strategy,
+            insecure: self.insecure,
+            max_concurrency: self.max_concurrency,
+            max_read_bytes: self.max_read_bytes,
+            read_buffer_size: self.read_buffer_size,
+            preserve_timestamps: self.preserve_timestamps,
+            metadata_directive: self.metadata_directive,
+            replace_metadata: self.replace_metadata,
+            read_max_retries: self.read_max_retries,
+            write_max_retries: self.write_max_retries,
+            retry_initial_backoff_ms: self.retry_initial_backoff_ms,
+            retry_max_backoff_ms: self.retry_max_backoff_ms,
+            force_single_put: self.force_single_put,
+            preserve_acl: self.preserve_acl,
+            preserve_tags: self.preserve_tags,
+            pad_to_part: self.pad_to_part.unwrap_or(true),
+            preserve_part_layout: self.preserve_part_layout,
+            if_none_match: self.if_none_match,
+            max_bytes_per_sec: self.max_bytes_per_sec,
+            sse_customer_key: self.sse_customer_key,
+            sse: self.sse,
+            storage_class: self.storage_class,
+            upload_token: self.upload_token,
+            compat: self.compat,
+            bucket_type: self.bucket_type,
+            requester_pays: self.requester_pays,
+            log_plan: self.log_plan,
+            stream_checksums: self.stream_checksums,
+            stat_cache_ttl_secs: self.stat_cache_ttl_secs,
+            stat_cache_size: self.stat_cache_size,
+            adaptive_part_size: self.adaptive_part_size,
+            coalesce_gap_bytes: self.coalesce_gap_bytes,
+            connect_timeout_secs: self.connect_timeout_secs,
+            request_timeout_secs: self.request_timeout_secs,
+            read_timeout_secs: self.read_timeout_secs,
+            ca_cert_path: self.ca_cert_path,
+            verify_edits: self.verify_edits,
+            checksum_algorithm: self.checksum_algorithm,
+            edit_lock: self.edit_lock,
+            lock_wait_secs: self.lock_wait_secs,
+            lock_ttl_secs: self.lock_ttl_secs,
+            propagate_object_lock: self.propagate_object_lock,
+        }
+    }
+}
+
+/// `endpoint` with a scheme: as given if it has one, else `https://`, or `http://` if `insecure`.
+pub(crate) fn normalize_endpoint(endpoint: &str, insecure: bool) -> Result<String> {
+    let endpoint = endpoint.trim();
+
+    let (scheme, rest) = match endpoint.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None if insecure => ("http", endpoint),
+        None => ("https", endpoint),
+    };
+
+    ensure!(scheme == "http" || scheme == "https", "endpoint {:?} must be an http:// or https:// URL", endpoint);
+
+    let authority = rest.split('/').next().unwrap_or_default();
+
+    ensure!(
+        !authority.is_empty() && !authority.contains(|c: char| c.is_whitespace() || c == '?' || c == '#'),
+        "endpoint {:?} has no valid host",
+        endpoint
+    );
+    Ok(format!("{}://{}", scheme, rest))
+}
+
+/// Whether `bucket` is an access point ARN, `arn:<partition>:s3:<region>:<account>:accesspoint/<name>`.
+pub(crate) fn is_access_point_arn(bucket: &str) -> bool {
+    bucket.starts_with("arn:")
+}
+
+/// Reject fixed part sizes S3 would refuse for every part but the last, or for any part,
+/// before they fail a multipart upload halfway.
+pub(crate) fn check_part_size(part_size: PartSize) -> Result<()> {
+    if let PartSize::Fixed(size) = part_size {
+        ensure!(
+            (MIN_PART_SIZE..=MAX_PART_SIZE).contains(&size),
+            "S3 config part_size {} is outside {}..={} bytes",
+            size,
+            MIN_PART_SIZE,
+            MAX_PART_SIZE
+        );
+    }
+    Ok(())
+}
+
+/// Accept a plain bucket name or a well-formed access point ARN.
+pub(crate) fn check_bucket(bucket: &str, force_path_style: bool) -> Result<()> {
+    if !is_access_point_arn(bucket) {
+        ensure!(
+            !bucket.is_empty() && !bucket.contains(['/', ':']),
+            "bucket {:?} is neither a bucket name nor an access point ARN",
+            bucket
+        );
+        return Ok(());
+    }
+
+    let fields = bucket.splitn(6, ':').collect::<Vec<_>>();
+
+    let valid = match fields.as_slice() {
+        ["arn", partition, "s3", region, account, resource] => {
+            let name = resource.strip_prefix("accesspoint/").unwrap_or_default();
+
+            // Multi-Region Access Points have no region, their alias ends in `.mrap`
+            !partition.is_empty()
+                && (!region.is_empty() || name.ends_with(".mrap"))
+                && !account.is_empty()
+                && !name.is_empty()
+                && !name.contains('/')
+        }
+        _ => false,
+    };
+
+    ensure!(valid, "bucket {:?} is not an access point ARN (arn:<partition>:s3:<region>:<account>:accesspoint/<name>, or arn:<partition>:s3::<account>:accesspoint/<alias>.mrap)", bucket);
+    ensure!(!force_path_style, "access point {} can't be addressed path-style", bucket);
+    Ok(())
+}
+
+/// Rejects the settings a directory bucket doesn't support.
+pub(crate) fn check_bucket_type(config: &S3Config) -> Result<()> {
+    if BucketType::of(config) == BucketType::Directory {
+        ensure!(!config.force_path_style, "directory bucket {} can't be addressed path-style", config.bucket);
+        ensure!(!config.preserve_acl && !config.preserve_tags, "directory bucket {} has no ACLs or tags to preserve", config.bucket);
+    }
+    Ok(())
+}
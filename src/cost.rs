@@ -0,0 +1,95 @@
+use std::ops::Range;
+
+use crate::PartOp;
+
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Prices of a provider in dollars, to turn a [`CostEstimate`] into a rough bill. They vary
+/// by provider, region and storage class, so callers fill in their own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    /// Per 1000 PUT class requests: creating, completing, and uploading or copying parts.
+    pub put_per_1000: f64,
+    /// Per 1000 GET class requests.
+    pub get_per_1000: f64,
+    /// Per GB downloaded.
+    pub download_per_gb: f64,
+}
+
+impl Pricing {
+    /// AWS S3 Standard in us-east-1, for a client in the same region, where downloads are free.
+    pub const AWS_S3_STANDARD: Pricing = Pricing {
+        put_per_1000: 0.005,
+        get_per_1000: 0.0004,
+        download_per_gb: 0.0,
+    };
+}
+
+/// Requests and bytes an edit takes, see [`Session::estimate_cost`](crate::Session::estimate_cost).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// `upload_part_copy` requests.
+    pub copy_requests: u64,
+    /// `upload_part` requests.
+    pub upload_requests: u64,
+    /// Ranged `get_object` requests fetching the original bytes that pad edits.
+    pub get_requests: u64,
+    /// Bytes copied server-side, never leaving the server.
+    pub bytes_copied: u64,
+    /// Bytes uploaded, the edits and their padding.
+    pub bytes_uploaded: u64,
+    /// Bytes downloaded to pad edits.
+    pub bytes_downloaded: u64,
+}
+
+impl CostEstimate {
+    /// Requests and bytes of carrying out `ops` as one multipart upload, for an edit replacing
+    /// the sorted, disjoint `edits` ranges.
+    pub fn of_plan(ops: &[PartOp], edits: &[Range<i64>]) -> Self {
+        let mut estimate = CostEstimate::default();
+
+        for op in ops {
+            match op {
+                PartOp::Copy(range) => {
+                    estimate.copy_requests += 1;
+                    estimate.bytes_copied += (range.end - range.start) as u64;
+                }
+                PartOp::Upload(range) => {
+                    estimate.upload_requests += 1;
+                    estimate.bytes_uploaded += (range.end - range.start) as u64;
+
+                    // one fetch for each gap between the edits in the part
+                    let mut offset = range.start;
+
+                    for edit in edits.iter().filter(|e| e.start < range.end && range.start < e.end) {
+                        let start = std::cmp::max(edit.start, range.start);
+
+                        if start > offset {
+                            estimate.get_requests += 1;
+                            estimate.bytes_downloaded += (start - offset) as u64;
+                        }
+                        offset = std::cmp::min(edit.end, range.end);
+                    }
+
+                    if offset < range.end {
+                        estimate.get_requests += 1;
+                        estimate.bytes_downloaded += (range.end - offset) as u64;
+                    }
+                }
+            }
+        }
+        estimate
+    }
+
+    /// PUT class requests, the parts plus creating and completing the upload.
+    pub fn put_requests(&self) -> u64 {
+        self.copy_requests + self.upload_requests + 2
+    }
+
+    /// Rough cost in dollars at `pricing`.
+    pub fn usd(&self, pricing: &Pricing) -> f64 {
+        self.put_requests() as f64 / 1000.0 * pricing.put_per_1000
+            + self.get_requests as f64 / 1000.0 * pricing.get_per_1000
+            + self.bytes_downloaded as f64 / GB * pricing.download_per_gb
+    }
+}
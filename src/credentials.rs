@@ -0,0 +1,48 @@
+use aws_config::default_provider::credentials::DefaultCredentialsChain;
+use aws_credential_types::provider::{future, ProvideCredentials};
+use aws_types::region::Region;
+use tokio::sync::OnceCell;
+
+/// The AWS SDK's default credentials chain, as `aws_config::from_env()` loads it, for configs
+/// without static keys. Building the chain is async, so it's built on the first request for
+/// credentials and shared by the clones of the session after that.
+#[derive(Debug)]
+pub(crate) struct DefaultChain {
+    profile: Option<String>,
+    region: Option<String>,
+    chain: OnceCell<DefaultCredentialsChain>,
+}
+
+impl DefaultChain {
+    pub(crate) fn new(profile: Option<String>, region: Option<String>) -> Self {
+        DefaultChain {
+            profile,
+            region,
+            chain: OnceCell::new(),
+        }
+    }
+}
+
+impl ProvideCredentials for DefaultChain {
+    fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
+    where
+        Self: 'a,
+    {
+        future::ProvideCredentials::new(async move {
+            let chain = self.chain.get_or_init(|| async {
+                let mut builder = DefaultCredentialsChain::builder();
+
+                if let Some(profile) = &self.profile {
+                    builder = builder.profile_name(profile);
+                }
+                // STS and SSO are regional, the chain finds the region itself without one
+                if let Some(region) = &self.region {
+                    builder = builder.region(Region::new(region.clone()));
+                }
+                builder.build().await
+            }).await;
+
+            chain.provide_credentials().await
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::{CostEstimate, EditError, ModifyStats, Part, PartOp, Session};
+
+/// The parts [`Session::modify_many`] of an object would write, worked out by
+/// [`Session::plan_modify`] from one `head_object`, to be reviewed or logged before
+/// [`EditPlan::execute`] carries it out.
+pub struct EditPlan<'a> {
+    pub(crate) session: &'a Session,
+    pub(crate) key: String,
+    pub(crate) etag: Option<String>,
+    pub(crate) obj_len: i64,
+    pub(crate) new_len: i64,
+    pub(crate) ops: Vec<PartOp>,
+    pub(crate) cost: CostEstimate,
+    pub(crate) parts: Vec<Part>,
+}
+
+impl EditPlan<'_> {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Length of the object the plan was made for.
+    pub fn object_len(&self) -> i64 {
+        self.obj_len
+    }
+
+    /// Length of the object once edited.
+    pub fn new_len(&self) -> i64 {
+        self.new_len
+    }
+
+    /// The parts in part number order, from 1.
+    pub fn ops(&self) -> &[PartOp] {
+        &self.ops
+    }
+
+    /// Requests and bytes the plan takes.
+    pub fn cost(&self) -> &CostEstimate {
+        &self.cost
+    }
+
+    /// Carry out the plan. Fails with [`EditError::Conflict`], writing nothing, if the object
+    /// was replaced since the plan was made, as the plan may not fit it anymore.
+    ///
+    /// The parts follow the plan as long as the session's settings are those it was made
+    /// with; `preserve_part_layout` and single puts aren't part of it.
+    pub async fn execute(self) -> Result<ModifyStats> {
+        let stat = self.session.current_stat(&self.key).await?;
+
+        if stat.etag != self.etag {
+            return Err(EditError::Conflict { key: self.key }.into());
+        }
+
+        let parts = self.parts.into_iter()
+            .map(|part| Part { expected_obj_len: Some(self.obj_len), ..part })
+            .collect();
+        self.session.modify_many(&self.key, parts).await
+    }
+}
+
+impl fmt::Debug for EditPlan<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EditPlan")
+            .field("key", &self.key)
+            .field("etag", &self.etag)
+            .field("obj_len", &self.obj_len)
+            .field("new_len", &self.new_len)
+            .field("ops", &self.ops)
+            .field("cost", &self.cost)
+            .finish_non_exhaustive()
+    }
+}
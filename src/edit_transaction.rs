@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+
+use anyhow::{ensure, Result};
+use tracing::{info, warn};
+
+use crate::session::RewriteHeaders;
+use crate::{temp_key, ModifyStats, Part, Session, Source};
+
+/// Edits of several objects that are all written or, if one fails, all rolled back, started
+/// with [`Session::edit_transaction`].
+///
+/// Before each object is edited it's copied server-side to a backup object named like the
+/// temporary objects of `atomic_edits`. If an edit fails, the objects already edited are
+/// copied back from their backups, in reverse order, and the error is returned. Backups are
+/// deleted once the transaction is done either way, except those of objects that couldn't
+/// be restored, which the error names.
+///
+/// Readers can see some objects edited and others not until the commit returns, and writes
+/// by others to an edited object in the meantime are lost by a rollback.
+pub struct EditTransaction<'a> {
+    session: &'a Session,
+    edits: Vec<(String, Vec<Part>)>,
+}
+
+impl<'a> EditTransaction<'a> {
+    pub(crate) fn new(session: &'a Session) -> Self {
+        EditTransaction {
+            session,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Apply `parts` to `key`, as [`Session::modify_many`], when committed. Objects are edited
+    /// in the order they were added, and each may be added once.
+    pub fn modify_many(&mut self, key: &str, parts: Vec<Part>) -> &mut Self {
+        self.edits.push((key.to_string(), parts));
+        self
+    }
+
+    /// Edit the objects, rolling back those already edited if one fails. Returns the stats of
+    /// each edit in the order they were added.
+    pub async fn commit(self) -> Result<Vec<ModifyStats>> {
+        let session = self.session;
+        let mut keys = HashSet::with_capacity(self.edits.len());
+
+        for (key, _) in &self.edits {
+            ensure!(keys.insert(key.as_str()), "{} is edited more than once in the transaction", key);
+        }
+
+        // (key, backup key, length, headers) of the objects backed up so far
+        let mut backups = Vec::with_capacity(self.edits.len());
+        let mut stats = Vec::with_capacity(self.edits.len());
+
+        let res = async {
+            for (key, parts) in self.edits {
+                let obj = session.head_source(Source::from(key.as_str())).await?;
+                let len = obj.content_length().unwrap_or_default();
+                let headers = RewriteHeaders::copied(&obj);
+                let backup = temp_key(&session.config, &key);
+
+                session.copy_whole(&key, &backup, len, headers.clone()).await?;
+                backups.push((key.clone(), backup, len, headers));
+
+                stats.push(session.modify_many(&key, parts).await?);
+            }
+            Result::<_, anyhow::Error>::Ok(())
+        }.await;
+
+        // backups that are the only copy left of an object that couldn't be restored
+        let mut kept = Vec::new();
+
+        let res = match res {
+            Ok(()) => Ok(stats),
+            Err(mut err) => {
+                // a failed edit leaves its object as it was, but one that failed verification was written
+                for (key, backup, len, headers) in backups.iter().rev() {
+                    info!(key, "roll back");
+
+                    if let Err(restore_err) = session.copy_whole(backup, key, *len, headers.clone()).await {
+                        warn!(key, backup, "failed to roll back: {}", restore_err);
+                        err = err.context(format!("rolling back {} failed, its original is kept at {}: {:#}", key, backup, restore_err));
+                        kept.push(backup);
+                    }
+                }
+                Err(err)
+            }
+        };
+
+        for (_, backup, _, _) in backups.iter().filter(|(_, backup, _, _)| !kept.contains(&backup)) {
+            if let Err(e) = session.delete_object(backup).await {
+                warn!(backup, "failed to delete backup: {}", e);
+            }
+        }
+
+        res
+    }
+}
@@ -0,0 +1,187 @@
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::SdkError;
+use thiserror::Error;
+
+use crate::etag::hex;
+
+/// Failures callers may want to act on, returned inside the [`anyhow::Error`] of a failed
+/// operation; get one back with `err.downcast_ref::<EditError>()`.
+#[derive(Debug, Error)]
+pub enum EditError {
+    #[error("{key} not found")]
+    ObjectNotFound {
+        key: String,
+    },
+    /// A write with `if_none_match` set found the destination existing; nothing was written.
+    #[error("{key} already exists")]
+    AlreadyExists {
+        key: String,
+    },
+    /// The server rejected the credentials: unknown access key, wrong secret, or an expired token.
+    #[error("credentials rejected by the server")]
+    AuthFailed,
+    /// The object is in Glacier or Deep Archive and can't be read until restored.
+    ///
+    /// A restore takes minutes to hours (up to 12 hours from Glacier Flexible Retrieval,
+    /// 48 hours from Deep Archive); retry the edit once it's done.
+    #[error("{key} is archived{}", if *restore_requested { ", restore requested" } else { "" })]
+    ObjectArchived {
+        key: String,
+        /// Whether this attempt issued (or found in progress) a `restore_object` request.
+        restore_requested: bool,
+    },
+    /// The planned parts don't cover the object exactly: a bug in the planner.
+    #[error("part plan has a gap or overlap at offset {offset}")]
+    PlanInconsistent {
+        offset: i64,
+    },
+    /// A planned part other than the last one is under S3's 5MB minimum.
+    #[error("part {part_number} is {size} bytes, below the minimum part size")]
+    PartTooSmall {
+        part_number: i32,
+        size: i64,
+    },
+    /// The plan has more parts than the 10000 one multipart upload can hold.
+    #[error("part plan has {parts} parts, more than the maximum")]
+    TooManyParts {
+        parts: usize,
+    },
+    /// A part number given to an [`Upload`](crate::Upload) is outside 1-10000 or has a part
+    /// already.
+    #[error("part number {part_number} is out of range or already taken")]
+    InvalidPartNumber {
+        part_number: i32,
+    },
+    /// The edited object would be bigger than the 5TB S3 can store, e.g. after appending to
+    /// an object near that size; nothing was written.
+    #[error("edited object would be {size} bytes, more than the 5TB maximum object size")]
+    ObjectTooLarge {
+        size: i64,
+    },
+    /// A body produced a different number of bytes than its declared length. `read` counts
+    /// up to where the mismatch was noticed, short of the real length for a long body.
+    #[error("body length doesn't match the declared {declared} bytes, read {read}")]
+    BodyLengthMismatch {
+        declared: i64,
+        read: i64,
+    },
+    /// The object changed between reading it and writing the edit; nothing was written.
+    #[error("{key} changed while being edited")]
+    Conflict {
+        key: String,
+    },
+    /// Another edit held the `edit_lock` of the object for longer than `lock_wait_secs`;
+    /// nothing was written.
+    #[error("{key} is locked by another edit")]
+    Locked {
+        key: String,
+    },
+    /// The object is under Object Lock retention or legal hold, which the new version an edit
+    /// writes wouldn't have; nothing was written. Set `propagate_object_lock` to carry them over.
+    #[error("{key} is under object lock retention or legal hold")]
+    ObjectLocked {
+        key: String,
+    },
+    /// A hook set with [`Session::with_hooks`](crate::Session::with_hooks) vetoed the edit,
+    /// with the hook's error as the source; any upload was aborted and nothing was written.
+    #[error("{key} edit vetoed by a hook")]
+    Vetoed {
+        key: String,
+    },
+    /// The session's cancellation token was cancelled during the edit, see
+    /// [`Session::with_cancellation`](crate::Session::with_cancellation); nothing was written.
+    #[error("{key} edit was cancelled")]
+    Cancelled {
+        key: String,
+    },
+    /// The bytes a checksum-gated edit would replace don't have the expected SHA-256;
+    /// nothing was written.
+    #[error("{key} bytes {start}-{end} have SHA-256 {}, not the expected one", hex(actual_sha256))]
+    PreconditionFailed {
+        key: String,
+        start: i64,
+        end: i64,
+        actual_sha256: [u8; 32],
+    },
+    /// The object doesn't start with the bytes a part required, see
+    /// [`Part::with_expected_prefix`](crate::Part::with_expected_prefix); nothing was written.
+    #[error("{key} doesn't start with the expected bytes")]
+    FingerprintMismatch {
+        key: String,
+    },
+    /// The bytes read back from an edited range, with `verify_edits` set, aren't those written.
+    /// The edit was written; rewrite it.
+    #[error("{key} bytes {start}-{end} read back differ from the edit written")]
+    VerificationFailed {
+        key: String,
+        start: i64,
+        end: i64,
+    },
+    /// An offset or range given to an edit or read falls outside the `len` byte object, or
+    /// an edit would leave a hole after its end; nothing was written.
+    #[error("{key} range {start}-{end} is outside the {len} byte object")]
+    RangeOutOfBounds {
+        key: String,
+        start: i64,
+        end: i64,
+        len: i64,
+    },
+    /// The server asked for fewer requests (`SlowDown`, 503) and the SDK's retries gave up;
+    /// the [`EditError::S3`] with the request ids is the source. Retry the operation later.
+    #[error("S3 throttled the request")]
+    Throttled,
+    /// The edited object would not have the length a part required, see
+    /// [`Part::with_expected_total_len`](crate::Part::with_expected_total_len); nothing was written.
+    #[error("edited object would be {actual} bytes, expected {expected}")]
+    LengthMismatch {
+        expected: i64,
+        actual: i64,
+    },
+    /// A request the server answered with an error, with the ids S3 support asks for when
+    /// investigating it: `x-amz-request-id` and `x-amz-id-2`. `source` is the SDK's error,
+    /// see [`EditError::sdk_error`].
+    #[error(
+        "S3 request failed{}, x-amz-request-id: {}, x-amz-id-2: {}",
+        code.as_deref().map(|code| format!(" with {}", code)).unwrap_or_default(),
+        request_id.as_deref().unwrap_or("-"),
+        extended_request_id.as_deref().unwrap_or("-")
+    )]
+    S3 {
+        code: Option<String>,
+        request_id: Option<String>,
+        extended_request_id: Option<String>,
+        #[source]
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+}
+
+impl EditError {
+    /// Whether the same operation may succeed if retried later as it is: the server was
+    /// throttling, the object changed while being edited or another edit held its lock.
+    /// Other failures need different arguments, settings or credentials.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, EditError::Throttled | EditError::Conflict { .. } | EditError::Locked { .. })
+    }
+
+    /// The SDK error of a failed request of the operation failing with `E`, e.g.
+    /// `err.sdk_error::<GetObjectError>()`, for [`EditError::S3`] failures of that operation.
+    pub fn sdk_error<E: std::error::Error + 'static>(&self) -> Option<&SdkError<E, HttpResponse>> {
+        match self {
+            EditError::S3 { source: Some(source), .. } => source.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// The modeled error of the operation failing with `E` the server answered with, to match
+    /// on its variants, e.g. `err.service_error::<GetObjectError>().is_some_and(|e| e.is_no_such_key())`.
+    pub fn service_error<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        self.sdk_error::<E>()?.as_service_error()
+    }
+}
+
+/// The SDK error of the operation failing with `E` anywhere in the chain of `err`, whether
+/// wrapped in [`EditError::S3`] or in another [`EditError`] such as
+/// [`EditError::AuthFailed`].
+pub fn sdk_error<E: std::error::Error + 'static>(err: &anyhow::Error) -> Option<&SdkError<E, HttpResponse>> {
+    err.chain().find_map(|cause| cause.downcast_ref())
+}
@@ -0,0 +1,53 @@
+use md5::{Digest, Md5};
+
+/// The ETag S3 gives an object completed from `parts` by a multipart upload:
+/// the hex MD5 of the concatenated binary MD5s of each part, followed by `-<number of parts>`.
+///
+/// This only predicts the server's ETag when the parts are exactly the ones uploaded
+/// (same boundaries, same order) and the object isn't encrypted with SSE-KMS or SSE-C,
+/// whose ETags aren't MD5-based. Objects written by a single PutObject have the plain
+/// MD5 of the body as ETag instead, without a part count.
+/// The result is unquoted, while S3 returns ETags wrapped in double quotes.
+pub fn compute_multipart_etag(parts: &[&[u8]]) -> String {
+    let mut digests = Md5::new();
+
+    for part in parts {
+        digests.update(Md5::digest(part));
+    }
+
+    format!("{}-{}", hex(&digests.finalize()), parts.len())
+}
+
+/// The ETag of an object completed from parts with the ETags `part_etags`, which are the
+/// parts' MD5s in hex, quoted or not. `None` if one isn't, as with SSE-KMS or SSE-C.
+pub(crate) fn multipart_etag_of_parts<'a>(part_etags: impl ExactSizeIterator<Item = &'a str>) -> Option<String> {
+    let count = part_etags.len();
+    let mut digests = Md5::new();
+
+    for etag in part_etags {
+        let etag = etag.trim_matches('"');
+
+        if etag.len() != 32 || !etag.is_ascii() {
+            return None;
+        }
+
+        for i in (0..32).step_by(2) {
+            digests.update([u8::from_str_radix(&etag[i..i + 2], 16).ok()?]);
+        }
+    }
+
+    Some(format!("{}-{}", hex(&digests.finalize()), count))
+}
+
+/// Whether `etag`, quoted or not, is the MD5 of `data`, as S3 makes the ETag of a part
+/// uploaded without SSE-KMS or SSE-C.
+pub(crate) fn is_md5_of(etag: &str, data: &[u8]) -> bool {
+    etag.trim_matches('"') == hex(&Md5::digest(data))
+}
+
+/// Lowercase hex of `bytes`, as digests are usually written.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -0,0 +1,132 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::config::interceptors::{BeforeTransmitInterceptorContextMut, FinalizerInterceptorContextRef};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::error::BoxError;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use tracing::{info, warn};
+
+// how long an endpoint a request couldn't reach is passed over
+const DOWN_FOR: Duration = Duration::from_secs(30);
+
+/// Interceptor sending each request attempt to the first endpoint not found down in the last
+/// [`DOWN_FOR`], the configured one first and then its `failover_endpoints`. An attempt that
+/// can't connect or times out marks its endpoint down, so the SDK's retry of it goes to the
+/// next one; with every endpoint down, attempts go to the one down the longest.
+///
+/// The SDK addresses requests to the first endpoint; the others replace its scheme and
+/// authority, keeping the bucket's subdomain with virtual-hosted addressing.
+#[derive(Debug)]
+pub(crate) struct Failover {
+    endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    scheme: String,
+    authority: String,
+    down_until: Mutex<Option<Instant>>,
+}
+
+// index of the endpoint the current attempt was sent to
+#[derive(Debug, Clone, Copy)]
+struct Attempted(usize);
+
+impl Storable for Attempted {
+    type Storer = StoreReplace<Self>;
+}
+
+impl Failover {
+    /// Failover across `endpoints`, normalized URLs, in order of preference.
+    pub(crate) fn new(endpoints: &[String]) -> Self {
+        let endpoints = endpoints.iter()
+            .filter_map(|endpoint| {
+                let (scheme, authority, _) = split_uri(endpoint)?;
+                Some(Endpoint { scheme: scheme.to_string(), authority: authority.to_string(), down_until: Mutex::new(None) })
+            })
+            .collect();
+
+        Failover { endpoints }
+    }
+
+    /// Index of the endpoint to send the next attempt to.
+    fn pick(&self) -> usize {
+        let now = Instant::now();
+        let down_until = |endpoint: &Endpoint| endpoint.down_until.lock().unwrap().filter(|until| *until > now);
+
+        self.endpoints.iter()
+            .position(|endpoint| down_until(endpoint).is_none())
+            .or_else(|| (0..self.endpoints.len()).min_by_key(|&i| down_until(&self.endpoints[i])))
+            .unwrap_or(0)
+    }
+}
+
+impl Intercept for Failover {
+    fn name(&self) -> &'static str {
+        "Failover"
+    }
+
+    fn modify_before_signing(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some((_, authority, rest)) = split_uri(context.request().uri()) else {
+            return Ok(());
+        };
+
+        // requests to other endpoints, e.g. of an access point, are left alone
+        let subdomain = authority.strip_suffix(self.endpoints[0].authority.as_str())
+            .filter(|subdomain| subdomain.is_empty() || subdomain.ends_with('.'));
+
+        let Some(subdomain) = subdomain else {
+            return Ok(());
+        };
+
+        let i = self.pick();
+        cfg.interceptor_state().store_put(Attempted(i));
+
+        if i > 0 {
+            let endpoint = &self.endpoints[i];
+            let uri = format!("{}://{}{}{}", endpoint.scheme, subdomain, endpoint.authority, rest);
+            context.request_mut().set_uri(uri)?;
+        }
+        Ok(())
+    }
+
+    fn read_after_attempt(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(&Attempted(i)) = cfg.load::<Attempted>() else {
+            return Ok(());
+        };
+        let endpoint = &self.endpoints[i];
+        let mut down_until = endpoint.down_until.lock().unwrap();
+
+        match context.output_or_error() {
+            Some(Err(e)) if e.is_connector_error() || e.is_timeout_error() => {
+                warn!(endpoint = endpoint.authority, "endpoint unreachable, failing over: {:?}", e);
+                *down_until = Some(Instant::now() + DOWN_FOR);
+            }
+            // the endpoint answered, if only with an error
+            Some(_) if down_until.is_some() => {
+                info!(endpoint = endpoint.authority, "endpoint back up");
+                *down_until = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Scheme, authority and the rest, path and query, of `uri`.
+fn split_uri(uri: &str) -> Option<(&str, &str, &str)> {
+    let (scheme, rest) = uri.split_once("://")?;
+    let end = rest.find(['/', '?']).unwrap_or(rest.len());
+    Some((scheme, &rest[..end], &rest[end..]))
+}
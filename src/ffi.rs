@@ -0,0 +1,135 @@
+//! C interface to the blocking API, for tools written in C, C++, Go and others.
+//!
+//! Functions return [`EDIT_S3_OK`] or a negative error code, and the message of the last
+//! failure on the calling thread is kept for [`edit_s3_last_error`]. Cargo can't enable a
+//! crate type per feature, so build the library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`); the
+//! signatures are plain C for cbindgen to generate a header from.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use anyhow::{anyhow, Result};
+
+use crate::{init_session, modify, EditError, Part, S3Config, Session};
+
+pub const EDIT_S3_OK: c_int = 0;
+/// Any failure without a code of its own.
+pub const EDIT_S3_ERROR: c_int = -1;
+/// A null pointer, or a string that isn't UTF-8.
+pub const EDIT_S3_INVALID_ARGUMENT: c_int = -2;
+pub const EDIT_S3_NOT_FOUND: c_int = -3;
+/// The edit starts past the end of the object.
+pub const EDIT_S3_OUT_OF_BOUNDS: c_int = -4;
+/// The object changed during the edit, which may be retried.
+pub const EDIT_S3_CONFLICT: c_int = -5;
+pub const EDIT_S3_AUTH_FAILED: c_int = -6;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Build the global session from the JSON config `config_json`, in the format of the config
+/// file, instead of reading the file named by `S3_STORE_CONFIG` on first use.
+///
+/// # Safety
+///
+/// `config_json` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn edit_s3_init(config_json: *const c_char) -> c_int {
+    run(|| {
+        let config: S3Config = serde_json::from_str(str_arg(config_json)?)?;
+        init_session(Session::new(config)?)
+    })
+}
+
+/// Overwrite the `data_len` bytes at `offset` of `key` with `data`, extending the object if
+/// they run past its end, as [`modify`]. `key` may be an `s3://bucket/key` URI.
+///
+/// # Safety
+///
+/// `key` must be null or point to a NUL-terminated string, and `data` to `data_len` readable
+/// bytes, or be null if `data_len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn edit_s3_modify(key: *const c_char, offset: i64, data: *const u8, data_len: usize) -> c_int {
+    run(|| {
+        let key = str_arg(key)?;
+
+        let data = match (data.is_null(), data_len) {
+            (_, 0) => Vec::new(),
+            (true, _) => return Err(anyhow!("data is null").context(InvalidArgument)),
+            (false, _) => std::slice::from_raw_parts(data, data_len).to_vec(),
+        };
+
+        modify(key, Part::new(offset, data))?;
+        Ok(())
+    })
+}
+
+/// The message of the last failure on this thread, null if none failed yet. The string is
+/// owned by the library and valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn edit_s3_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+// marks errors of arguments the caller passed
+#[derive(Debug)]
+struct InvalidArgument;
+
+impl std::fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid argument")
+    }
+}
+
+/// `ptr` as a `&str`.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string living for `'a`.
+unsafe fn str_arg<'a>(ptr: *const c_char) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(anyhow!("string argument is null").context(InvalidArgument));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|e| anyhow::Error::new(e).context(InvalidArgument))
+}
+
+/// Run `f`, keeping its error message, or that of a panic, which mustn't unwind into C.
+fn run(f: impl FnOnce() -> Result<()>) -> c_int {
+    let res = catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow!("edit-s3-file panicked")));
+
+    match res {
+        Ok(()) => EDIT_S3_OK,
+        Err(e) => {
+            let code = error_code(&e);
+            // messages with NUL bytes are cut there
+            let mut message = format!("{:#}", e);
+            message.truncate(message.find('\0').unwrap_or(message.len()));
+            LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+            code
+        }
+    }
+}
+
+fn error_code(err: &anyhow::Error) -> c_int {
+    if err.downcast_ref::<InvalidArgument>().is_some() {
+        return EDIT_S3_INVALID_ARGUMENT;
+    }
+
+    match err.downcast_ref::<EditError>() {
+        Some(EditError::ObjectNotFound { .. }) => EDIT_S3_NOT_FOUND,
+        Some(EditError::RangeOutOfBounds { .. }) => EDIT_S3_OUT_OF_BOUNDS,
+        Some(EditError::Conflict { .. }) => EDIT_S3_CONFLICT,
+        Some(EditError::AuthFailed) => EDIT_S3_AUTH_FAILED,
+        _ => EDIT_S3_ERROR,
+    }
+}
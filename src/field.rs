@@ -0,0 +1,25 @@
+/// A fixed-size value that can be written into an object as raw bytes, for patching a
+/// field of a fixed-layout binary file with [`Part::le`](crate::Part::le) or
+/// [`Part::be`](crate::Part::be).
+pub trait Field: Copy {
+    fn to_le_vec(self) -> Vec<u8>;
+    fn to_be_vec(self) -> Vec<u8>;
+}
+
+macro_rules! impl_field {
+    ($($ty:ty),*) => {
+        $(
+            impl Field for $ty {
+                fn to_le_vec(self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+
+                fn to_be_vec(self) -> Vec<u8> {
+                    self.to_be_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_field!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
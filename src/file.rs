@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, Result};
+use tracing::warn;
+
+use crate::{session_for, Part, Session, DEFAULT_READ_WINDOW, RT};
+
+/// File-like access to an object through `std::io::{Read, Write, Seek}`, for code written
+/// against `std::fs::File`.
+///
+/// Reads go through a read-ahead window of [`DEFAULT_READ_WINDOW`] bytes fetched with ranged
+/// `get_object` calls. Writes are kept in memory as dirty regions, which reads see, until
+/// [`Write::flush`] (or drop) applies them all as one [`Session::modify_many`]. A write may
+/// extend the object, but not start past its end, which fails with
+/// [`io::ErrorKind::InvalidInput`] rather than leave a hole. The object's length is taken
+/// when it is opened, and nothing guards against others writing it in the meantime.
+///
+/// A flush on drop can't report failures, so flush before dropping to see them. Like the
+/// other blocking functions, this must not be used from within an async runtime.
+pub struct S3File {
+    session: Session,
+    key: String,
+    // length with the dirty regions, and as last flushed
+    len: u64,
+    stored_len: u64,
+    pos: u64,
+    // start and bytes of the last window read
+    window: Option<(u64, Vec<u8>)>,
+    // disjoint, non-adjacent regions written since the last flush, by start
+    dirty: BTreeMap<u64, Vec<u8>>,
+}
+
+impl S3File {
+    /// Open `key` on the global session, `key` may be an `s3://bucket/key` URI.
+    pub fn open(key: &str) -> Result<Self> {
+        let (session, key) = session_for(key)?;
+        Self::from_session(&session, key)
+    }
+
+    pub fn from_session(session: &Session, key: &str) -> Result<Self> {
+        let len = RT.block_on(session.stat(key))?
+            .ok_or_else(|| anyhow!("{} not found", key))?
+            .len as u64;
+
+        Ok(S3File {
+            session: session.clone(),
+            key: key.to_string(),
+            len,
+            stored_len: len,
+            pos: 0,
+            window: None,
+            dirty: BTreeMap::new(),
+        })
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Length of the object with the writes not flushed yet.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy the stored bytes at `offset` into `buf`, up to the end of the window holding
+    /// `offset`, fetching it if needed. Returns the bytes copied.
+    fn read_stored(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let cached = self.window.as_ref()
+            .is_some_and(|(start, data)| *start <= offset && offset < *start + data.len() as u64);
+
+        if !cached {
+            let len = std::cmp::min(DEFAULT_READ_WINDOW as u64, self.stored_len - offset);
+            let data = RT.block_on(self.session.read_at(&self.key, offset as i64, len as i64))
+                .map_err(io::Error::other)?;
+            self.window = Some((offset, data));
+        }
+
+        let (start, data) = self.window.as_ref().unwrap();
+        let from = (offset - start) as usize;
+        let n = std::cmp::min(buf.len(), data.len() - from);
+        buf[..n].copy_from_slice(&data[from..from + n]);
+        Ok(n)
+    }
+}
+
+impl Read for S3File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let pos = self.pos;
+        let max = std::cmp::min(buf.len() as u64, self.len - pos) as usize;
+
+        // the dirty region holding `pos`, or else where the next one starts
+        let holding = self.dirty.range(..=pos).next_back()
+            .filter(|(start, data)| pos < **start + data.len() as u64);
+
+        let n = match holding {
+            Some((start, data)) => {
+                let from = (pos - start) as usize;
+                let n = std::cmp::min(max, data.len() - from);
+                buf[..n].copy_from_slice(&data[from..from + n]);
+                n
+            }
+            None => {
+                // written regions cover everything past `stored_len`
+                let next = self.dirty.range(pos..).next().map_or(self.stored_len, |(start, _)| std::cmp::min(*start, self.stored_len));
+                let max = std::cmp::min(max as u64, next - pos) as usize;
+                self.read_stored(pos, &mut buf[..max])?
+            }
+        };
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for S3File {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.pos > self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} write at {} would leave a hole after the end of the {} byte object", self.key, self.pos, self.len),
+            ));
+        }
+
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let mut start = self.pos;
+        let end = start + data.len() as u64;
+
+        // merge the regions overlapping or touching the new one into it
+        let touching = self.dirty.range(..=end)
+            .filter(|(s, d)| **s + d.len() as u64 >= start)
+            .map(|(s, _)| *s)
+            .collect::<Vec<_>>();
+
+        let mut merged_end = end;
+        let mut olds = Vec::with_capacity(touching.len());
+
+        for s in touching {
+            let old = self.dirty.remove(&s).unwrap();
+            start = std::cmp::min(start, s);
+            merged_end = std::cmp::max(merged_end, s + old.len() as u64);
+            olds.push((s, old));
+        }
+
+        let mut merged = vec![0; (merged_end - start) as usize];
+
+        for (s, old) in olds {
+            let from = (s - start) as usize;
+            merged[from..from + old.len()].copy_from_slice(&old);
+        }
+        let from = (self.pos - start) as usize;
+        merged[from..from + data.len()].copy_from_slice(data);
+
+        self.dirty.insert(start, merged);
+        self.pos = end;
+        self.len = std::cmp::max(self.len, end);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+
+        let parts = self.dirty.iter()
+            .map(|(start, data)| Part::new(*start as i64, data.clone()))
+            .collect();
+
+        RT.block_on(self.session.modify_many(&self.key, parts))
+            .map_err(io::Error::other)?;
+
+        self.dirty.clear();
+        self.window = None;
+        self.stored_len = self.len;
+        Ok(())
+    }
+}
+
+impl Seek for S3File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            }
+            SeekFrom::End(offset) => (self.len, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+
+        match base.checked_add_signed(offset) {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative or overflowing position")),
+        }
+    }
+}
+
+impl Drop for S3File {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!(key = self.key, "failed to flush file: {}", e);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+
+/// Checks run at the steps of a session's edits, set with
+/// [`Session::with_hooks`](crate::Session::with_hooks), e.g. to enforce a size or checksum
+/// policy. A hook returning an error vetoes the edit, which then fails with
+/// [`EditError::Vetoed`](crate::EditError::Vetoed) caused by that error.
+pub trait EditHooks: Send + Sync {
+    /// Called with the `head_object` of `key` before an edit of it is planned; nothing has
+    /// been written yet. Edits of a part with an assumed length and no ETag to match make no
+    /// `head_object` and don't call it.
+    fn before_plan(&self, key: &str, obj: &HeadObjectOutput) -> Result<()> {
+        let _ = (key, obj);
+        Ok(())
+    }
+
+    /// Called with the parts of a multipart upload before it is completed. A veto aborts the
+    /// upload, even one started with an `upload_token`, leaving the object as it was. Single
+    /// puts don't call it.
+    fn before_complete(&self, report: &EditReport) -> Result<()> {
+        let _ = report;
+        Ok(())
+    }
+}
+
+/// A multipart upload about to be completed, given to [`EditHooks::before_complete`].
+#[derive(Debug, Clone)]
+pub struct EditReport {
+    pub key: String,
+    pub upload_id: String,
+    /// Part numbers and ETags, in part number order.
+    pub parts: Vec<(i32, String)>,
+    /// Length of the object the parts make up, unless some were added with
+    /// [`Upload::add_uploaded_part`](crate::Upload::add_uploaded_part), of unknown length.
+    pub len: Option<i64>,
+    /// ETag the object will have, unless the parts' ETags aren't MD5s (SSE-KMS, SSE-C) or the
+    /// bucket is a directory bucket.
+    pub expected_etag: Option<String>,
+    /// Checksums of `checksum_algorithm` by part number, if set.
+    pub checksums: HashMap<i32, String>,
+}
+
+/// The [`EditHooks`] of a session, shared by its clones and uploads.
+#[derive(Clone)]
+pub(crate) struct Hooks(Arc<dyn EditHooks>);
+
+impl Hooks {
+    pub(crate) fn new(hooks: impl EditHooks + 'static) -> Self {
+        Hooks(Arc::new(hooks))
+    }
+}
+
+impl std::ops::Deref for Hooks {
+    type Target = dyn EditHooks;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hooks(..)")
+    }
+}
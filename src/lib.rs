@@ -1,175 +1,1966 @@
-#![feature(once_cell_try)]
-#![feature(lazy_cell)]
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::pin::Pin;
+#[cfg(feature = "blocking")]
+use std::sync::LazyLock;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime};
 
-use std::sync::{LazyLock, OnceLock};
-
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use aws_sdk_s3::Client;
-use aws_sdk_s3::config::{Credentials, SharedCredentialsProvider};
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::operation::{RequestId, RequestIdExt};
+use aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder;
 use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
-use aws_types::region::Region;
-use aws_types::SdkConfig;
+use aws_sdk_s3::types::builders::CompletedPartBuilder;
+use aws_sdk_s3::types::{Checksum, ChecksumAlgorithm, ObjectAttributes, RestoreRequest};
+use bytes::Bytes;
+use futures_util::future::{try_join, try_join_all};
+use futures_util::StreamExt;
+use memmap2::MmapOptions;
 use serde::Deserialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+#[cfg(feature = "blocking")]
 use tokio::runtime::Runtime;
+use tracing::info;
+
+pub use backend::{modify_with_backend, ObjectStoreBackend};
+pub use chunked::ChunkedObject;
+pub use config::S3ConfigBuilder;
+pub use cost::{CostEstimate, Pricing};
+pub use edit_plan::EditPlan;
+pub use edit_transaction::EditTransaction;
+pub use error::{sdk_error, EditError};
+pub use etag::compute_multipart_etag;
+pub use field::Field;
+pub use hooks::{EditHooks, EditReport};
+#[cfg(feature = "blocking")]
+pub use file::S3File;
+pub use plan::{coalesce_edits, plan, plan_adaptive, plan_along_parts, validate_plan, PartOp, PartSize, MAX_COPY_PART_SIZE, MAX_OBJECT_SIZE, MAX_PARTS, MAX_PART_SIZE, MIN_PART_SIZE};
+pub use list::ObjectPages;
+#[cfg(feature = "testcontainers")]
+pub use minio::MinioContainer;
+pub use mock::MockBackend;
+pub use part_source::PartSource;
+pub use patch::{encode_patch, parse_patch};
+pub use presign::{PresignedEdit, PresignedPart, PresignedRequest};
+pub use progress::ProgressEvent;
+pub use reader::{AsyncRangeReader, DEFAULT_READ_WINDOW};
+pub use session::Session;
+pub use transaction::Transaction;
+pub use upload::Upload;
+#[cfg(feature = "blocking")]
+pub use writer::RegionWriter;
+// the token `Session::with_cancellation` takes
+pub use tokio_util::sync::CancellationToken;
+
+use config::is_access_point_arn;
+use spill::{PartBody, PartBuffer};
+use upload::Completion;
+use sse::{SseCustomer, SseCustomerExt};
+
+mod backend;
+mod chunked;
+mod config;
+mod cost;
+mod credentials;
+mod edit_plan;
+mod edit_transaction;
+mod error;
+mod etag;
+mod failover;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod field;
+#[cfg(feature = "blocking")]
+mod file;
+mod hooks;
+mod list;
+mod lock;
+#[cfg(feature = "testcontainers")]
+mod minio;
+mod mock;
+mod part_source;
+mod patch;
+mod presign;
+mod plan;
+mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+mod reader;
+mod session;
+mod spill;
+mod sse;
+mod stat_cache;
+mod telemetry;
+mod throttle;
+mod transaction;
+mod upload;
+#[cfg(feature = "blocking")]
+mod writer;
 
 pub struct Part {
     index: i64,
-    data: Option<Vec<u8>>,
+    data: Option<Bytes>,
+    expected_obj_len: Option<i64>,
+    expected_total_len: Option<i64>,
+    assume_len: Option<i64>,
+    expected_prefix: Option<Vec<u8>>,
 }
 
 impl Part {
     pub fn new(index: i64, data: Vec<u8>) -> Self {
         Part {
             index,
-            data: Some(data),
+            data: Some(Bytes::from(data)),
+            expected_obj_len: None,
+            expected_total_len: None,
+            assume_len: None,
+            expected_prefix: None,
+        }
+    }
+
+    /// Part that is only applied if the object is still `expected_len` bytes long,
+    /// for indexes computed from an earlier snapshot of the object.
+    pub fn with_expected_object_len(index: i64, data: Vec<u8>, expected_len: i64) -> Self {
+        Part {
+            expected_obj_len: Some(expected_len),
+            ..Part::new(index, data)
+        }
+    }
+
+    /// Fail with [`EditError::LengthMismatch`] instead of writing if the edited object
+    /// wouldn't be `len` bytes long, for edits of fixed-layout files that must not grow or shrink.
+    pub fn with_expected_total_len(mut self, len: i64) -> Self {
+        self.expected_total_len = Some(len);
+        self
+    }
+
+    /// Take the object to be `len` bytes long instead of fetching it first, one round trip
+    /// less for callers that know the length already.
+    ///
+    /// The length isn't checked: an object shorter than `len` fails late, when a copy of its
+    /// missing bytes is rejected, and a longer one is cut to `len`. Without the fetch its
+    /// headers are unknown too, so the rewritten object gets those of
+    /// [`MetadataDirective::Replace`]. Conditional edits ([`Session::modify_cas`]) fetch the
+    /// object anyway, as do edits in a bucket with Object Lock, or whose lock configuration
+    /// can't be read: only the fetch tells whether the object is locked.
+    pub fn with_assumed_len(mut self, len: i64) -> Self {
+        self.assume_len = Some(len);
+        self
+    }
+
+    /// Fail with [`EditError::FingerprintMismatch`] instead of writing if the object doesn't
+    /// start with `prefix`, e.g. the magic number of a file format, so that an offset meant
+    /// for one layout isn't patched into an object of another. The prefix is checked with a
+    /// ranged read of the object the edit is made from.
+    pub fn with_expected_prefix(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        self.expected_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Part writing the `len` bytes at `file_offset` of the file at `path` at `dst_index`,
+    /// memory-mapped rather than read: parts the file covers are uploaded straight from the
+    /// map, so memory use doesn't grow with `len`, and pages of it are only read as their
+    /// part is sent. Fails if the file is shorter than `file_offset + len`.
+    ///
+    /// The file must not be truncated or written to until the edit is done; reading a page
+    /// cut off by truncation kills the process with `SIGBUS`.
+    pub fn from_file_mmap(path: impl AsRef<Path>, file_offset: u64, len: u64, dst_index: i64) -> Result<Self> {
+        let path = path.as_ref();
+
+        if len == 0 {
+            return Ok(Part::new(dst_index, Vec::new()));
         }
+
+        let file = std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let file_len = file.metadata()?.len();
+        ensure!(
+            file_offset.checked_add(len).is_some_and(|end| end <= file_len),
+            "{} is {} bytes, too short for {} bytes at {}",
+            path.display(),
+            file_len,
+            len,
+            file_offset
+        );
+
+        // SAFETY: the map is only read, and the caller keeps the file from changing while it is
+        let map = unsafe {
+            MmapOptions::new()
+                .offset(file_offset)
+                .len(usize::try_from(len)?)
+                .map(&file)
+        };
+        let map = map.with_context(|| format!("failed to map {}", path.display()))?;
+
+        Ok(Part {
+            data: Some(Bytes::from_owner(map)),
+            ..Part::new(dst_index, Vec::new())
+        })
+    }
+
+    /// Part writing `value` little-endian at `index`, e.g. `Part::le(16, 42u64)` for the
+    /// 8 bytes at 16..24.
+    pub fn le(index: i64, value: impl Field) -> Self {
+        Part::new(index, value.to_le_vec())
+    }
+
+    /// Part writing `value` big-endian at `index`.
+    pub fn be(index: i64, value: impl Field) -> Self {
+        Part::new(index, value.to_be_vec())
     }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct S3Config {
+    /// Endpoint URL; `https://` is assumed without a scheme, `http://` with `insecure` set.
     pub endpoint: String,
+    /// Endpoints serving the same buckets as `endpoint`, e.g. the other gateways of a MinIO
+    /// deployment, that request attempts fail over to in order when the ones before them
+    /// can't be reached; one found down is passed over for 30 seconds. An attempt fails over
+    /// on the SDK's retry of it, so it takes `read_max_retries`/`write_max_retries` of at least
+    /// 1. They may differ from `endpoint` only in scheme and host.
+    #[serde(default)]
+    pub failover_endpoints: Vec<String>,
+    /// Bucket name, or an access point ARN to go through the access point, which for a Multi-Region
+    /// Access Point has no region (`arn:aws:s3::<account>:accesspoint/<alias>.mrap`) and is signed
+    /// with SigV4A. Access point aliases (`<name>-<id>-s3alias`) and Object Lambda aliases
+    /// (`...--ol-s3`) are used like bucket names; Object Lambda only serves reads, so edits through
+    /// one fail.
     pub bucket: String,
-    pub region: String,
+    /// Region requests are signed for. Without one, [`DEFAULT_REGION`] is used, which most
+    /// S3-compatible servers and gateways accept whatever region they think they are in.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Static credentials. Without them, credentials come from the AWS default chain:
+    /// environment variables, the shared config and credentials files, web identity, SSO and
+    /// the ECS or EC2 instance role.
     pub access_key: Option<String>,
     pub secret_key: Option<String>,
+    /// Named profile of the shared AWS config and credentials files the default chain reads,
+    /// instead of `AWS_PROFILE` or `default`.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Address buckets as `endpoint/bucket` instead of `bucket.endpoint`, as MinIO and most
+    /// S3-compatible servers expect.
+    #[serde(default)]
+    pub force_path_style: bool,
+    /// Size of uploaded parts, from [`MIN_PART_SIZE`] to [`MAX_PART_SIZE`]. Copied parts are
+    /// capped at [`MAX_COPY_PART_SIZE`] whatever this is.
+    #[serde(default)]
+    pub part_size: PartSize,
+    /// Largest part body assembled in memory when an edit is padded with original bytes;
+    /// bigger ones are spilled to a temp file. Unbounded by default.
+    #[serde(default)]
+    pub max_memory_bytes: Option<u64>,
+    /// Bytes of windows a [`ChunkedObject`] keeps cached, [`DEFAULT_CHUNK_CACHE_BYTES`] if not set.
+    #[serde(default)]
+    pub chunk_cache_bytes: Option<u64>,
+    /// Request a restore when an edit hits an archived object, instead of only failing with
+    /// [`EditError::ObjectArchived`]. The edit still fails; retry it once the restore is done.
+    #[serde(default)]
+    pub restore_archived: bool,
+    /// Fetch the rewritten object's checksum with `get_object_attributes` after each modify,
+    /// returned in [`ModifyStats::final_checksum`].
+    #[serde(default)]
+    pub compute_final_checksum: bool,
+    /// Build each edit as a temporary object next to the target and copy it over the target
+    /// only once complete, removing the temporary object either way. This costs a server-side
+    /// copy of the whole object per edit, plus a delete.
+    #[serde(default)]
+    pub atomic_edits: bool,
+    /// Name of the temporary objects of `atomic_edits`, `<key>.tmp-<uuid>` by default.
+    #[serde(default)]
+    pub temp_key_strategy: TempKeyStrategy,
+    /// Use `http://` for an endpoint given without a scheme.
+    #[serde(default)]
+    pub insecure: bool,
+    /// Most requests a session runs at once in operations that parallelize, such as
+    /// [`Session::read_ranges`] and the part copies of edits. [`DEFAULT_CONCURRENCY`] if not set.
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /// Largest object [`Session::read`] loads into memory, [`DEFAULT_MAX_READ_BYTES`] if not set.
+    #[serde(default)]
+    pub max_read_bytes: Option<u64>,
+    /// Largest piece object bodies are read in, [`DEFAULT_READ_BUFFER_SIZE`] if not set. Lower
+    /// it to cap the memory of each read in flight, raise it for fewer, bigger copies.
+    #[serde(default)]
+    pub read_buffer_size: Option<usize>,
+    /// S3 gives every rewritten object a new `Last-Modified`, and it can't be set. With this set,
+    /// modifies keep the source's `Expires` and record its `Last-Modified` (the first one, across
+    /// repeated edits) as HTTP date in the [`ORIGINAL_LAST_MODIFIED_KEY`] user metadata.
+    #[serde(default)]
+    pub preserve_timestamps: bool,
+    /// Metadata of rewritten objects: the source's ([`MetadataDirective::Copy`], the default)
+    /// or only `replace_metadata`.
+    #[serde(default)]
+    pub metadata_directive: MetadataDirective,
+    /// User metadata of rewritten objects with [`MetadataDirective::Replace`].
+    #[serde(default)]
+    pub replace_metadata: HashMap<String, String>,
+    /// Retries of reads and part uploads and copies, [`DEFAULT_READ_MAX_RETRIES`] if not set.
+    /// These are safe to repeat: a part number uploaded again replaces the earlier one.
+    #[serde(default)]
+    pub read_max_retries: Option<u32>,
+    /// Retries of requests that change the object or upload state (put, create, complete,
+    /// copy, delete, ...), [`DEFAULT_WRITE_MAX_RETRIES`] if not set. Kept low because repeating
+    /// one whose response was lost, such as a completion, can fail or act twice.
+    #[serde(default)]
+    pub write_max_retries: Option<u32>,
+    /// Backoff before the first retry of a failed request, doubling on each further one up to
+    /// `retry_max_backoff_ms`, with full jitter. The SDK's default (1 second) if not set.
+    #[serde(default)]
+    pub retry_initial_backoff_ms: Option<u64>,
+    /// Longest backoff between retries, the SDK's default (20 seconds) if not set.
+    #[serde(default)]
+    pub retry_max_backoff_ms: Option<u64>,
+    /// Rewrite edited objects with a single `put_object` of the whole edited object, downloaded
+    /// into memory, instead of a multipart upload with server-side copies, for servers that
+    /// mishandle `upload_part_copy`, which [`Session::detect_capabilities`] can tell. Objects over
+    /// 5GB, the most one put can write, fail.
+    #[serde(default)]
+    pub force_single_put: bool,
+    /// Read the object's ACL grants before each modify and put them back on the result, which
+    /// S3 otherwise resets to the bucket default. Skipped on buckets with ACLs disabled.
+    #[serde(default)]
+    pub preserve_acl: bool,
+    /// Read the object's tags before each modify and put them back on the result, which
+    /// otherwise has none. Skipped on servers without tagging support.
+    #[serde(default)]
+    pub preserve_tags: bool,
+    /// Pad edits smaller than S3's 5MB minimum part size with the original bytes around them
+    /// into a legal part, which reads up to a few MB of neighboring data per edit from the
+    /// object. On by default; with it off such edits fail instead of reading anything.
+    #[serde(default = "pad_to_part_default")]
+    pub pad_to_part: bool,
+    /// Rebuild multipart objects along their own part boundaries, see [`plan_along_parts`]:
+    /// untouched parts are copied as they are and edited ones uploaded whole, so the result
+    /// keeps the source's layout. Edits read whole source parts instead of a few MB around
+    /// them. Single-part objects and servers that don't report parts use the default plan.
+    #[serde(default)]
+    pub preserve_part_layout: bool,
+    /// Only create objects: writes fail with [`EditError::AlreadyExists`] instead of replacing
+    /// an existing destination, for sessions writing new objects with `modify_into`, `replace`
+    /// and the like. In-place edits always fail with this set.
+    #[serde(default)]
+    pub if_none_match: bool,
+    /// Pace the part uploads of a session to this many bytes per second on average, shared by
+    /// its clones. Server-side part copies don't go through the client's link and aren't
+    /// paced. Whole parts are paced, so with parts as big as the default 1GB the rate is only
+    /// kept over several parts. Unlimited if not set.
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+    /// Base64 256-bit key of objects encrypted with SSE-C. It is sent, with its MD5, on every
+    /// request that reads or writes object data, as the source and the destination key of
+    /// copies, so all objects a session edits or copies between must use this key.
+    #[serde(default)]
+    pub sse_customer_key: Option<String>,
+    /// Encrypt the objects the session writes with SSE-S3 or SSE-KMS, whatever the bucket
+    /// default. Without it an edit keeps the encryption of the object it rewrites. Can't be
+    /// combined with `sse_customer_key`.
+    #[serde(default)]
+    pub sse: Option<SseMode>,
+    /// Storage class of the objects the session writes, as S3 names it, e.g. `"STANDARD_IA"`
+    /// or `"INTELLIGENT_TIERING"`. Without it an edit keeps the storage class of the object it
+    /// rewrites, and new objects get the bucket's default.
+    #[serde(default)]
+    pub storage_class: Option<String>,
+    /// Idempotency token of the uploads of this session, see [`Session::with_upload_token`].
+    #[serde(default)]
+    pub upload_token: Option<String>,
+    /// Server the endpoint is, turning on the settings it needs, see [`CompatMode`].
+    #[serde(default)]
+    pub compat: CompatMode,
+    /// Kind of `bucket`, detected from its name if not set: names ending in `--x-s3` are
+    /// [`BucketType::Directory`].
+    #[serde(default)]
+    pub bucket_type: Option<BucketType>,
+    /// Send `x-amz-request-payer: requester` with every request, to edit objects of a
+    /// requester-pays bucket at the session's own expense. Not applied to the client of
+    /// [`Session::from_client`]; see [`Session::with_requester_pays`] to override it.
+    #[serde(default)]
+    pub requester_pays: bool,
+    /// Log every part of an edit's plan, with its number, kind and byte range, before the
+    /// upload starts, so an edit can be followed from the logs alone.
+    #[serde(default)]
+    pub log_plan: bool,
+    /// Send a CRC32 checksum with bodies streamed by [`Session::replace`] in a single put. The
+    /// SDK computes it while sending the `aws-chunked` body and adds it as a trailer, which the
+    /// server checks, so the body needn't be buffered first. AWS S3 and MinIO support trailers;
+    /// GCS, older Ceph RGW and some other servers reject such uploads. Multipart parts are
+    /// buffered in memory and not covered.
+    #[serde(default)]
+    pub stream_checksums: bool,
+    /// Keep what [`Session::stat`] fetches for this many seconds, saving the `head_object` of
+    /// repeated stats and of the calls checking the object length first, such as
+    /// [`Session::read_ranges`]. Writes through the session, or its clones, drop the cached
+    /// stat of the key they write; writes by other clients are only seen once it expires.
+    /// Nothing is cached if not set.
+    #[serde(default)]
+    pub stat_cache_ttl_secs: Option<u64>,
+    /// Most keys the stat cache holds, [`DEFAULT_STAT_CACHE_SIZE`] if not set.
+    #[serde(default)]
+    pub stat_cache_size: Option<usize>,
+    /// Copy the unchanged regions of edits in parts as big as an `upload_part_copy` allows,
+    /// with `part_size` only sizing uploaded parts, see [`plan_adaptive`]. Worth it with small
+    /// part sizes, where large objects otherwise take many copy requests.
+    #[serde(default)]
+    pub adaptive_part_size: bool,
+    /// Merge edits separated by at most this many bytes into one uploaded region, see
+    /// [`coalesce_edits`]. Trades downloading and uploading the bytes between them for fewer
+    /// copy requests and parts, worth it where requests cost more than transfer, e.g. for
+    /// many small edits a few MB apart. Edits are only merged as [`plan`] does if not set.
+    #[serde(default)]
+    pub coalesce_gap_bytes: Option<i64>,
+    /// Seconds to wait for a connection to the endpoint, the SDK's default if not set.
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    /// Seconds each request attempt may take, response body included, before it fails and is
+    /// retried like other failures. Keep it above what uploading or copying a whole part takes
+    /// on the slowest link. Unlimited if not set.
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+    /// Seconds a response may go without sending a byte before the attempt fails and is
+    /// retried, which catches stalled connections without capping slow transfers as
+    /// `request_timeout_secs` does. The SDK's default if not set.
+    #[serde(default)]
+    pub read_timeout_secs: Option<u64>,
+    /// PEM file of root certificates to trust besides the system ones, for endpoints with a
+    /// certificate from a private CA, as MinIO and Ceph often have.
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    /// Read back the edited ranges after each modify and compare them with the edits, failing
+    /// with [`EditError::VerificationFailed`] if they differ. Costs a ranged `get_object` and
+    /// the download of every edit.
+    #[serde(default)]
+    pub verify_edits: bool,
+    /// Additional checksum of multipart uploads: uploaded parts are sent with it, which the
+    /// server checks, copied parts get it computed server-side, and the completion lists
+    /// them all, so the server checks the object against the parts end to end. Parts added
+    /// with [`Upload::add_uploaded_part`] have none and fail the completion. Not set by default.
+    #[serde(default)]
+    pub checksum_algorithm: Option<PartChecksum>,
+    /// Hold a `<key>.lock` object, created only if absent, while editing `key`, so processes
+    /// editing the same key take turns instead of one edit overwriting the other. Every
+    /// writer of the key must set it. Covers the edits of [`Session::modify_many`] and the
+    /// functions built on it, and those rewriting an object part by part, such as
+    /// [`Session::splice`], [`Session::modify_from`] and [`Session::insert`].
+    #[serde(default)]
+    pub edit_lock: bool,
+    /// Seconds to wait for a held lock before failing with [`EditError::Locked`],
+    /// [`DEFAULT_LOCK_WAIT_SECS`] if not set.
+    #[serde(default)]
+    pub lock_wait_secs: Option<u64>,
+    /// Age in seconds after which a lock is taken as left behind by a crashed process and
+    /// removed, [`DEFAULT_LOCK_TTL_SECS`] if not set. Keep it above the longest edit.
+    #[serde(default)]
+    pub lock_ttl_secs: Option<u64>,
+    /// Give the edited object the Object Lock retention and legal hold of the one it
+    /// rewrites, which a new version doesn't inherit. Without it, an edit of an object under
+    /// retention or legal hold fails with [`EditError::ObjectLocked`]. Edits of locked objects
+    /// skip `atomic_edits`, as the temp object would be locked too.
+    #[serde(default)]
+    pub propagate_object_lock: bool,
+}
+
+/// What a rewrite does with the source object's metadata, like S3's `x-amz-metadata-directive`
+/// on copies. In config files this is `"copy"` or `"replace"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataDirective {
+    /// Keep the source's user metadata, content type and other content headers.
+    #[default]
+    Copy,
+    /// Write only [`S3Config::replace_metadata`], without content headers.
+    Replace,
+}
+
+/// Server-side encryption of written objects, see [`S3Config::sse`]. In config files this is
+/// `"s3"`, or `{"kms": {}}` for the AWS managed key and `{"kms": {"key_id": "<key id>"}}` for
+/// a customer managed one.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SseMode {
+    /// SSE-S3, keys managed by S3 (`AES256`).
+    S3,
+    /// SSE-KMS (`aws:kms`), with the key `key_id`, or the AWS managed key if not set.
+    Kms {
+        #[serde(default)]
+        key_id: Option<String>,
+    },
+}
+
+/// Additional checksum algorithm of multipart uploads, see [`S3Config::checksum_algorithm`].
+/// In config files this is `"crc32"`, `"crc32c"`, `"sha1"` or `"sha256"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PartChecksum {
+    Crc32,
+    Crc32c,
+    Sha1,
+    Sha256,
+}
+
+impl PartChecksum {
+    pub(crate) fn algorithm(self) -> ChecksumAlgorithm {
+        match self {
+            PartChecksum::Crc32 => ChecksumAlgorithm::Crc32,
+            PartChecksum::Crc32c => ChecksumAlgorithm::Crc32C,
+            PartChecksum::Sha1 => ChecksumAlgorithm::Sha1,
+            PartChecksum::Sha256 => ChecksumAlgorithm::Sha256,
+        }
+    }
+
+    /// The one of a part's checksums, `[crc32, crc32c, sha1, sha256]`, of this algorithm.
+    pub(crate) fn pick(self, checksums: [Option<&str>; 4]) -> Option<String> {
+        let [crc32, crc32c, sha1, sha256] = checksums;
+
+        let checksum = match self {
+            PartChecksum::Crc32 => crc32,
+            PartChecksum::Crc32c => crc32c,
+            PartChecksum::Sha1 => sha1,
+            PartChecksum::Sha256 => sha256,
+        };
+        checksum.map(String::from)
+    }
+
+    /// `part` completed with its `checksum` of this algorithm.
+    pub(crate) fn complete(self, part: CompletedPartBuilder, checksum: Option<String>) -> CompletedPartBuilder {
+        match self {
+            PartChecksum::Crc32 => part.set_checksum_crc32(checksum),
+            PartChecksum::Crc32c => part.set_checksum_crc32_c(checksum),
+            PartChecksum::Sha1 => part.set_checksum_sha1(checksum),
+            PartChecksum::Sha256 => part.set_checksum_sha256(checksum),
+        }
+    }
+}
+
+/// Known S3-compatible servers, whose quirks a session works around when `compat` names
+/// them. A mode only turns settings on, never off, so explicitly set ones are kept.
+/// In config files this is the lowercase name, e.g. `"minio"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompatMode {
+    /// Amazon S3, changes nothing.
+    #[default]
+    Aws,
+    /// Sets `force_path_style`, as MinIO doesn't serve bucket subdomains unless configured to.
+    MinIO,
+    /// Sets `force_path_style`, which Ceph RGW gateways serve without wildcard DNS.
+    Ceph,
+    /// Sets `force_path_style` for Backblaze B2's S3 API.
+    B2,
+    /// Sets `force_path_style` for Wasabi.
+    Wasabi,
+    /// Sets `force_path_style` and `force_single_put` for Google Cloud Storage's XML API,
+    /// which has no `upload_part_copy`.
+    Gcs,
+}
+
+impl CompatMode {
+    pub(crate) fn apply(self, config: &mut S3Config) {
+        if self != CompatMode::Aws {
+            config.force_path_style = true;
+        }
+
+        if self == CompatMode::Gcs {
+            config.force_single_put = true;
+        }
+    }
+}
+
+/// Kind of bucket a session edits, see [`S3Config::bucket_type`]. In config files this is
+/// `"general"` or `"directory"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketType {
+    General,
+    /// An S3 Express One Zone directory bucket. The SDK authenticates to it with sessions
+    /// from `CreateSession` and addresses it virtual-hosted-style, so `force_path_style`,
+    /// `preserve_acl` and `preserve_tags`, which it has no support for, are rejected. Its
+    /// ETags aren't MD5s, so completions that may have happened aren't checked against them.
+    Directory,
+}
+
+impl BucketType {
+    pub(crate) fn of(config: &S3Config) -> Self {
+        config.bucket_type.unwrap_or(match config.bucket.ends_with("--x-s3") {
+            true => BucketType::Directory,
+            false => BucketType::General,
+        })
+    }
+}
+
+/// How the temporary object of an atomic edit of a key is named, e.g. to put it where
+/// lifecycle rules clean up what a crash leaves behind. In config files this is
+/// `{"suffix": ".tmp-"}` or `{"prefix": "tmp/"}`.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TempKeyStrategy {
+    /// `<key><suffix><uuid>`.
+    Suffix(String),
+    /// `<prefix><key>.tmp-<uuid>`.
+    Prefix(String),
+    /// Named by the function from the edited key. It must return a new key on every call,
+    /// as concurrent edits of the same key would otherwise share one.
+    #[serde(skip)]
+    Custom(Arc<dyn Fn(&str) -> String + Send + Sync>),
+}
+
+impl Default for TempKeyStrategy {
+    fn default() -> Self {
+        TempKeyStrategy::Suffix(".tmp-".to_string())
+    }
+}
+
+impl fmt::Debug for TempKeyStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TempKeyStrategy::Suffix(suffix) => f.debug_tuple("Suffix").field(suffix).finish(),
+            TempKeyStrategy::Prefix(prefix) => f.debug_tuple("Prefix").field(prefix).finish(),
+            TempKeyStrategy::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+fn pad_to_part_default() -> bool {
+    true
 }
 
-pub const PATH_ENV: &'static str = "S3_STORE_CONFIG";
+pub const PATH_ENV: &str = "S3_STORE_CONFIG";
+pub const DEFAULT_CONCURRENCY: usize = 8;
+pub const DEFAULT_REGION: &str = "us-east-1";
+/// User metadata key (`x-amz-meta-original-last-modified`) written by `preserve_timestamps`.
+pub const ORIGINAL_LAST_MODIFIED_KEY: &str = "original-last-modified";
+pub const DEFAULT_READ_MAX_RETRIES: u32 = 5;
+pub const DEFAULT_WRITE_MAX_RETRIES: u32 = 1;
 // 1GB
-const PART_SIZE: i64 = 1024 * 1024 * 1024;
-static CLIENT: OnceLock<(String, Client)> = OnceLock::new();
+pub const DEFAULT_MAX_READ_BYTES: u64 = 1024 * 1024 * 1024;
+// 1MB
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 1024 * 1024;
+// 64MB
+pub const DEFAULT_CHUNK_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+/// What [`Session::redacted_config`] puts in place of secrets.
+pub const REDACTED: &str = "<redacted>";
+/// Conflicts [`Session::fetch_add_u64_le`] and [`Session::update_range`] retry before failing
+/// with [`EditError::Conflict`].
+pub const FETCH_ADD_RETRIES: usize = 100;
+pub const DEFAULT_STAT_CACHE_SIZE: usize = 1024;
+pub const DEFAULT_LOCK_WAIT_SECS: u64 = 60;
+pub const DEFAULT_LOCK_TTL_SECS: u64 = 60 * 60;
+/// Bytes [`Session::sync_from_file`] compares at a time, the granularity of the changes it finds.
+pub const SYNC_BLOCK_SIZE: usize = 1024 * 1024;
+
+static SESSION: OnceLock<Session> = OnceLock::new();
+// held while building the global session, so it's built once
+static SESSION_BUILD: Mutex<()> = Mutex::new(());
 
+#[cfg(feature = "blocking")]
 static RT: LazyLock<Runtime> = LazyLock::new(|| {
-    tokio::runtime::Builder::new_current_thread()
+    tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap()
 });
 
-pub fn modify(
-    key: &str,
-    mut modify_part: Part,
+/// The global session behind the free functions: the one given to [`init_session`], or else
+/// one built from the config file named by [`PATH_ENV`].
+///
+/// It's built exactly once, also when first called from many threads at once: the others wait
+/// for that build. A failed build isn't kept, so the next call retries it, e.g. once the
+/// config file exists.
+///
+/// The free functions also take `s3://bucket/key` URIs in place of a key, to reach objects
+/// in another bucket with the same endpoint and credentials.
+pub fn session() -> Result<&'static Session> {
+    if let Some(session) = SESSION.get() {
+        return Ok(session);
+    }
+
+    // nothing is left half done by a panicking build
+    let _build = SESSION_BUILD.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(session) = SESSION.get() {
+        return Ok(session);
+    }
+
+    let path = std::env::var(PATH_ENV)?;
+    let config: S3Config = serde_json::from_reader(std::fs::File::open(path)?)?;
+    let session = Session::new(config)?;
+    Ok(SESSION.get_or_init(|| session))
+}
+
+/// Make `session` the global session, e.g. one built from an [`S3Config`] value or with
+/// [`Session::with_credentials_provider`], so the free functions don't need a config file.
+/// Fails if the global session is already set or built.
+pub fn init_session(session: Session) -> Result<()> {
+    SESSION.set(session).map_err(|_| anyhow!("the global session is already initialized"))
+}
+
+/// [`Session::redacted_config`] of the global session, `None` if it isn't built yet.
+pub fn current_config() -> Option<S3Config> {
+    SESSION.get().map(Session::redacted_config)
+}
+
+/// Bucket of the global session, `None` if it isn't built yet.
+pub fn current_bucket() -> Option<&'static str> {
+    SESSION.get().map(Session::bucket)
+}
+
+/// Endpoint of the global session, `None` if it isn't built yet.
+pub fn current_endpoint() -> Option<&'static str> {
+    SESSION.get().map(Session::endpoint)
+}
+
+// the global session, or one for the bucket of an `s3://bucket/key` URI, and the bare key
+pub(crate) fn session_for(key: &str) -> Result<(Cow<'static, Session>, &str)> {
+    let session = session()?;
+
+    match parse_s3_uri(key)? {
+        Some((bucket, key)) => Ok((Cow::Owned(session.with_bucket(bucket)?), key)),
+        None => Ok((Cow::Borrowed(session), key)),
+    }
+}
+
+// sessions for a source and destination that must be in the same bucket
+fn sessions_for<'a>(src_key: &'a str, dst_key: &'a str) -> Result<(Cow<'static, Session>, &'a str, &'a str)> {
+    let (session, src) = session_for(src_key)?;
+    let (dst_session, dst) = session_for(dst_key)?;
+    ensure!(session.bucket() == dst_session.bucket(), "{} and {} are in different buckets", src_key, dst_key);
+    Ok((session, src, dst))
+}
+
+/// Split an `s3://bucket/key` URI into bucket and key, `None` for a plain key.
+///
+/// The bucket may be an access point ARN, `s3://arn:aws:s3:<region>:<account>:accesspoint/<name>/<key>`.
+pub fn parse_s3_uri(uri: &str) -> Result<Option<(&str, &str)>> {
+    let Some(rest) = uri.strip_prefix("s3://") else {
+        return Ok(None);
+    };
+
+    // the access point name is separated from the rest of the ARN by a slash too
+    let bucket_len = match rest.find(":accesspoint/") {
+        Some(i) if rest.starts_with("arn:") => {
+            let name_start = i + ":accesspoint/".len();
+            rest[name_start..].find('/').map(|j| name_start + j)
+        }
+        _ => rest.find('/'),
+    };
+
+    match bucket_len {
+        Some(len) if len > 0 && len + 1 < rest.len() => Ok(Some((&rest[..len], &rest[len + 1..]))),
+        _ => Err(anyhow!("malformed S3 URI {:?}, expected s3://bucket/key", uri)),
+    }
+}
+
+/// Fetch the bytes `start..end` of an object with a ranged `get_object`.
+pub(crate) async fn fetch_range(
+    client: Client,
+    bucket: String,
+    key: String,
+    sse: SseCustomer,
+    read_buffer_size: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<u8>> {
+    if start >= end {
+        return Ok(Vec::new());
+    }
+
+    let obj = client.get_object()
+        .bucket(bucket)
+        .key(key)
+        .range(format!("bytes={}-{}", start, end - 1))
+        .sse_customer(&sse)
+        .send()
+        .await?;
+
+    let mut body = body_reader(obj.body, std::cmp::min(read_buffer_size, (end - start) as usize));
+    let mut data = Vec::with_capacity((end - start) as usize);
+    body.read_to_end(&mut data).await?;
+    Ok(data)
+}
+
+/// `body` read at most `read_buffer_size` bytes at a time: every `fill_buf` returns that much
+/// or less, whatever the size of the chunks received.
+pub(crate) fn body_reader(body: ByteStream, read_buffer_size: usize) -> Pin<Box<dyn AsyncBufRead + Send>> {
+    Box::pin(BufReader::with_capacity(read_buffer_size.max(1), body.into_async_read()))
+}
+
+/// `read_buffer_size` of `config`, with the default applied.
+pub(crate) fn read_buffer_size(config: &S3Config) -> usize {
+    config.read_buffer_size.unwrap_or(DEFAULT_READ_BUFFER_SIZE)
+}
+
+/// `If-None-Match` of the requests that complete a write, `*` with `if_none_match` set.
+pub(crate) fn if_none_match(config: &S3Config) -> Option<String> {
+    config.if_none_match.then(|| "*".to_string())
+}
+
+/// Override for requests that change the object, which run with `write_max_retries` instead
+/// of the client's `read_max_retries`.
+pub(crate) fn write_retries(config: &S3Config) -> aws_sdk_s3::config::Builder {
+    let retries = config.write_max_retries.unwrap_or(DEFAULT_WRITE_MAX_RETRIES);
+    aws_sdk_s3::config::Builder::new().retry_config(retry_config(config, retries))
+}
+
+/// Standard retries, with the backoff of `retry_initial_backoff_ms` and `retry_max_backoff_ms`,
+/// up to `retries` times.
+pub(crate) fn retry_config(config: &S3Config, retries: u32) -> RetryConfig {
+    let mut retry_config = RetryConfig::standard().with_max_attempts(retries + 1);
+
+    if let Some(ms) = config.retry_initial_backoff_ms {
+        retry_config = retry_config.with_initial_backoff(Duration::from_millis(ms));
+    }
+    if let Some(ms) = config.retry_max_backoff_ms {
+        retry_config = retry_config.with_max_backoff(Duration::from_millis(ms));
+    }
+    retry_config
+}
+
+/// `copy_source` of `key`, which for access points is `<arn>/object/<key>`. The key is
+/// URL-encoded as S3 expects; the bucket, which may be an access point alias, is kept as is.
+pub(crate) fn copy_source(bucket: &str, key: &str) -> String {
+    let key = encode_key(key);
+
+    if is_access_point_arn(bucket) {
+        format!("{}/object/{}", bucket, key)
+    } else {
+        format!("/{}/{}", bucket, key)
+    }
+}
+
+/// `key` percent-encoded but for unreserved characters and the `/` between its segments.
+fn encode_key(key: &str) -> String {
+    percent_encode(key, b"/")
+}
+
+/// `s` percent-encoded but for unreserved characters and those of `keep`.
+fn percent_encode(s: &str, keep: &[u8]) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            b if keep.contains(&b) => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// The object an edit takes its unchanged bytes from: `key` at its current version, or at
+/// `version_id` in a versioned bucket, in the session's bucket unless `bucket` is set.
+///
+/// With `etag`, reads and copies of the object only succeed while it still has that ETag and
+/// fail with [`EditError::Conflict`] otherwise, so an edit never mixes bytes of two versions.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Source<'a> {
+    pub(crate) key: &'a str,
+    pub(crate) version_id: Option<&'a str>,
+    pub(crate) bucket: Option<&'a str>,
+    pub(crate) etag: Option<&'a str>,
+}
+
+impl<'a> From<&'a str> for Source<'a> {
+    fn from(key: &'a str) -> Self {
+        Source { key, version_id: None, bucket: None, etag: None }
+    }
+}
+
+impl<'a> Source<'a> {
+    /// Bucket of the object, that of `config` unless set.
+    pub(crate) fn bucket(&self, config: &'a S3Config) -> &'a str {
+        self.bucket.unwrap_or(&config.bucket)
+    }
+
+    /// `copy_source` of the object, with its version if set, encoded as a query parameter.
+    pub(crate) fn copy_source(&self, config: &'a S3Config) -> String {
+        let bucket = self.bucket(config);
+
+        match self.version_id {
+            Some(version_id) => format!("{}?versionId={}", copy_source(bucket, self.key), percent_encode(version_id, b"")),
+            None => copy_source(bucket, self.key),
+        }
+    }
+}
+
+/// Build the body of an uploaded part: edit data where edits cover `range`, original
+/// bytes fetched from `src` everywhere else.
+pub(crate) async fn assemble_part(
+    client: &Client,
+    config: &S3Config,
+    src: Source<'_>,
+    range: Range<i64>,
+    edits: &[(Range<i64>, Bytes)],
+) -> Result<PartBody> {
+    let covering = edits.iter()
+        .find(|(r, _)| r.start <= range.start && range.end <= r.end);
+
+    if let Some((r, data)) = covering {
+        return Ok(PartBody::Memory(data.slice((range.start - r.start) as usize..(range.end - r.start) as usize)));
+    }
+
+    let mut buf = PartBuffer::new((range.end - range.start) as u64, config.max_memory_bytes).await?;
+    let mut offset = range.start;
+
+    for (r, data) in edits.iter().filter(|(r, _)| r.start < range.end && r.end > range.start) {
+        if offset < r.start {
+            copy_range_into(client, config, src, offset..r.start, &mut buf).await?;
+        }
+
+        let start = std::cmp::max(offset, r.start);
+        let end = std::cmp::min(range.end, r.end);
+        buf.write(&data[(start - r.start) as usize..(end - r.start) as usize]).await?;
+        offset = end;
+    }
+
+    if offset < range.end {
+        copy_range_into(client, config, src, offset..range.end, &mut buf).await?;
+    }
+
+    let body = buf.finish().await?;
+    ensure!(body.len()? as i64 == range.end - range.start, "{} changed while reading range {}-{}", src.key, range.start, range.end - 1);
+    Ok(body)
+}
+
+/// Stream the original bytes of `range` into `buf` without holding them in memory.
+async fn copy_range_into(
+    client: &Client,
+    config: &S3Config,
+    src: Source<'_>,
+    range: Range<i64>,
+    buf: &mut PartBuffer,
 ) -> Result<()> {
-    let (bucket, client) = CLIENT.get_or_try_init(|| {
-        let path = std::env::var(PATH_ENV)?;
-        let config: S3Config = serde_json::from_reader(std::fs::File::open(path)?)?;
-
-        let mut builder = SdkConfig::builder()
-            .endpoint_url(config.endpoint)
-            .region(Region::new(config.region));
-
-        if let (Some(ak), Some(sk)) = (config.access_key, config.secret_key) {
-            builder = builder.credentials_provider(SharedCredentialsProvider::new(Credentials::new(
-                ak,
-                sk,
-                None,
-                None,
-                "Static",
-            )))
-        }
-        Result::<_, anyhow::Error>::Ok((config.bucket, Client::new(&builder.build())))
-    })?;
-
-    RT.block_on(async {
-        let obj = client.get_object()
-            .bucket(bucket)
-            .key(key)
-            .send()
-            .await?;
-
-        let obj_len = obj.content_length().ok_or_else(|| anyhow!("{} content length is empty", key))?;
-        let modify_part_len = modify_part.data.as_ref().unwrap().len() as i64;
-        ensure!(modify_part.index + modify_part_len <= obj_len);
-
-        let upload_out = client.create_multipart_upload()
-            .bucket(bucket)
-            .key(key)
-            .send()
-            .await?;
-
-        let upload_id = upload_out
-            .upload_id()
-            .ok_or_else(|| anyhow!("{}, must need upload id", key))?;
-
-        let mut part_num = 1;
-        let mut offset = 0;
-        let mut etags = Vec::new();
-
-        while offset < obj_len {
-            let mut end;
-
-            if offset == modify_part.index {
-                end = modify_part.index + modify_part_len;
-
-                println!("upload, part_num: {}", part_num);
-
-                let etag = client.upload_part()
-                    .bucket(bucket)
-                    .key(key)
-                    .upload_id(upload_id)
-                    .part_number(part_num)
-                    .body(ByteStream::from(modify_part.data.take().unwrap()))
-                    .send()
-                    .await?
-                    .e_tag
-                    .ok_or_else(|| anyhow!("{} must need e_tag", key))?;
-
-                etags.push(etag);
-            } else {
-                end = std::cmp::min(offset + PART_SIZE, obj_len);
-
-                if modify_part.index > offset &&
-                    modify_part.index < end {
-                    end = modify_part.index;
+    let out = client.get_object()
+        .bucket(src.bucket(config))
+        .key(src.key)
+        .set_version_id(src.version_id.map(String::from))
+        .range(format!("bytes={}-{}", range.start, range.end - 1))
+        .set_if_match(src.etag.map(String::from))
+        .sse_customer(&SseCustomer::new(config))
+        .send()
+        .await;
+
+    let out = match out {
+        Err(e) if src.etag.is_some() && e.code() == Some("PreconditionFailed") => {
+            return Err(anyhow::Error::new(e).context(EditError::Conflict { key: src.key.to_string() }));
+        }
+        res => res?,
+    };
+
+    let mut body = body_reader(out.body, read_buffer_size(config));
+
+    loop {
+        let chunk = body.fill_buf().await?;
+
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        buf.write(chunk).await?;
+        let n = chunk.len();
+        body.consume(n);
+    }
+}
+
+/// Build `key` in one multipart upload started by `create`, copying every byte outside
+/// `edits` from `src`, which is `key` itself for in-place edits. `obj_len` is the length of
+/// the result, past the end of `src` if the last edit extends it. With `if_match` the upload
+/// only completes if `key` still has that ETag.
+pub(crate) async fn rewrite(
+    session: &Session,
+    src: Source<'_>,
+    key: &str,
+    obj_len: i64,
+    edits: &[(Range<i64>, Bytes)],
+    create: CreateMultipartUploadFluentBuilder,
+    if_match: Option<&str>,
+) -> Result<ModifyStats> {
+    let (client, config) = (&session.client, &session.config);
+
+    let ranges = edits.iter().map(|(range, _)| range.clone()).collect::<Vec<_>>();
+    let part_size = config.part_size.resolve(obj_len);
+
+    // nothing is copied from a source the edits cover, which may not even exist
+    let covered = ranges.iter().map(|r| r.end - r.start).sum::<i64>();
+
+    let source_parts = match config.preserve_part_layout && covered < obj_len {
+        true => session.object_parts(src).await?.filter(|parts| parts.parts.len() == parts.total_parts as usize),
+        false => None,
+    };
+
+    let ops = match source_parts {
+        Some(parts) => {
+            let sizes = parts.parts.iter().map(|(_, size)| *size).collect::<Vec<_>>();
+            plan_along_parts(obj_len, &sizes, &ranges, part_size)
+        }
+        None => plan_edits(config, obj_len, &ranges, part_size),
+    };
+    validate_plan(&ops, obj_len)?;
+    session.report_plan(key, &ops);
+
+    if !config.pad_to_part {
+        for op in &ops {
+            let PartOp::Upload(range) = op else {
+                continue;
+            };
+
+            let covered = covered_by(&ranges, range);
+            ensure!(covered == range.end - range.start, "{} part {}-{} needs padding with original bytes, but pad_to_part is off", key, range.start, range.end - 1);
+        }
+    }
+
+    let mut upload = Upload::begin(session, key, create).await?;
+    let mut stats = ModifyStats::default();
+
+    for op in &ops {
+        match op {
+            PartOp::Upload(range) => {
+                stats.ranges.push((RangeKind::Uploaded, range.start, range.end));
+                stats.bytes_read += (range.end - range.start - covered_by(&ranges, range)) as u64;
+            }
+            PartOp::Copy(range) => stats.ranges.push((RangeKind::Copied, range.start, range.end)),
+        }
+    }
+
+    let res = async {
+        let first = upload.reserve_parts(ops.len())?;
+        let upload = &upload;
+
+        // copies run server-side, up to `max_concurrency` at once, while uploads, which hold
+        // a part in memory each, go one after another next to them
+        let copies = ops.iter()
+            .zip(first..)
+            .filter_map(|(op, part_num)| match op {
+                PartOp::Copy(range) => Some((part_num, range.clone())),
+                PartOp::Upload(_) => None,
+            })
+            .map(|(part_num, range)| async move {
+                let _permit = session.permits.acquire().await?;
+                let etag = upload.copy_part(part_num, src, range.start, range.end - range.start).await?;
+                Result::<_, anyhow::Error>::Ok((part_num, etag))
+            });
+
+        let uploads = async {
+            let mut etags = Vec::new();
+
+            for (op, part_num) in ops.iter().zip(first..) {
+                if let PartOp::Upload(range) = op {
+                    let body = assemble_part(client, config, src, range.clone(), edits).await?;
+                    etags.push((part_num, upload.upload_part(part_num, body.stream().await?).await?));
                 }
+            }
+            Result::<_, anyhow::Error>::Ok(etags)
+        };
+
+        let (copied, uploaded) = try_join(try_join_all(copies), uploads).await?;
+        Result::<_, anyhow::Error>::Ok(copied.into_iter().chain(uploaded))
+    }.await;
+
+    match res {
+        Ok(parts) => upload.add_parts(parts),
+        Err(e) => return Err(upload.fail(e).await),
+    }
+
+    stats.record(upload.finish(if_match).await?);
+
+    if config.compute_final_checksum {
+        stats.final_checksum = final_checksum(client, config, key).await?;
+    }
+
+    Ok(stats)
+}
+
+/// The [`plan`], or with `adaptive_part_size` set the [`plan_adaptive`], of an edit, its
+/// ranges first merged across gaps of up to `coalesce_gap_bytes`.
+pub(crate) fn plan_edits(config: &S3Config, obj_len: i64, edits: &[Range<i64>], part_size: i64) -> Vec<PartOp> {
+    let coalesced;
+    let edits = match config.coalesce_gap_bytes {
+        Some(gap) => {
+            coalesced = coalesce_edits(edits, gap);
+            &coalesced
+        }
+        None => edits,
+    };
+
+    match config.adaptive_part_size {
+        true => plan_adaptive(obj_len, edits, part_size),
+        false => plan(obj_len, edits, part_size),
+    }
+}
+
+/// Log the parts of `ops`, ranges in the `bytes=a-b` form of copy requests.
+pub(crate) fn log_plan(key: &str, ops: &[PartOp]) {
+    info!(key, parts = ops.len(), "plan");
+
+    for (i, op) in ops.iter().enumerate() {
+        let (kind, range) = match op {
+            PartOp::Copy(range) => ("copy", range),
+            PartOp::Upload(range) => ("upload", range),
+        };
+        info!(part_number = i + 1, kind, "plan part, range: bytes={}-{}", range.start, range.end - 1);
+    }
+}
+
+/// Bytes of `range` the sorted, disjoint `edits` cover.
+fn covered_by(edits: &[Range<i64>], range: &Range<i64>) -> i64 {
+    edits.iter()
+        .map(|r| std::cmp::min(r.end, range.end) - std::cmp::max(r.start, range.start))
+        .filter(|&len| len > 0)
+        .sum()
+}
+
+pub(crate) async fn final_checksum(client: &Client, config: &S3Config, key: &str) -> Result<Option<Checksum>> {
+    let out = client.get_object_attributes()
+        .bucket(&config.bucket)
+        .key(key)
+        .object_attributes(ObjectAttributes::Checksum)
+        .sse_customer(&SseCustomer::new(config))
+        .send()
+        .await?;
+
+    Ok(out.checksum)
+}
+
+/// Key an atomic edit of `key` is built under before being copied over it.
+pub(crate) fn temp_key(config: &S3Config, key: &str) -> String {
+    let id = uuid::Uuid::new_v4();
+
+    match &config.temp_key_strategy {
+        TempKeyStrategy::Suffix(suffix) => format!("{}{}{}", key, suffix, id),
+        TempKeyStrategy::Prefix(prefix) => format!("{}{}.tmp-{}", prefix, key, id),
+        TempKeyStrategy::Custom(f) => f(key),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeKind {
+    /// Copied server-side from the original object, or from the source of a splice.
+    Copied,
+    /// Sent from the client: edit data, plus any original bytes padding it to a legal part.
+    Uploaded,
+}
+
+/// What a completed rewrite did to the object.
+#[derive(Debug, Clone, Default)]
+pub struct ModifyStats {
+    /// `(kind, start, end)` of every part in part number order, `end` exclusive.
+    /// Together they cover the whole object.
+    pub ranges: Vec<(RangeKind, i64, i64)>,
+    /// Checksum of the whole rewritten object, if `compute_final_checksum` is set and the
+    /// server keeps one. It may only exist when the object's uploads used a checksum algorithm,
+    /// and `None` is returned by servers without checksum support.
+    pub final_checksum: Option<Checksum>,
+    /// Version id of the written object, if the bucket is versioned and the server reported it.
+    pub version_id: Option<String>,
+    /// ETag of the written object, if the server reported it, e.g. for a later
+    /// [`Session::modify_cas`] or `if_match` read.
+    pub etag: Option<String>,
+    /// Id of the multipart upload that wrote the object, for matching it with server logs.
+    /// `None` for single puts and server-side copies.
+    pub upload_id: Option<String>,
+    /// Original bytes downloaded to pad edits into uploaded parts.
+    pub bytes_read: u64,
+    /// Retries the part uploads and copies took on top of their first attempts, e.g. after
+    /// `SlowDown` or 5xx answers, see `read_max_retries`.
+    pub part_retries: u32,
+}
+
+impl ModifyStats {
+    /// Bytes sent from the client: edit data plus the original bytes padding it.
+    pub fn bytes_uploaded(&self) -> u64 {
+        self.bytes_of(RangeKind::Uploaded)
+    }
+
+    /// Bytes copied server-side, which never pass through the client.
+    pub fn bytes_copied(&self) -> u64 {
+        self.bytes_of(RangeKind::Copied)
+    }
+
+    fn bytes_of(&self, kind: RangeKind) -> u64 {
+        self.ranges.iter()
+            .filter(|(k, _, _)| *k == kind)
+            .map(|(_, start, end)| (end - start) as u64)
+            .sum()
+    }
+
+    pub(crate) fn record(&mut self, completion: Completion) {
+        self.upload_id = Some(completion.upload_id);
+        self.version_id = completion.version_id;
+        self.etag = completion.etag;
+        self.part_retries = completion.part_retries;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectStat {
+    pub len: i64,
+    pub etag: Option<String>,
+    pub content_type: Option<String>,
+    pub last_modified: Option<SystemTime>,
+    pub metadata: HashMap<String, String>,
+}
+
+/// An object found by [`Session::list_objects`].
+#[derive(Debug, Clone)]
+pub struct ObjectSummary {
+    pub key: String,
+    pub size: i64,
+    pub etag: Option<String>,
+}
+
+/// A version of an object found by [`Session::list_object_versions`].
+#[derive(Debug, Clone)]
+pub struct ObjectVersion {
+    pub version_id: String,
+    pub is_latest: bool,
+    pub size: i64,
+    pub etag: Option<String>,
+    pub last_modified: Option<SystemTime>,
+}
+
+/// Part layout of an object uploaded with multipart, see [`Session::get_object_parts`].
+#[derive(Debug, Clone)]
+pub struct ObjectParts {
+    pub total_parts: i32,
+    /// `(part_number, size)` of every part in order. Empty if the server only reports the
+    /// count, as S3 does for objects uploaded without checksums.
+    pub parts: Vec<(i32, i64)>,
+}
+
+/// What an S3-compatible server supports, see [`Session::detect_capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Multipart parts can be copied server-side; without it set `force_single_put`.
+    pub upload_part_copy: bool,
+    /// `get_object_attributes` works, needed by [`Session::get_object_parts`] and
+    /// `compute_final_checksum`.
+    pub object_attributes: bool,
+    /// Objects can be written with an additional checksum and report it back.
+    pub checksums: bool,
+}
+
+pub(crate) async fn head(
+    client: &Client,
+    config: &S3Config,
+    key: &str,
+) -> Result<Option<ObjectStat>> {
+    let out = match client.head_object()
+        .bucket(&config.bucket)
+        .key(key)
+        .sse_customer(&SseCustomer::new(config))
+        .send()
+        .await
+    {
+        Ok(out) => out,
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let stat = ObjectStat {
+        len: out.content_length().ok_or_else(|| anyhow!("{} content length is empty", key))?,
+        etag: out.e_tag,
+        content_type: out.content_type,
+        last_modified: out.last_modified.map(SystemTime::try_from).transpose()?,
+        metadata: out.metadata.unwrap_or_default(),
+    };
+    Ok(Some(stat))
+}
+
+// days a restored copy of an archived object is kept, enough to retry the edit
+const RESTORE_DAYS: i32 = 1;
+
+// error codes of requests whose credentials the server didn't accept
+const AUTH_ERROR_CODES: &[&str] = &["InvalidAccessKeyId", "SignatureDoesNotMatch", "ExpiredToken", "InvalidToken"];
+
+pub(crate) fn is_auth_failure<E: ProvideErrorMetadata>(err: &SdkError<E, HttpResponse>) -> bool {
+    match err.code() {
+        Some(code) => AUTH_ERROR_CODES.contains(&code),
+        // HEAD responses have no body to carry an error code
+        None => err.raw_response().is_some_and(|r| r.status().as_u16() == 403),
+    }
+}
+
+// error codes of requests refused for their rate, retried by the SDK a few times already
+const THROTTLE_ERROR_CODES: &[&str] = &["SlowDown", "ServiceUnavailable", "RequestLimitExceeded", "Throttling"];
+
+// error codes of conditional writes whose object changed in the meantime
+const CONFLICT_ERROR_CODES: &[&str] = &["PreconditionFailed", "ConditionalRequestConflict"];
+
+/// Typed mapping of the SDK failures callers may act on.
+pub(crate) trait SdkResultExt<T> {
+    /// Attach the request ids of a failure the server answered as [`EditError::S3`], under
+    /// [`EditError::Throttled`] if it was refused for the request rate.
+    fn with_request_ids(self) -> Result<T>;
+
+    /// [`SdkResultExt::with_request_ids`] for a write to `key`, with a failed `If-Match` (if
+    /// `if_match` was sent) mapped to [`EditError::Conflict`] and a failed `If-None-Match` of an
+    /// `if_none_match` write to [`EditError::AlreadyExists`].
+    fn check_conditions(self, config: &S3Config, key: &str, if_match: bool) -> Result<T>;
+
+    /// Map rejected credentials to [`EditError::AuthFailed`] and an `InvalidObjectState`
+    /// failure on `key` to [`EditError::ObjectArchived`], requesting a restore first if
+    /// `restore_archived` is set. Other errors pass through.
+    async fn classify(self, client: &Client, config: &S3Config, key: &str) -> Result<T>;
+}
+
+impl<T, E> SdkResultExt<T> for std::result::Result<T, SdkError<E, HttpResponse>>
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    fn with_request_ids(self) -> Result<T> {
+        let err = match self {
+            Ok(out) => return Ok(out),
+            Err(e) => e,
+        };
+
+        // only responses have ids, not timeouts or connection failures
+        if err.raw_response().is_none() {
+            return Err(err.into());
+        }
 
-                println!("copy, part_num: {}, range: {}-{}", part_num, offset, end -1);
-
-                let etag = client.upload_part_copy()
-                    .copy_source(format!("/{}/{}", bucket, key))
-                    .copy_source_range(format!("bytes={}-{}", offset, end - 1))
-                    .bucket(bucket)
-                    .key(key)
-                    .upload_id(upload_id)
-                    .part_number(part_num)
-                    .send()
-                    .await?
-                    .copy_part_result
-                    .ok_or_else(|| anyhow!("{} must need copy part result", key))?
-                    .e_tag
-                    .ok_or_else(|| anyhow!("{} must need e_tag", key))?;
-
-                etags.push(etag);
+        let throttled = THROTTLE_ERROR_CODES.contains(&err.code().unwrap_or_default())
+            || err.raw_response().is_some_and(|r| r.status().as_u16() == 503);
+
+        let ids = EditError::S3 {
+            code: err.code().map(String::from),
+            request_id: err.request_id().map(String::from),
+            extended_request_id: err.extended_request_id().map(String::from),
+            source: Some(Box::new(err)),
+        };
+
+        if throttled {
+            return Err(anyhow::Error::new(ids).context(EditError::Throttled));
+        }
+        Err(ids.into())
+    }
+
+    fn check_conditions(self, config: &S3Config, key: &str, if_match: bool) -> Result<T> {
+        match self {
+            // a concurrent conditional write of the same key fails with 409
+            Err(e) if if_match && CONFLICT_ERROR_CODES.contains(&e.code().unwrap_or_default()) => {
+                Err(anyhow::Error::new(e).context(EditError::Conflict { key: key.to_string() }))
             }
+            Err(e) if config.if_none_match && e.code() == Some("PreconditionFailed") => {
+                Err(anyhow::Error::new(e).context(EditError::AlreadyExists { key: key.to_string() }))
+            }
+            res => res.with_request_ids(),
+        }
+    }
+
+    async fn classify(self, client: &Client, config: &S3Config, key: &str) -> Result<T> {
+        let err = match self {
+            Ok(out) => return Ok(out),
+            Err(e) if is_auth_failure(&e) => return Err(anyhow::Error::new(e).context(EditError::AuthFailed)),
+            Err(e) if e.code() == Some("InvalidObjectState") => e,
+            Err(e) => return Err(e).with_request_ids(),
+        };
+
+        let mut restore_requested = false;
 
-            offset = end;
-            part_num += 1;
+        if config.restore_archived {
+            info!(key, "restore");
+
+            let res = client.restore_object()
+                .bucket(&config.bucket)
+                .key(key)
+                .restore_request(RestoreRequest::builder().days(RESTORE_DAYS).build())
+                .customize()
+                .config_override(write_retries(config))
+                .send()
+                .await;
+
+            match res {
+                Ok(_) => restore_requested = true,
+                Err(e) if e.code() == Some("RestoreAlreadyInProgress") => restore_requested = true,
+                Err(e) => return Err(anyhow::Error::new(e).context(err)),
+            }
         }
 
-        let parts = etags.into_iter()
-            .enumerate()
-            .map(|(i, e_tag)| {
-                CompletedPart::builder()
-                    .part_number(i as i32 + 1)
-                    .e_tag(e_tag)
-                    .build()
-            })
-            .collect::<Vec<_>>();
-
-        client.complete_multipart_upload()
-            .multipart_upload(
-                CompletedMultipartUpload::builder()
-                    .set_parts(Some(parts))
-                    .build(),
-            )
-            .bucket(bucket)
-            .key(key)
-            .upload_id(upload_id)
-            .send()
-            .await?;
-
-        Ok(())
-    })
+        Err(anyhow::Error::new(err).context(EditError::ObjectArchived { key: key.to_string(), restore_requested }))
+    }
+}
+
+// 5GB, the largest object `copy_object` can copy in one request
+pub(crate) const MAX_COPY_SIZE: i64 = 5 * 1024 * 1024 * 1024;
+// 5GB, the largest object `put_object` can write in one request
+pub(crate) const MAX_PUT_SIZE: i64 = 5 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct MultipartUpload {
+    pub key: String,
+    pub upload_id: String,
+    pub initiated: SystemTime,
+}
+
+/// [`Session::verify_credentials`] on the global session.
+pub async fn verify_credentials_async() -> Result<()> {
+    session()?.verify_credentials().await
+}
+
+/// Blocking wrapper of [`verify_credentials_async`].
+#[cfg(feature = "blocking")]
+pub fn verify_credentials() -> Result<()> {
+    RT.block_on(verify_credentials_async())
+}
+
+/// [`Session::modify`] on the global session. Callers already inside a tokio runtime must
+/// use this and the other `*_async` functions: the blocking wrappers drive their own runtime,
+/// which panics when started from within another.
+pub async fn modify_async(
+    key: &str,
+    modify_part: Part,
+) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.modify(key, modify_part).await
+}
+
+/// Blocking wrapper of [`modify_async`].
+#[cfg(feature = "blocking")]
+pub fn modify(key: &str, modify_part: Part) -> Result<ModifyStats> {
+    RT.block_on(modify_async(key, modify_part))
+}
+
+/// [`Session::would_use_multipart`] on the global session.
+pub async fn would_use_multipart_async(key: &str, part: &Part) -> Result<bool> {
+    let (session, key) = session_for(key)?;
+    session.would_use_multipart(key, part).await
+}
+
+/// Blocking wrapper of [`would_use_multipart_async`].
+#[cfg(feature = "blocking")]
+pub fn would_use_multipart(key: &str, part: &Part) -> Result<bool> {
+    RT.block_on(would_use_multipart_async(key, part))
+}
+
+/// [`Session::pattern_range`] on the global session.
+pub async fn pattern_range_async(key: &str, offset: i64, len: i64, pattern: &[u8]) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.pattern_range(key, offset, len, pattern).await
+}
+
+/// Blocking wrapper of [`pattern_range_async`].
+#[cfg(feature = "blocking")]
+pub fn pattern_range(key: &str, offset: i64, len: i64, pattern: &[u8]) -> Result<ModifyStats> {
+    RT.block_on(pattern_range_async(key, offset, len, pattern))
+}
+
+/// [`Session::modify_from`] on the global session.
+pub async fn modify_from_async(key: &str, offset: i64, source: PartSource) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.modify_from(key, offset, source).await
+}
+
+/// Blocking wrapper of [`modify_from_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_from(key: &str, offset: i64, source: PartSource) -> Result<ModifyStats> {
+    RT.block_on(modify_from_async(key, offset, source))
+}
+
+/// [`Session::sync_from_file`] on the global session.
+pub async fn sync_from_file_async(key: &str, path: impl AsRef<Path>) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.sync_from_file(key, path).await
+}
+
+/// Blocking wrapper of [`sync_from_file_async`].
+#[cfg(feature = "blocking")]
+pub fn sync_from_file(key: &str, path: impl AsRef<Path>) -> Result<ModifyStats> {
+    RT.block_on(sync_from_file_async(key, path))
+}
+
+/// [`Session::append`] on the global session.
+pub async fn append_async(key: &str, data: Vec<u8>) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.append(key, data).await
+}
+
+/// Blocking wrapper of [`append_async`].
+#[cfg(feature = "blocking")]
+pub fn append(key: &str, data: Vec<u8>) -> Result<ModifyStats> {
+    RT.block_on(append_async(key, data))
+}
+
+/// [`Session::write_at`] on the global session.
+pub async fn write_at_async(key: &str, offset: i64, data: Vec<u8>) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.write_at(key, offset, data).await
+}
+
+/// Blocking wrapper of [`write_at_async`].
+#[cfg(feature = "blocking")]
+pub fn write_at(key: &str, offset: i64, data: Vec<u8>) -> Result<ModifyStats> {
+    RT.block_on(write_at_async(key, offset, data))
+}
+
+/// [`Session::truncate`] on the global session.
+pub async fn truncate_async(key: &str, new_len: i64) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.truncate(key, new_len).await
+}
+
+/// Blocking wrapper of [`truncate_async`].
+#[cfg(feature = "blocking")]
+pub fn truncate(key: &str, new_len: i64) -> Result<ModifyStats> {
+    RT.block_on(truncate_async(key, new_len))
+}
+
+/// [`Session::remove_range`] on the global session.
+pub async fn remove_range_async(key: &str, offset: i64, len: i64) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.remove_range(key, offset, len).await
+}
+
+/// Blocking wrapper of [`remove_range_async`].
+#[cfg(feature = "blocking")]
+pub fn remove_range(key: &str, offset: i64, len: i64) -> Result<ModifyStats> {
+    RT.block_on(remove_range_async(key, offset, len))
+}
+
+/// [`Session::insert`] on the global session.
+pub async fn insert_async(key: &str, offset: i64, data: Vec<u8>) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.insert(key, offset, data).await
+}
+
+/// Blocking wrapper of [`insert_async`].
+#[cfg(feature = "blocking")]
+pub fn insert(key: &str, offset: i64, data: Vec<u8>) -> Result<ModifyStats> {
+    RT.block_on(insert_async(key, offset, data))
+}
+
+/// [`Session::estimate_cost`] on the global session.
+pub async fn estimate_cost_async(key: &str, parts: &[Part]) -> Result<CostEstimate> {
+    let (session, key) = session_for(key)?;
+    session.estimate_cost(key, parts).await
+}
+
+/// Blocking wrapper of [`estimate_cost_async`].
+#[cfg(feature = "blocking")]
+pub fn estimate_cost(key: &str, parts: &[Part]) -> Result<CostEstimate> {
+    RT.block_on(estimate_cost_async(key, parts))
+}
+
+/// [`Session::modify_if_checksum`] on the global session.
+pub async fn modify_if_checksum_async(key: &str, modify_part: Part, expected_sha256: [u8; 32]) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.modify_if_checksum(key, modify_part, expected_sha256).await
+}
+
+/// Blocking wrapper of [`modify_if_checksum_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_if_checksum(key: &str, modify_part: Part, expected_sha256: [u8; 32]) -> Result<ModifyStats> {
+    RT.block_on(modify_if_checksum_async(key, modify_part, expected_sha256))
+}
+
+/// [`Session::delete_object`] on the global session.
+pub async fn delete_object_async(key: &str) -> Result<()> {
+    let (session, key) = session_for(key)?;
+    session.delete_object(key).await
+}
+
+/// Blocking wrapper of [`delete_object_async`].
+#[cfg(feature = "blocking")]
+pub fn delete_object(key: &str) -> Result<()> {
+    RT.block_on(delete_object_async(key))
+}
+
+/// [`Session::delete_object_version`] on the global session.
+pub async fn delete_object_version_async(key: &str, version_id: &str) -> Result<()> {
+    let (session, key) = session_for(key)?;
+    session.delete_object_version(key, version_id).await
+}
+
+/// Blocking wrapper of [`delete_object_version_async`].
+#[cfg(feature = "blocking")]
+pub fn delete_object_version(key: &str, version_id: &str) -> Result<()> {
+    RT.block_on(delete_object_version_async(key, version_id))
+}
+
+/// [`Session::delete_if_match`] on the global session.
+pub async fn delete_if_match_async(key: &str, expected_etag: &str) -> Result<bool> {
+    let (session, key) = session_for(key)?;
+    session.delete_if_match(key, expected_etag).await
+}
+
+/// Blocking wrapper of [`delete_if_match_async`].
+#[cfg(feature = "blocking")]
+pub fn delete_if_match(key: &str, expected_etag: &str) -> Result<bool> {
+    RT.block_on(delete_if_match_async(key, expected_etag))
+}
+
+/// [`Session::splice`] on the global session. Both keys must be in the same bucket.
+pub async fn splice_async(
+    src_key: &str,
+    src_offset: i64,
+    len: i64,
+    dst_key: &str,
+    dst_offset: i64,
+) -> Result<ModifyStats> {
+    let (session, src_key, dst_key) = sessions_for(src_key, dst_key)?;
+    session.splice(src_key, src_offset, len, dst_key, dst_offset).await
+}
+
+/// Blocking wrapper of [`splice_async`].
+#[cfg(feature = "blocking")]
+pub fn splice(src_key: &str, src_offset: i64, len: i64, dst_key: &str, dst_offset: i64) -> Result<ModifyStats> {
+    RT.block_on(splice_async(src_key, src_offset, len, dst_key, dst_offset))
+}
+
+/// [`Session::modify_cas`] on the global session.
+pub async fn modify_cas_async<F>(key: &str, range: Range<i64>, recompute: F, retries: usize) -> Result<ModifyStats>
+where
+    F: FnMut(&[u8]) -> Part,
+{
+    let (session, key) = session_for(key)?;
+    session.modify_cas(key, range, recompute, retries).await
+}
+
+/// Blocking wrapper of [`modify_cas_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_cas<F>(key: &str, range: Range<i64>, recompute: F, retries: usize) -> Result<ModifyStats>
+where
+    F: FnMut(&[u8]) -> Part,
+{
+    RT.block_on(modify_cas_async(key, range, recompute, retries))
+}
+
+/// [`Session::fetch_add_u64_le`] on the global session.
+pub async fn fetch_add_u64_le_async(key: &str, offset: i64, delta: u64) -> Result<u64> {
+    let (session, key) = session_for(key)?;
+    session.fetch_add_u64_le(key, offset, delta).await
+}
+
+/// Blocking wrapper of [`fetch_add_u64_le_async`].
+#[cfg(feature = "blocking")]
+pub fn fetch_add_u64_le(key: &str, offset: i64, delta: u64) -> Result<u64> {
+    RT.block_on(fetch_add_u64_le_async(key, offset, delta))
+}
+
+/// [`Session::update_range`] on the global session.
+pub async fn update_range_async<F>(key: &str, offset: i64, len: i64, update: F) -> Result<ModifyStats>
+where
+    F: FnMut(&mut [u8]),
+{
+    let (session, key) = session_for(key)?;
+    session.update_range(key, offset, len, update).await
+}
+
+/// Blocking wrapper of [`update_range_async`].
+#[cfg(feature = "blocking")]
+pub fn update_range<F>(key: &str, offset: i64, len: i64, update: F) -> Result<ModifyStats>
+where
+    F: FnMut(&mut [u8]),
+{
+    RT.block_on(update_range_async(key, offset, len, update))
+}
+
+/// [`Session::get_object_parts`] on the global session.
+pub async fn get_object_parts_async(key: &str) -> Result<Option<ObjectParts>> {
+    let (session, key) = session_for(key)?;
+    session.get_object_parts(key).await
+}
+
+/// Blocking wrapper of [`get_object_parts_async`].
+#[cfg(feature = "blocking")]
+pub fn get_object_parts(key: &str) -> Result<Option<ObjectParts>> {
+    RT.block_on(get_object_parts_async(key))
+}
+
+/// [`Session::modify_many`] on the global session.
+pub async fn modify_many_async(
+    key: &str,
+    parts: Vec<Part>,
+) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.modify_many(key, parts).await
+}
+
+/// Blocking wrapper of [`modify_many_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_many(key: &str, parts: Vec<Part>) -> Result<ModifyStats> {
+    RT.block_on(modify_many_async(key, parts))
+}
+
+/// [`Session::apply_patch`] on the global session.
+pub async fn apply_patch_async(key: &str, patch: &[u8]) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.apply_patch(key, patch).await
+}
+
+/// Blocking wrapper of [`apply_patch_async`].
+#[cfg(feature = "blocking")]
+pub fn apply_patch(key: &str, patch: &[u8]) -> Result<ModifyStats> {
+    RT.block_on(apply_patch_async(key, patch))
+}
+
+/// [`Session::modify_many_with_progress`] on the global session, calling `on_progress` with
+/// each event as the edit goes.
+pub async fn modify_many_with_progress_async(key: &str, parts: Vec<Part>, mut on_progress: impl FnMut(ProgressEvent)) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    let (events, edit) = session.modify_many_with_progress(key, parts);
+    let report = events.for_each(|event| {
+        on_progress(event);
+        std::future::ready(())
+    });
+
+    let (res, ()) = futures_util::future::join(edit, report).await;
+    res
+}
+
+/// Blocking wrapper of [`modify_many_with_progress_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_many_with_progress(key: &str, parts: Vec<Part>, on_progress: impl FnMut(ProgressEvent)) -> Result<ModifyStats> {
+    RT.block_on(modify_many_with_progress_async(key, parts, on_progress))
+}
+
+/// [`Session::modify_into`] on the global session. The keys may be `s3://` URIs of
+/// different buckets, see [`modify_many_into_async`].
+pub async fn modify_into_async(
+    src_key: &str,
+    dst_key: &str,
+    modify_part: Part,
+) -> Result<ModifyStats> {
+    modify_many_into_async(src_key, dst_key, vec![modify_part]).await
+}
+
+/// Blocking wrapper of [`modify_into_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_into(src_key: &str, dst_key: &str, modify_part: Part) -> Result<ModifyStats> {
+    RT.block_on(modify_into_async(src_key, dst_key, modify_part))
+}
+
+/// [`Session::modify_many_into`] on the global session. With `s3://` URIs of different
+/// buckets, [`Session::modify_many_across`] writing into the bucket of `dst_key`.
+pub async fn modify_many_into_async(
+    src_key: &str,
+    dst_key: &str,
+    parts: Vec<Part>,
+) -> Result<ModifyStats> {
+    let (src_session, src_key) = session_for(src_key)?;
+    let (session, dst_key) = session_for(dst_key)?;
+
+    match src_session.bucket() == session.bucket() {
+        true => session.modify_many_into(src_key, dst_key, parts).await,
+        false => session.modify_many_across(src_session.bucket(), src_key, dst_key, parts).await,
+    }
+}
+
+/// Blocking wrapper of [`modify_many_into_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_many_into(src_key: &str, dst_key: &str, parts: Vec<Part>) -> Result<ModifyStats> {
+    RT.block_on(modify_many_into_async(src_key, dst_key, parts))
+}
+
+/// [`Session::modify_many_across`] on the global session.
+pub async fn modify_many_across_async(
+    src_bucket: &str,
+    src_key: &str,
+    dst_key: &str,
+    parts: Vec<Part>,
+) -> Result<ModifyStats> {
+    let (session, dst_key) = session_for(dst_key)?;
+    session.modify_many_across(src_bucket, src_key, dst_key, parts).await
+}
+
+/// Blocking wrapper of [`modify_many_across_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_many_across(src_bucket: &str, src_key: &str, dst_key: &str, parts: Vec<Part>) -> Result<ModifyStats> {
+    RT.block_on(modify_many_across_async(src_bucket, src_key, dst_key, parts))
+}
+
+/// [`Session::modify_batch`] on the global session, with keys of its bucket.
+pub async fn modify_batch_async(edits: Vec<(String, Vec<Part>)>, concurrency: usize) -> Result<HashMap<String, Result<ModifyStats>>> {
+    session()?.modify_batch(edits, concurrency).await
+}
+
+/// Blocking wrapper of [`modify_batch_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_batch(edits: Vec<(String, Vec<Part>)>, concurrency: usize) -> Result<HashMap<String, Result<ModifyStats>>> {
+    RT.block_on(modify_batch_async(edits, concurrency))
+}
+
+/// [`Session::modify_version`] on the global session.
+pub async fn modify_version_async(key: &str, version_id: &str, parts: Vec<Part>) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.modify_version(key, version_id, parts).await
+}
+
+/// Blocking wrapper of [`modify_version_async`].
+#[cfg(feature = "blocking")]
+pub fn modify_version(key: &str, version_id: &str, parts: Vec<Part>) -> Result<ModifyStats> {
+    RT.block_on(modify_version_async(key, version_id, parts))
+}
+
+/// [`Session::set_contents`] on the global session.
+pub async fn set_contents_async(key: &str, data: Vec<u8>) -> Result<ModifyStats> {
+    let (session, key) = session_for(key)?;
+    session.set_contents(key, data).await
+}
+
+/// Blocking wrapper of [`set_contents_async`].
+#[cfg(feature = "blocking")]
+pub fn set_contents(key: &str, data: Vec<u8>) -> Result<ModifyStats> {
+    RT.block_on(set_contents_async(key, data))
+}
+
+/// [`Session::read`] on the global session.
+pub async fn read_async(key: &str) -> Result<Vec<u8>> {
+    let (session, key) = session_for(key)?;
+    session.read(key).await
+}
+
+/// Blocking wrapper of [`read_async`].
+#[cfg(feature = "blocking")]
+pub fn read(key: &str) -> Result<Vec<u8>> {
+    RT.block_on(read_async(key))
+}
+
+/// [`Session::read_at`] on the global session.
+pub async fn read_at_async(key: &str, offset: i64, len: i64) -> Result<Vec<u8>> {
+    let (session, key) = session_for(key)?;
+    session.read_at(key, offset, len).await
+}
+
+/// Blocking wrapper of [`read_at_async`].
+#[cfg(feature = "blocking")]
+pub fn read_at(key: &str, offset: i64, len: i64) -> Result<Vec<u8>> {
+    RT.block_on(read_at_async(key, offset, len))
+}
+
+/// [`Session::read_ranges`] on the global session.
+pub async fn read_ranges_async(key: &str, ranges: &[(i64, i64)]) -> Result<Vec<Vec<u8>>> {
+    let (session, key) = session_for(key)?;
+    session.read_ranges(key, ranges).await
+}
+
+/// Blocking wrapper of [`read_ranges_async`].
+#[cfg(feature = "blocking")]
+pub fn read_ranges(key: &str, ranges: &[(i64, i64)]) -> Result<Vec<Vec<u8>>> {
+    RT.block_on(read_ranges_async(key, ranges))
+}
+
+/// [`Session::replace`] on the global session.
+pub async fn replace_async(
+    key: &str,
+    body: ByteStream,
+    len: i64,
+) -> Result<()> {
+    let (session, key) = session_for(key)?;
+    session.replace(key, body, len).await
+}
+
+/// Blocking wrapper of [`replace_async`].
+#[cfg(feature = "blocking")]
+pub fn replace(key: &str, body: ByteStream, len: i64) -> Result<()> {
+    RT.block_on(replace_async(key, body, len))
+}
+
+/// [`Session::replace_unsized`] on the global session.
+pub async fn replace_unsized_async(key: &str, body: ByteStream) -> Result<i64> {
+    let (session, key) = session_for(key)?;
+    session.replace_unsized(key, body).await
+}
+
+/// Blocking wrapper of [`replace_unsized_async`].
+#[cfg(feature = "blocking")]
+pub fn replace_unsized(key: &str, body: ByteStream) -> Result<i64> {
+    RT.block_on(replace_unsized_async(key, body))
+}
+
+/// [`Session::stat`] on the global session.
+pub async fn stat_async(key: &str) -> Result<Option<ObjectStat>> {
+    let (session, key) = session_for(key)?;
+    session.stat(key).await
+}
+
+/// Blocking wrapper of [`stat_async`].
+#[cfg(feature = "blocking")]
+pub fn stat(key: &str) -> Result<Option<ObjectStat>> {
+    RT.block_on(stat_async(key))
+}
+
+/// [`Session::update_metadata`] on the global session.
+pub async fn update_metadata_async(
+    key: &str,
+    metadata: HashMap<String, String>,
+    content_type: Option<String>,
+) -> Result<()> {
+    let (session, key) = session_for(key)?;
+    session.update_metadata(key, metadata, content_type).await
+}
+
+/// Blocking wrapper of [`update_metadata_async`].
+#[cfg(feature = "blocking")]
+pub fn update_metadata(key: &str, metadata: HashMap<String, String>, content_type: Option<String>) -> Result<()> {
+    RT.block_on(update_metadata_async(key, metadata, content_type))
+}
+
+/// [`Session::list_objects`] on the global session.
+pub async fn list_objects_async(prefix: &str) -> Result<Vec<ObjectSummary>> {
+    session()?.list_objects(prefix).await
+}
+
+/// Blocking wrapper of [`list_objects_async`].
+#[cfg(feature = "blocking")]
+pub fn list_objects(prefix: &str) -> Result<Vec<ObjectSummary>> {
+    RT.block_on(list_objects_async(prefix))
+}
+
+/// [`Session::list_object_versions`] on the global session.
+pub async fn list_object_versions_async(key: &str) -> Result<Vec<ObjectVersion>> {
+    let (session, key) = session_for(key)?;
+    session.list_object_versions(key).await
+}
+
+/// Blocking wrapper of [`list_object_versions_async`].
+#[cfg(feature = "blocking")]
+pub fn list_object_versions(key: &str) -> Result<Vec<ObjectVersion>> {
+    RT.block_on(list_object_versions_async(key))
+}
+
+/// [`Session::read_version_range`] on the global session.
+pub async fn read_version_range_async(key: &str, version_id: &str, start: i64, end: i64) -> Result<Vec<u8>> {
+    let (session, key) = session_for(key)?;
+    session.read_version_range(key, version_id, start, end).await
+}
+
+/// Blocking wrapper of [`read_version_range_async`].
+#[cfg(feature = "blocking")]
+pub fn read_version_range(key: &str, version_id: &str, start: i64, end: i64) -> Result<Vec<u8>> {
+    RT.block_on(read_version_range_async(key, version_id, start, end))
+}
+
+/// [`Session::list_stale_uploads`] on the global session.
+pub async fn list_stale_uploads_async(
+    key_prefix: Option<&str>,
+    older_than: Duration,
+) -> Result<Vec<MultipartUpload>> {
+    session()?.list_stale_uploads(key_prefix, older_than).await
+}
+
+/// Blocking wrapper of [`list_stale_uploads_async`].
+#[cfg(feature = "blocking")]
+pub fn list_stale_uploads(key_prefix: Option<&str>, older_than: Duration) -> Result<Vec<MultipartUpload>> {
+    RT.block_on(list_stale_uploads_async(key_prefix, older_than))
+}
+
+/// [`Session::abort_stale_uploads`] on the global session.
+pub async fn abort_stale_uploads_async(
+    key_prefix: Option<&str>,
+    older_than: Duration,
+) -> Result<Vec<String>> {
+    session()?.abort_stale_uploads(key_prefix, older_than).await
+}
+
+/// Blocking wrapper of [`abort_stale_uploads_async`].
+#[cfg(feature = "blocking")]
+pub fn abort_stale_uploads(key_prefix: Option<&str>, older_than: Duration) -> Result<Vec<String>> {
+    RT.block_on(abort_stale_uploads_async(key_prefix, older_than))
 }
\ No newline at end of file
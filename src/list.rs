@@ -0,0 +1,61 @@
+use anyhow::{ensure, Result};
+use aws_sdk_s3::Client;
+
+use crate::{ObjectSummary, Session};
+
+/// Pages of the objects under a prefix, fetched one `list_objects_v2` call at a time so huge
+/// listings needn't be held in memory, see [`Session::list_object_pages`].
+#[derive(Debug)]
+pub struct ObjectPages {
+    client: Client,
+    bucket: String,
+    prefix: String,
+    continuation_token: Option<String>,
+    done: bool,
+}
+
+impl ObjectPages {
+    pub(crate) fn new(session: &Session, prefix: &str) -> Self {
+        ObjectPages {
+            client: session.client.clone(),
+            bucket: session.config.bucket.clone(),
+            prefix: prefix.to_string(),
+            continuation_token: None,
+            done: false,
+        }
+    }
+
+    /// The next page of up to 1000 objects in key order, `None` once all were returned.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<ObjectSummary>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let out = self.client.list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(&self.prefix)
+            .set_continuation_token(self.continuation_token.take())
+            .send()
+            .await?;
+
+        let page = out.contents()
+            .iter()
+            .filter_map(|object| {
+                Some(ObjectSummary {
+                    key: object.key()?.to_string(),
+                    size: object.size().unwrap_or(0),
+                    etag: object.e_tag().map(String::from),
+                })
+            })
+            .collect();
+
+        if out.is_truncated().unwrap_or(false) {
+            self.continuation_token = out.next_continuation_token;
+            ensure!(self.continuation_token.is_some(), "list objects truncated without continuation token");
+        } else {
+            self.done = true;
+        }
+
+        Ok(Some(page))
+    }
+}
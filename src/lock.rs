@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::primitives::ByteStream;
+use tracing::{info, warn};
+
+use crate::{EditError, SdkResultExt, Session, DEFAULT_LOCK_TTL_SECS, DEFAULT_LOCK_WAIT_SECS};
+
+// codes of a conditional request on a lock another holder created or removed in between
+const HELD_ERROR_CODES: &[&str] = &["PreconditionFailed", "ConditionalRequestConflict"];
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The `edit_lock` of a key: its `<key>.lock` object, created with `If-None-Match: *` so
+/// only one process has it at a time, until [`EditLock::release`].
+pub(crate) struct EditLock {
+    lock_key: String,
+    etag: Option<String>,
+}
+
+impl EditLock {
+    /// Create the lock object of `key`, polling up to `lock_wait_secs` while another process
+    /// holds it and removing it if it's older than `lock_ttl_secs`.
+    pub(crate) async fn acquire(session: &Session, key: &str) -> Result<EditLock> {
+        let config = &session.config;
+        let lock_key = format!("{}.lock", key);
+        let wait = Duration::from_secs(config.lock_wait_secs.unwrap_or(DEFAULT_LOCK_WAIT_SECS));
+        let ttl = Duration::from_secs(config.lock_ttl_secs.unwrap_or(DEFAULT_LOCK_TTL_SECS));
+        let started = Instant::now();
+
+        loop {
+            let res = session.client.put_object()
+                .bucket(&config.bucket)
+                .key(&lock_key)
+                .if_none_match("*")
+                .body(ByteStream::from(uuid::Uuid::new_v4().to_string().into_bytes()))
+                .send()
+                .await;
+
+            match res {
+                Ok(out) => return Ok(EditLock { lock_key, etag: out.e_tag }),
+                Err(e) if HELD_ERROR_CODES.contains(&e.code().unwrap_or_default()) => {}
+                Err(e) => return Err(e).with_request_ids(),
+            }
+
+            // a crashed holder never releases its lock
+            let held = match session.client.head_object().bucket(&config.bucket).key(&lock_key).send().await {
+                Ok(out) => Some(out),
+                Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => None,
+                Err(e) => return Err(e).with_request_ids(),
+            };
+
+            if let Some(held) = held {
+                let age = held.last_modified()
+                    .and_then(|t| SystemTime::try_from(*t).ok())
+                    .and_then(|t| SystemTime::now().duration_since(t).ok());
+
+                if age.is_some_and(|age| age > ttl) {
+                    warn!(key, "removing lock older than {}s", ttl.as_secs());
+
+                    let res = session.client.delete_object()
+                        .bucket(&config.bucket)
+                        .key(&lock_key)
+                        .set_if_match(held.e_tag)
+                        .send()
+                        .await;
+
+                    match res {
+                        Err(e) if !HELD_ERROR_CODES.contains(&e.code().unwrap_or_default()) => return Err(e).with_request_ids(),
+                        _ => continue,
+                    }
+                }
+            }
+
+            if started.elapsed() >= wait {
+                return Err(EditError::Locked { key: key.to_string() }.into());
+            }
+
+            info!(key, "waiting for lock");
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Delete the lock object, unless it was replaced after being taken as left behind.
+    pub(crate) async fn release(self, session: &Session) {
+        let res = session.client.delete_object()
+            .bucket(&session.config.bucket)
+            .key(&self.lock_key)
+            .set_if_match(self.etag)
+            .send()
+            .await;
+
+        if let Err(e) = res {
+            warn!(lock = self.lock_key, "failed to release lock: {}", e);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! A throwaway MinIO server in a Docker container, for integration tests in CI without AWS
+//! credentials.
+
+use anyhow::{anyhow, Result};
+use testcontainers_modules::minio::MinIO;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+use testcontainers_modules::testcontainers::ContainerAsync;
+
+use crate::{CompatMode, S3Config, Session};
+
+// credentials the MinIO image starts with
+const MINIO_USER: &str = "minioadmin";
+const MINIO_PASSWORD: &str = "minioadmin";
+
+/// A MinIO container with one bucket, removed when dropped. Needs a Docker daemon.
+pub struct MinioContainer {
+    config: S3Config,
+    _container: ContainerAsync<MinIO>,
+}
+
+impl MinioContainer {
+    /// Start MinIO and create `bucket` in it.
+    pub async fn start(bucket: &str) -> Result<Self> {
+        let container = MinIO::default()
+            .start()
+            .await
+            .map_err(|e| anyhow!("failed to start MinIO container: {}", e))?;
+        let host = container.get_host().await?;
+        let port = container.get_host_port_ipv4(9000).await?;
+
+        let config = S3Config::builder()
+            .endpoint(format!("http://{}:{}", host, port))
+            .bucket(bucket)
+            .credentials(MINIO_USER, MINIO_PASSWORD)
+            .compat(CompatMode::MinIO)
+            .build()?;
+
+        let session = Session::new(config.clone())?;
+        session.client()
+            .create_bucket()
+            .bucket(bucket)
+            .send()
+            .await
+            .map_err(|e| anyhow!("failed to create bucket {}: {}", bucket, e))?;
+
+        Ok(MinioContainer { config, _container: container })
+    }
+
+    /// Config of the bucket, to build sessions with other settings from.
+    pub fn config(&self) -> &S3Config {
+        &self.config
+    }
+
+    pub fn session(&self) -> Result<Session> {
+        Session::new(self.config.clone())
+    }
+}
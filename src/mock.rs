@@ -0,0 +1,151 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, ensure, Result};
+use bytes::{Bytes, BytesMut};
+use md5::{Digest, Md5};
+
+use crate::etag::{hex, multipart_etag_of_parts};
+use crate::{EditError, ObjectStat, ObjectStoreBackend, MAX_PARTS};
+
+/// In-memory [`ObjectStoreBackend`], to test code built on
+/// [`modify_with_backend`](crate::modify_with_backend) without a server or credentials.
+///
+/// It keeps to S3 where edits depend on it: copies take the object as it is when they run, failing
+/// if it no longer has the ETag they require, completing replaces the object with the parts listed,
+/// which must all have been added with those ETags, and ETags are MD5s, of multipart objects as S3
+/// makes them. Aborting drops the parts; [`MockBackend::uploads_in_progress`] tells whether an edit
+/// left any.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    // data and ETag by key
+    objects: Mutex<HashMap<String, (Bytes, String)>>,
+    uploads: Mutex<HashMap<String, MockUpload>>,
+    next_upload: AtomicU64,
+}
+
+#[derive(Debug)]
+struct MockUpload {
+    key: String,
+    // ETag and data by part number
+    parts: BTreeMap<i32, (String, Bytes)>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        MockBackend::default()
+    }
+
+    /// Store `data` as `key`, as a single put would.
+    pub fn put(&self, key: &str, data: impl Into<Bytes>) {
+        let data = data.into();
+        let etag = md5_etag(&data);
+        self.objects.lock().unwrap().insert(key.to_string(), (data, etag));
+    }
+
+    pub fn get(&self, key: &str) -> Option<Bytes> {
+        self.objects.lock().unwrap().get(key).map(|(data, _)| data.clone())
+    }
+
+    /// Uploads started and neither completed nor aborted.
+    pub fn uploads_in_progress(&self) -> usize {
+        self.uploads.lock().unwrap().len()
+    }
+
+    fn add_part(&self, key: &str, upload_id: &str, part_num: i32, data: Bytes) -> Result<String> {
+        ensure!((1..=MAX_PARTS as i32).contains(&part_num), EditError::InvalidPartNumber { part_number: part_num });
+
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads.get_mut(upload_id)
+            .filter(|upload| upload.key == key)
+            .ok_or_else(|| anyhow!("{} has no upload {}", key, upload_id))?;
+
+        let etag = md5_etag(&data);
+        upload.parts.insert(part_num, (etag.clone(), data));
+        Ok(etag)
+    }
+}
+
+impl ObjectStoreBackend for MockBackend {
+    async fn head(&self, key: &str) -> Result<Option<ObjectStat>> {
+        let stat = self.objects.lock().unwrap().get(key).map(|(data, etag)| ObjectStat {
+            len: data.len() as i64,
+            etag: Some(etag.clone()),
+            content_type: None,
+            last_modified: None,
+            metadata: HashMap::new(),
+        });
+        Ok(stat)
+    }
+
+    async fn read_range(&self, key: &str, range: Range<i64>) -> Result<Bytes> {
+        let data = self.get(key).ok_or_else(|| anyhow::Error::new(EditError::ObjectNotFound { key: key.to_string() }))?;
+        let len = data.len() as i64;
+
+        ensure!(
+            0 <= range.start && range.start <= range.end && range.end <= len,
+            EditError::RangeOutOfBounds { key: key.to_string(), start: range.start, end: range.end, len }
+        );
+        Ok(data.slice(range.start as usize..range.end as usize))
+    }
+
+    async fn create_upload(&self, key: &str) -> Result<String> {
+        let upload_id = format!("upload-{}", self.next_upload.fetch_add(1, Ordering::Relaxed));
+        let upload = MockUpload { key: key.to_string(), parts: BTreeMap::new() };
+        self.uploads.lock().unwrap().insert(upload_id.clone(), upload);
+        Ok(upload_id)
+    }
+
+    async fn copy_part(&self, key: &str, upload_id: &str, part_num: i32, range: Range<i64>, etag: Option<&str>) -> Result<String> {
+        let current = self.objects.lock().unwrap().get(key).map(|(_, etag)| etag.clone());
+        ensure!(etag.is_none() || current.as_deref() == etag, EditError::Conflict { key: key.to_string() });
+
+        let data = self.read_range(key, range).await?;
+        self.add_part(key, upload_id, part_num, data)
+    }
+
+    async fn upload_part(&self, key: &str, upload_id: &str, part_num: i32, data: Bytes) -> Result<String> {
+        self.add_part(key, upload_id, part_num, data)
+    }
+
+    async fn complete(&self, key: &str, upload_id: &str, parts: Vec<(i32, String)>) -> Result<Option<String>> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads.get(upload_id)
+            .filter(|upload| upload.key == key)
+            .ok_or_else(|| anyhow!("{} has no upload {}", key, upload_id))?;
+
+        ensure!(!parts.is_empty(), "{} upload {} completed without parts", key, upload_id);
+        ensure!(parts.windows(2).all(|pair| pair[0].0 < pair[1].0), "{} upload {} parts out of order", key, upload_id);
+
+        let mut data = BytesMut::new();
+
+        for (part_num, etag) in &parts {
+            let (_, part) = upload.parts.get(part_num)
+                .filter(|(part_etag, _)| part_etag == etag)
+                .ok_or_else(|| anyhow!("{} upload {} has no part {} with ETag {}", key, upload_id, part_num, etag))?;
+            data.extend_from_slice(part);
+        }
+
+        let etag = multipart_etag_of_parts(parts.iter().map(|(_, etag)| etag.as_str()))
+            .map(|etag| format!("\"{}\"", etag))
+            .ok_or_else(|| anyhow!("{} upload {} has a part without an MD5 ETag", key, upload_id))?;
+
+        uploads.remove(upload_id);
+        self.objects.lock().unwrap().insert(key.to_string(), (data.freeze(), etag.clone()));
+        Ok(Some(etag))
+    }
+
+    async fn abort(&self, key: &str, upload_id: &str) -> Result<()> {
+        let mut uploads = self.uploads.lock().unwrap();
+        ensure!(uploads.get(upload_id).is_some_and(|upload| upload.key == key), "{} has no upload {}", key, upload_id);
+        uploads.remove(upload_id);
+        Ok(())
+    }
+}
+
+/// Quoted hex MD5 of `data`, the ETag S3 gives a single put or an uploaded part.
+fn md5_etag(data: &[u8]) -> String {
+    format!("\"{}\"", hex(&Md5::digest(data)))
+}
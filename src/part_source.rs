@@ -0,0 +1,96 @@
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::pin::Pin;
+
+use anyhow::{ensure, Result};
+use aws_sdk_s3::primitives::ByteStream;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Replacement bytes of [`Session::modify_from`](crate::Session::modify_from), read a part
+/// at a time as the parts are uploaded instead of held in memory whole.
+pub struct PartSource {
+    len: i64,
+    body: Body,
+}
+
+enum Body {
+    Memory(Vec<u8>, usize),
+    Async(Pin<Box<dyn AsyncRead + Send>>),
+    Blocking(Box<dyn Read + Send>),
+}
+
+impl PartSource {
+    pub fn bytes(data: Vec<u8>) -> Self {
+        PartSource {
+            len: data.len() as i64,
+            body: Body::Memory(data, 0),
+        }
+    }
+
+    /// The whole of the file at `path`, its length taken when this is called.
+    pub fn file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let len = file.metadata()?.len() as i64;
+        Ok(PartSource::reader(tokio::fs::File::from_std(file), len))
+    }
+
+    /// The first `len` bytes of `reader`; reading fails if it ends before them.
+    pub fn reader(reader: impl AsyncRead + Send + 'static, len: i64) -> Self {
+        PartSource {
+            len,
+            body: Body::Async(Box::pin(reader)),
+        }
+    }
+
+    /// The first `len` bytes of a blocking `reader`. Reads block the task driving the edit,
+    /// so prefer [`PartSource::reader`] on a runtime that runs other work.
+    pub fn blocking_reader(reader: impl Read + Send + 'static, len: i64) -> Self {
+        PartSource {
+            len,
+            body: Body::Blocking(Box::new(reader)),
+        }
+    }
+
+    /// The first `len` bytes of `body`, e.g. the body of another object's `get_object`.
+    pub fn stream(body: ByteStream, len: i64) -> Self {
+        PartSource::reader(body.into_async_read(), len)
+    }
+
+    pub fn len(&self) -> i64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The next `len` bytes.
+    pub(crate) async fn read(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        let mut filled = 0;
+
+        while filled < len {
+            let n = match &mut self.body {
+                Body::Memory(data, pos) => {
+                    let n = std::cmp::min(len - filled, data.len() - *pos);
+                    buf[filled..filled + n].copy_from_slice(&data[*pos..*pos + n]);
+                    *pos += n;
+                    n
+                }
+                Body::Async(reader) => reader.read(&mut buf[filled..]).await?,
+                Body::Blocking(reader) => reader.read(&mut buf[filled..])?,
+            };
+
+            ensure!(n > 0, "part source ended {} bytes short", len - filled);
+            filled += n;
+        }
+        Ok(buf)
+    }
+}
+
+impl fmt::Debug for PartSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartSource").field("len", &self.len).finish_non_exhaustive()
+    }
+}
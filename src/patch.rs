@@ -0,0 +1,54 @@
+use anyhow::{anyhow, ensure, Result};
+
+use crate::Part;
+
+// start of every patch, then the format version
+const MAGIC: &[u8] = b"ES3PATCH";
+const VERSION: u8 = 1;
+
+/// The edits of a patch, in the format [`encode_patch`] writes and [`Session::apply_patch`]
+/// applies: `ES3PATCH` and a version byte of 1, then for each edit its offset and length as
+/// big-endian `u64`s followed by its bytes.
+///
+/// Edits must not overlap, as for [`Session::modify_many`], but may come in any order.
+///
+/// [`Session::apply_patch`]: crate::Session::apply_patch
+/// [`Session::modify_many`]: crate::Session::modify_many
+pub fn parse_patch(patch: &[u8]) -> Result<Vec<Part>> {
+    ensure!(patch.starts_with(MAGIC), "not a patch, missing the {:?} header", std::str::from_utf8(MAGIC).unwrap());
+    let version = *patch.get(MAGIC.len()).ok_or_else(|| anyhow!("patch truncated in its header"))?;
+    ensure!(version == VERSION, "unsupported patch version {}", version);
+
+    let mut rest = &patch[MAGIC.len() + 1..];
+    let mut parts = Vec::new();
+
+    while !rest.is_empty() {
+        let pos = patch.len() - rest.len();
+        ensure!(rest.len() >= 16, "patch truncated in the edit header at byte {}", pos);
+
+        let offset = u64::from_be_bytes(rest[..8].try_into().unwrap());
+        let len = u64::from_be_bytes(rest[8..16].try_into().unwrap());
+        rest = &rest[16..];
+
+        let offset = i64::try_from(offset).map_err(|_| anyhow!("patch edit at byte {} has offset {} out of range", pos, offset))?;
+        ensure!(len <= rest.len() as u64, "patch truncated in the {} byte edit at byte {}", len, pos);
+
+        let (data, tail) = rest.split_at(len as usize);
+        parts.push(Part::new(offset, data.to_vec()));
+        rest = tail;
+    }
+    Ok(parts)
+}
+
+/// A patch of `edits`, `(offset, bytes)` pairs, for [`parse_patch`] to read back.
+pub fn encode_patch<'a>(edits: impl IntoIterator<Item = (i64, &'a [u8])>) -> Vec<u8> {
+    let mut patch = MAGIC.to_vec();
+    patch.push(VERSION);
+
+    for (offset, data) in edits {
+        patch.extend_from_slice(&(offset as u64).to_be_bytes());
+        patch.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        patch.extend_from_slice(data);
+    }
+    patch
+}
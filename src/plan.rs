@@ -0,0 +1,301 @@
+use std::ops::Range;
+
+use serde::Deserialize;
+
+use crate::EditError;
+
+// 5MB, S3's minimum size of every part but the last
+pub const MIN_PART_SIZE: i64 = 5 * 1024 * 1024;
+// S3's maximum number of parts in one multipart upload
+pub const MAX_PARTS: i64 = 10000;
+// 5GB, the largest source range one `upload_part_copy` may copy
+pub const MAX_COPY_PART_SIZE: i64 = 5 * 1024 * MB;
+// 5GB, S3's maximum size of an uploaded part
+pub const MAX_PART_SIZE: i64 = 5 * 1024 * MB;
+// 5TB, the largest object S3 can store, and so build with one multipart upload
+pub const MAX_OBJECT_SIZE: i64 = 5 * 1024 * 1024 * MB;
+const MB: i64 = 1024 * 1024;
+
+/// Size of the parts an object is rebuilt from.
+///
+/// In config files this is either a number of bytes or the string `"auto"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "PartSizeRepr")]
+pub enum PartSize {
+    Fixed(i64),
+    /// The smallest whole number of MB that fits the object in [`MAX_PARTS`] parts,
+    /// but at least [`MIN_PART_SIZE`].
+    Auto,
+}
+
+impl Default for PartSize {
+    fn default() -> Self {
+        // 1GB
+        PartSize::Fixed(1024 * MB)
+    }
+}
+
+impl PartSize {
+    pub fn resolve(self, obj_len: i64) -> i64 {
+        match self {
+            PartSize::Fixed(size) => size,
+            PartSize::Auto => {
+                let per_part = (obj_len + MAX_PARTS - 1) / MAX_PARTS;
+                let rounded = (per_part + MB - 1) / MB * MB;
+                std::cmp::max(rounded, MIN_PART_SIZE)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PartSizeRepr {
+    Fixed(i64),
+    Named(String),
+}
+
+impl TryFrom<PartSizeRepr> for PartSize {
+    type Error = String;
+
+    fn try_from(repr: PartSizeRepr) -> Result<Self, Self::Error> {
+        match repr {
+            PartSizeRepr::Fixed(size) => Ok(PartSize::Fixed(size)),
+            PartSizeRepr::Named(name) if name == "auto" => Ok(PartSize::Auto),
+            PartSizeRepr::Named(name) => Err(format!("invalid part size {:?}, expected bytes or \"auto\"", name)),
+        }
+    }
+}
+
+/// One part of the rebuilt object, in part number order.
+///
+/// Ranges are offsets in the resulting object. `Copy` ranges are copied server-side from
+/// the same range of the source and never overlap an edit. `Upload` ranges are assembled
+/// client-side from the edits they cover plus original bytes fetched for the rest, which
+/// is how edits smaller than [`MIN_PART_SIZE`] are padded into a legal part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartOp {
+    Copy(Range<i64>),
+    Upload(Range<i64>),
+}
+
+/// Split `range` into parts of `part_size` bytes, rebalancing the last two parts so that
+/// no part is smaller than [`MIN_PART_SIZE`] unless the whole range is.
+fn split(range: Range<i64>, part_size: i64, op: fn(Range<i64>) -> PartOp, out: &mut Vec<PartOp>) {
+    let len = range.end - range.start;
+
+    if len <= 0 {
+        return;
+    }
+
+    let mut bounds = Vec::new();
+    let mut offset = range.start;
+
+    while offset < range.end {
+        let end = std::cmp::min(offset + part_size, range.end);
+        bounds.push(offset..end);
+        offset = end;
+    }
+
+    if let [.., prev, last] = bounds.as_mut_slice() {
+        let last_len = last.end - last.start;
+
+        if last_len < MIN_PART_SIZE {
+            let shift = MIN_PART_SIZE - last_len;
+
+            if prev.end - prev.start - shift >= MIN_PART_SIZE {
+                prev.end -= shift;
+                last.start -= shift;
+            } else {
+                prev.end = last.end;
+                bounds.pop();
+            }
+        }
+    }
+
+    out.extend(bounds.into_iter().map(op));
+}
+
+/// Lay out an object of `obj_len` bytes whose `edits` ranges are replaced by new data.
+///
+/// `edits` must be sorted, non-overlapping and within the object. Edits smaller than
+/// [`MIN_PART_SIZE`] are padded with neighboring original bytes, and edits separated by
+/// less than [`MIN_PART_SIZE`] are merged into one uploaded region, so that every part
+/// but the last is at least [`MIN_PART_SIZE`]. Copied gaps and uploaded regions are split
+/// into parts of about `part_size` bytes, copied parts never more than [`MAX_COPY_PART_SIZE`].
+///
+/// S3 builds a part either from uploaded bytes or from one server-side copy, never both, so
+/// the padding has to be downloaded and uploaded again. It is kept to what the part needs:
+/// a lone small edit is padded to exactly [`MIN_PART_SIZE`], including one near the start,
+/// whose padding runs from offset 0 rather than leaving a too small copy in front of it.
+pub fn plan(obj_len: i64, edits: &[Range<i64>], part_size: i64) -> Vec<PartOp> {
+    plan_with(obj_len, edits, part_size, std::cmp::min(part_size, MAX_COPY_PART_SIZE))
+}
+
+/// [`plan`] with the copied gaps split into parts of [`MAX_COPY_PART_SIZE`] whatever
+/// `part_size` is, which then only sizes uploaded parts.
+///
+/// Copied bytes never transit the client: an `upload_part_copy` is one request whatever its
+/// size, so the fewer and bigger the copies, the fewer requests and parts an edit takes, e.g.
+/// 3 parts instead of 20 for a small edit in the middle of a 100MB object with 5MB parts.
+/// Uploaded parts are still padded only to [`MIN_PART_SIZE`], which is what the client sends
+/// and, for the padding, downloads; see [`ModifyStats::bytes_read`](crate::ModifyStats::bytes_read).
+pub fn plan_adaptive(obj_len: i64, edits: &[Range<i64>], part_size: i64) -> Vec<PartOp> {
+    plan_with(obj_len, edits, part_size, MAX_COPY_PART_SIZE)
+}
+
+/// `edits`, sorted and non-overlapping, with those separated by at most `max_gap` bytes
+/// merged into one range covering the gap.
+///
+/// Planned with the gaps merged, nearby edits share uploaded parts instead of each taking
+/// parts of its own with copies between them: the gap bytes are downloaded and uploaded
+/// again, but the edit takes fewer requests. [`plan`] already merges gaps smaller than
+/// [`MIN_PART_SIZE`], which need no copy part anyway.
+pub fn coalesce_edits(edits: &[Range<i64>], max_gap: i64) -> Vec<Range<i64>> {
+    let mut merged: Vec<Range<i64>> = Vec::with_capacity(edits.len());
+
+    for edit in edits.iter().filter(|e| e.start < e.end) {
+        match merged.last_mut() {
+            Some(prev) if edit.start - prev.end <= max_gap => prev.end = std::cmp::max(prev.end, edit.end),
+            _ => merged.push(edit.clone()),
+        }
+    }
+    merged
+}
+
+fn plan_with(obj_len: i64, edits: &[Range<i64>], part_size: i64, copy_part_size: i64) -> Vec<PartOp> {
+    let mut padded = edits.iter()
+        .filter(|e| e.start < e.end)
+        .map(|edit| {
+            let mut region = edit.clone();
+
+            if region.end - region.start < MIN_PART_SIZE {
+                if region.start < MIN_PART_SIZE {
+                    // no copy fits in front of it, so pad from the start only as far as needed
+                    region.start = 0;
+                    region.end = std::cmp::max(region.end, std::cmp::min(obj_len, MIN_PART_SIZE));
+                } else {
+                    region.end = std::cmp::min(obj_len, region.start + MIN_PART_SIZE);
+                    region.start = std::cmp::max(0, std::cmp::min(region.start, region.end - MIN_PART_SIZE));
+                }
+            }
+            region
+        })
+        .collect::<Vec<_>>();
+
+    // padding near the end of the object extends to the left and may reorder regions
+    padded.sort_by_key(|r| r.start);
+
+    let mut dirty: Vec<Range<i64>> = Vec::new();
+
+    for region in padded {
+        match dirty.last_mut() {
+            Some(prev) if region.start - prev.end < MIN_PART_SIZE => {
+                prev.end = std::cmp::max(prev.end, region.end);
+            }
+            _ => dirty.push(region),
+        }
+    }
+
+    // a leading copy part would be too small
+    if let Some(first) = dirty.first_mut() {
+        if first.start < MIN_PART_SIZE {
+            first.start = 0;
+        }
+    }
+
+    let mut ops = Vec::new();
+    let mut offset = 0;
+
+    for region in dirty {
+        split(offset..region.start, copy_part_size, PartOp::Copy, &mut ops);
+        offset = region.end;
+        split(region, part_size, PartOp::Upload, &mut ops);
+    }
+
+    split(offset..obj_len, copy_part_size, PartOp::Copy, &mut ops);
+    ops
+}
+
+/// Lay out an object of `obj_len` bytes whose `edits` ranges are replaced by new data along
+/// the boundaries of its source, a multipart object with parts of `source_parts` bytes.
+///
+/// Source parts no edit touches are copied as they are, and touched ones uploaded whole, so
+/// the parts keep their sizes and the result its source's layout; parts past `obj_len` are
+/// dropped and the one it falls in cut. Bytes past the end of the source are uploaded in
+/// parts of about `part_size` bytes, merged with the last source part if that one is smaller
+/// than [`MIN_PART_SIZE`]. Sources with parts that aren't a legal layout give plans that
+/// [`validate_plan`] rejects.
+pub fn plan_along_parts(obj_len: i64, source_parts: &[i64], edits: &[Range<i64>], part_size: i64) -> Vec<PartOp> {
+    let mut ops = Vec::new();
+    let mut offset = 0;
+
+    for &size in source_parts {
+        let range = offset..std::cmp::min(offset + size, obj_len);
+        offset += size;
+
+        if range.is_empty() {
+            break;
+        }
+
+        let touched = edits.iter().any(|e| e.start < range.end && range.start < e.end && e.start < e.end);
+
+        ops.push(match touched {
+            true => PartOp::Upload(range),
+            false => PartOp::Copy(range),
+        });
+    }
+
+    let source_end = match ops.last() {
+        Some(PartOp::Copy(range) | PartOp::Upload(range)) => range.end,
+        None => 0,
+    };
+
+    if source_end < obj_len {
+        let mut start = source_end;
+
+        // a short last part would be too small once the appended bytes follow it
+        if let Some(PartOp::Copy(last) | PartOp::Upload(last)) = ops.last() {
+            if last.end - last.start < MIN_PART_SIZE {
+                start = last.start;
+                ops.pop();
+            }
+        }
+        split(start..obj_len, part_size, PartOp::Upload, &mut ops);
+    }
+    ops
+}
+
+/// Check that the object is at most [`MAX_OBJECT_SIZE`], that the parts cover `0..obj_len`
+/// exactly, in order, without gaps or overlaps, that every part but the last is at least
+/// [`MIN_PART_SIZE`] and that there are at most [`MAX_PARTS`], which S3 otherwise only
+/// reports when completing the upload.
+pub fn validate_plan(ops: &[PartOp], obj_len: i64) -> Result<(), EditError> {
+    if obj_len > MAX_OBJECT_SIZE {
+        return Err(EditError::ObjectTooLarge { size: obj_len });
+    }
+
+    if ops.len() as i64 > MAX_PARTS {
+        return Err(EditError::TooManyParts { parts: ops.len() });
+    }
+
+    let mut offset = 0;
+
+    for (i, op) in ops.iter().enumerate() {
+        let (PartOp::Copy(range) | PartOp::Upload(range)) = op;
+
+        if range.start != offset || range.end <= range.start {
+            return Err(EditError::PlanInconsistent { offset });
+        }
+
+        if i + 1 < ops.len() && range.end - range.start < MIN_PART_SIZE {
+            return Err(EditError::PartTooSmall { part_number: i as i32 + 1, size: range.end - range.start });
+        }
+        offset = range.end;
+    }
+
+    if offset != obj_len {
+        return Err(EditError::PlanInconsistent { offset });
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use aws_sdk_s3::presigning::PresignedRequest as SdkPresignedRequest;
+use serde::Serialize;
+
+/// An edit laid out as presigned requests by [`Session::presign_edit`], for a worker without
+/// credentials of its own to carry out. Serializes to JSON for handing it over.
+///
+/// The worker only reads the original bytes and uploads parts; the copies and the completion
+/// stay with the session that planned the edit, see [`Session::complete_presigned_edit`].
+///
+/// [`Session::presign_edit`]: crate::Session::presign_edit
+/// [`Session::complete_presigned_edit`]: crate::Session::complete_presigned_edit
+#[derive(Debug, Clone, Serialize)]
+pub struct PresignedEdit {
+    pub key: String,
+    /// The multipart upload the part requests add to, started already.
+    pub upload_id: String,
+    /// ETag of the object the plan was made for, which the copies and reads require.
+    pub etag: Option<String>,
+    pub object_len: i64,
+    /// Length of the object once edited.
+    pub new_len: i64,
+    /// The parts in part number order.
+    pub parts: Vec<PresignedPart>,
+}
+
+/// A part of a [`PresignedEdit`], covering the bytes `start..end` of the edited object.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PresignedPart {
+    /// Copied from the same range of the object when the edit is completed; nothing for the
+    /// worker to send.
+    Copy {
+        part_number: i32,
+        start: i64,
+        end: i64,
+    },
+    /// Send the part's bytes as the body of `request`: those of the object, from `read` where
+    /// the part overlaps it, with the edits written over them and zeros past its end. The
+    /// ETag it returns is handed back for the completion.
+    Upload {
+        part_number: i32,
+        start: i64,
+        end: i64,
+        read: Option<PresignedRequest>,
+        request: PresignedRequest,
+    },
+}
+
+/// A presigned S3 request: send `method` to `url` with `headers`, which are signed too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PresignedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl From<SdkPresignedRequest> for PresignedRequest {
+    fn from(req: SdkPresignedRequest) -> Self {
+        PresignedRequest {
+            method: req.method().to_string(),
+            url: req.uri().to_string(),
+            headers: req.headers().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::PartOp;
+
+/// A step of a multipart upload, reported to the function given to
+/// [`Session::with_progress`](crate::Session::with_progress) or streamed by
+/// [`Session::modify_many_with_progress`](crate::Session::modify_many_with_progress).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// The parts of the rewrite of `key` were planned; the part numbered `n` is `ops[n - 1]`.
+    Planned {
+        key: String,
+        ops: Vec<PartOp>,
+    },
+    /// A part was copied server-side from the source object.
+    PartCopied {
+        part_number: i32,
+        len: i64,
+    },
+    /// A part was uploaded.
+    PartUploaded {
+        part_number: i32,
+        len: i64,
+    },
+    /// The upload was completed into `key`.
+    Completed {
+        key: String,
+    },
+}
+
+/// Receiver of the [`ProgressEvent`]s of a session's uploads.
+#[derive(Clone)]
+pub(crate) struct Progress(Arc<dyn Fn(ProgressEvent) + Send + Sync>);
+
+impl Progress {
+    pub(crate) fn new(f: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        Progress(Arc::new(f))
+    }
+
+    pub(crate) fn emit(&self, event: ProgressEvent) {
+        (self.0)(event)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Progress(..)")
+    }
+}
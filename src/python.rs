@@ -0,0 +1,81 @@
+//! Python module over the blocking API, built with maturin from `pyproject.toml`.
+//!
+//! Every function releases the GIL while it waits on S3. Keys may be `s3://bucket/key` URIs
+//! and the global session is configured like for Rust callers, through `S3_STORE_CONFIG`.
+//! [`edit_s3_file`] is the module itself, public for embedding it in an interpreter.
+
+// pyo3 0.22 converts the `PyResult` of every `#[pyfunction]` into itself, which clippy
+// reports against the signature of each
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyFileNotFoundError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::{session_for, EditError, Part, PartOp, RT};
+
+/// Overwrite the bytes at `offset` of `key` with `data`, extending the object if they run
+/// past its end. Returns the new ETag.
+#[pyfunction]
+fn modify(py: Python<'_>, key: &str, offset: i64, data: &[u8]) -> PyResult<Option<String>> {
+    let stats = py.allow_threads(|| crate::modify(key, Part::new(offset, data.to_vec())))
+        .map_err(to_py_err)?;
+    Ok(stats.etag)
+}
+
+/// The `length` bytes at `offset` of `key`.
+#[pyfunction]
+fn read_at<'py>(py: Python<'py>, key: &str, offset: i64, length: i64) -> PyResult<Bound<'py, PyBytes>> {
+    let data = py.allow_threads(|| crate::read_at(key, offset, length))
+        .map_err(to_py_err)?;
+    Ok(PyBytes::new_bound(py, &data))
+}
+
+/// Add `data` to the end of `key`. Returns the new ETag.
+#[pyfunction]
+fn append(py: Python<'_>, key: &str, data: &[u8]) -> PyResult<Option<String>> {
+    let stats = py.allow_threads(|| crate::append(key, data.to_vec()))
+        .map_err(to_py_err)?;
+    Ok(stats.etag)
+}
+
+/// The parts writing `data` at `offset` of `key` would take, as `(kind, start, end)` tuples
+/// with `kind` either `"copy"` or `"upload"` and `end` exclusive, without writing anything.
+#[pyfunction]
+fn plan(py: Python<'_>, key: &str, offset: i64, data: &[u8]) -> PyResult<Vec<(&'static str, i64, i64)>> {
+    let ops = py.allow_threads(|| {
+        let (session, key) = session_for(key)?;
+        let plan = RT.block_on(session.plan_modify(key, vec![Part::new(offset, data.to_vec())]))?;
+        anyhow::Ok(plan.ops().to_vec())
+    }).map_err(to_py_err)?;
+
+    let ops = ops.into_iter()
+        .map(|op| match op {
+            PartOp::Copy(range) => ("copy", range.start, range.end),
+            PartOp::Upload(range) => ("upload", range.start, range.end),
+        })
+        .collect();
+    Ok(ops)
+}
+
+/// The `edit_s3_file` module.
+#[pymodule]
+pub fn edit_s3_file(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(modify, m)?)?;
+    m.add_function(wrap_pyfunction!(read_at, m)?)?;
+    m.add_function(wrap_pyfunction!(append, m)?)?;
+    m.add_function(wrap_pyfunction!(plan, m)?)?;
+    Ok(())
+}
+
+/// `FileNotFoundError` for a missing object, `ValueError` for a range outside it and
+/// `RuntimeError` for the rest, with the whole chain of causes as message.
+fn to_py_err(err: anyhow::Error) -> PyErr {
+    let message = format!("{:#}", err);
+
+    match err.downcast_ref::<EditError>() {
+        Some(EditError::ObjectNotFound { .. }) => PyFileNotFoundError::new_err(message),
+        Some(EditError::RangeOutOfBounds { .. }) => PyValueError::new_err(message),
+        _ => PyRuntimeError::new_err(message),
+    }
+}
@@ -0,0 +1,172 @@
+use std::future::Future;
+use std::io;
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::{ensure, Result};
+use aws_sdk_s3::Client;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use crate::sse::SseCustomer;
+use crate::{fetch_range, read_buffer_size, session_for, Session};
+
+// 8MB
+pub const DEFAULT_READ_WINDOW: usize = 8 * 1024 * 1024;
+
+type Fetch = Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send>>;
+
+/// `AsyncRead + AsyncSeek` over the byte range `start..end` of an object.
+///
+/// Bytes are fetched lazily with ranged `get_object` calls of up to `window` bytes
+/// (read-ahead, [`DEFAULT_READ_WINDOW`] unless set with [`AsyncRangeReader::with_window`]).
+/// Positions are relative to `start`: seeking to 0 rewinds to the beginning of the range.
+pub struct AsyncRangeReader {
+    client: Client,
+    bucket: String,
+    key: String,
+    sse: SseCustomer,
+    read_buffer_size: usize,
+    start: u64,
+    end: u64,
+    pos: u64,
+    window: usize,
+    buf: Vec<u8>,
+    buf_offset: u64,
+    fetch: Option<(u64, Fetch)>,
+}
+
+impl AsyncRangeReader {
+    /// Reader on the global session, see [`Session::range_reader`] for other sessions.
+    /// `key` may be an `s3://bucket/key` URI.
+    pub fn new(key: &str, start: u64, end: u64) -> Result<Self> {
+        let (session, key) = session_for(key)?;
+        Self::from_session(&session, key, start, end)
+    }
+
+    pub(crate) fn from_session(session: &Session, key: &str, start: u64, end: u64) -> Result<Self> {
+        ensure!(start <= end, "invalid range {}-{}", start, end);
+
+        Ok(AsyncRangeReader {
+            client: session.client.clone(),
+            bucket: session.config.bucket.clone(),
+            key: key.to_string(),
+            sse: SseCustomer::new(&session.config),
+            read_buffer_size: read_buffer_size(&session.config),
+            start,
+            end,
+            pos: start,
+            window: DEFAULT_READ_WINDOW,
+            buf: Vec::new(),
+            buf_offset: start,
+            fetch: None,
+        })
+    }
+
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    fn buffered(&self) -> Option<&[u8]> {
+        let buf_end = self.buf_offset + self.buf.len() as u64;
+
+        if self.pos >= self.buf_offset && self.pos < buf_end {
+            Some(&self.buf[(self.pos - self.buf_offset) as usize..])
+        } else {
+            None
+        }
+    }
+}
+
+impl AsyncRead for AsyncRangeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.pos >= this.end || out.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(data) = this.buffered() {
+                let n = std::cmp::min(data.len(), out.remaining());
+                out.put_slice(&data[..n]);
+                this.pos += n as u64;
+                return Poll::Ready(Ok(()));
+            }
+
+            // a seek may have moved away from the in-flight window
+            if matches!(this.fetch, Some((offset, _)) if offset != this.pos) {
+                this.fetch = None;
+            }
+
+            if this.fetch.is_none() {
+                let fetch_end = std::cmp::min(this.pos + this.window as u64, this.end);
+                let fut = fetch_range(
+                    this.client.clone(),
+                    this.bucket.clone(),
+                    this.key.clone(),
+                    this.sse.clone(),
+                    this.read_buffer_size,
+                    this.pos,
+                    fetch_end,
+                );
+                this.fetch = Some((this.pos, Box::pin(fut)));
+            }
+
+            let (offset, fetch) = this.fetch.as_mut().unwrap();
+            let offset = *offset;
+
+            let data = match fetch.as_mut().poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(res) => {
+                    this.fetch = None;
+                    res.map_err(io::Error::other)?
+                }
+            };
+
+            // the object is shorter than the requested range
+            if data.is_empty() {
+                this.end = this.pos;
+            }
+
+            this.buf = data;
+            this.buf_offset = offset;
+        }
+    }
+}
+
+impl AsyncSeek for AsyncRangeReader {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+
+        let target = match position {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => this.len().checked_add_signed(n),
+            SeekFrom::Current(n) => (this.pos - this.start).checked_add_signed(n),
+        };
+
+        let target = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+        })?;
+
+        this.pos = this.start.saturating_add(target);
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.pos - self.start))
+    }
+}
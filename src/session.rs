@@ -0,0 +1,3224 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::ops::Range;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, ensure, Result};
+use aws_sdk_s3::Client;
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextMut;
+use aws_sdk_s3::config::{ConfigBag, Credentials, Intercept, ProvideCredentials, RuntimeComponents, SharedCredentialsProvider};
+use aws_sdk_s3::config::timeout::TimeoutConfig;
+use aws_sdk_s3::config::{SharedHttpClient, SharedInterceptor};
+use aws_sdk_s3::error::{BoxError, ProvideErrorMetadata};
+use aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::operation::upload_part_copy::UploadPartCopyError;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::{ByteStream, DateTime, DateTimeFormat};
+use aws_sdk_s3::types::{AccessControlPolicy, ChecksumAlgorithm, ChecksumMode, CompletedMultipartUpload, CompletedPart, MetadataDirective as S3MetadataDirective, ObjectAttributes, ObjectLockEnabled, ObjectLockLegalHoldStatus, ObjectLockMode, ServerSideEncryption, StorageClass, Tag, Tagging};
+use aws_smithy_http_client::tls::rustls_provider::CryptoMode;
+use aws_smithy_http_client::tls::{Provider as TlsProvider, TlsContext, TrustStore};
+use aws_smithy_http_client::Builder as HttpClientBuilder;
+use aws_types::region::Region;
+use aws_types::SdkConfig;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use futures_util::future::try_join_all;
+use futures_util::{stream, Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt};
+use tokio::sync::{mpsc, OnceCell, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+use crate::config::{check_bucket, check_bucket_type, check_part_size, is_access_point_arn, normalize_endpoint};
+use crate::credentials::DefaultChain;
+use crate::lock::EditLock;
+use crate::sse::{check_sse, CopySourceSseCustomerExt, SseCustomer, SseCustomerExt, SseModeExt};
+use crate::stat_cache::StatCache;
+use crate::presign::{PresignedEdit, PresignedPart};
+use crate::failover::Failover;
+use crate::hooks::Hooks;
+use crate::progress::Progress;
+use crate::throttle::Throttle;
+use crate::{assemble_part, body_reader, copy_source, fetch_range, final_checksum, head, if_none_match, is_auth_failure, log_plan, parse_patch, plan_edits, read_buffer_size, retry_config, rewrite, sdk_error, temp_key, validate_plan, write_retries, AsyncRangeReader, Capabilities, ChunkedObject, CostEstimate, EditError, EditHooks, EditPlan, EditTransaction, MetadataDirective, ModifyStats, MultipartUpload, ObjectPages, ObjectSummary, ObjectVersion, ProgressEvent, RangeKind, ObjectParts, ObjectStat, Part, PartOp, PartSource, S3Config, SdkResultExt, Source, Transaction, Upload, DEFAULT_CONCURRENCY, DEFAULT_MAX_READ_BYTES, DEFAULT_READ_MAX_RETRIES, DEFAULT_REGION, DEFAULT_STAT_CACHE_SIZE, FETCH_ADD_RETRIES, SYNC_BLOCK_SIZE, REDACTED, MAX_COPY_PART_SIZE, MAX_COPY_SIZE, MAX_OBJECT_SIZE, MAX_PUT_SIZE, ORIGINAL_LAST_MODIFIED_KEY};
+
+/// A client bound to one bucket, which every operation of the crate runs against.
+///
+/// The free functions use a global session built from the config file named by
+/// [`PATH_ENV`](crate::PATH_ENV). Build one yourself to use several buckets or
+/// credentials in one process, or with [`Session::from_sdk_config`] to customize the
+/// underlying SDK (interceptors, retry classifiers, HTTP client, ...).
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub(crate) config: S3Config,
+    pub(crate) client: Client,
+    // clients for other endpoints and regions made by `with_endpoint` and `in_region`,
+    // keyed by (endpoint, region) and shared by clones of the session
+    clients: Arc<Mutex<HashMap<(String, String), Client>>>,
+    // bounds the requests in flight across clones of the session
+    pub(crate) permits: Arc<Semaphore>,
+    // result of `detect_capabilities`, probed once per endpoint
+    capabilities: Arc<OnceCell<Capabilities>>,
+    // whether the bucket has Object Lock enabled, looked up once for assumed-length edits
+    object_lock: Arc<OnceCell<bool>>,
+    // uploads started through `ObjectStoreBackend`, by id, until completed or aborted
+    pub(crate) backend_uploads: Arc<Mutex<HashMap<String, Arc<Upload>>>>,
+    // paces part transfers across clones of the session, with `max_bytes_per_sec` set
+    pub(crate) throttle: Option<Arc<Throttle>>,
+    // receives the progress of uploads, set by `with_progress`
+    pub(crate) progress: Option<Progress>,
+    // checks the edits of the session, set by `with_hooks`
+    pub(crate) hooks: Option<Hooks>,
+    // stops the multipart uploads of the session, set by `with_cancellation`
+    pub(crate) cancel: Option<CancellationToken>,
+    // stats of `head_object` by key, with `stat_cache_ttl_secs` set, shared by clones of the session
+    pub(crate) stat_cache: Option<Arc<StatCache>>,
+}
+
+impl Session {
+    /// Session with static credentials from `access_key`/`secret_key` if both are set, or else
+    /// from the AWS default chain, reading the shared files' `profile` if set.
+    pub fn new(config: S3Config) -> Result<Self> {
+        let provider = match (&config.access_key, &config.secret_key) {
+            (Some(ak), Some(sk)) => SharedCredentialsProvider::new(Credentials::new(
+                ak,
+                sk,
+                None,
+                None,
+                "Static",
+            )),
+            _ => SharedCredentialsProvider::new(DefaultChain::new(config.profile.clone(), config.region.clone())),
+        };
+
+        Session::build(config, Some(provider))
+    }
+
+    /// Session taking credentials from `provider`, e.g. one rotating short-lived credentials
+    /// from Vault, instead of `access_key`/`secret_key`.
+    pub fn with_credentials_provider(config: S3Config, provider: Arc<dyn ProvideCredentials>) -> Result<Self> {
+        Session::build(config, Some(SharedCredentialsProvider::from(provider)))
+    }
+
+    fn build(mut config: S3Config, provider: Option<SharedCredentialsProvider>) -> Result<Self> {
+        config.compat.apply(&mut config);
+        check_bucket(&config.bucket, config.force_path_style)?;
+        check_part_size(config.part_size)?;
+        check_sse(config.sse_customer_key.as_deref(), config.sse.as_ref())?;
+        check_bucket_type(&config)?;
+        let endpoint = normalize_endpoint(&config.endpoint, config.insecure)?;
+        let failover_endpoints = config.failover_endpoints.iter()
+            .map(|endpoint| normalize_endpoint(endpoint, config.insecure))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut builder = SdkConfig::builder()
+            .endpoint_url(&endpoint)
+            .region(Region::new(config.region.clone().unwrap_or_else(|| DEFAULT_REGION.to_string())));
+        builder.set_credentials_provider(provider);
+        builder.set_timeout_config(timeout_config(&config));
+
+        if let Some(path) = &config.ca_cert_path {
+            builder.set_http_client(Some(ca_http_client(path)?));
+        }
+
+        let read_retries = config.read_max_retries.unwrap_or(DEFAULT_READ_MAX_RETRIES);
+
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&builder.build())
+            .force_path_style(config.force_path_style)
+            .retry_config(retry_config(&config, read_retries))
+            // requests to an access point go to the ARN's region, not the configured one
+            .use_arn_region(is_access_point_arn(&config.bucket));
+
+        if config.requester_pays {
+            s3_config = s3_config.interceptor(RequesterPays);
+        }
+
+        if !failover_endpoints.is_empty() {
+            let endpoints = std::iter::once(endpoint).chain(failover_endpoints).collect::<Vec<_>>();
+            s3_config = s3_config.interceptor(Failover::new(&endpoints));
+        }
+        let s3_config = s3_config.build();
+
+        Ok(Session::from_parts(config, Client::from_conf(s3_config)))
+    }
+
+    /// Session on `bucket` with a client built from a caller-provided `SdkConfig`,
+    /// e.g. one shared with other AWS services. Part size and memory settings are the defaults.
+    pub fn from_sdk_config(sdk_config: &SdkConfig, bucket: &str) -> Self {
+        Session::from_client(Client::new(sdk_config), bucket)
+    }
+
+    /// Session on `bucket` with an already built client, for S3-specific settings such as
+    /// path-style addressing that `SdkConfig` can't carry.
+    pub fn from_client(client: Client, bucket: &str) -> Self {
+        // endpoint, region and credentials only matter for building a client
+        let config = S3Config {
+            bucket: bucket.to_string(),
+            region: client.config().region().map(|r| r.to_string()),
+            ..S3Config::default()
+        };
+
+        Session::from_parts(config, client)
+    }
+
+    fn from_parts(config: S3Config, client: Client) -> Self {
+        let permits = Semaphore::new(config.max_concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1));
+        let throttle = config.max_bytes_per_sec.map(|rate| Arc::new(Throttle::new(rate)));
+        let stat_cache = new_stat_cache(&config);
+
+        Session {
+            config,
+            client,
+            clients: Arc::default(),
+            permits: Arc::new(permits),
+            capabilities: Arc::default(),
+            object_lock: Arc::default(),
+            backend_uploads: Arc::default(),
+            throttle,
+            progress: None,
+            hooks: None,
+            cancel: None,
+            stat_cache,
+        }
+    }
+
+    /// The same session with `x-amz-request-payer: requester` sent with every request or not,
+    /// whatever `requester_pays` says, e.g. for the edits of one requester-pays bucket among
+    /// others. Unlike the setting, it applies to the client of [`Session::from_client`] too.
+    pub fn with_requester_pays(&self, requester_pays: bool) -> Session {
+        let interceptors = self.client.config()
+            .interceptors()
+            .filter(|interceptor| interceptor.name() != RequesterPays.name())
+            .collect::<Vec<_>>();
+
+        let mut conf = self.client.config().to_builder();
+        conf.set_interceptors(interceptors);
+
+        if requester_pays {
+            conf.push_interceptor(SharedInterceptor::new(RequesterPays));
+        }
+
+        Session {
+            config: S3Config { requester_pays, ..self.config.clone() },
+            client: Client::from_conf(conf.build()),
+            // those of other regions and endpoints keep the setting they were made with
+            clients: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// The same session with requests signed for and sent to `region`, e.g. for objects behind
+    /// a multi-region or cross-region access point. Clients are built once per region.
+    pub fn in_region(&self, region: &str) -> Session {
+        if self.config.region.as_deref() == Some(region) {
+            return self.clone();
+        }
+
+        let client = self.clients.lock()
+            .unwrap()
+            .entry((self.config.endpoint.clone(), region.to_string()))
+            .or_insert_with(|| {
+                let conf = self.client.config()
+                    .to_builder()
+                    .region(Region::new(region.to_string()))
+                    .build();
+                Client::from_conf(conf)
+            })
+            .clone();
+
+        Session {
+            config: S3Config { region: Some(region.to_string()), ..self.config.clone() },
+            client,
+            ..self.clone()
+        }
+    }
+
+    /// The same session with requests sent to `endpoint`, e.g. for a gateway that routes some
+    /// objects differently. Clients are built once per endpoint and region.
+    pub fn with_endpoint(&self, endpoint: &str) -> Result<Session> {
+        let endpoint = normalize_endpoint(endpoint, self.config.insecure)?;
+
+        if self.config.endpoint == endpoint {
+            return Ok(self.clone());
+        }
+
+        let region = self.config.region.clone().unwrap_or_else(|| DEFAULT_REGION.to_string());
+
+        let client = self.clients.lock()
+            .unwrap()
+            .entry((endpoint.clone(), region))
+            .or_insert_with(|| {
+                let conf = self.client.config()
+                    .to_builder()
+                    .endpoint_url(&endpoint)
+                    .build();
+                Client::from_conf(conf)
+            })
+            .clone();
+
+        Ok(Session {
+            config: S3Config { endpoint, ..self.config.clone() },
+            client,
+            // another server may support other things
+            capabilities: Arc::default(),
+            object_lock: Arc::default(),
+            // and holds other objects
+            stat_cache: new_stat_cache(&self.config),
+            ..self.clone()
+        })
+    }
+
+    /// The same session for another bucket (or access point ARN) behind the same endpoint
+    /// and credentials.
+    pub fn with_bucket(&self, bucket: &str) -> Result<Session> {
+        if self.config.bucket == bucket {
+            return Ok(self.clone());
+        }
+
+        check_bucket(bucket, self.config.force_path_style)?;
+
+        let client = if is_access_point_arn(bucket) == is_access_point_arn(&self.config.bucket) {
+            self.client.clone()
+        } else {
+            let conf = self.client.config()
+                .to_builder()
+                .use_arn_region(is_access_point_arn(bucket))
+                .build();
+            Client::from_conf(conf)
+        };
+
+        Ok(Session {
+            config: S3Config { bucket: bucket.to_string(), ..self.config.clone() },
+            client,
+            object_lock: Arc::default(),
+            stat_cache: new_stat_cache(&self.config),
+            ..self.clone()
+        })
+    }
+
+    /// The same session with its multipart uploads made resumable under `token`, so that an
+    /// edit retried after a crash reuses the upload the failed attempt left instead of leaving
+    /// it behind and starting another.
+    ///
+    /// An upload started with a token is recorded in a small `<key>.upload-<token>` object
+    /// holding its upload id, deleted once the upload completes or aborts. Starting an upload
+    /// of the same key and token reads that record and resumes the upload if
+    /// `list_multipart_uploads` still shows it in progress; otherwise a new one is started.
+    /// A resumed upload keeps the metadata it was created with, and the retried edit picks up
+    /// where the failed one stopped: the parts `list_parts` shows stored are kept where they
+    /// hold what the retry would write, i.e. uploaded parts with the same bytes (by their MD5
+    /// ETag, so not under SSE-KMS or SSE-C) and copied parts of the same length while the
+    /// object still has the ETag it had when the upload started. The other parts are uploaded
+    /// or copied again, replacing those of the same numbers. Tokens must be unique per logical
+    /// edit: two edits of one key sharing a token at the same time write into the same upload,
+    /// and a different edit under the same token may keep parts planned for the first one.
+    /// The record is visible to listings while the upload runs.
+    pub fn with_upload_token(&self, token: &str) -> Session {
+        Session {
+            config: S3Config { upload_token: Some(token.to_string()), ..self.config.clone() },
+            ..self.clone()
+        }
+    }
+
+    /// The same session with `on_progress` called with the [`ProgressEvent`]s of its multipart
+    /// uploads, from the task running the upload. Single puts report nothing.
+    pub fn with_progress(&self, on_progress: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Session {
+        Session {
+            progress: Some(Progress::new(on_progress)),
+            ..self.clone()
+        }
+    }
+
+    /// The same session with `hooks` checking its edits before they are planned and before
+    /// their multipart uploads are completed, and vetoing them by returning an error.
+    pub fn with_hooks(&self, hooks: impl EditHooks + 'static) -> Session {
+        Session {
+            hooks: Some(Hooks::new(hooks)),
+            ..self.clone()
+        }
+    }
+
+    /// The same session with its multipart uploads stopped once `token` is cancelled, e.g. on
+    /// shutdown: no more parts are sent or copied, the upload is aborted (or kept for resuming
+    /// with an `upload_token`) and the edit fails with [`EditError::Cancelled`], leaving the
+    /// object as it was. Requests already in flight finish first.
+    pub fn with_cancellation(&self, token: CancellationToken) -> Session {
+        Session {
+            cancel: Some(token),
+            ..self.clone()
+        }
+    }
+
+    /// Log the plan of `key` if `log_plan` is set, and report it as [`ProgressEvent::Planned`].
+    pub(crate) fn report_plan(&self, key: &str, ops: &[PartOp]) {
+        if self.config.log_plan {
+            log_plan(key, ops);
+        }
+
+        if let Some(progress) = &self.progress {
+            progress.emit(ProgressEvent::Planned { key: key.to_string(), ops: ops.to_vec() });
+        }
+    }
+
+    /// [`Session::modify_many`] reporting its progress as a stream, for async callers that
+    /// rather `while let Some(event) = events.next().await` than pass a callback.
+    ///
+    /// The edit runs when the returned future is polled; poll both together, e.g. with
+    /// `join`. The stream ends once the edit finishes, whether it succeeded or failed.
+    pub fn modify_many_with_progress<'a>(
+        &self,
+        key: &'a str,
+        parts: Vec<Part>,
+    ) -> (impl Stream<Item = ProgressEvent>, impl Future<Output = Result<ModifyStats>> + 'a) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        // the sender is dropped with the session once the edit is done, closing the stream
+        let session = self.with_progress(move |event| {
+            let _ = tx.send(event);
+        });
+
+        let events = futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx));
+        let edit = async move { session.modify_many(key, parts).await };
+        (events, edit)
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.config.bucket
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.config.endpoint
+    }
+
+    /// The session's config with its secrets, `secret_key` and `sse_customer_key`, replaced
+    /// by [`REDACTED`], safe to log or display.
+    pub fn redacted_config(&self) -> S3Config {
+        let redact = |secret: &Option<String>| secret.as_ref().map(|_| REDACTED.to_string());
+
+        S3Config {
+            secret_key: redact(&self.config.secret_key),
+            sse_customer_key: redact(&self.config.sse_customer_key),
+            ..self.config.clone()
+        }
+    }
+
+    /// Check the credentials against the bucket with a `head_bucket`, so config mistakes show
+    /// up at startup instead of halfway through an edit. Rejected credentials fail with
+    /// [`EditError::AuthFailed`].
+    pub async fn verify_credentials(&self) -> Result<()> {
+        match self.client.head_bucket().bucket(&self.config.bucket).send().await {
+            Ok(_) => Ok(()),
+            Err(e) if is_auth_failure(&e) => Err(anyhow::Error::new(e).context(EditError::AuthFailed)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Probe the server for the features S3-compatible servers differ on, by writing a small
+    /// temporary object next to the bucket root and removing it again. Probed once per session
+    /// and its clones; later calls return the cached result.
+    pub async fn detect_capabilities(&self) -> Result<Capabilities> {
+        self.capabilities.get_or_try_init(|| async {
+            let key = temp_key(&self.config, "edit_s3_file-probe");
+
+            let res = self.probe(&key).await;
+
+            let res_delete = self.client.delete_object()
+                .bucket(&self.config.bucket)
+                .key(&key)
+                .send()
+                .await;
+
+            if let Err(e) = res_delete {
+                warn!(key, "failed to delete probe object: {}", e);
+            }
+            res
+        }).await.copied()
+    }
+
+    async fn probe(&self, key: &str) -> Result<Capabilities> {
+        let client = &self.client;
+        let bucket = &self.config.bucket;
+
+        // servers without checksum support reject the algorithm or ignore it
+        let checksum_put = client.put_object()
+            .bucket(bucket)
+            .key(key)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .body(ByteStream::from_static(b"probe"))
+            .checksum_algorithm(ChecksumAlgorithm::Crc32)
+            .send()
+            .await;
+
+        let checksums = match checksum_put {
+            Ok(_) => client.head_object()
+                .bucket(bucket)
+                .key(key)
+                .sse_customer(&SseCustomer::new(&self.config))
+                .checksum_mode(ChecksumMode::Enabled)
+                .send()
+                .await?
+                .checksum_crc32
+                .is_some(),
+            Err(e) if e.code().is_some() => {
+                self.put(key, ByteStream::from_static(b"probe"), 5, RewriteHeaders::default()).await?;
+                false
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let object_attributes = match client.get_object_attributes()
+            .bucket(bucket)
+            .key(key)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .object_attributes(ObjectAttributes::ObjectSize)
+            .send()
+            .await
+        {
+            Ok(_) => true,
+            Err(e) if e.code().is_some() => false,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut upload = self.begin_multipart(key).await?;
+        let res_copy = upload.copy_range(0, 5).await;
+        upload.abort().await?;
+
+        let upload_part_copy = match res_copy {
+            Ok(_) => true,
+            Err(e) if sdk_error::<UploadPartCopyError>(&e).is_some_and(|e| e.code().is_some()) => false,
+            Err(e) => return Err(e),
+        };
+
+        Ok(Capabilities { upload_part_copy, object_attributes, checksums })
+    }
+
+    pub async fn modify(
+        &self,
+        key: &str,
+        modify_part: Part,
+    ) -> Result<ModifyStats> {
+        self.modify_many(key, vec![modify_part]).await
+    }
+
+    /// Apply several disjoint edits to an object in a single multipart rewrite.
+    ///
+    /// Parts may come in any order and have gaps between them, but must not overlap. A part
+    /// starting at or running past the end of the object extends it, so `index == len` appends;
+    /// a part starting further out is rejected rather than leaving a hole.
+    pub async fn modify_many(
+        &self,
+        key: &str,
+        parts: Vec<Part>,
+    ) -> Result<ModifyStats> {
+        self.modify_many_into(key, key, parts).await
+    }
+
+    /// Apply the edits of `patch`, in the format of [`parse_patch`], as one
+    /// [`Session::modify_many`]. A malformed patch fails before anything is read or written.
+    pub async fn apply_patch(&self, key: &str, patch: &[u8]) -> Result<ModifyStats> {
+        let parts = parse_patch(patch).map_err(|e| e.context(format!("{} patch", key)))?;
+        self.modify_many(key, parts).await
+    }
+
+    /// Write `src_key` with `modify_part` applied to `dst_key`, leaving `src_key` untouched.
+    pub async fn modify_into(
+        &self,
+        src_key: &str,
+        dst_key: &str,
+        modify_part: Part,
+    ) -> Result<ModifyStats> {
+        self.modify_many_into(src_key, dst_key, vec![modify_part]).await
+    }
+
+    /// Copy the `len` bytes at `src_offset` of `src_key` into `dst_key` at `dst_offset`,
+    /// overwriting what's there and extending `dst_key` if they run past its end. Both lengths
+    /// are checked first, and reads and copies of both objects are pinned to the versions
+    /// checked. `dst_key` keeps what an edit keeps of it.
+    ///
+    /// Parts of the destination plan that fall within the spliced range are copied server-side
+    /// from `src_key`. Where the range has to be padded with neighboring bytes of `dst_key` to
+    /// make a legal part, both are fetched and uploaded instead, as for small edits.
+    #[instrument(level = "info", skip_all, fields(src_key = src_key, dst_key = dst_key, len = len))]
+    pub async fn splice(
+        &self,
+        src_key: &str,
+        src_offset: i64,
+        len: i64,
+        dst_key: &str,
+        dst_offset: i64,
+    ) -> Result<ModifyStats> {
+        self.locked(dst_key, Box::pin(self.splice_unlocked(src_key, src_offset, len, dst_key, dst_offset))).await
+    }
+
+    async fn splice_unlocked(
+        &self,
+        src_key: &str,
+        src_offset: i64,
+        len: i64,
+        dst_key: &str,
+        dst_offset: i64,
+    ) -> Result<ModifyStats> {
+        let client = &self.client;
+
+        ensure!(src_offset >= 0 && len >= 0 && dst_offset >= 0, "invalid splice of {} bytes from {} to {}", len, src_offset, dst_offset);
+
+        let src_obj = self.head_source(Source::from(src_key)).await?;
+        let src_len = src_obj.content_length().ok_or_else(|| anyhow!("{} content length is empty", src_key))?;
+        ensure!(src_offset + len <= src_len, EditError::RangeOutOfBounds { key: src_key.to_string(), start: src_offset, end: src_offset + len, len: src_len });
+
+        let rewrite = self.start_rewrite(dst_key).await?;
+        ensure!(dst_offset <= rewrite.len, EditError::RangeOutOfBounds { key: dst_key.to_string(), start: dst_offset, end: dst_offset, len: rewrite.len });
+
+        if len == 0 {
+            return Ok(ModifyStats::default());
+        }
+
+        let spliced = dst_offset..dst_offset + len;
+        let new_len = std::cmp::max(rewrite.len, spliced.end);
+        let part_size = std::cmp::min(self.config.part_size.resolve(new_len), MAX_COPY_PART_SIZE);
+
+        let ops = plan_edits(&self.config, new_len, std::slice::from_ref(&spliced), part_size);
+        validate_plan(&ops, new_len)?;
+        self.report_plan(dst_key, &ops);
+
+        let src = Source { etag: src_obj.e_tag(), ..Source::from(src_key) };
+        let dst = rewrite.source(dst_key);
+        let mut upload = self.begin_rewrite(dst_key, &rewrite).await?;
+        let mut stats = ModifyStats::default();
+
+        let res = async {
+            for op in ops {
+                match op {
+                    PartOp::Copy(range) => {
+                        stats.ranges.push((RangeKind::Copied, range.start, range.end));
+                        upload.copy_range_from_source(dst, range.start, range.end - range.start).await?;
+                    }
+                    PartOp::Upload(range) if spliced.start <= range.start && range.end <= spliced.end => {
+                        stats.ranges.push((RangeKind::Copied, range.start, range.end));
+
+                        let offset = src_offset + range.start - spliced.start;
+                        upload.copy_range_from_source(src, offset, range.end - range.start).await?;
+                    }
+                    PartOp::Upload(range) => {
+                        stats.ranges.push((RangeKind::Uploaded, range.start, range.end));
+
+                        // the spliced bytes in this part, padded with the destination's around them
+                        let start = std::cmp::max(range.start, spliced.start);
+                        let end = std::cmp::min(range.end, spliced.end);
+                        let mut edits = Vec::new();
+
+                        if start < end {
+                            let offset = src_offset - spliced.start;
+                            let data = self.read_range(src, start + offset..end + offset).await?;
+                            edits.push((start..end, Bytes::from(data)));
+                        }
+
+                        let body = assemble_part(client, &self.config, dst, range, &edits).await?;
+                        upload.upload_bytes(body.stream().await?).await?;
+                    }
+                }
+            }
+            Result::<_, anyhow::Error>::Ok(())
+        }.await;
+
+        if let Err(e) = res {
+            return Err(upload.fail(e).await);
+        }
+
+        let res = upload.finish(None).await.map(|completion| {
+            stats.record(completion);
+            stats
+        });
+        self.finish_rewrite(dst_key, rewrite, new_len, res, &[]).await
+    }
+
+    /// Overwrite the `len` bytes at `offset` of `key` with `pattern` repeated, the last
+    /// repetition cut short if `len` isn't a multiple of its length, extending `key` if they
+    /// run past its end.
+    ///
+    /// The pattern is generated a part at a time as the parts are uploaded, so even a range
+    /// of many GB takes no more memory than one part.
+    #[instrument(level = "info", skip_all, fields(key = key, offset = offset, len = len))]
+    pub async fn pattern_range(&self, key: &str, offset: i64, len: i64, pattern: &[u8]) -> Result<ModifyStats> {
+        self.locked(key, Box::pin(self.pattern_range_unlocked(key, offset, len, pattern))).await
+    }
+
+    async fn pattern_range_unlocked(&self, key: &str, offset: i64, len: i64, pattern: &[u8]) -> Result<ModifyStats> {
+        let client = &self.client;
+
+        ensure!(offset >= 0 && len >= 0, "{} pattern range {}+{} is invalid", key, offset, len);
+        ensure!(!pattern.is_empty() || len == 0, "{} pattern to repeat is empty", key);
+
+        let rewrite = self.start_rewrite(key).await?;
+        ensure!(offset <= rewrite.len, EditError::RangeOutOfBounds { key: key.to_string(), start: offset, end: offset, len: rewrite.len });
+
+        if len == 0 {
+            return Ok(ModifyStats::default());
+        }
+
+        let filled = offset..offset + len;
+        let new_len = std::cmp::max(rewrite.len, filled.end);
+
+        let ops = plan_edits(&self.config, new_len, std::slice::from_ref(&filled), self.config.part_size.resolve(new_len));
+        validate_plan(&ops, new_len)?;
+        self.report_plan(key, &ops);
+
+        let src = rewrite.source(key);
+        let mut upload = self.begin_rewrite(key, &rewrite).await?;
+        let mut stats = ModifyStats::default();
+
+        let res = async {
+            for op in ops {
+                match op {
+                    PartOp::Copy(range) => {
+                        stats.ranges.push((RangeKind::Copied, range.start, range.end));
+                        upload.copy_range_from_source(src, range.start, range.end - range.start).await?;
+                    }
+                    PartOp::Upload(range) => {
+                        stats.ranges.push((RangeKind::Uploaded, range.start, range.end));
+
+                        // the pattern bytes in this part, padded with the original ones around them
+                        let start = std::cmp::max(range.start, filled.start);
+                        let end = std::cmp::min(range.end, filled.end);
+                        let mut edits = Vec::new();
+
+                        if start < end {
+                            let phase = ((start - filled.start) % pattern.len() as i64) as usize;
+                            let data = pattern.iter()
+                                .cycle()
+                                .skip(phase)
+                                .take((end - start) as usize)
+                                .copied()
+                                .collect::<Vec<_>>();
+                            edits.push((start..end, Bytes::from(data)));
+                        }
+
+                        let body = assemble_part(client, &self.config, src, range, &edits).await?;
+                        upload.upload_bytes(body.stream().await?).await?;
+                    }
+                }
+            }
+            Result::<_, anyhow::Error>::Ok(())
+        }.await;
+
+        if let Err(e) = res {
+            return Err(upload.fail(e).await);
+        }
+
+        let res = upload.finish(None).await.map(|completion| {
+            stats.record(completion);
+            stats
+        });
+        self.finish_rewrite(key, rewrite, new_len, res, &[]).await
+    }
+
+    /// Overwrite the bytes at `offset` of `key` with those of `source`, extending `key` if they
+    /// run past its end, like a [`Part`] at `offset` whose bytes are read as they're uploaded:
+    /// a patch of many GB, from a file or another stream, takes no more memory than one part.
+    #[instrument(level = "info", skip_all, fields(key = key, offset = offset, len = source.len()))]
+    pub async fn modify_from(&self, key: &str, offset: i64, source: PartSource) -> Result<ModifyStats> {
+        self.locked(key, Box::pin(self.modify_from_unlocked(key, offset, source))).await
+    }
+
+    async fn modify_from_unlocked(&self, key: &str, offset: i64, mut source: PartSource) -> Result<ModifyStats> {
+        let client = &self.client;
+        let len = source.len();
+
+        ensure!(offset >= 0 && len >= 0, "{} range {}+{} is invalid", key, offset, len);
+
+        let rewrite = self.start_rewrite(key).await?;
+        ensure!(offset <= rewrite.len, EditError::RangeOutOfBounds { key: key.to_string(), start: offset, end: offset, len: rewrite.len });
+
+        if len == 0 {
+            return Ok(ModifyStats::default());
+        }
+
+        let edited = offset..offset + len;
+        let new_len = std::cmp::max(rewrite.len, edited.end);
+
+        let ops = plan_edits(&self.config, new_len, std::slice::from_ref(&edited), self.config.part_size.resolve(new_len));
+        validate_plan(&ops, new_len)?;
+        self.report_plan(key, &ops);
+
+        let src = rewrite.source(key);
+        let mut upload = self.begin_rewrite(key, &rewrite).await?;
+        let mut stats = ModifyStats::default();
+
+        let res = async {
+            for op in ops {
+                match op {
+                    PartOp::Copy(range) => {
+                        stats.ranges.push((RangeKind::Copied, range.start, range.end));
+                        upload.copy_range_from_source(src, range.start, range.end - range.start).await?;
+                    }
+                    PartOp::Upload(range) => {
+                        stats.ranges.push((RangeKind::Uploaded, range.start, range.end));
+
+                        // the source's bytes in this part, read in order as the parts are in
+                        // order, padded with the original ones around them
+                        let start = std::cmp::max(range.start, edited.start);
+                        let end = std::cmp::min(range.end, edited.end);
+                        let mut edits = Vec::new();
+
+                        if start < end {
+                            let data = source.read((end - start) as usize).await?;
+                            edits.push((start..end, Bytes::from(data)));
+                        }
+
+                        let body = assemble_part(client, &self.config, src, range, &edits).await?;
+                        upload.upload_bytes(body.stream().await?).await?;
+                    }
+                }
+            }
+            Result::<_, anyhow::Error>::Ok(())
+        }.await;
+
+        if let Err(e) = res {
+            return Err(upload.fail(e).await);
+        }
+
+        let res = upload.finish(None).await.map(|completion| {
+            stats.record(completion);
+            stats
+        });
+        self.finish_rewrite(key, rewrite, new_len, res, &[]).await
+    }
+
+    /// Append `data` to the end of `key`. Fails, without writing, if another write changes
+    /// the length of `key` in between.
+    pub async fn append(&self, key: &str, data: Vec<u8>) -> Result<ModifyStats> {
+        let len = self.current_len(key).await?;
+        self.modify(key, Part::with_expected_object_len(len, data, len)).await
+    }
+
+    /// Write `data` at `offset` of `key`, extending it if `data` runs past its end. Unlike
+    /// [`Session::modify`], `offset` may lie past the end: the gap up to it is filled with
+    /// zeros, which are uploaded along with `data` and so held in memory, so keep gaps small
+    /// or fill big ones with [`Session::pattern_range`] first. Writes filling a gap fail,
+    /// without writing, if another write changes the length of `key` in between.
+    pub async fn write_at(&self, key: &str, offset: i64, data: Vec<u8>) -> Result<ModifyStats> {
+        let len = self.current_len(key).await?;
+
+        if offset <= len {
+            return self.modify(key, Part::new(offset, data)).await;
+        }
+
+        let mut filled = vec![0; (offset - len) as usize];
+        filled.extend_from_slice(&data);
+        self.modify(key, Part::with_expected_object_len(len, filled, len)).await
+    }
+
+    /// Length of `key` from an uncached `head_object`, as edits at that offset fail with a
+    /// stale one.
+    async fn current_len(&self, key: &str) -> Result<i64> {
+        Ok(self.current_stat(key).await?.len)
+    }
+
+    /// Stat of `key` from an uncached `head_object`, failing if it doesn't exist.
+    pub(crate) async fn current_stat(&self, key: &str) -> Result<ObjectStat> {
+        head(&self.client, &self.config, key)
+            .await?
+            .ok_or_else(|| anyhow::Error::new(EditError::ObjectNotFound { key: key.to_string() }))
+    }
+
+    /// Cut `key` to its first `new_len` bytes, copied server-side. `new_len` past the end fails.
+    pub async fn truncate(&self, key: &str, new_len: i64) -> Result<ModifyStats> {
+        let stat = self.current_stat(key).await?;
+        ensure!(0 <= new_len && new_len <= stat.len, EditError::RangeOutOfBounds { key: key.to_string(), start: new_len, end: new_len, len: stat.len });
+
+        self.rebuild(key, stat, &[Segment::Source(0..new_len)]).await
+    }
+
+    /// Remove the `len` bytes at `offset` of `key`, shifting the bytes after them to `offset`.
+    /// The range must be within the object.
+    ///
+    /// The bytes before and after the range are copied server-side, except for the part
+    /// around the cut, which is fetched and uploaded so that no copied part spans it.
+    pub async fn remove_range(&self, key: &str, offset: i64, len: i64) -> Result<ModifyStats> {
+        let stat = self.current_stat(key).await?;
+        ensure!(0 <= offset && 0 <= len && offset + len <= stat.len, EditError::RangeOutOfBounds { key: key.to_string(), start: offset, end: offset + len, len: stat.len });
+
+        let segments = [Segment::Source(0..offset), Segment::Source(offset + len..stat.len)];
+        self.rebuild(key, stat, &segments).await
+    }
+
+    /// Insert `data` at `offset` of `key`, shifting the bytes from `offset` on after it.
+    /// `offset` may be the object's length, appending `data`.
+    ///
+    /// The bytes before and after `offset` are copied server-side, except for the parts
+    /// around `data`, which are uploaded along with it.
+    pub async fn insert(&self, key: &str, offset: i64, data: Vec<u8>) -> Result<ModifyStats> {
+        let stat = self.current_stat(key).await?;
+        ensure!(0 <= offset && offset <= stat.len, EditError::RangeOutOfBounds { key: key.to_string(), start: offset, end: offset, len: stat.len });
+
+        let len = stat.len;
+        let segments = [Segment::Source(0..offset), Segment::Data(Bytes::from(data)), Segment::Source(offset..len)];
+        self.rebuild(key, stat, &segments).await
+    }
+
+    /// Make `key` a copy of the local file at `path`, rsync-like: the object is compared with
+    /// the file in blocks of [`SYNC_BLOCK_SIZE`] bytes, the blocks that differ and the bytes
+    /// of the file past the object's end are uploaded, and the rest is copied server-side. An
+    /// object longer than the file is cut to its length, and one matching it already isn't
+    /// written.
+    ///
+    /// Parts of a multipart object that stored a SHA-256 checksum, e.g. written with
+    /// `checksum_algorithm` set to [`PartChecksum::Sha256`](crate::PartChecksum::Sha256), are
+    /// compared by checksum; the rest of the object is downloaded to be compared. The file is
+    /// read a block at a time, and the bytes to upload are read again a part at a time as the
+    /// parts are uploaded, so a file cut short in between fails the sync. Blocks are compared
+    /// at the same offsets, so bytes inserted into or removed from the middle of the file make
+    /// all blocks after them differ.
+    pub async fn sync_from_file(&self, key: &str, path: impl AsRef<Path>) -> Result<ModifyStats> {
+        let path = path.as_ref();
+        let stat = self.current_stat(key).await?;
+
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .map_err(|e| anyhow!("failed to open {}: {}", path.display(), e))?;
+        let file_len = file.metadata().await?.len() as i64;
+
+        let common = std::cmp::min(stat.len, file_len);
+        let verified = self.parts_matching(key, &mut file, common).await?;
+
+        // runs of blocks at the same offsets in both, and whether they differ
+        let mut runs: Vec<(Range<i64>, bool)> = Vec::new();
+        let mut changed = stat.len != file_len;
+        let mut local = vec![0; SYNC_BLOCK_SIZE];
+        let mut remote = vec![0; SYNC_BLOCK_SIZE];
+        let mut offset = 0;
+
+        for (range, verified) in split_verified(&verified, common) {
+            let mut object = match verified {
+                true => None,
+                false => {
+                    file.seek(SeekFrom::Start(range.start as u64)).await?;
+                    Some(self.range_reader(key, range.start as u64, range.end as u64)?)
+                }
+            };
+
+            while offset < range.end {
+                let n = std::cmp::min(SYNC_BLOCK_SIZE as i64, range.end - offset) as usize;
+
+                let differs = match &mut object {
+                    None => false,
+                    Some(object) => {
+                        file.read_exact(&mut local[..n]).await?;
+                        object.read_exact(&mut remote[..n]).await?;
+                        local[..n] != remote[..n]
+                    }
+                };
+                changed |= differs;
+
+                match runs.last_mut() {
+                    Some((run, run_differs)) if *run_differs == differs => run.end += n as i64,
+                    _ => runs.push((offset..offset + n as i64, differs)),
+                }
+                offset += n as i64;
+            }
+        }
+
+        if !changed {
+            return Ok(ModifyStats::default());
+        }
+
+        if file_len > common {
+            runs.push((common..file_len, true));
+        }
+
+        let file = Arc::<Path>::from(path);
+        let segments = runs.into_iter()
+            .map(|(range, differs)| match differs {
+                true => Segment::File(file.clone(), range),
+                false => Segment::Source(range),
+            })
+            .collect::<Vec<_>>();
+
+        debug!(key, path = %path.display(), segments = segments.len(), "sync from file");
+        self.rebuild(key, stat, &segments).await
+    }
+
+    /// Byte ranges of the parts of `key` whose stored SHA-256 checksum is that of the bytes of
+    /// `file` at the same offsets, among the first `len`, in order. Empty if the object has no
+    /// part checksums or they can't be read, so that its bytes are compared instead.
+    async fn parts_matching(&self, key: &str, file: &mut tokio::fs::File, len: i64) -> Result<Vec<Range<i64>>> {
+        let mut matching = Vec::new();
+        let mut marker = None;
+        let mut offset = 0;
+        let mut buf = vec![0; SYNC_BLOCK_SIZE];
+
+        loop {
+            let out = self.client.get_object_attributes()
+                .bucket(&self.config.bucket)
+                .key(key)
+                .sse_customer(&SseCustomer::new(&self.config))
+                .object_attributes(ObjectAttributes::ObjectParts)
+                .set_part_number_marker(marker.take())
+                .send()
+                .await;
+
+            let parts = match out {
+                Ok(out) => out.object_parts,
+                Err(e) => {
+                    debug!(key, "failed to get part checksums: {}", e);
+                    return Ok(Vec::new());
+                }
+            };
+            let Some(parts) = parts else {
+                break;
+            };
+
+            for part in parts.parts() {
+                let Some(size) = part.size else {
+                    return Ok(matching);
+                };
+                let range = offset..offset + size;
+                offset = range.end;
+
+                let Some(checksum) = part.checksum_sha256() else {
+                    continue;
+                };
+                if range.end > len {
+                    continue;
+                }
+
+                file.seek(SeekFrom::Start(range.start as u64)).await?;
+                let mut hasher = Sha256::new();
+                let mut pos = range.start;
+
+                while pos < range.end {
+                    let n = std::cmp::min(SYNC_BLOCK_SIZE as i64, range.end - pos) as usize;
+                    file.read_exact(&mut buf[..n]).await?;
+                    hasher.update(&buf[..n]);
+                    pos += n as i64;
+                }
+
+                if STANDARD.encode(hasher.finalize()) == checksum {
+                    matching.push(range);
+                }
+            }
+
+            if !parts.is_truncated.unwrap_or(false) {
+                break;
+            }
+            marker = parts.next_part_number_marker;
+        }
+        Ok(matching)
+    }
+
+    /// Rewrite `key`, whose stat is `stat`, as `segments` one after another, keeping what an
+    /// edit keeps of it. Fails with [`EditError::Conflict`] if it no longer has the ETag of
+    /// `stat`, as the segments were worked out from that version.
+    ///
+    /// Each start of a source segment that doesn't follow on from the one before is planned as
+    /// a one byte edit, so that it's padded into an uploaded part and no copied part crosses
+    /// it. Copied parts then each lie within one source segment, and are copied from where
+    /// that segment's bytes are in the source.
+    #[instrument(level = "info", skip_all, fields(key = key, len = stat.len))]
+    async fn rebuild(&self, key: &str, stat: ObjectStat, segments: &[Segment]) -> Result<ModifyStats> {
+        self.locked(key, Box::pin(self.rebuild_unlocked(key, stat, segments))).await
+    }
+
+    async fn rebuild_unlocked(&self, key: &str, stat: ObjectStat, segments: &[Segment]) -> Result<ModifyStats> {
+        let rewrite = self.start_rewrite(key).await?;
+
+        if rewrite.obj.e_tag() != stat.etag.as_deref() || rewrite.len != stat.len {
+            return Err(EditError::Conflict { key: key.to_string() }.into());
+        }
+
+        // (offset in the result, segment)
+        let mut placed = Vec::new();
+        let mut new_len = 0;
+        let mut edits = Vec::new();
+        let mut prev_end = Some(0);
+
+        for segment in segments {
+            let len = segment.len();
+
+            if len == 0 {
+                continue;
+            }
+
+            match segment {
+                Segment::Source(range) => {
+                    if prev_end != Some(range.start) {
+                        edits.push(new_len..new_len + 1);
+                    }
+                    prev_end = Some(range.end);
+                }
+                Segment::Data(_) | Segment::File(..) => {
+                    edits.push(new_len..new_len + len);
+                    prev_end = None;
+                }
+            }
+
+            placed.push((new_len, segment));
+            new_len += len;
+        }
+
+        // the new bytes held in memory, to read back with `verify_edits`
+        let data = placed.iter()
+            .filter_map(|(start, segment)| match segment {
+                Segment::Data(data) => Some((*start..start + data.len() as i64, data.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if new_len == 0 {
+            let res = self.put_whole(rewrite.target(key), Vec::new(), rewrite.headers.clone(), None).await;
+            return self.finish_rewrite(key, rewrite, new_len, res, &data).await;
+        }
+
+        // a one byte edit may run into the data following it
+        let mut merged: Vec<Range<i64>> = Vec::with_capacity(edits.len());
+
+        for edit in edits {
+            match merged.last_mut() {
+                Some(last) if edit.start < last.end => last.end = std::cmp::max(last.end, edit.end),
+                _ => merged.push(edit),
+            }
+        }
+        let edits = merged;
+
+        let part_size = std::cmp::min(self.config.part_size.resolve(new_len), MAX_COPY_PART_SIZE);
+        let ops = plan_edits(&self.config, new_len, &edits, part_size);
+        validate_plan(&ops, new_len)?;
+        self.report_plan(key, &ops);
+
+        let src = rewrite.source(key);
+        let mut upload = self.begin_rewrite(key, &rewrite).await?;
+        let mut stats = ModifyStats::default();
+
+        let res = async {
+            for op in ops {
+                match op {
+                    PartOp::Copy(range) => {
+                        stats.ranges.push((RangeKind::Copied, range.start, range.end));
+
+                        // copied parts lie within a source segment
+                        let (start, segment) = placed.iter()
+                            .rev()
+                            .find(|(start, _)| *start <= range.start)
+                            .ok_or_else(|| anyhow!("{} part {}-{} is before every segment", key, range.start, range.end))?;
+                        let Segment::Source(segment_src) = segment else {
+                            return Err(anyhow!("{} copied part {}-{} overlaps new data", key, range.start, range.end));
+                        };
+
+                        upload.copy_range_from_source(src, segment_src.start + range.start - start, range.end - range.start).await?;
+                    }
+                    PartOp::Upload(range) => {
+                        stats.ranges.push((RangeKind::Uploaded, range.start, range.end));
+
+                        let mut body = Vec::with_capacity((range.end - range.start) as usize);
+
+                        for (start, segment) in &placed {
+                            // the part's bytes in this segment, as offsets into the segment
+                            let lo = std::cmp::max(range.start, *start) - start;
+                            let hi = std::cmp::min(range.end, start + segment.len()) - start;
+
+                            if lo >= hi {
+                                continue;
+                            }
+
+                            match segment {
+                                Segment::Data(data) => body.extend_from_slice(&data[lo as usize..hi as usize]),
+                                Segment::File(path, file_range) => {
+                                    let mut file = tokio::fs::File::open(path)
+                                        .await
+                                        .map_err(|e| anyhow!("failed to open {}: {}", path.display(), e))?;
+                                    file.seek(SeekFrom::Start((file_range.start + lo) as u64)).await?;
+
+                                    let n = body.len();
+                                    body.resize(n + (hi - lo) as usize, 0);
+                                    file.read_exact(&mut body[n..])
+                                        .await
+                                        .map_err(|e| anyhow!("{} changed while being read: {}", path.display(), e))?;
+                                }
+                                Segment::Source(segment_src) => {
+                                    stats.bytes_read += (hi - lo) as u64;
+
+                                    let fetched = self.read_range(src, segment_src.start + lo..segment_src.start + hi).await?;
+                                    body.extend_from_slice(&fetched);
+                                }
+                            }
+                        }
+
+                        upload.upload_bytes(body).await?;
+                    }
+                }
+            }
+            Result::<_, anyhow::Error>::Ok(())
+        }.await;
+
+        if let Err(e) = res {
+            return Err(upload.fail(e).await);
+        }
+
+        let res = upload.finish(None).await.map(|completion| {
+            stats.record(completion);
+            stats
+        });
+        self.finish_rewrite(key, rewrite, new_len, res, &data).await
+    }
+
+    /// Start staging edits of `key` to write in one rewrite, see [`Transaction`].
+    pub fn begin(&self, key: &str) -> Transaction<'_> {
+        Transaction::new(self, key)
+    }
+
+    /// Start collecting edits of several objects to write all or none of, see
+    /// [`EditTransaction`].
+    pub fn edit_transaction(&self) -> EditTransaction<'_> {
+        EditTransaction::new(self)
+    }
+
+    /// [`Session::modify_many`] writing the result to `dst_key` instead of `src_key`.
+    pub async fn modify_many_into(
+        &self,
+        src_key: &str,
+        dst_key: &str,
+        parts: Vec<Part>,
+    ) -> Result<ModifyStats> {
+        self.edit(Source::from(src_key), dst_key, parts, None).await
+    }
+
+    /// [`Session::modify_many_into`] taking `src_key` from `src_bucket` instead of the session's
+    /// bucket, e.g. to save an edited copy of an object into another bucket. Copied parts are
+    /// copied server-side across the buckets, so the session's credentials must be able to read
+    /// `src_bucket`.
+    pub async fn modify_many_across(
+        &self,
+        src_bucket: &str,
+        src_key: &str,
+        dst_key: &str,
+        parts: Vec<Part>,
+    ) -> Result<ModifyStats> {
+        check_bucket(src_bucket, self.config.force_path_style)?;
+
+        let src = Source { key: src_key, version_id: None, bucket: Some(src_bucket), etag: None };
+        self.edit(src, dst_key, parts, None).await
+    }
+
+    /// [`Session::modify_many`] of several objects, running up to `concurrency` of the edits
+    /// at once, with the result of each by key. A failed edit doesn't stop the others.
+    ///
+    /// The keys must be distinct, as concurrent edits of one object would overwrite each
+    /// other. The parts copied within each edit still share `max_concurrency`.
+    pub async fn modify_batch(
+        &self,
+        edits: Vec<(String, Vec<Part>)>,
+        concurrency: usize,
+    ) -> Result<HashMap<String, Result<ModifyStats>>> {
+        let mut keys = HashSet::with_capacity(edits.len());
+
+        for (key, _) in &edits {
+            ensure!(keys.insert(key.as_str()), "{} is edited more than once in the batch", key);
+        }
+
+        // not `permits`: the edits would hold them all and starve their own part copies
+        let results = stream::iter(edits)
+            .map(|(key, parts)| async move {
+                let res = self.modify_many(&key, parts).await;
+
+                if let Err(e) = &res {
+                    warn!(key, "batch edit failed: {:#}", e);
+                }
+                (key, res)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+
+        Ok(results)
+    }
+
+    /// [`Session::modify_many`] taking the bytes outside `parts` from the version `version_id`
+    /// of `key`, e.g. one from [`Session::list_object_versions`], so an older version is
+    /// edited into a new current one. The new version id is in the returned stats.
+    pub async fn modify_version(
+        &self,
+        key: &str,
+        version_id: &str,
+        parts: Vec<Part>,
+    ) -> Result<ModifyStats> {
+        let src = Source { key, version_id: Some(version_id), bucket: None, etag: None };
+        self.edit(src, key, parts, None).await
+    }
+
+    /// Read `range` of `key` and write the part `recompute` makes of those bytes, retrying up
+    /// to `retries` times with freshly read bytes if the object changes in between.
+    ///
+    /// The write only completes if the object still has the ETag it had when read, so a
+    /// concurrent writer is never overwritten; once the retries are used up that fails with
+    /// [`EditError::Conflict`]. `range` may end past the object, which reads what exists.
+    pub async fn modify_cas<F>(&self, key: &str, range: Range<i64>, mut recompute: F, retries: usize) -> Result<ModifyStats>
+    where
+        F: FnMut(&[u8]) -> Part,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let (current, etag) = self.read_with_etag(key, range.clone()).await?;
+            let part = recompute(&current);
+
+            match self.edit(Source::from(key), key, vec![part], Some(&etag)).await {
+                Err(e) if attempt < retries && matches!(e.downcast_ref::<EditError>(), Some(EditError::Conflict { .. })) => {
+                    attempt += 1;
+                    info!(key, attempt, "conflict, retrying");
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Add `delta` to the little-endian `u64` at `offset` of `key`, wrapping on overflow, and
+    /// return the new value. Concurrent increments never lose updates: the write is a
+    /// [`Session::modify_cas`], retried with the freshly read value on conflict up to
+    /// [`FETCH_ADD_RETRIES`] times. Bytes of the counter past the end of the object count as
+    /// zero and are appended.
+    pub async fn fetch_add_u64_le(&self, key: &str, offset: i64, delta: u64) -> Result<u64> {
+        let mut value = 0;
+
+        self.modify_cas(key, offset..offset + 8, |current| {
+            let mut bytes = [0; 8];
+            bytes[..current.len()].copy_from_slice(current);
+            value = u64::from_le_bytes(bytes).wrapping_add(delta);
+            Part::le(offset, value)
+        }, FETCH_ADD_RETRIES).await?;
+
+        Ok(value)
+    }
+
+    /// Read the `len` bytes at `offset` of `key`, let `update` change them in place and write
+    /// them back, as a [`Session::modify_cas`] retried up to [`FETCH_ADD_RETRIES`] times, so
+    /// `update` may run once per attempt and sees the bytes freshly read each time. Bytes of
+    /// the range past the end of the object read as zero and are appended.
+    pub async fn update_range<F>(&self, key: &str, offset: i64, len: i64, mut update: F) -> Result<ModifyStats>
+    where
+        F: FnMut(&mut [u8]),
+    {
+        ensure!(len >= 0, "invalid length {}", len);
+
+        self.modify_cas(key, offset..offset + len, |current| {
+            let mut buf = vec![0; len as usize];
+            buf[..current.len()].copy_from_slice(current);
+            update(&mut buf);
+            Part::new(offset, buf)
+        }, FETCH_ADD_RETRIES).await
+    }
+
+    /// The bytes of `range` that exist and the ETag of the object they were read from.
+    async fn read_with_etag(&self, key: &str, range: Range<i64>) -> Result<(Vec<u8>, String)> {
+        ensure!(0 <= range.start && range.start <= range.end, "invalid range {}-{}", range.start, range.end);
+
+        // uncached, a stale ETag would only make the write conflict
+        let stat = head(&self.client, &self.config, key)
+            .await?
+            .ok_or_else(|| anyhow!("{} not found", key))?;
+        let etag = stat.etag.ok_or_else(|| anyhow!("{} has no ETag", key))?;
+        let end = std::cmp::min(range.end, stat.len);
+
+        if range.start >= end {
+            return Ok((Vec::new(), etag));
+        }
+
+        let out = self.client.get_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .range(format!("bytes={}-{}", range.start, end - 1))
+            .if_match(&etag)
+            .send()
+            .await;
+
+        let out = match out {
+            Err(e) if e.code() == Some("PreconditionFailed") => {
+                return Err(anyhow::Error::new(e).context(EditError::Conflict { key: key.to_string() }));
+            }
+            res => res.classify(&self.client, &self.config, key).await?,
+        };
+
+        Ok((out.body.collect().await?.to_vec(), etag))
+    }
+
+    #[instrument(level = "info", skip_all, fields(key = dst_key, parts = parts.len()))]
+    async fn edit(
+        &self,
+        src: Source<'_>,
+        dst_key: &str,
+        parts: Vec<Part>,
+        if_match: Option<&str>,
+    ) -> Result<ModifyStats> {
+        self.locked(dst_key, Box::pin(self.edit_unlocked(src, dst_key, parts, if_match))).await
+    }
+
+    /// Run `write` of `key` holding its lock if `edit_lock` is set. It's boxed, as the futures
+    /// of whole edits are too big to move around on the stack.
+    async fn locked<T>(&self, key: &str, write: Pin<Box<impl Future<Output = Result<T>>>>) -> Result<T> {
+        if !self.config.edit_lock {
+            return write.await;
+        }
+
+        let lock = EditLock::acquire(self, key).await?;
+        let res = write.await;
+        lock.release(self).await;
+        res
+    }
+
+    async fn edit_unlocked(
+        &self,
+        src: Source<'_>,
+        dst_key: &str,
+        mut parts: Vec<Part>,
+        if_match: Option<&str>,
+    ) -> Result<ModifyStats> {
+        let expected_lens = parts.iter()
+            .filter_map(|part| part.expected_obj_len)
+            .collect::<Vec<_>>();
+        let expected_total_lens = parts.iter()
+            .filter_map(|part| part.expected_total_len)
+            .collect::<Vec<_>>();
+        let assumed_lens = parts.iter()
+            .filter_map(|part| part.assume_len)
+            .collect::<Vec<_>>();
+        let expected_prefixes = parts.iter_mut()
+            .filter_map(|part| part.expected_prefix.take())
+            .collect::<Vec<_>>();
+
+        let mut edits = parts.into_iter()
+            .map(|mut part| {
+                let data = part.data.take().unwrap();
+                (part.index..part.index + data.len() as i64, data)
+            })
+            .collect::<Vec<_>>();
+
+        edits.sort_by_key(|(range, _)| range.start);
+
+        for pair in edits.windows(2) {
+            let (a, b) = (&pair[0].0, &pair[1].0);
+            ensure!(a.end <= b.start, "{} edit {}-{} overlaps edit {}-{}", src.key, a.start, a.end, b.start, b.end);
+        }
+
+        for pair in assumed_lens.windows(2) {
+            ensure!(pair[0] == pair[1], "{} is assumed to be both {} and {} bytes", src.key, pair[0], pair[1]);
+        }
+
+        // only the HEAD tells a locked object from another, so one is skipped where none can be
+        let (obj, obj_len) = match assumed_lens.first() {
+            Some(&len) if if_match.is_none() && !self.bucket_locks_objects().await => (None, len),
+            _ => {
+                let obj = self.head_source(src).await?;
+                let obj_len = obj.content_length().ok_or_else(|| anyhow!("{} content length is empty", src.key))?;
+
+                if if_match.is_some_and(|etag| obj.e_tag() != Some(etag)) {
+                    return Err(EditError::Conflict { key: src.key.to_string() }.into());
+                }
+
+                if let Some(hooks) = &self.hooks {
+                    hooks.before_plan(src.key, &obj).map_err(|e| e.context(EditError::Vetoed { key: src.key.to_string() }))?;
+                }
+                (Some(obj), obj_len)
+            }
+        };
+
+        for expected in expected_lens {
+            ensure!(expected == obj_len, "{} is {} bytes, expected {}", src.key, obj_len, expected);
+        }
+
+        if let Some(len) = expected_prefixes.iter().map(|prefix| prefix.len() as i64).max() {
+            let etag = obj.as_ref().and_then(|obj| obj.e_tag());
+            let head = self.read_prefix(src, std::cmp::min(len, obj_len), etag).await?;
+
+            if expected_prefixes.iter().any(|prefix| !head.starts_with(prefix)) {
+                return Err(EditError::FingerprintMismatch { key: src.key.to_string() }.into());
+            }
+        }
+
+        let mut new_len = obj_len;
+
+        if let (Some((first, _)), Some((last, _))) = (edits.first(), edits.last()) {
+            ensure!(first.start >= 0, "{} edit starts at negative offset {}", src.key, first.start);
+            ensure!(last.start <= obj_len, EditError::RangeOutOfBounds { key: src.key.to_string(), start: last.start, end: last.end, len: obj_len });
+            new_len = std::cmp::max(obj_len, last.end);
+        }
+
+        if new_len > MAX_OBJECT_SIZE {
+            let err = EditError::ObjectTooLarge { size: new_len };
+            return Err(anyhow::Error::new(err).context(format!("{} edit", dst_key)));
+        }
+
+        for expected in expected_total_lens {
+            if expected != new_len {
+                let err = EditError::LengthMismatch { expected, actual: new_len };
+                return Err(anyhow::Error::new(err).context(format!("{} edit", dst_key)));
+            }
+        }
+
+        // the unchanged bytes must all come from the version fetched, not one written since
+        let etag = obj.as_ref().and_then(|obj| obj.e_tag()).map(String::from);
+        let src = Source { etag: etag.as_deref(), ..src };
+
+        self.commit_edits(src, dst_key, obj, new_len, &edits, if_match).await
+    }
+
+    /// Whether objects of the bucket may be under Object Lock, looked up once per session and
+    /// its clones. A failed lookup counts as yes, so that the lock check isn't skipped.
+    async fn bucket_locks_objects(&self) -> bool {
+        let res = self.object_lock.get_or_try_init(|| async {
+            match self.client.get_object_lock_configuration().bucket(&self.config.bucket).send().await {
+                Ok(out) => {
+                    let enabled = out.object_lock_configuration()
+                        .and_then(|conf| conf.object_lock_enabled());
+                    Ok(enabled == Some(&ObjectLockEnabled::Enabled))
+                }
+                Err(e) if e.code() == Some("ObjectLockConfigurationNotFoundError") => Ok(false),
+                Err(e) => Err(e),
+            }
+        }).await;
+
+        match res {
+            Ok(&locks) => locks,
+            Err(e) => {
+                debug!(bucket = self.config.bucket, "failed to look up object lock configuration: {}", e);
+                true
+            }
+        }
+    }
+
+    /// The first `len` bytes of `src`, of the object with `etag` if given.
+    async fn read_prefix(&self, src: Source<'_>, len: i64, etag: Option<&str>) -> Result<Vec<u8>> {
+        self.read_range(Source { etag, ..src }, 0..len).await
+    }
+
+    /// The bytes of `range` of `src`, which must all exist.
+    async fn read_range(&self, src: Source<'_>, range: Range<i64>) -> Result<Vec<u8>> {
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let out = self.client.get_object()
+            .bucket(src.bucket(&self.config))
+            .key(src.key)
+            .set_version_id(src.version_id.map(String::from))
+            .sse_customer(&SseCustomer::new(&self.config))
+            .range(format!("bytes={}-{}", range.start, range.end - 1))
+            .set_if_match(src.etag.map(String::from))
+            .send()
+            .await;
+
+        let out = match out {
+            Err(e) if e.code() == Some("PreconditionFailed") => {
+                return Err(anyhow::Error::new(e).context(EditError::Conflict { key: src.key.to_string() }));
+            }
+            res => res.classify(&self.client, &self.config, src.key).await?,
+        };
+
+        Ok(out.body.collect().await?.to_vec())
+    }
+
+    /// `head_object` of `src`, its length, ETag and the headers a rewrite keeps in one request
+    /// without opening a body. A missing `src` fails with [`EditError::ObjectNotFound`].
+    pub(crate) async fn head_source(&self, src: Source<'_>) -> Result<HeadObjectOutput> {
+        let obj = match self.client.head_object()
+            .bucket(src.bucket(&self.config))
+            .key(src.key)
+            .set_version_id(src.version_id.map(String::from))
+            .sse_customer(&SseCustomer::new(&self.config))
+            .send()
+            .await
+        {
+            Ok(obj) => obj,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => {
+                return Err(anyhow::Error::new(e).context(EditError::ObjectNotFound { key: src.key.to_string() }));
+            }
+            Err(e) => return Err(e).classify(&self.client, &self.config, src.key).await,
+        };
+
+        // HEAD succeeds on archived objects; a read fails with InvalidObjectState, which
+        // requests a restore if configured, as the parts couldn't be copied anyway
+        if is_archived(&obj) {
+            self.read_prefix(src, 1, obj.e_tag()).await?;
+        }
+        Ok(obj)
+    }
+
+    /// The whole `src`, of the object with `etag` if given.
+    async fn read_source(&self, src: Source<'_>, etag: Option<&str>) -> Result<Vec<u8>> {
+        let out = self.client.get_object()
+            .bucket(src.bucket(&self.config))
+            .key(src.key)
+            .set_version_id(src.version_id.map(String::from))
+            .sse_customer(&SseCustomer::new(&self.config))
+            .set_if_match(etag.map(String::from))
+            .send()
+            .await;
+
+        let out = match out {
+            Err(e) if e.code() == Some("PreconditionFailed") => {
+                return Err(anyhow::Error::new(e).context(EditError::Conflict { key: src.key.to_string() }));
+            }
+            res => res.classify(&self.client, &self.config, src.key).await?,
+        };
+
+        Ok(out.body.collect().await?.to_vec())
+    }
+
+    /// Write `src`, whose `head_object` is `obj` if it was fetched, with `edits` applied and
+    /// cut or extended to `new_len` bytes to `dst_key`. Edits must be sorted, disjoint and
+    /// within `new_len`, and every byte past the end of `src` covered by one. With `if_match`
+    /// the write only happens if `dst_key` still has that ETag.
+    pub(crate) async fn commit_edits(
+        &self,
+        src: Source<'_>,
+        dst_key: &str,
+        obj: Option<HeadObjectOutput>,
+        new_len: i64,
+        edits: &[(Range<i64>, Bytes)],
+        if_match: Option<&str>,
+    ) -> Result<ModifyStats> {
+        let acl = match self.config.preserve_acl {
+            true => self.get_acl(src).await?,
+            false => None,
+        };
+        let tags = match self.config.preserve_tags {
+            true => self.get_tags(src).await?,
+            false => None,
+        };
+
+        let stats = self.write_edits(src, dst_key, obj, new_len, edits, if_match).await?;
+
+        if let Some(acl) = acl {
+            self.put_acl(dst_key, acl).await?;
+        }
+
+        if let Some(tags) = tags {
+            self.put_tags(dst_key, tags).await?;
+        }
+
+        if self.config.verify_edits {
+            self.verify_edits(dst_key, stats.etag.as_deref(), edits).await?;
+        }
+        Ok(stats)
+    }
+
+    /// Read back the `edits` ranges of `key`, of the object with `etag` if known, failing with
+    /// [`EditError::VerificationFailed`] where they don't hold the edits' bytes.
+    async fn verify_edits(&self, key: &str, etag: Option<&str>, edits: &[(Range<i64>, Bytes)]) -> Result<()> {
+        for (range, data) in edits.iter().filter(|(range, _)| !range.is_empty()) {
+            let out = self.client.get_object()
+                .bucket(&self.config.bucket)
+                .key(key)
+                .sse_customer(&SseCustomer::new(&self.config))
+                .range(format!("bytes={}-{}", range.start, range.end - 1))
+                .set_if_match(etag.map(String::from))
+                .send()
+                .await;
+
+            let out = match out {
+                Err(e) if e.code() == Some("PreconditionFailed") => {
+                    return Err(anyhow::Error::new(e).context(EditError::Conflict { key: key.to_string() }));
+                }
+                res => res.classify(&self.client, &self.config, key).await?,
+            };
+
+            let read = out.body.collect().await?.into_bytes();
+
+            if read != *data {
+                return Err(EditError::VerificationFailed { key: key.to_string(), start: range.start, end: range.end }.into());
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_edits(
+        &self,
+        src: Source<'_>,
+        dst_key: &str,
+        obj: Option<HeadObjectOutput>,
+        new_len: i64,
+        edits: &[(Range<i64>, Bytes)],
+        if_match: Option<&str>,
+    ) -> Result<ModifyStats> {
+        let client = &self.client;
+        let headers = self.rewrite_headers(src.key, obj.as_ref())?;
+
+        // a single put replaces the object at once, so it's atomic without a temp object
+        if self.single_put(dst_key, new_len)? {
+            let mut data = self.read_source(src, obj.as_ref().and_then(|obj| obj.e_tag())).await?;
+            data.resize(new_len as usize, 0);
+
+            for (range, bytes) in edits {
+                data[range.start as usize..range.end as usize].copy_from_slice(bytes);
+            }
+
+            return self.put_whole(dst_key, data, headers, if_match).await;
+        }
+
+        let Some(tmp_key) = self.temp_target(dst_key, &headers, if_match.is_some()) else {
+            return rewrite(self, src, dst_key, new_len, edits, headers.create_request(client), if_match).await;
+        };
+
+        let res = rewrite(self, src, &tmp_key, new_len, edits, headers.clone().create_request(client), None).await;
+        self.commit_temp(&tmp_key, dst_key, new_len, headers, res).await
+    }
+
+    /// The temporary object a multipart write of `dst_key` with `headers` goes to first with
+    /// `atomic_edits` set, `None` to write `dst_key` in place.
+    fn temp_target(&self, dst_key: &str, headers: &RewriteHeaders, if_match: bool) -> Option<String> {
+        let locked = headers.object_lock_mode.is_some() || headers.object_lock_legal_hold_status.is_some();
+
+        // a conditional completion is atomic already, and a copy can't be made conditional;
+        // a locked temp object couldn't be deleted
+        if !self.config.atomic_edits || self.config.if_none_match || if_match || locked {
+            return None;
+        }
+        Some(temp_key(&self.config, dst_key))
+    }
+
+    /// Copy the `new_len` byte temporary object `tmp_key` over `dst_key` with `headers` once
+    /// `res` wrote it, and delete it either way.
+    async fn commit_temp(
+        &self,
+        tmp_key: &str,
+        dst_key: &str,
+        new_len: i64,
+        headers: RewriteHeaders,
+        res: Result<ModifyStats>,
+    ) -> Result<ModifyStats> {
+        let client = &self.client;
+
+        let res = async {
+            let mut stats = res?;
+            (stats.version_id, stats.etag) = self.copy_whole(tmp_key, dst_key, new_len, headers).await?;
+
+            if self.config.compute_final_checksum {
+                stats.final_checksum = final_checksum(client, &self.config, dst_key).await?;
+            }
+            Result::<_, anyhow::Error>::Ok(stats)
+        }.await;
+
+        debug!(key = tmp_key, "delete temp object");
+
+        // the edit itself is done or failed already, a leftover temp object doesn't change that
+        let res_delete = client.delete_object()
+            .bucket(&self.config.bucket)
+            .key(tmp_key)
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await;
+
+        if let Err(e) = res_delete {
+            warn!(key = tmp_key, "failed to delete temp object: {}", e);
+        }
+
+        res
+    }
+
+    /// Start a rewrite of `key` part by part, for edits [`Session::commit_edits`] can't plan,
+    /// as an edit starts: with a fresh `head_object` the hooks may veto, failing on a locked
+    /// object unless `propagate_object_lock` is set, and reading the ACL and tags to keep.
+    async fn start_rewrite(&self, key: &str) -> Result<Rewrite> {
+        let src = Source::from(key);
+        let obj = self.head_source(src).await?;
+        let len = obj.content_length().ok_or_else(|| anyhow!("{} content length is empty", key))?;
+
+        if let Some(hooks) = &self.hooks {
+            hooks.before_plan(key, &obj).map_err(|e| e.context(EditError::Vetoed { key: key.to_string() }))?;
+        }
+
+        let headers = self.rewrite_headers(key, Some(&obj))?;
+        let tmp_key = self.temp_target(key, &headers, false);
+
+        let src = Source { etag: obj.e_tag(), ..src };
+        let acl = match self.config.preserve_acl {
+            true => self.get_acl(src).await?,
+            false => None,
+        };
+        let tags = match self.config.preserve_tags {
+            true => self.get_tags(src).await?,
+            false => None,
+        };
+
+        Ok(Rewrite { obj, len, headers, tmp_key, acl, tags })
+    }
+
+    /// Multipart upload of `rewrite` of `key`, to its temporary object if it has one.
+    async fn begin_rewrite(&self, key: &str, rewrite: &Rewrite) -> Result<Upload> {
+        let create = rewrite.headers.clone().create_request(&self.client);
+        Upload::begin(self, rewrite.target(key), create).await
+    }
+
+    /// Finish `rewrite` of `key` to `new_len` bytes once `res` wrote its target: copy it over
+    /// `key` from its temporary object if it has one, put back the ACL and tags, and read back
+    /// `edits`, the new bytes held in memory, with `verify_edits` set.
+    async fn finish_rewrite(
+        &self,
+        key: &str,
+        rewrite: Rewrite,
+        new_len: i64,
+        res: Result<ModifyStats>,
+        edits: &[(Range<i64>, Bytes)],
+    ) -> Result<ModifyStats> {
+        let stats = match &rewrite.tmp_key {
+            Some(tmp_key) => self.commit_temp(tmp_key, key, new_len, rewrite.headers, res).await?,
+            None => {
+                let mut stats = res?;
+
+                if self.config.compute_final_checksum {
+                    stats.final_checksum = final_checksum(&self.client, &self.config, key).await?;
+                }
+                stats
+            }
+        };
+
+        if let Some(acl) = rewrite.acl {
+            self.put_acl(key, acl).await?;
+        }
+
+        if let Some(tags) = rewrite.tags {
+            self.put_tags(key, tags).await?;
+        }
+
+        if self.config.verify_edits {
+            self.verify_edits(key, stats.etag.as_deref(), edits).await?;
+        }
+        Ok(stats)
+    }
+
+    /// Whether an edit leaving `key` `new_len` bytes long is written with one `put_object`
+    /// rather than a multipart upload. This decides both [`Session::modify`] and
+    /// [`Session::would_use_multipart`].
+    fn single_put(&self, key: &str, new_len: i64) -> Result<bool> {
+        if !self.config.force_single_put {
+            return Ok(false);
+        }
+
+        ensure!(new_len <= MAX_PUT_SIZE, "{} would be {} bytes, more than a single put can write", key, new_len);
+        Ok(true)
+    }
+
+    /// Whether [`Session::modify`] of `key` with `part` would go through a multipart upload
+    /// rather than a single `put_object`, from one `head_object`. Fails like the modify would
+    /// on a missing object, a part leaving a hole, or one too big for a single put.
+    pub async fn would_use_multipart(&self, key: &str, part: &Part) -> Result<bool> {
+        let stat = self.stat(key)
+            .await?
+            .ok_or_else(|| anyhow::Error::new(EditError::ObjectNotFound { key: key.to_string() }))?;
+
+        let end = part.index + part.data.as_ref().map_or(0, |d| d.len() as i64);
+        ensure!(part.index >= 0, "{} edit starts at negative offset {}", key, part.index);
+        ensure!(part.index <= stat.len, EditError::RangeOutOfBounds { key: key.to_string(), start: part.index, end, len: stat.len });
+
+        Ok(!self.single_put(key, std::cmp::max(stat.len, end))?)
+    }
+
+    /// Requests and bytes [`Session::modify_many`] of `key` with `parts` would take, from one
+    /// `head_object` and the plan it would follow, without writing anything. Price it with
+    /// [`CostEstimate::usd`].
+    ///
+    /// This is the default multipart plan: single puts, `preserve_part_layout` and the extra
+    /// copy of atomic edits aren't accounted for.
+    pub async fn estimate_cost(&self, key: &str, parts: &[Part]) -> Result<CostEstimate> {
+        let stat = self.stat(key)
+            .await?
+            .ok_or_else(|| anyhow::Error::new(EditError::ObjectNotFound { key: key.to_string() }))?;
+
+        let (_, _, cost) = self.plan_parts(key, stat.len, parts)?;
+        Ok(cost)
+    }
+
+    /// Work out the parts [`Session::modify_many`] of `key` with `parts` would write, from one
+    /// uncached `head_object`, without writing anything. Review or log the plan, then write it
+    /// with [`EditPlan::execute`].
+    ///
+    /// Like [`Session::estimate_cost`], this is the default multipart plan.
+    pub async fn plan_modify(&self, key: &str, parts: Vec<Part>) -> Result<EditPlan<'_>> {
+        let stat = self.current_stat(key).await?;
+        let (new_len, ops, cost) = self.plan_parts(key, stat.len, &parts)?;
+
+        if self.config.log_plan {
+            log_plan(key, &ops);
+        }
+
+        Ok(EditPlan {
+            session: self,
+            key: key.to_string(),
+            etag: stat.etag,
+            obj_len: stat.len,
+            new_len,
+            ops,
+            cost,
+            parts,
+        })
+    }
+
+    /// Plan an edit of the `edits` ranges of `key` as presigned requests, for a worker without
+    /// credentials of its own, e.g. a browser holding the new bytes, to carry out.
+    ///
+    /// The multipart upload is started here, as the part requests are signed for its id. The
+    /// worker only reads and uploads the parts holding edits, handing back the ETags the
+    /// uploads returned to [`Session::complete_presigned_edit`], which copies the rest and
+    /// completes the upload; [`Session::abort_presigned_edit`] gives it up. Copies and reads
+    /// require the ETag the plan was made for, so the edit fails rather than mix in a newer
+    /// object. The requests expire after `expires_in`, at most 7 days. Only the part layout of
+    /// the session's settings applies: the edited object gets neither the metadata of the
+    /// original nor an atomic rewrite.
+    pub async fn presign_edit(&self, key: &str, edits: &[Range<i64>], expires_in: Duration) -> Result<PresignedEdit> {
+        let stat = self.current_stat(key).await?;
+        let (new_len, ops, _) = self.plan_ranges(key, stat.len, edits.to_vec())?;
+        let presigning = PresigningConfig::expires_in(expires_in)?;
+        let sse = SseCustomer::new(&self.config);
+
+        let out = self.client.create_multipart_upload()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .sse_customer(&sse)
+            .sse_mode(self.config.sse.as_ref())
+            .send()
+            .await
+            .with_request_ids()?;
+        let upload_id = out.upload_id.ok_or_else(|| anyhow!("{} must need upload id", key))?;
+
+        let res = async {
+            let mut parts = Vec::with_capacity(ops.len());
+
+            for (op, part_number) in ops.into_iter().zip(1..) {
+                let part = match op {
+                    PartOp::Copy(range) => PresignedPart::Copy { part_number, start: range.start, end: range.end },
+                    PartOp::Upload(range) => {
+                        let read = match range.start < stat.len {
+                            false => None,
+                            true => {
+                                let end = std::cmp::min(range.end, stat.len);
+                                let request = self.client.get_object()
+                                    .bucket(&self.config.bucket)
+                                    .key(key)
+                                    .range(format!("bytes={}-{}", range.start, end - 1))
+                                    .set_if_match(stat.etag.clone())
+                                    .sse_customer(&sse)
+                                    .presigned(presigning.clone())
+                                    .await?;
+                                Some(request.into())
+                            }
+                        };
+
+                        let request = self.client.upload_part()
+                            .bucket(&self.config.bucket)
+                            .key(key)
+                            .upload_id(&upload_id)
+                            .part_number(part_number)
+                            .sse_customer(&sse)
+                            .presigned(presigning.clone())
+                            .await?;
+                        PresignedPart::Upload { part_number, start: range.start, end: range.end, read, request: request.into() }
+                    }
+                };
+                parts.push(part);
+            }
+
+            Result::<_, anyhow::Error>::Ok(parts)
+        }.await;
+
+        let parts = match res {
+            Ok(presigned) => presigned,
+            Err(e) => {
+                if let Err(abort_err) = self.client.abort_multipart_upload()
+                    .bucket(&self.config.bucket)
+                    .key(key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await
+                {
+                    warn!(key, upload_id, "failed to abort upload: {}", abort_err);
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(PresignedEdit {
+            key: key.to_string(),
+            upload_id,
+            etag: stat.etag,
+            object_len: stat.len,
+            new_len,
+            parts,
+        })
+    }
+
+    /// Complete `edit` once the worker uploaded its parts, `uploaded` holding the part number
+    /// and returned ETag of each: the parts left to copy are copied from the object, if it
+    /// still has the ETag the plan was made for, and the upload completed. It's aborted if
+    /// that fails, e.g. as a part wasn't uploaded or the object changed.
+    pub async fn complete_presigned_edit(&self, edit: &PresignedEdit, uploaded: &[(i32, String)]) -> Result<ModifyStats> {
+        let sse = SseCustomer::new(&self.config);
+
+        let res = async {
+            let mut parts = Vec::with_capacity(edit.parts.len());
+            let mut ranges = Vec::with_capacity(edit.parts.len());
+
+            for part in &edit.parts {
+                let (part_number, etag) = match *part {
+                    PresignedPart::Copy { part_number, start, end } => {
+                        let out = self.client.upload_part_copy()
+                            .copy_source(Source::from(edit.key.as_str()).copy_source(&self.config))
+                            .copy_source_range(format!("bytes={}-{}", start, end - 1))
+                            .set_copy_source_if_match(edit.etag.clone())
+                            .bucket(&self.config.bucket)
+                            .key(&edit.key)
+                            .upload_id(&edit.upload_id)
+                            .part_number(part_number)
+                            .sse_customer(&sse)
+                            .copy_source_sse_customer(&sse)
+                            .send()
+                            .await
+                            .with_request_ids()?;
+                        let etag = out.copy_part_result.and_then(|res| res.e_tag)
+                            .ok_or_else(|| anyhow!("{} part {} must need etag", edit.key, part_number))?;
+
+                        ranges.push((RangeKind::Copied, start, end));
+                        (part_number, etag)
+                    }
+                    PresignedPart::Upload { part_number, start, end, .. } => {
+                        let etag = uploaded.iter()
+                            .find(|(number, _)| *number == part_number)
+                            .map(|(_, etag)| etag.clone())
+                            .ok_or_else(|| anyhow!("{} part {} wasn't uploaded", edit.key, part_number))?;
+
+                        ranges.push((RangeKind::Uploaded, start, end));
+                        (part_number, etag)
+                    }
+                };
+                parts.push(CompletedPart::builder().part_number(part_number).e_tag(etag).build());
+            }
+
+            let out = self.client.complete_multipart_upload()
+                .bucket(&self.config.bucket)
+                .key(&edit.key)
+                .upload_id(&edit.upload_id)
+                .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+                .sse_customer(&sse)
+                .send()
+                .await
+                .with_request_ids()?;
+
+            Result::<_, anyhow::Error>::Ok(ModifyStats {
+                ranges,
+                version_id: out.version_id,
+                etag: out.e_tag,
+                upload_id: Some(edit.upload_id.clone()),
+                ..ModifyStats::default()
+            })
+        }.await;
+
+        if res.is_err() {
+            if let Err(e) = self.abort_presigned_edit(edit).await {
+                warn!(key = edit.key, upload_id = edit.upload_id, "failed to abort upload: {}", e);
+            }
+        }
+        res
+    }
+
+    /// Give up `edit`, dropping the parts uploaded so far.
+    pub async fn abort_presigned_edit(&self, edit: &PresignedEdit) -> Result<()> {
+        self.client.abort_multipart_upload()
+            .bucket(&self.config.bucket)
+            .key(&edit.key)
+            .upload_id(&edit.upload_id)
+            .send()
+            .await
+            .with_request_ids()?;
+        Ok(())
+    }
+
+    /// The edited length, default multipart plan and cost of editing the `obj_len` byte `key`
+    /// with `parts`.
+    fn plan_parts(&self, key: &str, obj_len: i64, parts: &[Part]) -> Result<(i64, Vec<PartOp>, CostEstimate)> {
+        let edits = parts.iter()
+            .map(|part| part.index..part.index + part.data.as_ref().map_or(0, |d| d.len() as i64))
+            .collect();
+        self.plan_ranges(key, obj_len, edits)
+    }
+
+    /// [`Session::plan_parts`] of edits of the `edits` ranges.
+    fn plan_ranges(&self, key: &str, obj_len: i64, mut edits: Vec<Range<i64>>) -> Result<(i64, Vec<PartOp>, CostEstimate)> {
+        edits.retain(|range| !range.is_empty());
+        edits.sort_by_key(|range| range.start);
+
+        let mut new_len = obj_len;
+
+        if let (Some(first), Some(last)) = (edits.first(), edits.last()) {
+            ensure!(first.start >= 0, "{} edit starts at negative offset {}", key, first.start);
+            ensure!(last.start <= obj_len, EditError::RangeOutOfBounds { key: key.to_string(), start: last.start, end: last.end, len: obj_len });
+            new_len = std::cmp::max(obj_len, last.end);
+        }
+
+        let ops = plan_edits(&self.config, new_len, &edits, self.config.part_size.resolve(new_len));
+        validate_plan(&ops, new_len)?;
+
+        let cost = CostEstimate::of_plan(&ops, &edits);
+        Ok((new_len, ops, cost))
+    }
+
+    /// Headers of the object rewritten from `obj`, following `metadata_directive` and
+    /// `preserve_timestamps` and in its storage class and encryption, or those of
+    /// [`MetadataDirective::Replace`] if it wasn't fetched, which is only the case in buckets
+    /// without Object Lock. `storage_class` overrides the storage class either way. A locked `obj`
+    /// fails with [`EditError::ObjectLocked`] unless `propagate_object_lock` is set.
+    pub(crate) fn rewrite_headers(&self, key: &str, obj: Option<&HeadObjectOutput>) -> Result<RewriteHeaders> {
+        let storage_class = self.config.storage_class.as_deref().map(StorageClass::from);
+
+        let replaced = RewriteHeaders {
+            metadata: self.config.replace_metadata.clone(),
+            storage_class: storage_class.clone(),
+            ..RewriteHeaders::default()
+        };
+
+        let Some(obj) = obj else {
+            return Ok(replaced);
+        };
+
+        let mut headers = match self.config.metadata_directive {
+            MetadataDirective::Copy => RewriteHeaders {
+                expires: None,
+                ..RewriteHeaders::copied(obj)
+            },
+            MetadataDirective::Replace => replaced,
+        };
+
+        // not a header the directive is about: a rewrite mustn't move the object to STANDARD
+        headers.storage_class = storage_class.or_else(|| obj.storage_class().cloned());
+        // nor fall back to the bucket's default encryption; `sse` overrides both when writing
+        headers.encryption = obj.server_side_encryption().cloned();
+        headers.kms_key_id = obj.ssekms_key_id().map(String::from);
+
+        // locks protect versions, so the old one keeps them but the new one starts unlocked
+        let retained = obj.object_lock_retain_until_date().is_some_and(|until| until.secs() > DateTime::from(SystemTime::now()).secs());
+        let legal_hold = obj.object_lock_legal_hold_status() == Some(&ObjectLockLegalHoldStatus::On);
+
+        if retained || legal_hold {
+            ensure!(self.config.propagate_object_lock, EditError::ObjectLocked { key: key.to_string() });
+
+            if retained {
+                headers.object_lock_mode = obj.object_lock_mode().cloned();
+                headers.object_lock_retain_until_date = obj.object_lock_retain_until_date().cloned();
+            }
+            if legal_hold {
+                headers.object_lock_legal_hold_status = Some(ObjectLockLegalHoldStatus::On);
+            }
+        }
+
+        if self.config.preserve_timestamps {
+            headers.expires = obj.expires_string()
+                .and_then(|s| DateTime::from_str(s, DateTimeFormat::HttpDate).ok());
+
+            // across repeated edits, keep the oldest
+            let original = match obj.metadata().and_then(|m| m.get(ORIGINAL_LAST_MODIFIED_KEY)) {
+                Some(original) => Some(original.clone()),
+                None => obj.last_modified().map(|t| t.fmt(DateTimeFormat::HttpDate)).transpose()?,
+            };
+
+            if let Some(original) = original {
+                headers.metadata.insert(ORIGINAL_LAST_MODIFIED_KEY.to_string(), original);
+            }
+        }
+
+        Ok(headers)
+    }
+
+    /// Owner and grants of `src`, `None` if the server doesn't support ACLs.
+    async fn get_acl(&self, src: Source<'_>) -> Result<Option<AccessControlPolicy>> {
+        let key = src.key;
+
+        let out = match self.client.get_object_acl()
+            .bucket(src.bucket(&self.config))
+            .key(key)
+            .set_version_id(src.version_id.map(String::from))
+            .send()
+            .await
+        {
+            Ok(out) => out,
+            Err(e) if e.code().is_some_and(|code| ACL_UNSUPPORTED_CODES.contains(&code)) => return Ok(None),
+            Err(e) => return Err(e).classify(&self.client, &self.config, key).await,
+        };
+
+        let policy = AccessControlPolicy::builder()
+            .set_owner(out.owner)
+            .set_grants(out.grants)
+            .build();
+        Ok(Some(policy))
+    }
+
+    /// Put back the grants read by [`Session::get_acl`]. Buckets with ACLs disabled
+    /// (bucket owner enforced) reject this, and have nothing to restore.
+    async fn put_acl(&self, key: &str, acl: AccessControlPolicy) -> Result<()> {
+        debug!(key, "put acl");
+
+        let res = self.client.put_object_acl()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .access_control_policy(acl)
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(e) if e.code().is_some_and(|code| ACL_UNSUPPORTED_CODES.contains(&code)) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Tags of `src`, `None` if it has none or the server doesn't support tagging.
+    async fn get_tags(&self, src: Source<'_>) -> Result<Option<Vec<Tag>>> {
+        let out = match self.client.get_object_tagging()
+            .bucket(src.bucket(&self.config))
+            .key(src.key)
+            .set_version_id(src.version_id.map(String::from))
+            .send()
+            .await
+        {
+            Ok(out) => out,
+            Err(e) if e.code() == Some("NotImplemented") => return Ok(None),
+            Err(e) => return Err(e).classify(&self.client, &self.config, src.key).await,
+        };
+
+        Ok(Some(out.tag_set).filter(|tags| !tags.is_empty()))
+    }
+
+    async fn put_tags(&self, key: &str, tags: Vec<Tag>) -> Result<()> {
+        debug!(key, "put tags");
+
+        self.client.put_object_tagging()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .tagging(Tagging::builder().set_tag_set(Some(tags)).build()?)
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await
+            .with_request_ids()?;
+        Ok(())
+    }
+
+    /// Write the edited object `data` to `key` with one `put_object`.
+    async fn put_whole(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        headers: RewriteHeaders,
+        if_match: Option<&str>,
+    ) -> Result<ModifyStats> {
+        let len = data.len() as i64;
+        let mut stats = ModifyStats::default();
+
+        debug!(key, len, "put");
+
+        let out = self.client.put_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .content_length(len)
+            .body(ByteStream::from(data))
+            .set_expires(headers.expires)
+            .set_metadata(Some(headers.metadata))
+            .set_content_type(headers.content_type)
+            .set_content_encoding(headers.content_encoding)
+            .set_content_disposition(headers.content_disposition)
+            .set_content_language(headers.content_language)
+            .set_cache_control(headers.cache_control)
+            .set_storage_class(headers.storage_class)
+            .set_server_side_encryption(headers.encryption)
+            .set_ssekms_key_id(headers.kms_key_id)
+            .set_object_lock_mode(headers.object_lock_mode)
+            .set_object_lock_retain_until_date(headers.object_lock_retain_until_date)
+            .set_object_lock_legal_hold_status(headers.object_lock_legal_hold_status)
+            .sse_mode(self.config.sse.as_ref())
+            .set_if_match(if_match.map(String::from))
+            .set_if_none_match(if_none_match(&self.config))
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await
+            .check_conditions(&self.config, key, if_match.is_some())?;
+
+        self.invalidate_stat(key);
+
+        if len > 0 {
+            stats.ranges.push((RangeKind::Uploaded, 0, len));
+        }
+        stats.version_id = out.version_id;
+        stats.etag = out.e_tag;
+
+        if self.config.compute_final_checksum {
+            stats.final_checksum = final_checksum(&self.client, &self.config, key).await?;
+        }
+
+        Ok(stats)
+    }
+
+    /// Server-side copy of the whole `obj_len` byte object `src_key` to `dst_key` with
+    /// `headers`, returning the version id and ETag of the copy. A single copy takes the user
+    /// metadata and content headers from `src_key`, a multipart one from `headers`.
+    pub(crate) async fn copy_whole(
+        &self,
+        src_key: &str,
+        dst_key: &str,
+        obj_len: i64,
+        headers: RewriteHeaders,
+    ) -> Result<(Option<String>, Option<String>)> {
+        let client = &self.client;
+        let bucket = &self.config.bucket;
+
+        debug!(src_key, dst_key, "copy object");
+
+        if obj_len <= MAX_COPY_SIZE {
+            let out = client.copy_object()
+                .copy_source(copy_source(bucket, src_key))
+                .bucket(bucket)
+                .key(dst_key)
+                .sse_customer(&SseCustomer::new(&self.config))
+                .copy_source_sse_customer(&SseCustomer::new(&self.config))
+                .metadata_directive(S3MetadataDirective::Copy)
+                .set_storage_class(headers.storage_class)
+                .set_server_side_encryption(headers.encryption)
+                .set_ssekms_key_id(headers.kms_key_id)
+                .set_object_lock_mode(headers.object_lock_mode)
+                .set_object_lock_retain_until_date(headers.object_lock_retain_until_date)
+                .set_object_lock_legal_hold_status(headers.object_lock_legal_hold_status)
+                .sse_mode(self.config.sse.as_ref())
+                .customize()
+                .config_override(write_retries(&self.config))
+                .send()
+                .await
+                .with_request_ids()?;
+
+            self.invalidate_stat(dst_key);
+            let etag = out.copy_object_result.and_then(|result| result.e_tag);
+            return Ok((out.version_id, etag));
+        }
+
+        let stats = rewrite(self, src_key.into(), dst_key, obj_len, &[], headers.create_request(client), None).await?;
+        Ok((stats.version_id, stats.etag))
+    }
+
+    /// Set the object to exactly `data`, dropping whatever it held past `data.len()`, in a
+    /// single multipart upload (a `put_object` if `data` is empty). Unlike [`Session::replace`]
+    /// the data is in memory already, and the result reports the uploaded parts.
+    pub async fn set_contents(&self, key: &str, data: Vec<u8>) -> Result<ModifyStats> {
+        let client = &self.client;
+        let len = data.len() as i64;
+
+        if len == 0 {
+            client.put_object()
+                .bucket(&self.config.bucket)
+                .key(key)
+                .sse_customer(&SseCustomer::new(&self.config))
+                .sse_mode(self.config.sse.as_ref())
+                .content_length(0)
+                .set_if_none_match(if_none_match(&self.config))
+                .customize()
+                .config_override(write_retries(&self.config))
+                .send()
+                .await
+                .check_conditions(&self.config, key, false)?;
+
+            self.invalidate_stat(key);
+            return Ok(ModifyStats::default());
+        }
+
+        let edits = [(0..len, Bytes::from(data))];
+        rewrite(self, key.into(), key, len, &edits, client.create_multipart_upload(), None).await
+    }
+
+    /// The whole object, for objects up to `max_read_bytes`. A missing key fails with
+    /// [`EditError::ObjectNotFound`].
+    pub async fn read(&self, key: &str) -> Result<Vec<u8>> {
+        let max = self.config.max_read_bytes.unwrap_or(DEFAULT_MAX_READ_BYTES);
+
+        let out = match self.client.get_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .send()
+            .await
+        {
+            Ok(out) => out,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Err(anyhow::Error::new(e).context(EditError::ObjectNotFound { key: key.to_string() }));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let len = out.content_length().unwrap_or(0).max(0) as u64;
+        ensure!(len <= max, "{} is {} bytes, more than max_read_bytes {}", key, len, max);
+
+        let mut body = body_reader(out.body, read_buffer_size(&self.config));
+        let mut data = Vec::with_capacity(len as usize);
+
+        loop {
+            let chunk = body.fill_buf().await?;
+
+            if chunk.is_empty() {
+                return Ok(data);
+            }
+
+            ensure!(data.len() + chunk.len() <= max as usize, "{} is more than max_read_bytes {}", key, max);
+            data.extend_from_slice(chunk);
+            let n = chunk.len();
+            body.consume(n);
+        }
+    }
+
+    /// Apply `part` only if the bytes it replaces currently hash to `expected_sha256`, else fail
+    /// with [`EditError::PreconditionFailed`] carrying the actual hash. The bytes are read with
+    /// a ranged `get_object` and hashed as they arrive; for a part running past the end of the
+    /// object only the existing bytes are hashed.
+    ///
+    /// The edit is made from the object the bytes were read from: it only completes while the
+    /// object still has that ETag, else it fails with [`EditError::Conflict`], so a concurrent
+    /// writer changing the bytes in between is never overwritten.
+    pub async fn modify_if_checksum(&self, key: &str, part: Part, expected_sha256: [u8; 32]) -> Result<ModifyStats> {
+        let start = part.index;
+        let end = start + part.data.as_ref().map_or(0, |d| d.len() as i64);
+        let mut hasher = Sha256::new();
+        let mut etag = None;
+
+        if start < end {
+            let res = self.client.get_object()
+                .bucket(&self.config.bucket)
+                .key(key)
+                .sse_customer(&SseCustomer::new(&self.config))
+                .range(format!("bytes={}-{}", start, end - 1))
+                .send()
+                .await;
+
+            match res {
+                Ok(out) => {
+                    etag = out.e_tag;
+                    let mut body = body_reader(out.body, read_buffer_size(&self.config));
+
+                    loop {
+                        let chunk = body.fill_buf().await?;
+
+                        if chunk.is_empty() {
+                            break;
+                        }
+
+                        hasher.update(chunk);
+                        let n = chunk.len();
+                        body.consume(n);
+                    }
+                }
+                // the part starts at the end of the object, nothing is replaced
+                Err(e) if e.code() == Some("InvalidRange") => (),
+                Err(e) => return Err(e).classify(&self.client, &self.config, key).await,
+            }
+        }
+
+        let actual_sha256: [u8; 32] = hasher.finalize().into();
+
+        if actual_sha256 != expected_sha256 {
+            return Err(EditError::PreconditionFailed { key: key.to_string(), start, end, actual_sha256 }.into());
+        }
+
+        // nothing was read for a part at the end of the object, whose ETag pins it all the same
+        let etag = match etag {
+            Some(etag) => etag,
+            None => self.current_stat(key)
+                .await?
+                .etag
+                .ok_or_else(|| anyhow!("{} has no ETag", key))?,
+        };
+
+        self.edit(Source::from(key), key, vec![part], Some(&etag)).await
+    }
+
+    /// The `len` bytes at `offset` of `key`, with one ranged `get_object` after the length
+    /// check, failing with [`EditError::RangeOutOfBounds`] past the end of the object. To
+    /// stream a range too large to hold in memory, use [`Session::range_reader`].
+    pub async fn read_at(&self, key: &str, offset: i64, len: i64) -> Result<Vec<u8>> {
+        let mut ranges = self.read_ranges(key, &[(offset, offset + len)]).await?;
+        Ok(ranges.remove(0))
+    }
+
+    /// Read several `(start, end)` ranges of `key`, `end` exclusive, fetched concurrently up to
+    /// `max_concurrency` at a time and returned in input order. All ranges are checked against
+    /// the object length first, with a single `head_object`.
+    pub async fn read_ranges(&self, key: &str, ranges: &[(i64, i64)]) -> Result<Vec<Vec<u8>>> {
+        let stat = self.stat(key)
+            .await?
+            .ok_or_else(|| anyhow!("{} not found", key))?;
+
+        for &(start, end) in ranges {
+            ensure!(0 <= start && start <= end && end <= stat.len, EditError::RangeOutOfBounds { key: key.to_string(), start, end, len: stat.len });
+        }
+
+        let fetches = ranges.iter().map(|&(start, end)| async move {
+            let _permit = self.permits.acquire().await?;
+
+            fetch_range(
+                self.client.clone(),
+                self.config.bucket.clone(),
+                key.to_string(),
+                SseCustomer::new(&self.config),
+                read_buffer_size(&self.config),
+                start as u64,
+                end as u64,
+            ).await
+        });
+
+        try_join_all(fetches).await
+    }
+
+    /// Overwrite the whole object with `len` bytes read from `body`.
+    ///
+    /// Bodies up to the part size go through a single `put_object`; larger bodies are
+    /// uploaded as a multipart upload, buffering one part in memory at a time. A body that
+    /// turns out longer or shorter than `len` fails with [`EditError::BodyLengthMismatch`].
+    pub async fn replace(
+        &self,
+        key: &str,
+        body: ByteStream,
+        len: i64,
+    ) -> Result<()> {
+        self.replace_with(key, body, len, RewriteHeaders::default()).await
+    }
+
+    /// [`Session::replace`] keeping the headers of the object it overwrites, as edits do: its
+    /// user metadata and content headers following `metadata_directive`, its storage class and
+    /// encryption, and with `preserve_timestamps` its timestamps. Takes a `head_object` first;
+    /// a missing object is written as by [`Session::replace`]. A locked object fails with
+    /// [`EditError::ObjectLocked`] unless `propagate_object_lock` is set.
+    pub async fn replace_preserving(
+        &self,
+        key: &str,
+        body: ByteStream,
+        len: i64,
+    ) -> Result<()> {
+        let obj = match self.client.head_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .send()
+            .await
+        {
+            Ok(obj) => Some(obj),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => None,
+            Err(e) => return Err(e).classify(&self.client, &self.config, key).await,
+        };
+
+        let headers = match &obj {
+            Some(obj) => self.rewrite_headers(key, Some(obj))?,
+            None => RewriteHeaders::default(),
+        };
+        self.replace_with(key, body, len, headers).await
+    }
+
+    async fn replace_with(&self, key: &str, body: ByteStream, len: i64, headers: RewriteHeaders) -> Result<()> {
+        let part_size = self.config.part_size.resolve(len);
+
+        // a short single-request body would leave the server waiting for the missing bytes
+        if let (lower, Some(upper)) = body.size_hint() {
+            if lower == upper && upper as i64 != len {
+                return Err(length_mismatch(key, len, upper as i64));
+            }
+        }
+
+        if len <= part_size {
+            return self.put(key, body, len, headers).await;
+        }
+
+        self.upload_stream(key, body, Vec::new(), part_size, Some(len), headers).await?;
+        Ok(())
+    }
+
+    /// Overwrite the whole object with `body` when its length isn't known up front, e.g.
+    /// data from a pipe, and return the number of bytes written.
+    ///
+    /// Up to one part is buffered to find out whether the body fits a single `put_object`;
+    /// longer bodies are uploaded as a multipart upload like [`Session::replace`]. With
+    /// [`PartSize::Auto`](crate::PartSize::Auto) parts are sized for the largest object S3 allows.
+    pub async fn replace_unsized(&self, key: &str, mut body: ByteStream) -> Result<i64> {
+        let part_size = self.config.part_size.resolve(MAX_OBJECT_SIZE);
+        let mut buf = Vec::new();
+
+        while buf.len() as i64 <= part_size {
+            match body.try_next().await? {
+                Some(chunk) => buf.extend_from_slice(&chunk),
+                None => {
+                    let len = buf.len() as i64;
+                    self.put(key, ByteStream::from(buf), len, RewriteHeaders::default()).await?;
+                    return Ok(len);
+                }
+            }
+        }
+
+        self.upload_stream(key, body, buf, part_size, None, RewriteHeaders::default()).await
+    }
+
+    async fn put(&self, key: &str, body: ByteStream, len: i64, headers: RewriteHeaders) -> Result<()> {
+        debug!(key, len, "put");
+
+        self.client.put_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .content_length(len)
+            .body(body)
+            .set_expires(headers.expires)
+            .set_metadata(Some(headers.metadata))
+            .set_content_type(headers.content_type)
+            .set_content_encoding(headers.content_encoding)
+            .set_content_disposition(headers.content_disposition)
+            .set_content_language(headers.content_language)
+            .set_cache_control(headers.cache_control)
+            .set_storage_class(headers.storage_class)
+            .set_server_side_encryption(headers.encryption)
+            .set_ssekms_key_id(headers.kms_key_id)
+            .set_object_lock_mode(headers.object_lock_mode)
+            .set_object_lock_retain_until_date(headers.object_lock_retain_until_date)
+            .set_object_lock_legal_hold_status(headers.object_lock_legal_hold_status)
+            .sse_mode(self.config.sse.as_ref())
+            .set_checksum_algorithm(self.config.stream_checksums.then_some(ChecksumAlgorithm::Crc32))
+            .set_if_none_match(if_none_match(&self.config))
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await
+            .check_conditions(&self.config, key, false)?;
+
+        self.invalidate_stat(key);
+        Ok(())
+    }
+
+    /// Multipart upload with `headers` of the bytes in `buf` followed by the rest of `body`,
+    /// checked against `declared` if the length is known. Returns the number of bytes uploaded.
+    async fn upload_stream(
+        &self,
+        key: &str,
+        mut body: ByteStream,
+        mut buf: Vec<u8>,
+        part_size: i64,
+        declared: Option<i64>,
+        headers: RewriteHeaders,
+    ) -> Result<i64> {
+        let mut upload = Upload::begin(self, key, headers.create_request(&self.client)).await?;
+        let mut read = buf.len() as i64;
+
+        let res = async {
+            loop {
+                let chunk = body.try_next().await?;
+                let eof = chunk.is_none();
+
+                if let Some(chunk) = chunk {
+                    read += chunk.len() as i64;
+
+                    if let Some(declared) = declared.filter(|&declared| read > declared) {
+                        return Err(length_mismatch(key, declared, read));
+                    }
+                    buf.extend_from_slice(&chunk);
+                }
+
+                while buf.len() as i64 >= part_size || (eof && !buf.is_empty()) {
+                    let rest = buf.split_off(std::cmp::min(buf.len(), part_size as usize));
+                    let data = std::mem::replace(&mut buf, rest);
+                    upload.upload_bytes(data).await?;
+                }
+
+                if eof {
+                    break;
+                }
+            }
+
+            if let Some(declared) = declared.filter(|&declared| read != declared) {
+                return Err(length_mismatch(key, declared, read));
+            }
+            Result::<_, anyhow::Error>::Ok(())
+        }.await;
+
+        if let Err(e) = res {
+            return Err(upload.fail(e).await);
+        }
+
+        upload.complete().await?;
+        Ok(read)
+    }
+
+    /// Start a multipart upload to `key`, for edit sequences [`Session::modify_many`] doesn't
+    /// cover: add parts copied from the current object or uploaded, then complete or abort it.
+    pub async fn begin_multipart(&self, key: &str) -> Result<Upload> {
+        Upload::begin(self, key, self.client.create_multipart_upload()).await
+    }
+
+    /// Delete `key`. Deleting a missing key succeeds, so this can be repeated safely.
+    pub async fn delete_object(&self, key: &str) -> Result<()> {
+        self.delete(key, None).await
+    }
+
+    /// Delete one version of `key` in a versioned bucket, succeeding if it doesn't exist.
+    pub async fn delete_object_version(&self, key: &str, version_id: &str) -> Result<()> {
+        self.delete(key, Some(version_id)).await
+    }
+
+    async fn delete(&self, key: &str, version_id: Option<&str>) -> Result<()> {
+        debug!(key, "delete");
+
+        let res = self.client.delete_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .set_version_id(version_id.map(String::from))
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await;
+
+        self.invalidate_stat(key);
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(e) if e.code() == Some("NoSuchKey") || e.code() == Some("NoSuchVersion") => Ok(()),
+            Err(e) => Err(e).classify(&self.client, &self.config, key).await,
+        }
+    }
+
+    /// Delete `key` only if its ETag is still `expected_etag` (quoted or not), returning whether
+    /// it was deleted. The ETag is checked with a `head_object` first, and the delete itself is
+    /// conditional too, so a write landing in between is not deleted on servers that honor it.
+    pub async fn delete_if_match(&self, key: &str, expected_etag: &str) -> Result<bool> {
+        let expected = expected_etag.trim_matches('"');
+
+        let Some(stat) = head(&self.client, &self.config, key).await? else {
+            return Ok(false);
+        };
+
+        if stat.etag.as_deref().map(|etag| etag.trim_matches('"')) != Some(expected) {
+            return Ok(false);
+        }
+
+        debug!(key, "delete");
+
+        let res = self.client.delete_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .if_match(format!("\"{}\"", expected))
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await;
+
+        self.invalidate_stat(key);
+
+        match res {
+            Ok(_) => Ok(true),
+            Err(e) if e.code() == Some("PreconditionFailed") || e.code() == Some("NoSuchKey") => Ok(false),
+            Err(e) => Err(e).classify(&self.client, &self.config, key).await,
+        }
+    }
+
+    /// The bytes `start..end` of the version `version_id` of `key`.
+    pub async fn read_version_range(&self, key: &str, version_id: &str, start: i64, end: i64) -> Result<Vec<u8>> {
+        ensure!(0 <= start && start <= end, "invalid range {}-{}", start, end);
+
+        if start == end {
+            return Ok(Vec::new());
+        }
+
+        let out = self.client.get_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .version_id(version_id)
+            .sse_customer(&SseCustomer::new(&self.config))
+            .range(format!("bytes={}-{}", start, end - 1))
+            .send()
+            .await
+            .classify(&self.client, &self.config, key)
+            .await?;
+
+        let data = out.body.collect().await?.to_vec();
+        ensure!(data.len() as i64 == end - start, "{} version {} has no range {}-{}", key, version_id, start, end - 1);
+        Ok(data)
+    }
+
+    /// Length and metadata of an object from a single `head_object`, `None` if it doesn't exist.
+    /// With `stat_cache_ttl_secs` set, a stat fetched less than that long ago is returned
+    /// without asking the server again.
+    pub async fn stat(&self, key: &str) -> Result<Option<ObjectStat>> {
+        let Some(cache) = &self.stat_cache else {
+            return head(&self.client, &self.config, key).await;
+        };
+
+        if let Some(stat) = cache.get(key) {
+            return Ok(Some(stat));
+        }
+
+        let stat = head(&self.client, &self.config, key).await?;
+
+        if let Some(stat) = &stat {
+            cache.insert(key, stat.clone());
+        }
+        Ok(stat)
+    }
+
+    /// Drop the cached stat of `key`, after writing it.
+    pub(crate) fn invalidate_stat(&self, key: &str) {
+        if let Some(cache) = &self.stat_cache {
+            cache.invalidate(key);
+        }
+    }
+
+    /// Existing part boundaries of an object uploaded with multipart, from
+    /// `get_object_attributes`. `None` for single-part objects and servers that don't report them.
+    pub async fn get_object_parts(&self, key: &str) -> Result<Option<ObjectParts>> {
+        self.object_parts(key.into()).await
+    }
+
+    /// [`Session::get_object_parts`] of any version of an object.
+    pub(crate) async fn object_parts(&self, src: Source<'_>) -> Result<Option<ObjectParts>> {
+        let key = src.key;
+        let mut marker = None;
+        let mut total_parts = None;
+        let mut parts = Vec::new();
+
+        loop {
+            let out = self.client.get_object_attributes()
+                .bucket(src.bucket(&self.config))
+                .key(key)
+                .set_version_id(src.version_id.map(String::from))
+                .sse_customer(&SseCustomer::new(&self.config))
+                .object_attributes(ObjectAttributes::ObjectParts)
+                .set_part_number_marker(marker.take())
+                .send()
+                .await;
+
+            let out = match out {
+                Ok(out) => out,
+                Err(e) if e.code() == Some("NotImplemented") => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+
+            let Some(object_parts) = out.object_parts else {
+                break;
+            };
+
+            total_parts = total_parts.or(object_parts.total_parts_count);
+
+            for part in object_parts.parts() {
+                if let (Some(number), Some(size)) = (part.part_number, part.size) {
+                    parts.push((number, size));
+                }
+            }
+
+            if !object_parts.is_truncated.unwrap_or(false) {
+                break;
+            }
+
+            marker = object_parts.next_part_number_marker;
+            ensure!(marker.is_some(), "{} object parts truncated without next part number marker", key);
+        }
+
+        Ok(total_parts.map(|total_parts| ObjectParts { total_parts, parts }))
+    }
+
+    /// Replace an object's user metadata and, if given, its content type without changing its bytes.
+    ///
+    /// Objects up to 5GB are copied onto themselves with a single `copy_object`; bigger ones are
+    /// rebuilt with a multipart upload of server-side part copies. Without `content_type` the
+    /// current one is kept.
+    pub async fn update_metadata(
+        &self,
+        key: &str,
+        metadata: HashMap<String, String>,
+        content_type: Option<String>,
+    ) -> Result<()> {
+        let client = &self.client;
+        let bucket = &self.config.bucket;
+
+        let stat = head(client, &self.config, key)
+            .await?
+            .ok_or_else(|| anyhow!("{} not found", key))?;
+
+        let content_type = content_type.or(stat.content_type);
+
+        if stat.len <= MAX_COPY_SIZE {
+            client.copy_object()
+                .copy_source(copy_source(bucket, key))
+                .bucket(bucket)
+                .key(key)
+                .sse_customer(&SseCustomer::new(&self.config))
+                .copy_source_sse_customer(&SseCustomer::new(&self.config))
+                .sse_mode(self.config.sse.as_ref())
+                .metadata_directive(S3MetadataDirective::Replace)
+                .set_metadata(Some(metadata))
+                .set_content_type(content_type)
+                .customize()
+                .config_override(write_retries(&self.config))
+                .send()
+                .await
+                .classify(client, &self.config, key)
+                .await?;
+
+            self.invalidate_stat(key);
+            return Ok(());
+        }
+
+        let create = client.create_multipart_upload()
+            .set_metadata(Some(metadata))
+            .set_content_type(content_type);
+
+        rewrite(self, key.into(), key, stat.len, &[], create, None).await?;
+        Ok(())
+    }
+
+    /// Every object whose key starts with `prefix`, in key order, following continuation
+    /// tokens across as many `list_objects_v2` calls as it takes.
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectSummary>> {
+        let mut pages = self.list_object_pages(prefix);
+        let mut objects = Vec::new();
+
+        while let Some(page) = pages.next_page().await? {
+            objects.extend(page);
+        }
+        Ok(objects)
+    }
+
+    /// Every version of `key` in a versioned bucket, newest first, leaving out delete markers.
+    /// An unversioned object has the single version `"null"`.
+    pub async fn list_object_versions(&self, key: &str) -> Result<Vec<ObjectVersion>> {
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+        let mut versions = Vec::new();
+
+        loop {
+            let out = self.client.list_object_versions()
+                .bucket(&self.config.bucket)
+                .prefix(key)
+                .set_key_marker(key_marker.take())
+                .set_version_id_marker(version_id_marker.take())
+                .send()
+                .await
+                .with_request_ids()?;
+
+            // the prefix also matches longer keys
+            for version in out.versions().iter().filter(|version| version.key() == Some(key)) {
+                let Some(version_id) = version.version_id() else {
+                    continue;
+                };
+
+                versions.push(ObjectVersion {
+                    version_id: version_id.to_string(),
+                    is_latest: version.is_latest().unwrap_or(false),
+                    size: version.size().unwrap_or(0),
+                    etag: version.e_tag().map(String::from),
+                    last_modified: version.last_modified().map(|t| SystemTime::try_from(*t)).transpose()?,
+                });
+            }
+
+            if !out.is_truncated().unwrap_or(false) {
+                break;
+            }
+
+            key_marker = out.next_key_marker().map(String::from);
+            version_id_marker = out.next_version_id_marker().map(String::from);
+            ensure!(key_marker.is_some(), "list object versions truncated without next key marker");
+        }
+
+        Ok(versions)
+    }
+
+    /// [`Session::list_objects`] a page at a time.
+    pub fn list_object_pages(&self, prefix: &str) -> ObjectPages {
+        ObjectPages::new(self, prefix)
+    }
+
+    /// List in-progress multipart uploads initiated more than `older_than` ago,
+    /// e.g. uploads leaked by crashed or failed edits.
+    pub async fn list_stale_uploads(
+        &self,
+        key_prefix: Option<&str>,
+        older_than: Duration,
+    ) -> Result<Vec<MultipartUpload>> {
+        let cutoff = SystemTime::now() - older_than;
+
+        let mut uploads = self.list_uploads(key_prefix).await?;
+        uploads.retain(|upload| upload.initiated <= cutoff);
+        Ok(uploads)
+    }
+
+    /// Every in-progress multipart upload under `key_prefix`.
+    pub(crate) async fn list_uploads(&self, key_prefix: Option<&str>) -> Result<Vec<MultipartUpload>> {
+        let mut key_marker = None;
+        let mut upload_id_marker = None;
+        let mut uploads = Vec::new();
+
+        loop {
+            let out = self.client.list_multipart_uploads()
+                .bucket(&self.config.bucket)
+                .set_prefix(key_prefix.map(String::from))
+                .set_key_marker(key_marker.take())
+                .set_upload_id_marker(upload_id_marker.take())
+                .send()
+                .await?;
+
+            for upload in out.uploads() {
+                let (Some(key), Some(upload_id), Some(initiated)) = (upload.key(), upload.upload_id(), upload.initiated()) else {
+                    continue;
+                };
+
+                uploads.push(MultipartUpload {
+                    key: key.to_string(),
+                    upload_id: upload_id.to_string(),
+                    initiated: SystemTime::try_from(*initiated)?,
+                });
+            }
+
+            if !out.is_truncated().unwrap_or(false) {
+                break;
+            }
+
+            key_marker = out.next_key_marker().map(String::from);
+            upload_id_marker = out.next_upload_id_marker().map(String::from);
+            ensure!(key_marker.is_some(), "list multipart uploads truncated without next key marker");
+        }
+
+        Ok(uploads)
+    }
+
+    /// Abort every upload returned by [`Session::list_stale_uploads`], returning the aborted upload ids.
+    pub async fn abort_stale_uploads(
+        &self,
+        key_prefix: Option<&str>,
+        older_than: Duration,
+    ) -> Result<Vec<String>> {
+        let stale = self.list_stale_uploads(key_prefix, older_than).await?;
+        let mut aborted = Vec::with_capacity(stale.len());
+
+        for upload in stale {
+            info!(key = upload.key, upload_id = upload.upload_id, "abort stale upload");
+
+            self.client.abort_multipart_upload()
+                .bucket(&self.config.bucket)
+                .key(&upload.key)
+                .upload_id(&upload.upload_id)
+                .customize()
+                .config_override(write_retries(&self.config))
+                .send()
+                .await?;
+
+            aborted.push(upload.upload_id);
+        }
+
+        Ok(aborted)
+    }
+
+    /// Reader over the bytes `start..end` of `key`, see [`AsyncRangeReader`].
+    pub fn range_reader(&self, key: &str, start: u64, end: u64) -> Result<AsyncRangeReader> {
+        AsyncRangeReader::from_session(self, key, start, end)
+    }
+
+    /// [`ChunkedObject`] over `key`, for reads at scattered offsets.
+    pub async fn chunked(&self, key: &str) -> Result<ChunkedObject> {
+        ChunkedObject::from_session(self, key).await
+    }
+}
+
+/// A piece of an object rebuilt by [`Session::rebuild`]: a range of the source's bytes or new
+/// ones, in memory or a range of a local file read as their part is uploaded.
+enum Segment {
+    Source(Range<i64>),
+    Data(Bytes),
+    File(Arc<Path>, Range<i64>),
+}
+
+impl Segment {
+    fn len(&self) -> i64 {
+        match self {
+            Segment::Source(range) | Segment::File(_, range) => range.end - range.start,
+            Segment::Data(data) => data.len() as i64,
+        }
+    }
+}
+
+/// An object being rewritten part by part, from [`Session::start_rewrite`].
+struct Rewrite {
+    // reads and copies of the object are pinned to its ETag
+    obj: HeadObjectOutput,
+    len: i64,
+    headers: RewriteHeaders,
+    // the temporary object written first with `atomic_edits`
+    tmp_key: Option<String>,
+    acl: Option<AccessControlPolicy>,
+    tags: Option<Vec<Tag>>,
+}
+
+impl Rewrite {
+    /// `key` as it was when the rewrite started: reads and copies of it fail with
+    /// [`EditError::Conflict`] once it has changed.
+    fn source<'a>(&'a self, key: &'a str) -> Source<'a> {
+        Source { etag: self.obj.e_tag(), ..Source::from(key) }
+    }
+
+    /// The object the rewrite of `key` writes.
+    fn target<'a>(&'a self, key: &'a str) -> &'a str {
+        self.tmp_key.as_deref().unwrap_or(key)
+    }
+}
+
+/// Headers given to a rewritten object.
+#[derive(Clone, Default)]
+pub(crate) struct RewriteHeaders {
+    expires: Option<DateTime>,
+    metadata: HashMap<String, String>,
+    content_type: Option<String>,
+    content_encoding: Option<String>,
+    content_disposition: Option<String>,
+    content_language: Option<String>,
+    cache_control: Option<String>,
+    storage_class: Option<StorageClass>,
+    encryption: Option<ServerSideEncryption>,
+    kms_key_id: Option<String>,
+    object_lock_mode: Option<ObjectLockMode>,
+    object_lock_retain_until_date: Option<DateTime>,
+    object_lock_legal_hold_status: Option<ObjectLockLegalHoldStatus>,
+}
+
+impl RewriteHeaders {
+    /// Headers of a copy of `obj` as it is: its user metadata, content headers, storage class
+    /// and encryption, but not its locks.
+    pub(crate) fn copied(obj: &HeadObjectOutput) -> Self {
+        RewriteHeaders {
+            expires: obj.expires_string().and_then(|s| DateTime::from_str(s, DateTimeFormat::HttpDate).ok()),
+            metadata: obj.metadata().cloned().unwrap_or_default(),
+            content_type: obj.content_type().map(String::from),
+            content_encoding: obj.content_encoding().map(String::from),
+            content_disposition: obj.content_disposition().map(String::from),
+            content_language: obj.content_language().map(String::from),
+            cache_control: obj.cache_control().map(String::from),
+            storage_class: obj.storage_class().cloned(),
+            encryption: obj.server_side_encryption().cloned(),
+            kms_key_id: obj.ssekms_key_id().map(String::from),
+            ..RewriteHeaders::default()
+        }
+    }
+
+    /// `create_multipart_upload` of an object with these headers.
+    pub(crate) fn create_request(self, client: &Client) -> CreateMultipartUploadFluentBuilder {
+        client.create_multipart_upload()
+            .set_expires(self.expires)
+            .set_metadata(Some(self.metadata))
+            .set_content_type(self.content_type)
+            .set_content_encoding(self.content_encoding)
+            .set_content_disposition(self.content_disposition)
+            .set_content_language(self.content_language)
+            .set_cache_control(self.cache_control)
+            .set_storage_class(self.storage_class)
+            .set_server_side_encryption(self.encryption)
+            .set_ssekms_key_id(self.kms_key_id)
+            .set_object_lock_mode(self.object_lock_mode)
+            .set_object_lock_retain_until_date(self.object_lock_retain_until_date)
+            .set_object_lock_legal_hold_status(self.object_lock_legal_hold_status)
+    }
+}
+
+// error codes of ACL requests on buckets with ACLs disabled, or servers without ACL support
+const ACL_UNSUPPORTED_CODES: &[&str] = &["AccessControlListNotSupported", "NotImplemented"];
+
+/// `0..len` split into the `verified` ranges, sorted and disjoint, and those between them, each
+/// with whether it's verified.
+fn split_verified(verified: &[Range<i64>], len: i64) -> Vec<(Range<i64>, bool)> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+
+    for range in verified {
+        if range.start > offset {
+            ranges.push((offset..range.start, false));
+        }
+        ranges.push((range.clone(), true));
+        offset = range.end;
+    }
+
+    if offset < len {
+        ranges.push((offset..len, false));
+    }
+    ranges
+}
+
+fn length_mismatch(key: &str, declared: i64, read: i64) -> anyhow::Error {
+    anyhow::Error::new(EditError::BodyLengthMismatch { declared, read }).context(format!("{} body", key))
+}
+
+/// Timeouts of `connect_timeout_secs`, `request_timeout_secs` and `read_timeout_secs`, `None`
+/// to keep the SDK's defaults if none is set.
+fn timeout_config(config: &S3Config) -> Option<TimeoutConfig> {
+    if config.connect_timeout_secs.is_none() && config.request_timeout_secs.is_none() && config.read_timeout_secs.is_none() {
+        return None;
+    }
+
+    let mut builder = TimeoutConfig::builder();
+    builder.set_connect_timeout(config.connect_timeout_secs.map(Duration::from_secs));
+    builder.set_operation_attempt_timeout(config.request_timeout_secs.map(Duration::from_secs));
+    builder.set_read_timeout(config.read_timeout_secs.map(Duration::from_secs));
+    Some(builder.build())
+}
+
+/// HTTPS client trusting the certificates of the PEM file at `path` besides the system roots.
+fn ca_http_client(path: &str) -> Result<SharedHttpClient> {
+    let pem = std::fs::read(path).map_err(|e| anyhow!("failed to read CA certificates {}: {}", path, e))?;
+    let trust_store = TrustStore::default().with_pem_certificate(pem);
+
+    let tls_context = TlsContext::builder()
+        .with_trust_store(trust_store)
+        .build()
+        .map_err(|e| anyhow!("invalid CA certificates {}: {}", path, e))?;
+
+    let client = HttpClientBuilder::new()
+        .tls_provider(TlsProvider::Rustls(CryptoMode::AwsLc))
+        .tls_context(tls_context)
+        .build_https();
+    Ok(client)
+}
+
+/// Interceptor adding `x-amz-request-payer: requester` to every request before it's signed.
+#[derive(Debug)]
+struct RequesterPays;
+
+impl Intercept for RequesterPays {
+    fn name(&self) -> &'static str {
+        "RequesterPays"
+    }
+
+    fn modify_before_signing(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        context.request_mut().headers_mut().insert("x-amz-request-payer", "requester");
+        Ok(())
+    }
+}
+
+/// Whether `obj` is in an archive storage class or tier and not restored, so it can't be read.
+fn is_archived(obj: &HeadObjectOutput) -> bool {
+    let archived = obj.archive_status().is_some()
+        || matches!(obj.storage_class(), Some(StorageClass::Glacier | StorageClass::DeepArchive));
+
+    archived && !obj.restore().is_some_and(|restore| restore.contains("ongoing-request=\"false\""))
+}
+
+fn new_stat_cache(config: &S3Config) -> Option<Arc<StatCache>> {
+    config.stat_cache_ttl_secs.map(|ttl| {
+        let size = config.stat_cache_size.unwrap_or(DEFAULT_STAT_CACHE_SIZE);
+        Arc::new(StatCache::new(Duration::from_secs(ttl), size))
+    })
+}
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use tokio::io::AsyncWriteExt;
+
+/// Temp file removed on drop, whether the upload using it succeeded or not.
+pub struct SpillFile {
+    path: PathBuf,
+}
+
+impl SpillFile {
+    fn create() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        let name = format!("edit_s3_file-{}-{}.part", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        SpillFile { path: std::env::temp_dir().join(name) }
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Body of an uploaded part, kept in memory or spilled to a temp file when larger than
+/// the configured `max_memory_bytes`.
+pub enum PartBuffer {
+    Memory(Vec<u8>),
+    Spilled(SpillFile, tokio::fs::File),
+}
+
+pub enum PartBody {
+    Memory(Bytes),
+    Spilled(SpillFile),
+}
+
+impl PartBuffer {
+    pub async fn new(len: u64, max_memory_bytes: Option<u64>) -> Result<Self> {
+        match max_memory_bytes {
+            Some(max) if len > max => {
+                let spill = SpillFile::create();
+                let file = tokio::fs::File::create(&spill.path).await?;
+                Ok(PartBuffer::Spilled(spill, file))
+            }
+            _ => Ok(PartBuffer::Memory(Vec::with_capacity(len as usize))),
+        }
+    }
+
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            PartBuffer::Memory(buf) => buf.extend_from_slice(data),
+            PartBuffer::Spilled(_, file) => file.write_all(data).await?,
+        }
+        Ok(())
+    }
+
+    pub async fn finish(self) -> Result<PartBody> {
+        match self {
+            PartBuffer::Memory(buf) => Ok(PartBody::Memory(Bytes::from(buf))),
+            PartBuffer::Spilled(spill, mut file) => {
+                file.flush().await?;
+                Ok(PartBody::Spilled(spill))
+            }
+        }
+    }
+}
+
+impl PartBody {
+    pub fn len(&self) -> Result<u64> {
+        match self {
+            PartBody::Memory(data) => Ok(data.len() as u64),
+            PartBody::Spilled(spill) => Ok(std::fs::metadata(&spill.path)?.len()),
+        }
+    }
+
+    /// The spill file, if any, must outlive the returned stream.
+    pub async fn stream(&self) -> Result<ByteStream> {
+        match self {
+            PartBody::Memory(data) => Ok(ByteStream::from(data.clone())),
+            PartBody::Spilled(spill) => Ok(ByteStream::from_path(&spill.path).await?),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{anyhow, ensure, Result};
+use aws_sdk_s3::operation::complete_multipart_upload::builders::CompleteMultipartUploadFluentBuilder;
+use aws_sdk_s3::operation::copy_object::builders::CopyObjectFluentBuilder;
+use aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder;
+use aws_sdk_s3::operation::get_object::builders::GetObjectFluentBuilder;
+use aws_sdk_s3::operation::get_object_attributes::builders::GetObjectAttributesFluentBuilder;
+use aws_sdk_s3::operation::head_object::builders::HeadObjectFluentBuilder;
+use aws_sdk_s3::operation::put_object::builders::PutObjectFluentBuilder;
+use aws_sdk_s3::operation::upload_part::builders::UploadPartFluentBuilder;
+use aws_sdk_s3::operation::upload_part_copy::builders::UploadPartCopyFluentBuilder;
+use aws_sdk_s3::types::ServerSideEncryption;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use md5::{Digest, Md5};
+
+use crate::{S3Config, SseMode};
+
+/// SSE-C headers of the requests that read or write object data, all `None` without
+/// `sse_customer_key`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SseCustomer {
+    algorithm: Option<String>,
+    key: Option<String>,
+    key_md5: Option<String>,
+}
+
+impl SseCustomer {
+    pub(crate) fn new(config: &S3Config) -> Self {
+        let Some(key) = &config.sse_customer_key else {
+            return SseCustomer::default();
+        };
+
+        // a key that doesn't decode was rejected when the session was built
+        let key_md5 = STANDARD.decode(key)
+            .ok()
+            .map(|raw| STANDARD.encode(Md5::digest(raw)));
+
+        SseCustomer {
+            algorithm: Some("AES256".to_string()),
+            key: Some(key.clone()),
+            key_md5,
+        }
+    }
+}
+
+/// Accept a base64 256-bit key, the only kind SSE-C (AES256) takes, and no `sse` beside it.
+pub(crate) fn check_sse(key: Option<&str>, sse: Option<&SseMode>) -> Result<()> {
+    ensure!(key.is_none() || sse.is_none(), "S3 config sets both sse_customer_key and sse");
+
+    if let Some(key) = key {
+        let raw = STANDARD.decode(key).map_err(|e| anyhow!("S3 config sse_customer_key isn't base64: {}", e))?;
+        ensure!(raw.len() == 32, "S3 config sse_customer_key is {} bytes, expected 32", raw.len());
+    }
+    Ok(())
+}
+
+/// `server_side_encryption` and `ssekms_key_id` of a request writing an object, as `sse`
+/// sets them, left as they are without it.
+pub(crate) trait SseModeExt {
+    fn sse_mode(self, sse: Option<&SseMode>) -> Self;
+}
+
+/// SSE-C headers of the object a request reads or writes.
+pub(crate) trait SseCustomerExt {
+    fn sse_customer(self, sse: &SseCustomer) -> Self;
+}
+
+/// SSE-C headers of the source a copy reads from.
+pub(crate) trait CopySourceSseCustomerExt {
+    fn copy_source_sse_customer(self, sse: &SseCustomer) -> Self;
+}
+
+macro_rules! impl_sse_customer {
+    ($($builder:ty),*) => {
+        $(
+            impl SseCustomerExt for $builder {
+                fn sse_customer(self, sse: &SseCustomer) -> Self {
+                    self.set_sse_customer_algorithm(sse.algorithm.clone())
+                        .set_sse_customer_key(sse.key.clone())
+                        .set_sse_customer_key_md5(sse.key_md5.clone())
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_copy_source_sse_customer {
+    ($($builder:ty),*) => {
+        $(
+            impl CopySourceSseCustomerExt for $builder {
+                fn copy_source_sse_customer(self, sse: &SseCustomer) -> Self {
+                    self.set_copy_source_sse_customer_algorithm(sse.algorithm.clone())
+                        .set_copy_source_sse_customer_key(sse.key.clone())
+                        .set_copy_source_sse_customer_key_md5(sse.key_md5.clone())
+                }
+            }
+        )*
+    };
+}
+
+impl_sse_customer!(
+    GetObjectFluentBuilder,
+    HeadObjectFluentBuilder,
+    GetObjectAttributesFluentBuilder,
+    PutObjectFluentBuilder,
+    CopyObjectFluentBuilder,
+    CreateMultipartUploadFluentBuilder,
+    UploadPartFluentBuilder,
+    UploadPartCopyFluentBuilder,
+    CompleteMultipartUploadFluentBuilder
+);
+
+impl_copy_source_sse_customer!(CopyObjectFluentBuilder, UploadPartCopyFluentBuilder);
+
+macro_rules! impl_sse_mode {
+    ($($builder:ty),*) => {
+        $(
+            impl SseModeExt for $builder {
+                fn sse_mode(self, sse: Option<&SseMode>) -> Self {
+                    match sse {
+                        None => self,
+                        Some(SseMode::S3) => self.server_side_encryption(ServerSideEncryption::Aes256),
+                        Some(SseMode::Kms { key_id }) => self.server_side_encryption(ServerSideEncryption::AwsKms)
+                            .set_ssekms_key_id(key_id.clone()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_sse_mode!(PutObjectFluentBuilder, CopyObjectFluentBuilder, CreateMultipartUploadFluentBuilder);
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::ObjectStat;
+
+/// [`ObjectStat`]s of `head_object` by key, kept for `ttl` and shared by the clones of a
+/// session. Writes through the session invalidate the key they wrote; writes by anyone else
+/// go unnoticed until the entry expires.
+#[derive(Debug)]
+pub(crate) struct StatCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, (Instant, ObjectStat)>>,
+}
+
+impl StatCache {
+    pub(crate) fn new(ttl: Duration, capacity: usize) -> Self {
+        StatCache {
+            ttl,
+            capacity: std::cmp::max(capacity, 1),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Stat of `key`, if cached less than `ttl` ago.
+    pub(crate) fn get(&self, key: &str) -> Option<ObjectStat> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get(key) {
+            Some((at, stat)) if at.elapsed() < self.ttl => Some(stat.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache `stat` of `key`, making room by dropping expired entries, then the oldest.
+    pub(crate) fn insert(&self, key: &str, stat: ObjectStat) {
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            entries.retain(|_, (at, _)| at.elapsed() < self.ttl);
+
+            if entries.len() >= self.capacity {
+                let oldest = entries.iter()
+                    .min_by_key(|(_, (at, _))| *at)
+                    .map(|(key, _)| key.clone());
+
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(key.to_string(), (Instant::now(), stat));
+    }
+
+    pub(crate) fn invalidate(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
@@ -0,0 +1,60 @@
+// Metrics of the `metrics` feature, sent to whatever recorder the application installed;
+// without the feature these do nothing.
+
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+use metrics::{counter, histogram};
+
+/// A request to the S3 API `api`, e.g. `UploadPartCopy`, that took `elapsed`, retries
+/// included, and failed unless `ok`.
+#[cfg(feature = "metrics")]
+pub(crate) fn request(api: &'static str, elapsed: Duration, ok: bool) {
+    histogram!("edit_s3_file_request_duration_seconds", "api" => api).record(elapsed.as_secs_f64());
+
+    if !ok {
+        counter!("edit_s3_file_request_failures_total", "api" => api).increment(1);
+    }
+}
+
+/// A retry of a request to `api`.
+#[cfg(feature = "metrics")]
+pub(crate) fn retry(api: &'static str) {
+    counter!("edit_s3_file_retries_total", "api" => api).increment(1);
+}
+
+/// A part of `len` bytes copied server-side.
+#[cfg(feature = "metrics")]
+pub(crate) fn part_copied(len: i64) {
+    counter!("edit_s3_file_parts_copied_total").increment(1);
+    counter!("edit_s3_file_bytes_copied_total").increment(len as u64);
+}
+
+/// A part of `len` bytes uploaded.
+#[cfg(feature = "metrics")]
+pub(crate) fn part_uploaded(len: i64) {
+    counter!("edit_s3_file_parts_uploaded_total").increment(1);
+    counter!("edit_s3_file_bytes_uploaded_total").increment(len as u64);
+}
+
+/// A multipart upload completed, or failed unless `ok`.
+#[cfg(feature = "metrics")]
+pub(crate) fn upload_finished(ok: bool) {
+    let outcome = if ok { "completed" } else { "failed" };
+    counter!("edit_s3_file_uploads_total", "outcome" => outcome).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn request(_api: &'static str, _elapsed: Duration, _ok: bool) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn retry(_api: &'static str) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn part_copied(_len: i64) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn part_uploaded(_len: i64) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn upload_finished(_ok: bool) {}
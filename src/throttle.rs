@@ -0,0 +1,33 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Paces transfers to an average of `rate` bytes per second: each transfer is scheduled after
+/// the ones before it have had their share of time, and waits until then.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    rate: u64,
+    // when the transfers scheduled so far have used up their time
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub(crate) fn new(rate: u64) -> Self {
+        Throttle {
+            rate: rate.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for the turn of a transfer of `bytes`.
+    pub(crate) async fn acquire(&self, bytes: u64) {
+        let start = {
+            let mut next = self.next.lock().unwrap();
+            // idle time doesn't build up into a burst
+            let start = std::cmp::max(*next, Instant::now());
+            *next = start + Duration::from_secs_f64(bytes as f64 / self.rate as f64);
+            start
+        };
+
+        tokio::time::sleep_until(start.into()).await;
+    }
+}
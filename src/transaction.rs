@@ -0,0 +1,128 @@
+use std::ops::Range;
+
+use anyhow::{anyhow, ensure, Result};
+use bytes::Bytes;
+
+use crate::{EditError, ModifyStats, Session};
+
+enum Staged {
+    Write(i64, Bytes),
+    Truncate(i64),
+}
+
+// the bytes to write over each range, disjoint and sorted by offset
+type Writes = Vec<(Range<i64>, Bytes)>;
+
+/// Edits of one object staged in memory and written together by [`Transaction::commit`],
+/// started with [`Session::begin`].
+///
+/// Operations apply in the order they were staged, a later write replacing what an earlier
+/// one wrote at the same offsets. However many there are, the commit is a single rewrite of
+/// the object, so readers see either none of them or all of them.
+pub struct Transaction<'a> {
+    session: &'a Session,
+    key: String,
+    staged: Vec<Staged>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(session: &'a Session, key: &str) -> Self {
+        Transaction {
+            session,
+            key: key.to_string(),
+            staged: Vec::new(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Write `data` at `offset`, which may be at most the object's length at that point.
+    pub fn write_at(&mut self, offset: i64, data: Vec<u8>) {
+        self.staged.push(Staged::Write(offset, Bytes::from(data)));
+    }
+
+    /// Cut the object to `len` bytes, at most its length at that point.
+    pub fn truncate(&mut self, len: i64) {
+        self.staged.push(Staged::Truncate(len));
+    }
+
+    /// Apply the staged operations to the current object in one rewrite. They are all checked
+    /// against its length, and the resulting part plan validated, before anything is written.
+    pub async fn commit(self) -> Result<ModifyStats> {
+        let session = self.session;
+        let key = &self.key;
+
+        let obj = session.head_source(key.as_str().into()).await?;
+
+        let obj_len = obj.content_length().ok_or_else(|| anyhow!("{} content length is empty", key))?;
+        let (new_len, edits) = resolve(key, obj_len, self.staged)?;
+
+        // a multipart upload can't be empty
+        if new_len == 0 {
+            return session.set_contents(key, Vec::new()).await;
+        }
+
+        session.commit_edits(key.as_str().into(), key, Some(obj), new_len, &edits, None).await
+    }
+}
+
+/// Length of the object after `staged` and the disjoint, sorted writes to make on it.
+fn resolve(key: &str, obj_len: i64, staged: Vec<Staged>) -> Result<(i64, Writes)> {
+    let mut len = obj_len;
+    let mut writes: Writes = Vec::new();
+
+    for op in staged {
+        match op {
+            Staged::Write(offset, data) => {
+                ensure!(offset >= 0, "{} write starts at negative offset {}", key, offset);
+                ensure!(offset <= len, EditError::RangeOutOfBounds { key: key.to_string(), start: offset, end: offset + data.len() as i64, len });
+
+                let range = offset..offset + data.len() as i64;
+
+                if range.is_empty() {
+                    continue;
+                }
+
+                let mut kept = Vec::with_capacity(writes.len() + 2);
+
+                // keep what earlier writes left outside the new one
+                for (r, d) in writes {
+                    if r.end <= range.start || r.start >= range.end {
+                        kept.push((r, d));
+                        continue;
+                    }
+
+                    if r.start < range.start {
+                        kept.push((r.start..range.start, d.slice(..(range.start - r.start) as usize)));
+                    }
+
+                    if r.end > range.end {
+                        kept.push((range.end..r.end, d.slice((range.end - r.start) as usize..)));
+                    }
+                }
+
+                len = std::cmp::max(len, range.end);
+                kept.push((range, data));
+                kept.sort_by_key(|(r, _)| r.start);
+                writes = kept;
+            }
+            Staged::Truncate(new_len) => {
+                ensure!(0 <= new_len && new_len <= len, EditError::RangeOutOfBounds { key: key.to_string(), start: new_len, end: new_len, len });
+
+                writes.retain(|(r, _)| r.start < new_len);
+
+                if let Some((r, d)) = writes.last_mut() {
+                    if r.end > new_len {
+                        *d = d.slice(..(new_len - r.start) as usize);
+                        r.end = new_len;
+                    }
+                }
+                len = new_len;
+            }
+        }
+    }
+
+    Ok((len, writes))
+}
@@ -0,0 +1,756 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, ensure, Result};
+use aws_sdk_s3::Client;
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextRef;
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::error::{BoxError, ProvideErrorMetadata};
+use aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+use crate::etag::{is_md5_of, multipart_etag_of_parts};
+use crate::hooks::Hooks;
+use crate::progress::Progress;
+use crate::sse::{CopySourceSseCustomerExt, SseCustomer, SseCustomerExt, SseModeExt};
+use crate::stat_cache::StatCache;
+use crate::telemetry;
+use crate::throttle::Throttle;
+use crate::{head, if_none_match, BucketType, write_retries, EditError, EditReport, PartChecksum, ProgressEvent, S3Config, SdkResultExt, Session, Source, DEFAULT_WRITE_MAX_RETRIES, MAX_PARTS};
+
+/// A multipart upload in progress, the building block of every rewrite, started with
+/// [`Session::begin_multipart`](crate::Session::begin_multipart).
+///
+/// Each `copy_range*` or `upload_bytes` call adds the next part, numbered from 1; S3 needs
+/// every part but the last to be at least [`MIN_PART_SIZE`](crate::MIN_PART_SIZE), which
+/// is only checked on [`Upload::complete`]. Nothing is visible under the key until then.
+///
+/// To stitch together parts uploaded by other processes, number this upload's own parts
+/// past theirs with [`Upload::start_parts_at`] and add theirs with [`Upload::add_uploaded_part`].
+/// Part numbers must be within 1-10000 ([`MAX_PARTS`]) and unique, else
+/// [`EditError::InvalidPartNumber`]; the object is made of the parts in number order, gaps
+/// between numbers being fine.
+/// An upload dropped without `complete` or `abort` keeps its parts stored until aborted,
+/// see [`Session::abort_stale_uploads`](crate::Session::abort_stale_uploads).
+#[derive(Debug)]
+pub struct Upload {
+    client: Client,
+    config: S3Config,
+    key: String,
+    upload_id: String,
+    etags: Vec<(i32, String)>,
+    // checksums of `checksum_algorithm` by part number
+    checksums: Mutex<HashMap<i32, String>>,
+    // lengths of the parts copied and uploaded, by part number, for `EditHooks::before_complete`
+    lens: Mutex<HashMap<i32, i64>>,
+    next_part: i32,
+    // retries of part uploads and copies, counted by `RetryCounter`
+    part_retries: Arc<AtomicU32>,
+    throttle: Option<Arc<Throttle>>,
+    progress: Option<Progress>,
+    hooks: Option<Hooks>,
+    cancel: Option<CancellationToken>,
+    stat_cache: Option<Arc<StatCache>>,
+    sse: SseCustomer,
+    // object recording the upload id for `upload_token`, removed once the upload is done
+    marker: Option<String>,
+    // parts a resumed upload had already, by part number, and the ETag `key` had when it started
+    resumed_parts: HashMap<i32, ResumedPart>,
+    resumed_etag: Option<String>,
+}
+
+impl Upload {
+    /// Start the upload `create` describes (metadata, content type, ...) under `key`.
+    ///
+    /// With an `upload_token` the upload is resumed instead if one was started for the same
+    /// key and token and is still in progress; see [`Session::with_upload_token`].
+    pub(crate) async fn begin(
+        session: &Session,
+        key: &str,
+        create: CreateMultipartUploadFluentBuilder,
+    ) -> Result<Upload> {
+        let (client, config) = (&session.client, &session.config);
+        let sse = SseCustomer::new(config);
+        let marker = config.upload_token.as_deref().map(|token| format!("{}.upload-{}", key, token));
+
+        let resumed = match &marker {
+            Some(marker) => resumable_upload(session, key, marker).await?,
+            None => None,
+        };
+
+        let (upload_id, resumed_parts, resumed_etag) = match resumed {
+            Some(resumed) => {
+                let parts = uploaded_parts(client, config, key, &resumed.upload_id).await?;
+                info!(key, upload_id = %resumed.upload_id, parts = parts.len(), "resume upload");
+                (resumed.upload_id, parts, resumed.etag)
+            }
+            None => {
+                let upload_id = create
+                    .bucket(&config.bucket)
+                    .key(key)
+                    .sse_customer(&sse)
+                    .sse_mode(config.sse.as_ref())
+                    .set_checksum_algorithm(config.checksum_algorithm.map(PartChecksum::algorithm))
+                    .customize()
+                    .config_override(write_retries(config))
+                    .send()
+                    .await
+                    .with_request_ids()?
+                    .upload_id
+                    .ok_or_else(|| anyhow!("{}, must need upload id", key))?;
+
+                if let Some(marker) = &marker {
+                    let etag = head(client, config, key).await?.and_then(|stat| stat.etag);
+
+                    client.put_object()
+                        .bucket(&config.bucket)
+                        .key(marker)
+                        .body(ByteStream::from(upload_id.clone().into_bytes()))
+                        .set_metadata(etag.map(|etag| HashMap::from([(MARKER_ETAG_KEY.to_string(), etag)])))
+                        .customize()
+                        .config_override(write_retries(config))
+                        .send()
+                        .await
+                        .with_request_ids()?;
+                }
+                (upload_id, HashMap::new(), None)
+            }
+        };
+
+        Ok(Upload {
+            client: client.clone(),
+            config: config.clone(),
+            key: key.to_string(),
+            upload_id,
+            etags: Vec::new(),
+            checksums: Mutex::new(HashMap::new()),
+            lens: Mutex::new(HashMap::new()),
+            next_part: 1,
+            part_retries: Arc::new(AtomicU32::new(0)),
+            throttle: session.throttle.clone(),
+            progress: session.progress.clone(),
+            hooks: session.hooks.clone(),
+            cancel: session.cancel.clone(),
+            stat_cache: session.stat_cache.clone(),
+            sse,
+            marker,
+            resumed_parts,
+            resumed_etag,
+        })
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn upload_id(&self) -> &str {
+        &self.upload_id
+    }
+
+    /// Number the next added part gets.
+    pub fn next_part_number(&self) -> i32 {
+        self.next_part
+    }
+
+    /// Number the parts added from now on from `part_number` up, e.g. to leave the numbers
+    /// below it to parts other processes upload.
+    pub fn start_parts_at(&mut self, part_number: i32) -> Result<()> {
+        self.check_part_number(part_number)?;
+        self.next_part = part_number;
+        Ok(())
+    }
+
+    /// Add the part `part_number` that another process uploaded to this upload, with
+    /// `upload_part` or `upload_part_copy` on [`Upload::key`] and [`Upload::upload_id`], by
+    /// the ETag the server returned for it. The part isn't checked until completion, which
+    /// fails if the ETag doesn't match the stored part.
+    pub fn add_uploaded_part(&mut self, part_number: i32, etag: impl Into<String>) -> Result<()> {
+        self.check_part_number(part_number)?;
+        self.etags.push((part_number, etag.into()));
+        Ok(())
+    }
+
+    fn check_part_number(&self, part_number: i32) -> Result<()> {
+        if !(1..=MAX_PARTS as i32).contains(&part_number) || self.etags.iter().any(|(num, _)| *num == part_number) {
+            return Err(EditError::InvalidPartNumber { part_number }.into());
+        }
+        Ok(())
+    }
+
+    /// Add the `len` bytes at `offset` of the object being replaced as the next part,
+    /// copied server-side. Returns the part number.
+    pub async fn copy_range(&mut self, offset: i64, len: i64) -> Result<i32> {
+        let key = self.key.clone();
+        self.copy_range_from(&key, offset, len).await
+    }
+
+    /// Add the `len` bytes at `offset` of `src_key`, in the same bucket, as the next part,
+    /// copied server-side. Returns the part number.
+    pub async fn copy_range_from(&mut self, src_key: &str, offset: i64, len: i64) -> Result<i32> {
+        self.copy_range_from_source(Source::from(src_key), offset, len).await
+    }
+
+    /// [`Upload::copy_range_from`] of any version of the source object.
+    pub(crate) async fn copy_range_from_source(&mut self, src: Source<'_>, offset: i64, len: i64) -> Result<i32> {
+        let part_num = self.next_part_number();
+        self.check_part_number(part_num)?;
+
+        let etag = self.copy_part(part_num, src, offset, len).await?;
+        self.etags.push((part_num, etag));
+        self.next_part = part_num + 1;
+        Ok(part_num)
+    }
+
+    /// Number the next `count` added parts get, for adding them with [`Upload::copy_part`] and
+    /// [`Upload::upload_part`] concurrently and recording them with [`Upload::add_parts`].
+    pub(crate) fn reserve_parts(&self, count: usize) -> Result<i32> {
+        let first = self.next_part_number();
+
+        if count > 0 {
+            self.check_part_number(first)?;
+            self.check_part_number(first + count as i32 - 1)?;
+        }
+        Ok(first)
+    }
+
+    /// Record the ETags of parts added with numbers from [`Upload::reserve_parts`].
+    pub(crate) fn add_parts(&mut self, parts: impl IntoIterator<Item = (i32, String)>) {
+        for (part_num, etag) in parts {
+            self.next_part = std::cmp::max(self.next_part, part_num + 1);
+            self.etags.push((part_num, etag));
+        }
+    }
+
+    /// Copy the `len` bytes at `offset` of `src` into part `part_num`, returning its ETag.
+    #[instrument(level = "debug", name = "copy_part", skip_all, err(level = "debug"), fields(part_number = part_num, offset = offset, len = len))]
+    pub(crate) async fn copy_part(&self, part_num: i32, src: Source<'_>, offset: i64, len: i64) -> Result<String> {
+        self.check_cancelled()?;
+
+        if let Some(etag) = self.resumed_copy(part_num, &src, len) {
+            self.record_len(part_num, len);
+            self.emit(ProgressEvent::PartCopied { part_number: part_num, len });
+            return Ok(etag);
+        }
+
+        let started = Instant::now();
+
+        let out = self.client.upload_part_copy()
+            .copy_source(src.copy_source(&self.config))
+            .copy_source_range(format!("bytes={}-{}", offset, offset + len - 1))
+            .set_copy_source_if_match(src.etag.map(String::from))
+            .bucket(&self.config.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .part_number(part_num)
+            .sse_customer(&self.sse)
+            .copy_source_sse_customer(&self.sse)
+            .customize()
+            .interceptor(RetryCounter::new(&self.part_retries, "UploadPartCopy"))
+            .send()
+            .await;
+        telemetry::request("UploadPartCopy", started.elapsed(), out.is_ok());
+
+        let out = match out {
+            Err(e) if src.etag.is_some() && e.code() == Some("PreconditionFailed") => {
+                return Err(anyhow::Error::new(e).context(EditError::Conflict { key: src.key.to_string() }));
+            }
+            res => res.classify(&self.client, &self.config, src.key).await?,
+        };
+
+        let result = out.copy_part_result
+            .ok_or_else(|| anyhow!("{} must need copy part result", self.key))?;
+
+        if let Some(kind) = self.config.checksum_algorithm {
+            let checksums = [result.checksum_crc32(), result.checksum_crc32_c(), result.checksum_sha1(), result.checksum_sha256()];
+            self.record_checksum(part_num, kind.pick(checksums));
+        }
+
+        let etag = result.e_tag
+            .ok_or_else(|| anyhow!("{} must need e_tag", self.key))?;
+
+        debug!(elapsed_ms = started.elapsed().as_millis() as u64, "part copied");
+        telemetry::part_copied(len);
+        self.record_len(part_num, len);
+        self.emit(ProgressEvent::PartCopied { part_number: part_num, len });
+        Ok(etag)
+    }
+
+    /// Add `body` as the next part. Returns the part number.
+    pub async fn upload_bytes(&mut self, body: impl Into<ByteStream>) -> Result<i32> {
+        let part_num = self.next_part_number();
+        self.check_part_number(part_num)?;
+
+        let etag = self.upload_part(part_num, body.into()).await?;
+        self.etags.push((part_num, etag));
+        self.next_part = part_num + 1;
+        Ok(part_num)
+    }
+
+    /// Upload `body` as part `part_num`, returning its ETag.
+    #[instrument(level = "debug", name = "upload_part", skip_all, err(level = "debug"), fields(part_number = part_num, len = body.size_hint().0))]
+    pub(crate) async fn upload_part(&self, part_num: i32, body: ByteStream) -> Result<String> {
+        self.check_cancelled()?;
+
+        let len = body.size_hint().0 as i64;
+
+        if let Some(etag) = self.resumed_upload(part_num, &body) {
+            self.record_len(part_num, len);
+            self.emit(ProgressEvent::PartUploaded { part_number: part_num, len });
+            return Ok(etag);
+        }
+
+        if let Some(throttle) = &self.throttle {
+            throttle.acquire(body.size_hint().0).await;
+        }
+        let started = Instant::now();
+
+        let out = self.client.upload_part()
+            .bucket(&self.config.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .part_number(part_num)
+            .sse_customer(&self.sse)
+            .set_checksum_algorithm(self.config.checksum_algorithm.map(PartChecksum::algorithm))
+            .body(body)
+            .customize()
+            .interceptor(RetryCounter::new(&self.part_retries, "UploadPart"))
+            .send()
+            .await;
+        telemetry::request("UploadPart", started.elapsed(), out.is_ok());
+        let out = out.with_request_ids()?;
+
+        if let Some(kind) = self.config.checksum_algorithm {
+            let checksums = [out.checksum_crc32(), out.checksum_crc32_c(), out.checksum_sha1(), out.checksum_sha256()];
+            self.record_checksum(part_num, kind.pick(checksums));
+        }
+
+        let etag = out.e_tag
+            .ok_or_else(|| anyhow!("{} must need e_tag", self.key))?;
+
+        debug!(elapsed_ms = started.elapsed().as_millis() as u64, "part uploaded");
+        telemetry::part_uploaded(len);
+        self.record_len(part_num, len);
+        self.emit(ProgressEvent::PartUploaded { part_number: part_num, len });
+        Ok(etag)
+    }
+
+    /// Fail with [`EditError::Cancelled`] once the session's cancellation token is cancelled,
+    /// so no more requests are made for the upload.
+    fn check_cancelled(&self) -> Result<()> {
+        if self.cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            info!(key = self.key, upload_id = self.upload_id, "upload cancelled");
+            return Err(EditError::Cancelled { key: self.key.clone() }.into());
+        }
+        Ok(())
+    }
+
+    /// ETag of part `part_num` of the resumed upload if it is the copy of the `len` bytes
+    /// `src` would give: a copy of `key` from when it had the same ETag as now.
+    fn resumed_copy(&self, part_num: i32, src: &Source<'_>, len: i64) -> Option<String> {
+        let part = self.resumed_parts.get(&part_num)?;
+        let same_object = src.key == self.key
+            && src.bucket.is_none_or(|bucket| bucket == self.config.bucket)
+            && src.version_id.is_none();
+
+        let unchanged = src.etag.is_some() && src.etag == self.resumed_etag.as_deref();
+        (part.len == len && same_object && unchanged).then(|| self.reuse(part_num, part))
+    }
+
+    /// ETag of part `part_num` of the resumed upload if it holds the bytes of `body`.
+    fn resumed_upload(&self, part_num: i32, body: &ByteStream) -> Option<String> {
+        let part = self.resumed_parts.get(&part_num)?;
+        let data = body.bytes()?;
+        (part.len == data.len() as i64 && is_md5_of(&part.etag, data)).then(|| self.reuse(part_num, part))
+    }
+
+    fn reuse(&self, part_num: i32, part: &ResumedPart) -> String {
+        debug!(part_number = part_num, len = part.len, "reuse part");
+        self.record_checksum(part_num, part.checksum.clone());
+        part.etag.clone()
+    }
+
+    fn record_checksum(&self, part_num: i32, checksum: Option<String>) {
+        if let Some(checksum) = checksum {
+            self.checksums.lock().unwrap().insert(part_num, checksum);
+        }
+    }
+
+    fn record_len(&self, part_num: i32, len: i64) {
+        self.lens.lock().unwrap().insert(part_num, len);
+    }
+
+    fn emit(&self, event: ProgressEvent) {
+        if let Some(progress) = &self.progress {
+            progress.emit(event);
+        }
+    }
+
+    /// Assemble the added parts into the object, replacing whatever `key` held. A failed
+    /// completion aborts the upload, unless it was started with an `upload_token` to be resumed.
+    pub async fn complete(self) -> Result<()> {
+        self.finish(None).await?;
+        Ok(())
+    }
+
+    /// [`Upload::complete`] only if `key` still has the ETag `etag`, else fail with
+    /// [`EditError::Conflict`](crate::EditError::Conflict) leaving it as it is.
+    pub async fn complete_if_match(self, etag: &str) -> Result<()> {
+        self.finish(Some(etag)).await?;
+        Ok(())
+    }
+
+    /// Complete the upload, conditionally with `if_match`.
+    pub(crate) async fn finish(self, if_match: Option<&str>) -> Result<Completion> {
+        if let Err(e) = self.check_cancelled() {
+            return Err(self.fail(e).await);
+        }
+
+        if let Some(hooks) = &self.hooks {
+            if let Err(e) = hooks.before_complete(&self.report()) {
+                return Err(self.veto(e).await);
+            }
+        }
+
+        let res = self.complete_parts(if_match).await;
+
+        if let Some(stat_cache) = &self.stat_cache {
+            stat_cache.invalidate(&self.key);
+        }
+
+        let (version_id, etag) = match res {
+            Ok(completed) => completed,
+            Err(e) => return Err(self.fail(e).await),
+        };
+        telemetry::upload_finished(true);
+
+        if let Some(progress) = &self.progress {
+            progress.emit(ProgressEvent::Completed { key: self.key.clone() });
+        }
+
+        if let Some(marker) = &self.marker {
+            remove_marker(&self.client, &self.config.bucket, marker).await;
+        }
+        let part_retries = self.part_retries.load(Ordering::Relaxed);
+        Ok(Completion { upload_id: self.upload_id, version_id, etag, part_retries })
+    }
+
+    /// Abort the upload after `err` failed it, so that its parts don't stay stored, and return
+    /// `err`. Uploads made resumable by an `upload_token` are kept for the retry to resume. A
+    /// failed abort is only logged, leaving the upload to [`Session::abort_stale_uploads`].
+    pub(crate) async fn fail(self, err: anyhow::Error) -> anyhow::Error {
+        telemetry::upload_finished(false);
+
+        if self.marker.is_some() {
+            return err;
+        }
+
+        let (key, upload_id) = (self.key.clone(), self.upload_id.clone());
+
+        if let Err(e) = self.abort().await {
+            warn!(key, upload_id, "failed to abort upload: {}", e);
+        }
+        err
+    }
+
+    /// Abort the upload after `err` from [`EditHooks::before_complete`] vetoed it, resumable
+    /// or not, and return `err` as the cause of [`EditError::Vetoed`].
+    ///
+    /// [`EditHooks::before_complete`]: crate::EditHooks::before_complete
+    async fn veto(self, err: anyhow::Error) -> anyhow::Error {
+        telemetry::upload_finished(false);
+
+        let (key, upload_id) = (self.key.clone(), self.upload_id.clone());
+        info!(key, upload_id, "upload vetoed: {}", err);
+
+        if let Err(e) = self.abort().await {
+            warn!(key, upload_id, "failed to abort upload: {}", e);
+        }
+        err.context(EditError::Vetoed { key })
+    }
+
+    /// The parts added so far, as [`EditHooks::before_complete`] sees them.
+    ///
+    /// [`EditHooks::before_complete`]: crate::EditHooks::before_complete
+    fn report(&self) -> EditReport {
+        let mut parts = self.etags.clone();
+        parts.sort_by_key(|(part_num, _)| *part_num);
+
+        let lens = self.lens.lock().unwrap();
+        let len = parts.iter().map(|(part_num, _)| lens.get(part_num).copied()).sum();
+
+        EditReport {
+            key: self.key.clone(),
+            upload_id: self.upload_id.clone(),
+            expected_etag: self.expected_etag(&parts).map(|etag| format!("\"{}\"", etag)),
+            parts,
+            len,
+            checksums: self.checksums.lock().unwrap().clone(),
+        }
+    }
+
+    /// ETag without quotes of the object `etags` make up, if their ETags are MD5s and the
+    /// bucket computes multipart ETags.
+    fn expected_etag(&self, etags: &[(i32, String)]) -> Option<String> {
+        match BucketType::of(&self.config) {
+            BucketType::General => multipart_etag_of_parts(etags.iter().map(|(_, etag)| etag.as_str())),
+            BucketType::Directory => None,
+        }
+    }
+
+    /// Complete the upload. S3 may answer a completion with 200 and an error in the body, which
+    /// either fails the request or leaves the output without an ETag. As a completion that did
+    /// happen can't be repeated, such failures are checked against the object's ETag, which must
+    /// be the one the parts make up, before completing again after a jittered backoff, up to
+    /// `write_max_retries` times. Parts whose ETags aren't MD5s (SSE-KMS, SSE-C) can't be
+    /// checked, so those failures are returned as they are. The version id is only known when
+    /// the completion itself succeeded. Returns the version id and the ETag.
+    async fn complete_parts(&self, if_match: Option<&str>) -> Result<(Option<String>, Option<String>)> {
+        let mut etags = self.etags.clone();
+        // parts may finish out of order, each etag belongs to the part number it was uploaded as
+        etags.sort_by_key(|(part_num, _)| *part_num);
+
+        let expected = self.expected_etag(&etags);
+
+        let checksums = self.checksums.lock().unwrap().clone();
+
+        let parts = etags.into_iter()
+            .map(|(part_num, e_tag)| {
+                let part = CompletedPart::builder()
+                    .part_number(part_num)
+                    .e_tag(e_tag);
+
+                match self.config.checksum_algorithm {
+                    Some(kind) => kind.complete(part, checksums.get(&part_num).cloned()).build(),
+                    None => part.build(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let retries = self.config.write_max_retries.unwrap_or(DEFAULT_WRITE_MAX_RETRIES);
+        let mut attempt = 0;
+
+        loop {
+            let started = Instant::now();
+            let res = self.client.complete_multipart_upload()
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(parts.clone()))
+                        .build(),
+                )
+                .bucket(&self.config.bucket)
+                .key(&self.key)
+                .upload_id(&self.upload_id)
+                .sse_customer(&self.sse)
+                .set_if_match(if_match.map(String::from))
+                .set_if_none_match(if_none_match(&self.config))
+                .customize()
+                .config_override(write_retries(&self.config))
+                .send()
+                .await;
+            telemetry::request("CompleteMultipartUpload", started.elapsed(), res.is_ok());
+            let res = res.check_conditions(&self.config, &self.key, if_match.is_some());
+
+            let err = match res {
+                Ok(out) if out.e_tag.is_some() => return Ok((out.version_id, out.e_tag)),
+                Ok(_) => anyhow!("{} completion returned no ETag", self.key),
+                Err(e) if matches!(e.downcast_ref::<EditError>(), Some(EditError::Conflict { .. } | EditError::AlreadyExists { .. })) => {
+                    return Err(e);
+                }
+                Err(e) => e,
+            };
+
+            let Some(expected) = &expected else {
+                return Err(err);
+            };
+
+            // the completion's error is the one to report, whatever keeps the check from running
+            let stat = match head(&self.client, &self.config, &self.key).await {
+                Ok(stat) => stat,
+                Err(e) => {
+                    warn!(key = self.key, "failed to check the object after a failed completion: {}", e);
+                    return Err(err);
+                }
+            };
+
+            if let Some(etag) = stat.and_then(|stat| stat.etag).filter(|etag| etag.trim_matches('"') == expected) {
+                info!(key = self.key, "complete upload failed, but the object has the uploaded parts: {}", err);
+                return Ok((None, Some(etag)));
+            }
+
+            if attempt >= retries {
+                return Err(err);
+            }
+
+            attempt += 1;
+            tokio::time::sleep(complete_backoff(attempt)).await;
+        }
+    }
+
+    /// Drop the upload and its stored parts, leaving `key` as it was.
+    pub async fn abort(self) -> Result<()> {
+        info!(key = self.key, upload_id = self.upload_id, "abort upload");
+
+        self.client.abort_multipart_upload()
+            .bucket(&self.config.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .customize()
+            .config_override(write_retries(&self.config))
+            .send()
+            .await
+            .with_request_ids()?;
+
+        if let Some(marker) = &self.marker {
+            remove_marker(&self.client, &self.config.bucket, marker).await;
+        }
+        Ok(())
+    }
+}
+
+/// Metadata key of an upload marker holding the ETag of `key` when the upload was started.
+const MARKER_ETAG_KEY: &str = "object-etag";
+
+/// A part already stored in a resumed upload.
+#[derive(Debug)]
+struct ResumedPart {
+    len: i64,
+    etag: String,
+    // of `checksum_algorithm`, if set
+    checksum: Option<String>,
+}
+
+/// Upload recorded in a marker: its id, and the ETag of `key` when it was started.
+struct ResumedUpload {
+    upload_id: String,
+    etag: Option<String>,
+}
+
+/// The upload recorded in `marker` for `key`, if it is still in progress.
+async fn resumable_upload(session: &Session, key: &str, marker: &str) -> Result<Option<ResumedUpload>> {
+    let res = session.client.get_object()
+        .bucket(&session.config.bucket)
+        .key(marker)
+        .send()
+        .await;
+
+    let (upload_id, etag) = match res {
+        Ok(out) => {
+            let etag = out.metadata().and_then(|m| m.get(MARKER_ETAG_KEY)).cloned();
+            (String::from_utf8(out.body.collect().await?.to_vec())?, etag)
+        }
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+        Err(e) => return Err(e).with_request_ids(),
+    };
+
+    let in_progress = session.list_uploads(Some(key))
+        .await?
+        .into_iter()
+        .any(|upload| upload.key == key && upload.upload_id == upload_id);
+
+    Ok(in_progress.then_some(ResumedUpload { upload_id, etag }))
+}
+
+/// The parts stored in the upload `upload_id` of `key`, by part number.
+async fn uploaded_parts(client: &Client, config: &S3Config, key: &str, upload_id: &str) -> Result<HashMap<i32, ResumedPart>> {
+    let mut parts = HashMap::new();
+    let mut marker = None;
+
+    loop {
+        let out = client.list_parts()
+            .bucket(&config.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .set_part_number_marker(marker)
+            .send()
+            .await
+            .with_request_ids()?;
+
+        for part in out.parts() {
+            if let (Some(part_num), Some(len), Some(etag)) = (part.part_number, part.size, &part.e_tag) {
+                let checksum = config.checksum_algorithm.and_then(|kind| {
+                    kind.pick([part.checksum_crc32(), part.checksum_crc32_c(), part.checksum_sha1(), part.checksum_sha256()])
+                });
+                parts.insert(part_num, ResumedPart { len, etag: etag.clone(), checksum });
+            }
+        }
+
+        if !out.is_truncated.unwrap_or_default() {
+            return Ok(parts);
+        }
+        marker = out.next_part_number_marker;
+        ensure!(marker.is_some(), "{} list parts truncated without next part number marker", key);
+    }
+}
+
+/// Delete the upload id record of a completed or aborted upload. A leftover record only
+/// points at an upload that is no longer in progress, so failures are just logged.
+async fn remove_marker(client: &Client, bucket: &str, marker: &str) {
+    let res = client.delete_object()
+        .bucket(bucket)
+        .key(marker)
+        .send()
+        .await;
+
+    if let Err(e) = res {
+        warn!(marker, "failed to delete upload marker: {}", e);
+    }
+}
+
+/// What completing an upload reported: its id, and the version id, if the bucket is versioned,
+/// and ETag of the object it wrote, with the retries its parts took.
+pub(crate) struct Completion {
+    pub(crate) upload_id: String,
+    pub(crate) version_id: Option<String>,
+    pub(crate) etag: Option<String>,
+    pub(crate) part_retries: u32,
+}
+
+/// Interceptor of one request to `api` adding each attempt after its first to `retries`.
+#[derive(Debug)]
+struct RetryCounter {
+    api: &'static str,
+    attempts: AtomicU32,
+    retries: Arc<AtomicU32>,
+}
+
+impl RetryCounter {
+    fn new(retries: &Arc<AtomicU32>, api: &'static str) -> Self {
+        RetryCounter {
+            api,
+            attempts: AtomicU32::new(0),
+            retries: retries.clone(),
+        }
+    }
+}
+
+impl Intercept for RetryCounter {
+    fn name(&self) -> &'static str {
+        "RetryCounter"
+    }
+
+    fn read_before_attempt(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if self.attempts.fetch_add(1, Ordering::Relaxed) > 0 {
+            self.retries.fetch_add(1, Ordering::Relaxed);
+            telemetry::retry(self.api);
+        }
+        Ok(())
+    }
+}
+
+/// Backoff before completing again, doubling from 100ms on the first retry, plus up to as
+/// much jitter.
+fn complete_backoff(attempt: u32) -> Duration {
+    let base = 100u64 << std::cmp::min(attempt - 1, 6);
+    let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    Duration::from_millis(base + nanos as u64 % base)
+}
@@ -0,0 +1,90 @@
+use std::io;
+use std::io::Write;
+
+use anyhow::Result;
+use tracing::warn;
+
+use crate::{session_for, Part, Session, RT};
+
+/// `std::io::Write` over the `len` bytes at `offset` of an object, for code that writes
+/// through `write!` or `io::copy`.
+///
+/// Writes are only buffered: nothing reaches the object until [`Write::flush`] (or drop),
+/// which applies everything written since the last flush as one range edit, right after
+/// what was flushed before. Edits smaller than [`MIN_PART_SIZE`](crate::MIN_PART_SIZE) are
+/// padded with the object's neighboring bytes like any modify, so flushing often costs a
+/// rewrite each time but is never rejected for its size. Writing past `len` bytes in total
+/// fails with [`io::ErrorKind::InvalidInput`] and writes nothing of that call.
+///
+/// A flush on drop can't report failures, so flush before dropping to see them. Like the
+/// other blocking functions, this must not be used from within an async runtime.
+pub struct RegionWriter {
+    session: Session,
+    key: String,
+    offset: i64,
+    len: u64,
+    flushed: u64,
+    buf: Vec<u8>,
+}
+
+impl RegionWriter {
+    /// Writer on the global session, `key` may be an `s3://bucket/key` URI. The object must
+    /// exist and be at least `offset` bytes long.
+    pub fn new(key: &str, offset: i64, len: u64) -> Result<Self> {
+        let (session, key) = session_for(key)?;
+        Ok(Self::from_session(&session, key, offset, len))
+    }
+
+    pub fn from_session(session: &Session, key: &str, offset: i64, len: u64) -> Self {
+        RegionWriter {
+            session: session.clone(),
+            key: key.to_string(),
+            offset,
+            len,
+            flushed: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Bytes written so far, flushed or not.
+    pub fn written(&self) -> u64 {
+        self.flushed + self.buf.len() as u64
+    }
+}
+
+impl Write for RegionWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.written() + data.len() as u64 > self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} region of {} bytes at {} is full", self.key, self.len, self.offset),
+            ));
+        }
+
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let part = Part::new(self.offset + self.flushed as i64, self.buf.clone());
+
+        RT.block_on(self.session.modify(&self.key, part))
+            .map_err(io::Error::other)?;
+
+        self.flushed += self.buf.len() as u64;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl Drop for RegionWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!(key = self.key, "failed to flush region writer: {}", e);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Edits through `modify_with_backend` on a `Session`, against a mock server that keeps the
+//! headers of each request, checking the rewrite keeps the original's headers and pins its
+//! copies to the ETag it was planned for.
+
+mod common;
+
+use common::mock_s3::{head, initiated, not_found, ok, zeros, MockS3, Request};
+use edit_s3_file::{modify_with_backend, Part, PartChecksum, S3Config, Session, MIN_PART_SIZE};
+
+const LEN: usize = 2 * MIN_PART_SIZE as usize;
+
+/// Answers of a mock S3 serving one `LEN` byte object of zeros with a content type, user
+/// metadata, storage class and SSE-KMS key, and taking a multipart upload replacing it.
+fn respond(request: &Request) -> Vec<u8> {
+    match request.method.as_str() {
+        "HEAD" => head(
+            "ETag: \"etag\"\r\nContent-Type: text/csv\r\nx-amz-meta-owner: alice\r\n\
+            x-amz-storage-class: STANDARD_IA\r\nx-amz-server-side-encryption: aws:kms\r\n\
+            x-amz-server-side-encryption-aws-kms-key-id: key-id\r\n",
+            LEN,
+        ),
+        "GET" => {
+            let (start, end) = request.range().unwrap();
+            zeros("ETag: \"etag\"\r\n", start, end - start, LEN)
+        }
+        "POST" if request.target.contains("uploads") => initiated(),
+        "PUT" if request.header("x-amz-copy-source").is_some() => ok("", "<CopyPartResult><ETag>\"copied\"</ETag></CopyPartResult>"),
+        "PUT" => ok("ETag: \"uploaded\"\r\nx-amz-checksum-crc32: AAAAAA==\r\n", ""),
+        "POST" => ok("", "<CompleteMultipartUploadResult><ETag>\"new\"</ETag></CompleteMultipartUploadResult>"),
+        _ => not_found(),
+    }
+}
+
+#[test]
+fn session_backend_keeps_headers_and_pins_copies() {
+    let mock = MockS3::start(respond);
+
+    let config = S3Config::builder()
+        .endpoint(&mock.endpoint)
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .checksum_algorithm(PartChecksum::Crc32)
+        .build()
+        .unwrap();
+    let session = Session::new(config).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let stats = rt.block_on(modify_with_backend(&session, "key", vec![Part::new(10, vec![1; 10])], MIN_PART_SIZE)).unwrap();
+    assert_eq!(stats.etag.as_deref(), Some("\"new\""));
+
+    let requests = mock.requests();
+
+    let create = requests.iter().find(|r| r.method == "POST" && r.target.contains("uploads")).unwrap();
+    assert_eq!(create.header("content-type"), Some("text/csv"));
+    assert_eq!(create.header("x-amz-meta-owner"), Some("alice"));
+    assert_eq!(create.header("x-amz-storage-class"), Some("STANDARD_IA"));
+    assert_eq!(create.header("x-amz-server-side-encryption"), Some("aws:kms"));
+    assert_eq!(create.header("x-amz-server-side-encryption-aws-kms-key-id"), Some("key-id"));
+    assert_eq!(create.header("x-amz-checksum-algorithm"), Some("CRC32"));
+
+    let copy = requests.iter().find(|r| r.header("x-amz-copy-source").is_some()).unwrap();
+    assert_eq!(copy.header("x-amz-copy-source"), Some("/bucket/key"));
+    assert_eq!(copy.header("x-amz-copy-source-if-match"), Some("\"etag\""));
+}
@@ -0,0 +1,127 @@
+//! A mock S3 server for the tests that check the requests the crate sends, answering each
+//! with what the test makes of it.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// A request the mock got, header names lower-cased.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Start of the `Range` header's `bytes=start-end`, and its end, exclusive.
+    pub fn range(&self) -> Option<(usize, usize)> {
+        let (start, end) = self.header("range")?.trim_start_matches("bytes=").split_once('-')?;
+        Some((start.parse().ok()?, end.parse::<usize>().ok()? + 1))
+    }
+}
+
+/// Mock S3 on a local port, keeping every request it gets and answering it with the raw
+/// HTTP/1.1 response `respond` makes of it. Each connection is served on its own thread.
+pub struct MockS3 {
+    pub endpoint: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockS3 {
+    pub fn start(respond: impl Fn(&Request) -> Vec<u8> + Send + Sync + 'static) -> MockS3 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let respond = Arc::new(respond);
+        let server = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (respond, requests) = (respond.clone(), server.clone());
+                std::thread::spawn(move || serve(stream.unwrap(), &*respond, &requests));
+            }
+        });
+        MockS3 { endpoint, requests }
+    }
+
+    /// The requests so far, in the order they came in.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(stream: TcpStream, respond: &dyn Fn(&Request) -> Vec<u8>, requests: &Mutex<Vec<Request>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+
+        let mut headers = Vec::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+                headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+
+        let mut parts = request_line.split(' ');
+        let (method, target) = (parts.next().unwrap().to_string(), parts.next().unwrap().to_string());
+        let request = Request { method, target, headers, body };
+
+        let response = respond(&request);
+        requests.lock().unwrap().push(request);
+        stream.write_all(&response).unwrap();
+    }
+}
+
+/// `200 OK` with `headers`, each ending in `\r\n`, and `body`.
+pub fn ok(headers: &str, body: &str) -> Vec<u8> {
+    format!("HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\n\r\n{}", headers, body.len(), body).into_bytes()
+}
+
+/// `200 OK` to a `HeadObject` of a `len` byte object, with `headers`.
+pub fn head(headers: &str, len: usize) -> Vec<u8> {
+    format!("HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\n\r\n", headers, len).into_bytes()
+}
+
+/// `206 Partial Content` with `len` zeros, the range `start..start + len` of a `total` byte
+/// object, with `headers`.
+pub fn zeros(headers: &str, start: usize, len: usize, total: usize) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 206 Partial Content\r\n{}Content-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n\r\n",
+        headers, start, start + len - 1, total, len,
+    ).into_bytes();
+    response.resize(response.len() + len, 0);
+    response
+}
+
+pub fn not_found() -> Vec<u8> {
+    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec()
+}
+
+/// `InitiateMultipartUploadResult` of an upload of `key` with the id `upload`.
+pub fn initiated() -> Vec<u8> {
+    ok("", "<InitiateMultipartUploadResult><Bucket>bucket</Bucket><Key>key</Key>\
+        <UploadId>upload</UploadId></InitiateMultipartUploadResult>")
+}
@@ -0,0 +1,124 @@
+//! Shared helpers for the end-to-end tests, see `tests/s3.rs` for how to run them.
+
+#![allow(dead_code)]
+
+pub mod mock_s3;
+
+use std::sync::{LazyLock, Once};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aws_sdk_s3::config::{Credentials, Region};
+use aws_sdk_s3::Client;
+use edit_s3_file::{S3Config, S3ConfigBuilder};
+use tokio::runtime::Runtime;
+
+pub const MIB: usize = 1024 * 1024;
+
+pub fn setup() -> bool {
+    static INIT: Once = Once::new();
+
+    let Ok(endpoint) = std::env::var("EDIT_S3_IT_ENDPOINT") else {
+        eprintln!("EDIT_S3_IT_ENDPOINT not set, skipping");
+        return false;
+    };
+
+    INIT.call_once(|| {
+        let env = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+
+        let config = serde_json::json!({
+            "endpoint": endpoint,
+            "bucket": env("EDIT_S3_IT_BUCKET", "edit-s3-file-it"),
+            "region": "us-east-1",
+            "access_key": env("EDIT_S3_IT_ACCESS_KEY", "minioadmin"),
+            "secret_key": env("EDIT_S3_IT_SECRET_KEY", "minioadmin"),
+            "force_path_style": true,
+        });
+
+        let path = std::env::temp_dir().join(format!("edit-s3-file-it-{}.json", std::process::id()));
+        std::fs::write(&path, config.to_string()).unwrap();
+        std::env::set_var(edit_s3_file::PATH_ENV, path);
+    });
+    true
+}
+
+pub fn unique_key(name: &str) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    format!("it/{}-{}", name, nanos)
+}
+
+/// Deterministic, non-repeating-per-part content so misplaced ranges are detected.
+pub fn pattern(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u32).wrapping_mul(2654435761).to_le_bytes()[3] ^ seed).collect()
+}
+
+/// Runtime for the async parts of the tests. The crate's global client must only be used
+/// from one runtime, since pooled connections are driven by the runtime that opened them,
+/// so verification reads go through their own client.
+pub static TEST_RT: LazyLock<Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap()
+});
+
+static VERIFY_CLIENT: LazyLock<(String, Client)> = LazyLock::new(new_client);
+
+/// A fresh client for the test endpoint and its bucket, independent of the crate's global one.
+pub fn new_client() -> (String, Client) {
+    let env = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+
+    let config = aws_sdk_s3::Config::builder()
+        .behavior_version_latest()
+        .endpoint_url(env("EDIT_S3_IT_ENDPOINT", ""))
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new(
+            env("EDIT_S3_IT_ACCESS_KEY", "minioadmin"),
+            env("EDIT_S3_IT_SECRET_KEY", "minioadmin"),
+            None,
+            None,
+            "Static",
+        ))
+        .force_path_style(true)
+        .build();
+
+    (env("EDIT_S3_IT_BUCKET", "edit-s3-file-it"), Client::from_conf(config))
+}
+
+/// Config for the test endpoint and bucket, to build sessions with non-default settings.
+pub fn config_builder() -> S3ConfigBuilder {
+    let env = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+
+    S3Config::builder()
+        .endpoint(env("EDIT_S3_IT_ENDPOINT", ""))
+        .bucket(env("EDIT_S3_IT_BUCKET", "edit-s3-file-it"))
+        .region("us-east-1")
+        .credentials(env("EDIT_S3_IT_ACCESS_KEY", "minioadmin"), env("EDIT_S3_IT_SECRET_KEY", "minioadmin"))
+        .force_path_style(true)
+}
+
+/// Keys under `prefix`, read with the verification client.
+pub fn list_keys(prefix: &str) -> Vec<String> {
+    let (bucket, client) = &*VERIFY_CLIENT;
+
+    TEST_RT.block_on(async {
+        let out = client.list_objects_v2().bucket(bucket).prefix(prefix).send().await.unwrap();
+        out.contents().iter().filter_map(|o| o.key().map(String::from)).collect()
+    })
+}
+
+pub fn read_all(key: &str, len: usize) -> Vec<u8> {
+    let (bucket, client) = &*VERIFY_CLIENT;
+
+    TEST_RT.block_on(async {
+        let out = client.get_object().bucket(bucket).key(key).send().await.unwrap();
+        let data = out.body.collect().await.unwrap().to_vec();
+        assert_eq!(data.len(), len);
+        data
+    })
+}
+
+/// Overwrite `key` with the verification client, as a writer other than the crate would.
+pub fn put_direct(key: &str, data: &[u8]) {
+    let (bucket, client) = &*VERIFY_CLIENT;
+
+    TEST_RT.block_on(async {
+        client.put_object().bucket(bucket).key(key).body(data.to_vec().into()).send().await.unwrap();
+    });
+}
@@ -0,0 +1,65 @@
+//! Completion of multipart uploads against a mock server answering `CompleteMultipartUpload`
+//! with 200 and an error body, as S3 does for some internal failures.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::mock_s3::{head, initiated, not_found, ok, MockS3};
+use edit_s3_file::{compute_multipart_etag, S3Config, Session};
+use md5::{Digest, Md5};
+
+const ERROR_BODY: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+    <Error><Code>InternalError</Code><Message>We encountered an internal error. Please try again.</Message></Error>";
+
+/// Mock S3 serving one multipart upload. Whether the failed completion actually happened is
+/// up to `completed`, which decides what `head_object` reports.
+fn start(completed: bool) -> MockS3 {
+    let parts = Arc::new(Mutex::new(Vec::<Vec<u8>>::new()));
+
+    MockS3::start(move |request| match request.method.as_str() {
+        "POST" if request.target.contains("uploads") => initiated(),
+        "PUT" if request.target.contains("partNumber") => {
+            let etag = format!("{:x}", Md5::digest(&request.body));
+            parts.lock().unwrap().push(request.body.clone());
+            ok(&format!("ETag: \"{}\"\r\n", etag), "")
+        }
+        "POST" => ok("", ERROR_BODY),
+        "HEAD" if completed => {
+            let parts = parts.lock().unwrap();
+            let etag = compute_multipart_etag(&parts.iter().map(Vec::as_slice).collect::<Vec<_>>());
+            head(&format!("ETag: \"{}\"\r\n", etag), parts.iter().map(Vec::len).sum())
+        }
+        _ => not_found(),
+    })
+}
+
+fn upload(endpoint: &str) -> anyhow::Result<()> {
+    let config = S3Config::builder()
+        .endpoint(endpoint)
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .write_max_retries(0)
+        .build()?;
+    let session = Session::new(config)?;
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    rt.block_on(async {
+        let mut upload = session.begin_multipart("key").await?;
+        upload.upload_bytes(b"hello".to_vec()).await?;
+        upload.complete().await
+    })
+}
+
+#[test]
+fn complete_error_with_parts_in_place_succeeds() {
+    let mock = start(true);
+    upload(&mock.endpoint).unwrap();
+}
+
+#[test]
+fn complete_error_without_object_fails() {
+    let mock = start(false);
+    assert!(upload(&mock.endpoint).is_err());
+}
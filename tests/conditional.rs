@@ -0,0 +1,61 @@
+//! `modify_if_checksum` against a mock server, checking the edit is pinned to the ETag of the
+//! bytes it hashed.
+
+mod common;
+
+use common::mock_s3::{head, not_found, ok, zeros, MockS3, Request};
+use edit_s3_file::{EditError, Part, S3Config, Session};
+use sha2::{Digest, Sha256};
+
+const LEN: usize = 10;
+
+/// Mock S3 serving a `LEN` byte object of zeros whose reads have the ETag `"read"`, and whose
+/// `HeadObject` reports `head_etag`, as if it was overwritten in between if they differ.
+fn start(head_etag: &'static str) -> MockS3 {
+    MockS3::start(move |request: &Request| match request.method.as_str() {
+        "HEAD" => head(&format!("ETag: {}\r\n", head_etag), LEN),
+        "GET" => match request.range() {
+            Some((start, end)) => zeros("ETag: \"read\"\r\n", start, end - start, LEN),
+            None => ok("ETag: \"read\"\r\n", &"\0".repeat(LEN)),
+        },
+        "PUT" => ok("ETag: \"new\"\r\n", ""),
+        _ => not_found(),
+    })
+}
+
+fn modify_if_checksum(endpoint: &str) -> anyhow::Result<()> {
+    let config = S3Config::builder()
+        .endpoint(endpoint)
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .force_single_put(true)
+        .build()?;
+    let session = Session::new(config)?;
+
+    let expected = Sha256::digest([0; 4]).into();
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    rt.block_on(session.modify_if_checksum("key", Part::new(2, vec![1; 4]), expected))?;
+    Ok(())
+}
+
+#[test]
+fn checksum_edit_is_pinned_to_read() {
+    let mock = start("\"read\"");
+
+    modify_if_checksum(&mock.endpoint).unwrap();
+
+    let requests = mock.requests();
+    let put = requests.iter().find(|r| r.method == "PUT").unwrap();
+    assert_eq!(put.header("if-match"), Some("\"read\""));
+    assert_eq!(put.body, [0, 0, 1, 1, 1, 1, 0, 0, 0, 0]);
+}
+
+#[test]
+fn checksum_edit_of_changed_object_conflicts() {
+    let mock = start("\"changed\"");
+
+    let err = modify_if_checksum(&mock.endpoint).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::Conflict { .. })), "{:#}", err);
+    assert!(mock.requests().iter().all(|r| r.method != "PUT"));
+}
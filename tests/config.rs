@@ -0,0 +1,326 @@
+use edit_s3_file::{BucketType, CompatMode, PartChecksum, PartSize, S3Config, SseMode, TempKeyStrategy};
+
+fn builder() -> edit_s3_file::S3ConfigBuilder {
+    S3Config::builder()
+        .endpoint("http://127.0.0.1:9000")
+        .bucket("bucket")
+        .region("us-east-1")
+}
+
+#[test]
+fn builder_applies_defaults() {
+    let config = builder().build().unwrap();
+    assert_eq!(config.part_size, PartSize::default());
+    assert!(config.access_key.is_none());
+    assert!(!config.force_path_style);
+    assert!(config.max_memory_bytes.is_none());
+    assert!(config.max_bytes_per_sec.is_none());
+}
+
+#[test]
+fn builder_sets_fields() {
+    let config = builder()
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .part_size(PartSize::Auto)
+        .max_memory_bytes(64)
+        .max_bytes_per_sec(1024)
+        .read_buffer_size(4096)
+        .storage_class("STANDARD_IA")
+        .propagate_object_lock(true)
+        .requester_pays(true)
+        .build()
+        .unwrap();
+    assert_eq!(config.access_key.as_deref(), Some("ak"));
+    assert_eq!(config.secret_key.as_deref(), Some("sk"));
+    assert!(config.force_path_style);
+    assert_eq!(config.part_size, PartSize::Auto);
+    assert_eq!(config.max_memory_bytes, Some(64));
+    assert_eq!(config.max_bytes_per_sec, Some(1024));
+    assert_eq!(config.read_buffer_size, Some(4096));
+    assert_eq!(config.storage_class.as_deref(), Some("STANDARD_IA"));
+    assert!(config.propagate_object_lock);
+    assert!(config.requester_pays);
+}
+
+#[test]
+fn builder_requires_endpoint_bucket() {
+    assert!(S3Config::builder().bucket("b").region("r").build().is_err());
+    assert!(S3Config::builder().endpoint("http://h").region("r").build().is_err());
+    assert!(S3Config::builder().endpoint("http://h").bucket("b").build().unwrap().region.is_none());
+}
+
+#[test]
+fn builder_adds_missing_scheme() {
+    let config = builder().endpoint("s3.example.com").build().unwrap();
+    assert_eq!(config.endpoint, "https://s3.example.com");
+
+    let config = builder().endpoint("127.0.0.1:9000").insecure(true).build().unwrap();
+    assert_eq!(config.endpoint, "http://127.0.0.1:9000");
+
+    let config = builder().endpoint("https://s3.example.com").insecure(true).build().unwrap();
+    assert_eq!(config.endpoint, "https://s3.example.com");
+}
+
+#[test]
+fn builder_rejects_malformed_endpoint() {
+    for endpoint in ["", "ftp://host", "http://", "https:///path", "http://ho st", "/path"] {
+        assert!(builder().endpoint(endpoint).build().is_err(), "{}", endpoint);
+    }
+    assert!(builder().endpoint("https://s3.eu-west-1.amazonaws.com").build().is_ok());
+}
+
+#[test]
+fn failover_endpoints_normalized() {
+    let config = builder()
+        .insecure(true)
+        .failover_endpoints(vec!["gw2:9000".to_string(), "https://gw3:9000".to_string()])
+        .build()
+        .unwrap();
+    assert_eq!(config.failover_endpoints, ["http://gw2:9000", "https://gw3:9000"]);
+    assert!(edit_s3_file::Session::new(config).is_ok());
+
+    assert!(builder().failover_endpoints(vec!["ftp://gw2".to_string()]).build().is_err());
+}
+
+#[test]
+fn part_size_outside_s3_limits_rejected() {
+    let mib = 1024 * 1024;
+    for size in [0, 5 * mib - 1, 5 * 1024 * mib + 1] {
+        assert!(builder().part_size(PartSize::Fixed(size)).build().is_err(), "{}", size);
+    }
+    assert!(builder().part_size(PartSize::Fixed(5 * mib)).build().is_ok());
+
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "part_size": 1048576}"#;
+    let config = serde_json::from_str::<S3Config>(json).unwrap();
+    assert!(edit_s3_file::Session::new(config).is_err());
+}
+
+#[test]
+fn sse_customer_key_must_be_256_bit_base64() {
+    let key = format!("{}=", "A".repeat(43));
+    assert_eq!(builder().sse_customer_key(&key).build().unwrap().sse_customer_key, Some(key));
+
+    for key in ["not base64!", "AAAA", ""] {
+        assert!(builder().sse_customer_key(key).build().is_err(), "{}", key);
+    }
+}
+
+#[test]
+fn sse_from_json() {
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "sse": "s3"}"#;
+    assert_eq!(serde_json::from_str::<S3Config>(json).unwrap().sse, Some(SseMode::S3));
+
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "sse": {"kms": {"key_id": "alias/edits"}}}"#;
+    let sse = SseMode::Kms { key_id: Some("alias/edits".to_string()) };
+    assert_eq!(serde_json::from_str::<S3Config>(json).unwrap().sse, Some(sse));
+
+    let key = format!("{}=", "A".repeat(43));
+    assert!(builder().sse_customer_key(&key).sse(SseMode::S3).build().is_err());
+}
+
+#[test]
+fn profile_from_json_and_builder() {
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "profile": "edits"}"#;
+    let config = serde_json::from_str::<S3Config>(json).unwrap();
+    assert_eq!(config.profile.as_deref(), Some("edits"));
+    assert!(config.access_key.is_none());
+
+    assert_eq!(builder().build().unwrap().profile, None);
+    assert_eq!(builder().profile("edits").build().unwrap().profile.as_deref(), Some("edits"));
+}
+
+#[test]
+fn tls_and_timeouts_from_builder() {
+    let config = builder().read_timeout_secs(30).ca_cert_path("/nonexistent/ca.pem").build().unwrap();
+    assert_eq!(config.read_timeout_secs, Some(30));
+    assert_eq!(config.ca_cert_path.as_deref(), Some("/nonexistent/ca.pem"));
+
+    // a missing CA file fails the session, not the first request
+    assert!(edit_s3_file::Session::new(config).is_err());
+}
+
+#[test]
+fn compat_mode_turns_on_quirks() {
+    let config = builder().build().unwrap();
+    assert_eq!(config.compat, CompatMode::Aws);
+    assert!(!config.force_path_style && !config.force_single_put);
+
+    let config = builder().compat(CompatMode::MinIO).build().unwrap();
+    assert!(config.force_path_style && !config.force_single_put);
+
+    let config = builder().compat(CompatMode::Gcs).build().unwrap();
+    assert!(config.force_path_style && config.force_single_put);
+
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "compat": "wasabi"}"#;
+    assert_eq!(serde_json::from_str::<S3Config>(json).unwrap().compat, CompatMode::Wasabi);
+}
+
+#[test]
+fn temp_key_strategy_from_config() {
+    assert!(matches!(builder().build().unwrap().temp_key_strategy, TempKeyStrategy::Suffix(s) if s == ".tmp-"));
+
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "temp_key_strategy": {"prefix": "tmp/"}}"#;
+    let config = serde_json::from_str::<S3Config>(json).unwrap();
+    assert!(matches!(config.temp_key_strategy, TempKeyStrategy::Prefix(p) if p == "tmp/"));
+}
+
+#[test]
+fn builder_accepts_access_point_arn() {
+    let arn = "arn:aws:s3:us-west-2:123456789012:accesspoint/my-ap";
+    assert!(builder().bucket(arn).build().is_ok());
+    assert!(builder().bucket(arn).force_path_style(true).build().is_err());
+
+    let mrap = "arn:aws:s3::123456789012:accesspoint/mfzwi23gnjvgw.mrap";
+    assert!(builder().bucket(mrap).build().is_ok());
+}
+
+#[test]
+fn builder_rejects_malformed_bucket() {
+    for bucket in [
+        "",
+        "a/b",
+        "arn:aws:s3:us-west-2:123456789012:bucket/my-ap",
+        "arn:aws:s3:us-west-2::accesspoint/my-ap",
+        "arn:aws:s3::123456789012:accesspoint/my-ap",
+        "arn:aws:s3:us-west-2:123456789012:accesspoint/",
+        "arn:aws:iam::123456789012:user/me",
+    ] {
+        assert!(builder().bucket(bucket).build().is_err(), "{}", bucket);
+    }
+}
+
+#[test]
+fn session_defaults_region() {
+    let config = S3Config::builder().endpoint("http://127.0.0.1:9000").bucket("bucket").build().unwrap();
+    let session = edit_s3_file::Session::new(config).unwrap();
+    assert_eq!(session.client().config().region().map(|r| r.as_ref()), Some(edit_s3_file::DEFAULT_REGION));
+}
+
+#[test]
+fn pad_to_part_on_by_default() {
+    assert!(builder().build().unwrap().pad_to_part);
+    assert!(!builder().pad_to_part(false).build().unwrap().pad_to_part);
+
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket"}"#;
+    assert!(serde_json::from_str::<S3Config>(json).unwrap().pad_to_part);
+}
+
+#[test]
+fn preserve_part_layout_off_by_default() {
+    assert!(!builder().build().unwrap().preserve_part_layout);
+    assert!(builder().preserve_part_layout(true).build().unwrap().preserve_part_layout);
+}
+
+#[test]
+fn log_plan_off_by_default() {
+    assert!(!builder().build().unwrap().log_plan);
+    assert!(builder().log_plan(true).build().unwrap().log_plan);
+
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "log_plan": true}"#;
+    assert!(serde_json::from_str::<S3Config>(json).unwrap().log_plan);
+}
+
+#[test]
+fn stream_checksums_off_by_default() {
+    assert!(!builder().build().unwrap().stream_checksums);
+    assert!(builder().stream_checksums(true).build().unwrap().stream_checksums);
+}
+
+#[test]
+fn stat_cache_off_by_default() {
+    let config = builder().build().unwrap();
+    assert_eq!(config.stat_cache_ttl_secs, None);
+    assert_eq!(config.stat_cache_size, None);
+
+    let config = builder().stat_cache_ttl_secs(30).stat_cache_size(10).build().unwrap();
+    assert_eq!(config.stat_cache_ttl_secs, Some(30));
+    assert_eq!(config.stat_cache_size, Some(10));
+}
+
+#[test]
+fn adaptive_part_size_off_by_default() {
+    assert!(!builder().build().unwrap().adaptive_part_size);
+    assert!(builder().adaptive_part_size(true).build().unwrap().adaptive_part_size);
+}
+
+#[test]
+fn coalesce_gap_unset_by_default() {
+    assert_eq!(builder().build().unwrap().coalesce_gap_bytes, None);
+    assert_eq!(builder().coalesce_gap_bytes(16 * 1024 * 1024).build().unwrap().coalesce_gap_bytes, Some(16 * 1024 * 1024));
+}
+
+#[test]
+fn timeouts_unset_by_default() {
+    let config = builder().build().unwrap();
+    assert_eq!((config.connect_timeout_secs, config.request_timeout_secs), (None, None));
+
+    let config = builder().connect_timeout_secs(5).request_timeout_secs(600).build().unwrap();
+    assert_eq!((config.connect_timeout_secs, config.request_timeout_secs), (Some(5), Some(600)));
+    assert!(edit_s3_file::Session::new(config).is_ok());
+}
+
+#[test]
+fn retry_backoff_unset_by_default() {
+    let config = builder().build().unwrap();
+    assert_eq!((config.retry_initial_backoff_ms, config.retry_max_backoff_ms), (None, None));
+
+    let config = builder().retry_initial_backoff_ms(50).retry_max_backoff_ms(2000).build().unwrap();
+    assert_eq!((config.retry_initial_backoff_ms, config.retry_max_backoff_ms), (Some(50), Some(2000)));
+    assert!(edit_s3_file::Session::new(config).is_ok());
+}
+
+#[test]
+fn verify_edits_off_by_default() {
+    assert!(!builder().build().unwrap().verify_edits);
+    assert!(builder().verify_edits(true).build().unwrap().verify_edits);
+}
+
+#[test]
+fn checksum_algorithm_from_json() {
+    assert_eq!(builder().build().unwrap().checksum_algorithm, None);
+
+    let json = r#"{"endpoint": "http://127.0.0.1:9000", "bucket": "bucket", "checksum_algorithm": "crc32c"}"#;
+    assert_eq!(serde_json::from_str::<S3Config>(json).unwrap().checksum_algorithm, Some(PartChecksum::Crc32c));
+}
+
+#[test]
+fn redacted_config_hides_secrets() {
+    let key = format!("{}=", "A".repeat(43));
+    let config = builder().credentials("access", "secret").sse_customer_key(&key).build().unwrap();
+    let session = edit_s3_file::Session::new(config).unwrap();
+
+    let redacted = session.redacted_config();
+    assert_eq!(redacted.access_key.as_deref(), Some("access"));
+    assert_eq!(redacted.secret_key.as_deref(), Some(edit_s3_file::REDACTED));
+    assert_eq!(redacted.sse_customer_key.as_deref(), Some(edit_s3_file::REDACTED));
+    assert_eq!(session.endpoint(), "http://127.0.0.1:9000");
+
+    // nothing here builds the global session
+    assert!(edit_s3_file::current_config().is_none());
+    assert!(edit_s3_file::current_bucket().is_none());
+}
+
+#[test]
+fn session_with_endpoint() {
+    let session = edit_s3_file::Session::new(builder().build().unwrap()).unwrap();
+
+    let other = session.with_endpoint("127.0.0.2:9000").unwrap();
+    assert_eq!(other.bucket(), "bucket");
+    assert_eq!(other.client().config().region().map(|r| r.as_ref()), Some("us-east-1"));
+    assert!(session.with_endpoint("ftp://127.0.0.2").is_err());
+}
+
+#[test]
+fn directory_bucket_rejects_unsupported() {
+    let express = || builder().bucket("data--usw2-az1--x-s3");
+    assert!(express().build().is_ok());
+    assert!(express().force_path_style(true).build().is_err());
+    assert!(express().preserve_acl(true).build().is_err());
+    assert!(express().compat(CompatMode::MinIO).build().is_err());
+    assert!(express().force_path_style(true).bucket_type(BucketType::General).build().is_ok());
+    assert!(builder().bucket_type(BucketType::Directory).preserve_tags(true).build().is_err());
+
+    let json = r#"{"endpoint": "http://h", "bucket": "b", "bucket_type": "directory"}"#;
+    assert_eq!(serde_json::from_str::<S3Config>(json).unwrap().bucket_type, Some(BucketType::Directory));
+}
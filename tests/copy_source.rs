@@ -0,0 +1,67 @@
+//! The `x-amz-copy-source` of part copies, recorded by a mock server, for bucket names that
+//! are access point aliases and keys that need encoding.
+
+mod common;
+
+use common::mock_s3::{initiated, not_found, ok, MockS3, Request};
+use edit_s3_file::{S3Config, Session};
+
+// 32 hex digits, like the MD5 ETags of parts
+const ETAG: &str = "&quot;0123456789abcdef0123456789abcdef&quot;";
+
+/// Answers of a mock S3 accepting one multipart upload of part copies.
+fn respond(request: &Request) -> Vec<u8> {
+    match request.method.as_str() {
+        "POST" if request.target.contains("uploads") => initiated(),
+        "PUT" if request.header("x-amz-copy-source").is_some() => {
+            ok("", &format!("<CopyPartResult><ETag>{}</ETag></CopyPartResult>", ETAG))
+        }
+        "POST" => ok("", &format!("<CompleteMultipartUploadResult><ETag>{}</ETag></CompleteMultipartUploadResult>", ETAG)),
+        _ => not_found(),
+    }
+}
+
+fn copy_from(endpoint: &str, bucket: &str, src_key: &str) {
+    let config = S3Config::builder()
+        .endpoint(endpoint)
+        .bucket(bucket)
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .build()
+        .unwrap();
+    let session = Session::new(config).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let mut upload = session.begin_multipart("key").await.unwrap();
+        upload.copy_range_from(src_key, 0, 10).await.unwrap();
+        upload.complete().await.unwrap();
+    });
+}
+
+/// The copy sources of the part copies `mock` got.
+fn copy_sources(mock: &MockS3) -> Vec<String> {
+    mock.requests().iter().filter_map(|r| r.header("x-amz-copy-source").map(String::from)).collect()
+}
+
+#[test]
+fn access_point_alias_copy_source() {
+    let mock = MockS3::start(respond);
+
+    copy_from(&mock.endpoint, "my-ap-hrzrlukc5m36ft7okagglf3gmwluquse1b-s3alias", "dir/src");
+    copy_from(&mock.endpoint, "my-olap-rlt4i7tgxf9xq9cqv8mcaa4dtn2u0use1a--ol-s3", "src");
+
+    assert_eq!(copy_sources(&mock), vec![
+        "/my-ap-hrzrlukc5m36ft7okagglf3gmwluquse1b-s3alias/dir/src".to_string(),
+        "/my-olap-rlt4i7tgxf9xq9cqv8mcaa4dtn2u0use1a--ol-s3/src".to_string(),
+    ]);
+}
+
+#[test]
+fn copy_source_key_encoded() {
+    let mock = MockS3::start(respond);
+
+    copy_from(&mock.endpoint, "bucket", "dir/a b+c?d%é");
+
+    assert_eq!(copy_sources(&mock), vec!["/bucket/dir/a%20b%2Bc%3Fd%25%C3%A9".to_string()]);
+}
@@ -0,0 +1,28 @@
+use edit_s3_file::EditError;
+
+#[test]
+fn s3_error_shows_request_ids() {
+    let err = EditError::S3 {
+        code: Some("InternalError".to_string()),
+        request_id: Some("4442587FB7D0A2F9".to_string()),
+        extended_request_id: Some("vlR7PnpV2Ce81l0PRw6jlUpck7Jo5ZsQjryTjKlc5aLWGVHPZLj5NeC6qMa0emYBDXOo6QBU0Wo=".to_string()),
+        source: None,
+    };
+    let msg = err.to_string();
+    assert!(msg.contains("InternalError"), "{}", msg);
+    assert!(msg.contains("x-amz-request-id: 4442587FB7D0A2F9"), "{}", msg);
+    assert!(msg.contains("x-amz-id-2: vlR7PnpV2Ce81l0PRw6jlUpck7Jo5ZsQjryTjKlc5aLWGVHPZLj5NeC6qMa0emYBDXOo6QBU0Wo="), "{}", msg);
+
+    let err = EditError::S3 { code: None, request_id: None, extended_request_id: None, source: None };
+    assert_eq!(err.to_string(), "S3 request failed, x-amz-request-id: -, x-amz-id-2: -");
+}
+
+#[test]
+fn retryable_errors() {
+    assert!(EditError::Throttled.is_retryable());
+    assert!(EditError::Conflict { key: "k".to_string() }.is_retryable());
+
+    let err = EditError::RangeOutOfBounds { key: "k".to_string(), start: 10, end: 20, len: 15 };
+    assert!(!err.is_retryable());
+    assert_eq!(err.to_string(), "k range 10-20 is outside the 15 byte object");
+}
@@ -0,0 +1,13 @@
+use edit_s3_file::compute_multipart_etag;
+
+#[test]
+fn known_etags() {
+    assert_eq!(compute_multipart_etag(&[b"hello ", b"world"]), "e09e4fd6265b36115fe3db32df945d84-2");
+    assert_eq!(compute_multipart_etag(&[b""]), "59adb24ef3cdbe0297f05b395827453f-1");
+}
+
+#[test]
+fn boundaries_matter() {
+    assert_ne!(compute_multipart_etag(&[b"hello ", b"world"]), compute_multipart_etag(&[b"hello world"]));
+    assert_ne!(compute_multipart_etag(&[b"hello", b" world"]), compute_multipart_etag(&[b"hello ", b"world"]));
+}
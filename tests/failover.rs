@@ -0,0 +1,47 @@
+//! `failover_endpoints` with a configured endpoint nothing listens on, checking requests go
+//! on to a mock server given as its failover.
+
+mod common;
+
+use common::mock_s3::{head, not_found, ok, MockS3, Request};
+use edit_s3_file::{Part, S3Config, Session};
+
+const BODY: &[u8] = b"hello world";
+
+/// Answers of a mock S3 serving one object and taking `PutObject`s of it.
+fn respond(request: &Request) -> Vec<u8> {
+    match request.method.as_str() {
+        "HEAD" => head("ETag: \"etag\"\r\n", BODY.len()),
+        "GET" => ok("ETag: \"etag\"\r\n", std::str::from_utf8(BODY).unwrap()),
+        "PUT" => ok("ETag: \"new\"\r\n", ""),
+        _ => not_found(),
+    }
+}
+
+#[test]
+fn unreachable_endpoint_fails_over_to_next() {
+    let mock = MockS3::start(respond);
+
+    // nothing listens on port 1, so every request goes on to the mock
+    let config = S3Config::builder()
+        .endpoint("http://127.0.0.1:1")
+        .failover_endpoints(vec![mock.endpoint.clone()])
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .force_single_put(true)
+        .build()
+        .unwrap();
+    let session = Session::new(config).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        assert_eq!(session.stat("key").await.unwrap().unwrap().len, BODY.len() as i64);
+        session.modify("key", Part::new(0, b"HELLO".to_vec())).await.unwrap();
+    });
+
+    let requests = mock.requests();
+    assert!(requests.iter().any(|r| r.method == "HEAD"));
+    let puts = requests.iter().filter(|r| r.method == "PUT").map(|r| r.body.clone()).collect::<Vec<_>>();
+    assert_eq!(puts, [b"HELLO world".to_vec()]);
+}
@@ -0,0 +1,19 @@
+#![cfg(feature = "ffi")]
+
+use std::ffi::CStr;
+
+use edit_s3_file::ffi::{edit_s3_last_error, edit_s3_modify, EDIT_S3_INVALID_ARGUMENT};
+
+#[test]
+fn invalid_arguments_set_last_error() {
+    let key = c"key";
+
+    let code = unsafe { edit_s3_modify(std::ptr::null(), 0, std::ptr::null(), 0) };
+    assert_eq!(code, EDIT_S3_INVALID_ARGUMENT);
+
+    let message = unsafe { CStr::from_ptr(edit_s3_last_error()) };
+    assert!(message.to_str().unwrap().contains("null"), "{:?}", message);
+
+    let code = unsafe { edit_s3_modify(key.as_ptr(), 0, std::ptr::null(), 10) };
+    assert_eq!(code, EDIT_S3_INVALID_ARGUMENT);
+}
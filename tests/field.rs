@@ -0,0 +1,10 @@
+use edit_s3_file::Field;
+
+#[test]
+fn byte_order() {
+    assert_eq!(0x0102_0304u32.to_le_vec(), vec![4, 3, 2, 1]);
+    assert_eq!(0x0102_0304u32.to_be_vec(), vec![1, 2, 3, 4]);
+    assert_eq!((-2i16).to_be_vec(), vec![0xff, 0xfe]);
+    assert_eq!(1.0f64.to_le_vec(), 1.0f64.to_le_bytes());
+    assert_eq!(7u8.to_le_vec(), vec![7]);
+}
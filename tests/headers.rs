@@ -0,0 +1,192 @@
+//! Rewrites and replaces against a mock server, checking the object written keeps the
+//! headers of the one it replaces.
+
+mod common;
+
+use common::mock_s3::{head, initiated, not_found, ok, zeros, MockS3, Request};
+use aws_sdk_s3::primitives::ByteStream;
+use edit_s3_file::{Part, PartSource, S3Config, S3ConfigBuilder, Session};
+
+const LEN: usize = 10;
+const MIB: usize = 1024 * 1024;
+
+const HEADERS: &str = "ETag: \"etag\"\r\nContent-Type: text/plain\r\nx-amz-storage-class: STANDARD_IA\r\nx-amz-meta-owner: me\r\n";
+
+/// Answers of a mock S3 serving every key as a `len` byte object of zeros with `HEADERS`,
+/// and taking multipart uploads and copies of it.
+fn object(len: usize) -> impl Fn(&Request) -> Vec<u8> + Send + Sync + 'static {
+    move |request| {
+        let copy = request.header("x-amz-copy-source").is_some();
+
+        match request.method.as_str() {
+            "HEAD" => head(HEADERS, len),
+            "GET" => match request.range() {
+                Some((start, end)) => zeros("ETag: \"etag\"\r\n", start, end - start, len),
+                None => ok("ETag: \"etag\"\r\n", &"\0".repeat(len)),
+            },
+            "POST" if request.target.contains("?uploads") => initiated(),
+            "POST" => ok("", "<CompleteMultipartUploadResult><ETag>\"new\"</ETag></CompleteMultipartUploadResult>"),
+            "PUT" if copy && request.target.contains("partNumber") => ok("", "<CopyPartResult><ETag>\"part\"</ETag></CopyPartResult>"),
+            "PUT" if copy => ok("", "<CopyObjectResult><ETag>\"copy\"</ETag></CopyObjectResult>"),
+            "PUT" => ok("ETag: \"part\"\r\n", ""),
+            "DELETE" => ok("", ""),
+            _ => not_found(),
+        }
+    }
+}
+
+fn config(endpoint: &str) -> S3ConfigBuilder {
+    S3Config::builder()
+        .endpoint(endpoint)
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+}
+
+/// Whether `request` writes the object `key` of the bucket, rather than a temp or backup one.
+fn writes(request: &Request, key: &str) -> bool {
+    request.target.split('?').next() == Some(&format!("/bucket/{}", key))
+}
+
+#[test]
+fn atomic_edit_keeps_headers() {
+    let mock = MockS3::start(object(LEN));
+    let session = Session::new(config(&mock.endpoint).atomic_edits(true).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(session.modify("key", Part::new(2, vec![1; 4]))).unwrap();
+
+    let requests = mock.requests();
+    let create = requests.iter().find(|r| r.method == "POST" && r.target.contains("?uploads")).unwrap();
+    assert!(!writes(create, "key"));
+    assert_eq!(create.header("content-type"), Some("text/plain"));
+    assert_eq!(create.header("x-amz-storage-class"), Some("STANDARD_IA"));
+    assert_eq!(create.header("x-amz-meta-owner"), Some("me"));
+
+    let copy = requests.iter().find(|r| r.method == "PUT" && writes(r, "key")).unwrap();
+    assert!(copy.header("x-amz-copy-source").is_some());
+    assert_eq!(copy.header("x-amz-metadata-directive"), Some("COPY"));
+    assert_eq!(copy.header("x-amz-storage-class"), Some("STANDARD_IA"));
+}
+
+#[test]
+fn transaction_backup_keeps_headers() {
+    let mock = MockS3::start(object(LEN));
+    let session = Session::new(config(&mock.endpoint).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let mut tx = session.edit_transaction();
+        tx.modify_many("key", vec![Part::new(2, vec![1; 4])]);
+        tx.commit().await
+    }).unwrap();
+
+    let requests = mock.requests();
+    let backup = requests.iter().find(|r| r.method == "PUT" && r.header("x-amz-copy-source").is_some() && !writes(r, "key")).unwrap();
+    assert_eq!(backup.header("x-amz-metadata-directive"), Some("COPY"));
+    assert_eq!(backup.header("x-amz-storage-class"), Some("STANDARD_IA"));
+}
+
+#[test]
+fn replace_preserving_keeps_headers() {
+    let mock = MockS3::start(object(LEN));
+    let session = Session::new(config(&mock.endpoint).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(session.replace("key", ByteStream::from_static(b"hello"), 5)).unwrap();
+    rt.block_on(session.replace_preserving("key", ByteStream::from_static(b"hello"), 5)).unwrap();
+
+    let requests = mock.requests();
+    let puts = requests.iter().filter(|r| r.method == "PUT").collect::<Vec<_>>();
+    assert_eq!(puts[0].header("x-amz-meta-owner"), None);
+    assert_eq!(puts[1].header("content-type"), Some("text/plain"));
+    assert_eq!(puts[1].header("x-amz-storage-class"), Some("STANDARD_IA"));
+    assert_eq!(puts[1].header("x-amz-meta-owner"), Some("me"));
+    assert_eq!(puts[1].body, b"hello");
+}
+
+#[test]
+fn insert_keeps_headers_and_pins_reads() {
+    let mock = MockS3::start(object(LEN));
+    let session = Session::new(config(&mock.endpoint).edit_lock(true).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(session.insert("key", 5, b"ab".to_vec())).unwrap();
+
+    let requests = mock.requests();
+    let lock = requests.iter().position(|r| r.method == "PUT" && writes(r, "key.lock")).unwrap();
+    let create = requests.iter().position(|r| r.method == "POST" && r.target.contains("?uploads")).unwrap();
+    assert!(lock < create);
+    assert_eq!(requests[create].header("content-type"), Some("text/plain"));
+    assert_eq!(requests[create].header("x-amz-storage-class"), Some("STANDARD_IA"));
+    assert_eq!(requests[create].header("x-amz-meta-owner"), Some("me"));
+
+    let reads = requests.iter().filter(|r| r.method == "GET" && writes(r, "key")).collect::<Vec<_>>();
+    assert!(!reads.is_empty());
+    assert!(reads.iter().all(|r| r.header("if-match") == Some("\"etag\"")));
+}
+
+#[test]
+fn truncate_to_empty_keeps_headers() {
+    let mock = MockS3::start(object(LEN));
+    let session = Session::new(config(&mock.endpoint).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(session.truncate("key", 0)).unwrap();
+
+    let requests = mock.requests();
+    let put = requests.iter().find(|r| r.method == "PUT" && writes(r, "key")).unwrap();
+    assert!(put.body.is_empty());
+    assert_eq!(put.header("content-type"), Some("text/plain"));
+    assert_eq!(put.header("x-amz-storage-class"), Some("STANDARD_IA"));
+    assert_eq!(put.header("x-amz-meta-owner"), Some("me"));
+}
+
+/// Checks the rewrite of `key` the mock got keeps its headers, and that every read and copy
+/// of it is pinned to its ETag.
+fn assert_pinned_rewrite(requests: &[Request]) {
+    let create = requests.iter().find(|r| r.method == "POST" && r.target.contains("?uploads")).unwrap();
+    assert_eq!(create.header("content-type"), Some("text/plain"));
+    assert_eq!(create.header("x-amz-storage-class"), Some("STANDARD_IA"));
+    assert_eq!(create.header("x-amz-meta-owner"), Some("me"));
+
+    let copies = requests.iter().filter(|r| r.header("x-amz-copy-source").is_some()).collect::<Vec<_>>();
+    assert!(!copies.is_empty());
+    assert!(copies.iter().all(|r| r.header("x-amz-copy-source-if-match") == Some("\"etag\"")));
+    assert!(requests.iter().filter(|r| r.method == "GET").all(|r| r.header("if-match") == Some("\"etag\"")));
+}
+
+#[test]
+fn splice_keeps_headers_and_pins_copies() {
+    let mock = MockS3::start(object(12 * MIB));
+    let session = Session::new(config(&mock.endpoint).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(session.splice("other", 0, 6 * MIB as i64, "key", MIB as i64)).unwrap();
+
+    let requests = mock.requests();
+    assert_pinned_rewrite(&requests);
+    assert!(requests.iter().any(|r| r.method == "GET" && r.target.starts_with("/bucket/other")));
+}
+
+#[test]
+fn pattern_range_keeps_headers_and_pins_copies() {
+    let mock = MockS3::start(object(12 * MIB));
+    let session = Session::new(config(&mock.endpoint).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(session.pattern_range("key", 11 * MIB as i64, 16, b"ab")).unwrap();
+
+    assert_pinned_rewrite(&mock.requests());
+}
+
+#[test]
+fn modify_from_keeps_headers_and_pins_copies() {
+    let mock = MockS3::start(object(12 * MIB));
+    let session = Session::new(config(&mock.endpoint).build().unwrap()).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(session.modify_from("key", 11 * MIB as i64, PartSource::bytes(vec![1; 16]))).unwrap();
+
+    assert_pinned_rewrite(&mock.requests());
+}
@@ -0,0 +1,122 @@
+//! Edits through `MockBackend`, without a server, for the edge cases of the planner.
+
+use edit_s3_file::{compute_multipart_etag, modify_with_backend, EditError, MockBackend, ObjectStoreBackend, Part, RangeKind, MIN_PART_SIZE};
+
+const PART: i64 = MIN_PART_SIZE;
+
+fn block_on<F: std::future::Future>(f: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(f)
+}
+
+fn object(len: i64) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+/// Apply `parts` to a `len` byte object and check the result against the same edits in memory.
+fn check(len: i64, parts: &[(i64, Vec<u8>)]) -> edit_s3_file::ModifyStats {
+    let backend = MockBackend::new();
+    let mut expected = object(len);
+    backend.put("key", expected.clone());
+
+    for (offset, data) in parts {
+        let end = *offset as usize + data.len();
+        if end > expected.len() {
+            expected.resize(end, 0);
+        }
+        expected[*offset as usize..end].copy_from_slice(data);
+    }
+
+    let parts = parts.iter().map(|(offset, data)| Part::new(*offset, data.clone())).collect();
+    let stats = block_on(modify_with_backend(&backend, "key", parts, PART)).unwrap();
+
+    assert!(backend.get("key").unwrap() == expected);
+    assert_eq!(backend.uploads_in_progress(), 0);
+    stats
+}
+
+#[test]
+fn zero_length_edit() {
+    let stats = check(3 * PART, &[(PART, Vec::new())]);
+    assert!(stats.ranges.iter().all(|(kind, _, _)| *kind == RangeKind::Copied));
+}
+
+#[test]
+fn edits_at_part_boundaries() {
+    check(3 * PART, &[(PART, vec![1; 10])]);
+    check(3 * PART, &[(PART - 10, vec![2; 10])]);
+    check(3 * PART, &[(0, vec![3; PART as usize])]);
+    check(3 * PART, &[(2 * PART, vec![4; PART as usize])]);
+}
+
+#[test]
+fn edits_extending_object() {
+    check(2 * PART, &[(2 * PART, vec![5; 100])]);
+    check(2 * PART, &[(2 * PART - 50, vec![6; 100]), (10, vec![7; 10])]);
+}
+
+#[test]
+fn edit_past_end_rejected() {
+    let backend = MockBackend::new();
+    backend.put("key", object(PART));
+
+    let err = block_on(modify_with_backend(&backend, "key", vec![Part::new(PART + 1, vec![1])], PART)).unwrap_err();
+    assert!(matches!(err.downcast_ref(), Some(EditError::RangeOutOfBounds { .. })), "{:?}", err);
+    assert!(backend.get("key").unwrap() == object(PART));
+    assert_eq!(backend.uploads_in_progress(), 0);
+}
+
+#[test]
+fn completion_etag_is_multipart() {
+    let backend = MockBackend::new();
+
+    block_on(async {
+        let upload_id = backend.create_upload("key").await.unwrap();
+        let a = backend.upload_part("key", &upload_id, 1, vec![1; 10].into()).await.unwrap();
+        let b = backend.upload_part("key", &upload_id, 2, vec![2; 10].into()).await.unwrap();
+
+        // a part that was never added fails the completion
+        assert!(backend.complete("key", &upload_id, vec![(1, a.clone()), (3, b.clone())]).await.is_err());
+
+        let etag = backend.complete("key", &upload_id, vec![(1, a), (2, b)]).await.unwrap().unwrap();
+        assert_eq!(etag.trim_matches('"'), compute_multipart_etag(&[&[1; 10], &[2; 10]]));
+    });
+}
+
+#[test]
+fn copy_of_changed_object_conflicts() {
+    let backend = MockBackend::new();
+    backend.put("key", object(2 * PART));
+
+    block_on(async {
+        let etag = backend.head("key").await.unwrap().unwrap().etag.unwrap();
+        let upload_id = backend.create_upload("key").await.unwrap();
+        backend.copy_part("key", &upload_id, 1, 0..PART, Some(&etag)).await.unwrap();
+
+        backend.put("key", object(3 * PART));
+        let err = backend.copy_part("key", &upload_id, 2, PART..2 * PART, Some(&etag)).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(EditError::Conflict { .. })), "{:?}", err);
+
+        backend.abort("key", &upload_id).await.unwrap();
+    });
+}
+
+#[test]
+fn mmap_part_edits_from_file() {
+    let path = std::env::temp_dir().join(format!("edit_s3_file_mmap_{}", std::process::id()));
+    let file = vec![7u8; 3 * PART as usize];
+    std::fs::write(&path, &file).unwrap();
+
+    let backend = MockBackend::new();
+    let mut expected = object(4 * PART);
+    backend.put("key", expected.clone());
+
+    let part = Part::from_file_mmap(&path, 10, 2 * PART as u64, PART + 3).unwrap();
+    block_on(modify_with_backend(&backend, "key", vec![part], PART)).unwrap();
+
+    expected[PART as usize + 3..3 * PART as usize + 3].copy_from_slice(&file[10..2 * PART as usize + 10]);
+    assert!(backend.get("key").unwrap() == expected);
+
+    assert!(Part::from_file_mmap(&path, PART as u64, 2 * PART as u64 + 1, 0).is_err());
+    assert!(Part::from_file_mmap(&path, 0, 0, 0).is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,81 @@
+//! Edits of objects under Object Lock against a mock server reporting the lock state on
+//! `HeadObject`, checking they're refused or carry the lock over to the new version.
+
+mod common;
+
+use common::mock_s3::{head, not_found, ok, MockS3, Request};
+use edit_s3_file::{EditError, Part, S3Config, Session};
+
+const BODY: &str = "hello world";
+const LOCK_CONFIGURATION: &str = "<ObjectLockConfiguration><ObjectLockEnabled>Enabled</ObjectLockEnabled></ObjectLockConfiguration>";
+const LOCK_HEADERS: &str = "ETag: \"etag\"\r\n\
+    x-amz-object-lock-mode: COMPLIANCE\r\n\
+    x-amz-object-lock-retain-until-date: 2099-01-01T00:00:00.000Z\r\n\
+    x-amz-object-lock-legal-hold: ON\r\n";
+
+/// Answers of a mock S3 serving one object, under compliance retention until 2099 and legal
+/// hold.
+fn respond(request: &Request) -> Vec<u8> {
+    match request.method.as_str() {
+        "GET" if request.target.contains("object-lock") => ok("", LOCK_CONFIGURATION),
+        "HEAD" => head(LOCK_HEADERS, BODY.len()),
+        "GET" => ok(LOCK_HEADERS, BODY),
+        "PUT" => ok("ETag: \"new\"\r\n", ""),
+        _ => not_found(),
+    }
+}
+
+fn edit(endpoint: &str, propagate: bool, part: Part) -> anyhow::Result<()> {
+    let config = S3Config::builder()
+        .endpoint(endpoint)
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .force_single_put(true)
+        .propagate_object_lock(propagate)
+        .build()?;
+    let session = Session::new(config)?;
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    rt.block_on(async {
+        session.modify("key", part).await?;
+        Ok(())
+    })
+}
+
+fn puts(mock: &MockS3) -> usize {
+    mock.requests().iter().filter(|r| r.method == "PUT").count()
+}
+
+#[test]
+fn locked_object_is_refused() {
+    let mock = MockS3::start(respond);
+
+    let err = edit(&mock.endpoint, false, Part::new(0, b"HELLO".to_vec())).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::ObjectLocked { .. })));
+    assert_eq!(puts(&mock), 0);
+}
+
+#[test]
+fn locked_object_with_assumed_len_is_refused() {
+    let mock = MockS3::start(respond);
+
+    let part = Part::new(0, b"HELLO".to_vec()).with_assumed_len(BODY.len() as i64);
+    let err = edit(&mock.endpoint, false, part).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::ObjectLocked { .. })));
+    assert_eq!(puts(&mock), 0);
+}
+
+#[test]
+fn propagated_lock_is_set_on_new_version() {
+    let mock = MockS3::start(respond);
+
+    edit(&mock.endpoint, true, Part::new(0, b"HELLO".to_vec())).unwrap();
+
+    let requests = mock.requests();
+    let puts = requests.iter().filter(|r| r.method == "PUT").collect::<Vec<_>>();
+    assert_eq!(puts.len(), 1);
+    assert_eq!(puts[0].header("x-amz-object-lock-mode"), Some("COMPLIANCE"));
+    assert_eq!(puts[0].header("x-amz-object-lock-retain-until-date"), Some("2099-01-01T00:00:00Z"));
+    assert_eq!(puts[0].header("x-amz-object-lock-legal-hold"), Some("ON"));
+}
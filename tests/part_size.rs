@@ -0,0 +1,40 @@
+use edit_s3_file::{PartSize, MAX_PARTS, MIN_PART_SIZE};
+
+const MB: i64 = 1024 * 1024;
+
+#[test]
+fn auto_floors_at_min_part_size() {
+    assert_eq!(PartSize::Auto.resolve(0), MIN_PART_SIZE);
+    assert_eq!(PartSize::Auto.resolve(1), MIN_PART_SIZE);
+    assert_eq!(PartSize::Auto.resolve(10 * 1024 * MB), MIN_PART_SIZE);
+}
+
+#[test]
+fn auto_rounds_up_to_whole_mb() {
+    // 100GB / 10000 parts = 10.24MB per part
+    assert_eq!(PartSize::Auto.resolve(100 * 1024 * MB), 11 * MB);
+    // exactly 6MB per part
+    assert_eq!(PartSize::Auto.resolve(6 * MB * MAX_PARTS), 6 * MB);
+    assert_eq!(PartSize::Auto.resolve(6 * MB * MAX_PARTS + 1), 7 * MB);
+}
+
+#[test]
+fn auto_fits_in_max_parts() {
+    for len in [5 * 1024 * 1024 * MB, 123_456_789_012, 7 * 1024 * 1024 * MB - 1] {
+        let size = PartSize::Auto.resolve(len);
+        assert!((len + size - 1) / size <= MAX_PARTS, "{} bytes with {} byte parts", len, size);
+    }
+}
+
+#[test]
+fn fixed_is_unchanged() {
+    assert_eq!(PartSize::Fixed(64 * MB).resolve(1), 64 * MB);
+    assert_eq!(PartSize::default(), PartSize::Fixed(1024 * MB));
+}
+
+#[test]
+fn deserialize_from_number_or_auto() {
+    assert_eq!(serde_json::from_str::<PartSize>("8388608").unwrap(), PartSize::Fixed(8 * MB));
+    assert_eq!(serde_json::from_str::<PartSize>("\"auto\"").unwrap(), PartSize::Auto);
+    assert!(serde_json::from_str::<PartSize>("\"big\"").is_err());
+}
@@ -0,0 +1,26 @@
+use edit_s3_file::{encode_patch, parse_patch};
+
+#[test]
+fn encoded_patch_parses() {
+    let patch = encode_patch([(10, &b"abc"[..]), (0, &[][..]), (1 << 40, &[7; 100][..])]);
+    assert_eq!(patch.len(), 9 + 3 * 16 + 103);
+    assert_eq!(parse_patch(&patch).unwrap().len(), 3);
+    assert!(parse_patch(&encode_patch([])).unwrap().is_empty());
+}
+
+#[test]
+fn malformed_patch_rejected() {
+    let patch = encode_patch([(10, &b"abc"[..])]);
+
+    assert!(parse_patch(b"").is_err());
+    assert!(parse_patch(&patch[..8]).is_err());
+    // truncated in the edit header, then in its data
+    assert!(parse_patch(&patch[..20]).is_err());
+    assert!(parse_patch(&patch[..patch.len() - 1]).is_err());
+
+    let mut version = patch.clone();
+    version[8] = 2;
+    assert!(parse_patch(&version).is_err());
+
+    assert!(parse_patch(&encode_patch([(-1, &b"abc"[..])])).is_err());
+}
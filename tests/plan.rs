@@ -0,0 +1,287 @@
+// plans take edits as a slice of ranges, for a single edit an array of one range
+#![allow(clippy::single_range_in_vec_init)]
+
+use edit_s3_file::{coalesce_edits, plan, plan_adaptive, plan_along_parts, validate_plan, CostEstimate, EditError, PartOp, Pricing, MAX_COPY_PART_SIZE, MAX_OBJECT_SIZE, MAX_PARTS, MIN_PART_SIZE};
+
+const MB: i64 = 1024 * 1024;
+
+#[test]
+fn validate_accepts_small_last_part() {
+    let ops = [PartOp::Copy(0..MIN_PART_SIZE), PartOp::Upload(MIN_PART_SIZE..MIN_PART_SIZE + 1)];
+    assert!(validate_plan(&ops, MIN_PART_SIZE + 1).is_ok());
+    assert!(validate_plan(&[PartOp::Upload(0..1)], 1).is_ok());
+    assert!(validate_plan(&[], 0).is_ok());
+}
+
+#[test]
+fn validate_rejects_small_middle_part() {
+    let ops = [
+        PartOp::Copy(0..MIN_PART_SIZE),
+        PartOp::Upload(MIN_PART_SIZE..MIN_PART_SIZE + 10),
+        PartOp::Copy(MIN_PART_SIZE + 10..3 * MIN_PART_SIZE),
+    ];
+
+    match validate_plan(&ops, 3 * MIN_PART_SIZE) {
+        Err(EditError::PartTooSmall { part_number, size }) => {
+            assert_eq!(part_number, 2);
+            assert_eq!(size, 10);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn validate_rejects_small_first_part() {
+    let ops = [PartOp::Copy(0..MB), PartOp::Upload(MB..10 * MB)];
+    assert!(matches!(validate_plan(&ops, 10 * MB), Err(EditError::PartTooSmall { part_number: 1, .. })));
+}
+
+#[test]
+fn plans_pass_validation() {
+    let cases: &[(i64, &[(i64, i64)])] = &[
+        (12 * MB, &[(6 * MB, 6 * MB + 1)]),
+        (12 * MB, &[(12 * MB - 10, 12 * MB - 5)]),
+        (30 * MB, &[(10, 1010), (8 * MB, 11 * MB), (20 * MB, 20 * MB + 100)]),
+        (100 * MB, &[(0, 100 * MB)]),
+    ];
+
+    for &(len, edits) in cases {
+        let edits = edits.iter().map(|&(start, end)| start..end).collect::<Vec<_>>();
+        let ops = plan(len, &edits, 8 * MB);
+        assert!(validate_plan(&ops, len).is_ok(), "{} {:?}: {:?}", len, edits, ops);
+    }
+}
+
+fn inconsistent_at(ops: &[PartOp], obj_len: i64) -> Option<i64> {
+    match validate_plan(ops, obj_len) {
+        Err(EditError::PlanInconsistent { offset }) => Some(offset),
+        _ => None,
+    }
+}
+
+#[test]
+fn validate_rejects_gap() {
+    let ops = [PartOp::Copy(0..6 * MB), PartOp::Upload(6 * MB + 1..12 * MB)];
+    assert_eq!(inconsistent_at(&ops, 12 * MB), Some(6 * MB));
+}
+
+#[test]
+fn validate_rejects_overlap() {
+    let ops = [PartOp::Copy(0..6 * MB), PartOp::Upload(6 * MB - 1..12 * MB)];
+    assert_eq!(inconsistent_at(&ops, 12 * MB), Some(6 * MB));
+}
+
+#[test]
+fn validate_rejects_wrong_start_and_empty_parts() {
+    assert_eq!(inconsistent_at(&[PartOp::Upload(1..6 * MB)], 6 * MB), Some(0));
+    assert_eq!(inconsistent_at(&[PartOp::Copy(0..6 * MB), PartOp::Copy(6 * MB..6 * MB)], 6 * MB), Some(6 * MB));
+}
+
+#[test]
+fn validate_rejects_short_and_long_cover() {
+    let ops = [PartOp::Copy(0..6 * MB)];
+    assert_eq!(inconsistent_at(&ops, 7 * MB), Some(6 * MB));
+    assert_eq!(inconsistent_at(&ops, 5 * MB), Some(6 * MB));
+    assert_eq!(inconsistent_at(&[], 1), Some(0));
+}
+
+#[test]
+fn copy_parts_clamped_to_copy_limit() {
+    const GB: i64 = 1024 * MB;
+    let len = 30 * GB;
+    let edits = std::iter::once(0..10 * GB).collect::<Vec<_>>();
+    let ops = plan(len, &edits, 8 * GB);
+    assert!(validate_plan(&ops, len).is_ok());
+
+    let uploads = ops.iter()
+        .filter_map(|op| match op {
+            PartOp::Upload(r) => Some(r.end - r.start),
+            PartOp::Copy(_) => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(uploads, vec![8 * GB, 2 * GB]);
+
+    let copies = ops.iter()
+        .filter_map(|op| match op {
+            PartOp::Copy(r) => Some(r.end - r.start),
+            PartOp::Upload(_) => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(copies.iter().sum::<i64>(), 20 * GB);
+    assert!(copies.iter().all(|&size| size <= MAX_COPY_PART_SIZE), "{:?}", copies);
+}
+
+#[test]
+fn validate_rejects_too_many_parts() {
+    let ops = (0..MAX_PARTS + 1)
+        .map(|i| PartOp::Copy(i * MIN_PART_SIZE..(i + 1) * MIN_PART_SIZE))
+        .collect::<Vec<_>>();
+
+    match validate_plan(&ops, (MAX_PARTS + 1) * MIN_PART_SIZE) {
+        Err(EditError::TooManyParts { parts }) => assert_eq!(parts, MAX_PARTS as usize + 1),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(validate_plan(&ops[..MAX_PARTS as usize], MAX_PARTS * MIN_PART_SIZE).is_ok());
+}
+
+#[test]
+fn plan_along_parts_keeps_source_layout() {
+    let parts = [8 * MB, 8 * MB, 3 * MB];
+
+    let ops = plan_along_parts(19 * MB, &parts, &[9 * MB..9 * MB + 10], 16 * MB);
+    assert_eq!(ops, vec![
+        PartOp::Copy(0..8 * MB),
+        PartOp::Upload(8 * MB..16 * MB),
+        PartOp::Copy(16 * MB..19 * MB),
+    ]);
+    assert!(validate_plan(&ops, 19 * MB).is_ok());
+
+    // an edit on a boundary touches both parts
+    let ops = plan_along_parts(19 * MB, &parts, &[8 * MB - 1..8 * MB + 1], 16 * MB);
+    assert_eq!(ops, vec![
+        PartOp::Upload(0..8 * MB),
+        PartOp::Upload(8 * MB..16 * MB),
+        PartOp::Copy(16 * MB..19 * MB),
+    ]);
+}
+
+#[test]
+fn plan_along_parts_cuts_and_extends() {
+    let parts = [8 * MB, 8 * MB, 3 * MB];
+
+    let ops = plan_along_parts(10 * MB, &parts, &[], 16 * MB);
+    assert_eq!(ops, vec![PartOp::Copy(0..8 * MB), PartOp::Copy(8 * MB..10 * MB)]);
+
+    // the short last part is merged into the appended bytes
+    let ops = plan_along_parts(20 * MB, &parts, &[19 * MB..20 * MB], 16 * MB);
+    assert_eq!(ops, vec![
+        PartOp::Copy(0..8 * MB),
+        PartOp::Copy(8 * MB..16 * MB),
+        PartOp::Upload(16 * MB..20 * MB),
+    ]);
+    assert!(validate_plan(&ops, 20 * MB).is_ok());
+
+    let ops = plan_along_parts(17 * MB, &parts[..2], &[16 * MB..17 * MB], 16 * MB);
+    assert_eq!(ops, vec![
+        PartOp::Copy(0..8 * MB),
+        PartOp::Copy(8 * MB..16 * MB),
+        PartOp::Upload(16 * MB..17 * MB),
+    ]);
+}
+
+#[test]
+fn validate_rejects_too_large_object() {
+    // growing an object at the limit by one byte
+    let ops = plan(MAX_OBJECT_SIZE + 1, &[MAX_OBJECT_SIZE..MAX_OBJECT_SIZE + 1], 1024 * MB);
+    assert!(matches!(validate_plan(&ops, MAX_OBJECT_SIZE + 1), Err(EditError::ObjectTooLarge { size }) if size == MAX_OBJECT_SIZE + 1));
+
+    let ops = plan(MAX_OBJECT_SIZE, &[MAX_OBJECT_SIZE - 1..MAX_OBJECT_SIZE], 1024 * MB);
+    assert!(validate_plan(&ops, MAX_OBJECT_SIZE).is_ok());
+}
+
+#[test]
+fn small_edit_padded_to_min_part_size() {
+    let uploaded = |ops: &[PartOp]| ops.iter()
+        .map(|op| match op {
+            PartOp::Upload(r) => r.end - r.start,
+            PartOp::Copy(_) => 0,
+        })
+        .sum::<i64>();
+
+    for start in [0, MB, 4 * MB, 5 * MB, 10 * MB, 20 * MB - 1024] {
+        let ops = plan(20 * MB, &[start..start + 1024], 1024 * MB);
+        assert!(validate_plan(&ops, 20 * MB).is_ok());
+        assert_eq!(uploaded(&ops), MIN_PART_SIZE, "{:?}", ops);
+    }
+
+    let ops = plan(20 * MB, &[MB..MB + 1024], 1024 * MB);
+    assert_eq!(ops, vec![PartOp::Upload(0..MIN_PART_SIZE), PartOp::Copy(MIN_PART_SIZE..20 * MB)]);
+}
+
+#[test]
+fn small_edit_across_part_boundary() {
+    // 10 bytes across the 10MB boundary of 5MB parts are uploaded in one padded part
+    let ops = plan(20 * MB, &[10 * MB - 5..10 * MB + 5], 5 * MB);
+    assert!(validate_plan(&ops, 20 * MB).is_ok());
+    assert_eq!(ops, vec![
+        PartOp::Copy(0..10 * MB - 5),
+        PartOp::Upload(10 * MB - 5..15 * MB - 5),
+        PartOp::Copy(15 * MB - 5..20 * MB),
+    ]);
+
+    // and across the end of the object, padded to the left
+    let ops = plan(20 * MB + 2, &[20 * MB - 3..20 * MB + 2], 5 * MB);
+    assert!(validate_plan(&ops, 20 * MB + 2).is_ok());
+    assert_eq!(ops, vec![
+        PartOp::Copy(0..5 * MB),
+        PartOp::Copy(5 * MB..10 * MB),
+        PartOp::Copy(10 * MB..15 * MB + 2),
+        PartOp::Upload(15 * MB + 2..20 * MB + 2),
+    ]);
+}
+
+#[test]
+fn plan_adaptive_copies_in_large_parts() {
+    let edits = [50 * MB..50 * MB + 1024];
+    assert_eq!(plan(100 * MB, &edits, 5 * MB).len(), 20);
+
+    let ops = plan_adaptive(100 * MB, &edits, 5 * MB);
+    assert!(validate_plan(&ops, 100 * MB).is_ok());
+    assert_eq!(ops, vec![
+        PartOp::Copy(0..50 * MB),
+        PartOp::Upload(50 * MB..55 * MB),
+        PartOp::Copy(55 * MB..100 * MB),
+    ]);
+
+    // uploads still follow the part size, copies only the copy limit
+    const GB: i64 = 1024 * MB;
+    let edits = [0..12 * MB, 10 * GB..10 * GB + 1];
+    let ops = plan_adaptive(20 * GB, &edits, 5 * MB);
+    assert!(validate_plan(&ops, 20 * GB).is_ok());
+    assert_eq!(ops[..3], [PartOp::Upload(0..5 * MB), PartOp::Upload(5 * MB..12 * MB), PartOp::Copy(12 * MB..5 * GB + 12 * MB)]);
+    assert!(ops.iter().all(|op| match op {
+        PartOp::Copy(r) => r.end - r.start <= MAX_COPY_PART_SIZE,
+        PartOp::Upload(r) => r.end - r.start <= 7 * MB,
+    }), "{:?}", ops);
+}
+
+#[test]
+fn coalesce_merges_edits_within_gap() {
+    let edits = [0..10, 10 * MB..10 * MB + 1, 20 * MB..20 * MB + 1, 50 * MB..50 * MB + 1];
+    assert_eq!(coalesce_edits(&edits, 10 * MB), vec![0..20 * MB + 1, 50 * MB..50 * MB + 1]);
+    assert_eq!(coalesce_edits(&edits, 0), edits.to_vec());
+    assert_eq!(coalesce_edits(&[5..5, 7..9], MB), vec![7..9]);
+
+    // merged, the edits take one uploaded region instead of copies between uploads
+    let merged = coalesce_edits(&edits[1..3], 10 * MB);
+    let ops = plan(40 * MB, &merged, 5 * MB);
+    assert!(validate_plan(&ops, 40 * MB).is_ok());
+
+    let uploads = ops.iter().filter_map(|op| match op {
+        PartOp::Upload(r) => Some(r.clone()),
+        PartOp::Copy(_) => None,
+    }).collect::<Vec<_>>();
+    assert_eq!(uploads.first().unwrap().start, 10 * MB);
+    assert_eq!(uploads.last().unwrap().end, 20 * MB + 1);
+    assert!(uploads.windows(2).all(|pair| pair[0].end == pair[1].start), "{:?}", ops);
+}
+
+#[test]
+fn cost_of_small_edit() {
+    let edits = [MB..MB + 1024];
+    let ops = plan(20 * MB, &edits, 1024 * MB);
+    let estimate = CostEstimate::of_plan(&ops, &edits);
+
+    assert_eq!(estimate, CostEstimate {
+        copy_requests: 1,
+        upload_requests: 1,
+        get_requests: 2,
+        bytes_copied: (15 * MB) as u64,
+        bytes_uploaded: MIN_PART_SIZE as u64,
+        bytes_downloaded: (MIN_PART_SIZE - 1024) as u64,
+    });
+    assert_eq!(estimate.put_requests(), 4);
+
+    let usd = estimate.usd(&Pricing::AWS_S3_STANDARD);
+    assert!((usd - (4.0 * 0.005 + 2.0 * 0.0004) / 1000.0).abs() < 1e-12, "{}", usd);
+}
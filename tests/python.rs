@@ -0,0 +1,30 @@
+#![cfg(feature = "python")]
+
+use edit_s3_file::python::edit_s3_file as module;
+use edit_s3_file::PATH_ENV;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError};
+use pyo3::prelude::*;
+
+#[test]
+fn module_imports_into_embedded_interpreter() {
+    pyo3::append_to_inittab!(module);
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let m = py.import_bound("edit_s3_file").unwrap();
+
+        for name in ["modify", "read_at", "append", "plan"] {
+            assert!(m.getattr(name).unwrap().is_callable(), "{}", name);
+        }
+
+        // arguments are converted before anything is sent
+        let err = m.call_method1("read_at", ("key", "zero", 10)).unwrap_err();
+        assert!(err.is_instance_of::<PyTypeError>(py), "{}", err);
+
+        // without a global session, errors surface as Python exceptions with their causes
+        if std::env::var_os(PATH_ENV).is_none() {
+            let err = m.call_method1("read_at", ("key", 0, 10)).unwrap_err();
+            assert!(err.is_instance_of::<PyRuntimeError>(py), "{}", err);
+        }
+    });
+}
@@ -0,0 +1,1505 @@
+//! Tests of the async API, driven from a single runtime as async-only callers would.
+//! The blocking API and its internal runtime must not share the global client with
+//! another runtime, so these live in their own test binary. See `tests/s3.rs`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use aws_sdk_s3::config::Credentials;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart, StorageClass, Tag, Tagging};
+use edit_s3_file::{compute_multipart_etag, modify_with_backend, AsyncRangeReader, CancellationToken, ChunkedObject, EditError, EditHooks, EditReport, MetadataDirective, Part, PartChecksum, PartOp, PartSize, PresignedPart, ProgressEvent, RangeKind, Session, TempKeyStrategy, ORIGINAL_LAST_MODIFIED_KEY};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use common::{config_builder, list_keys, new_client, pattern, read_all, setup, unique_key, MIB, TEST_RT};
+
+mod common;
+
+async fn put(key: &str, data: &[u8]) {
+    edit_s3_file::replace_async(key, ByteStream::from(data.to_vec()), data.len() as i64).await.unwrap();
+}
+
+#[test]
+fn reader_seek() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("reader_seek");
+    let data = pattern(MIB, 2);
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+
+        let mut reader = AsyncRangeReader::new(&key, 1000, 200_000).unwrap().with_window(4096);
+
+        let mut buf = vec![0; 10_000];
+        reader.read_exact(&mut buf).await.unwrap();
+        assert!(buf == data[1000..11_000]);
+
+        reader.seek(std::io::SeekFrom::Start(150_000)).await.unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert!(rest == data[151_000..200_000]);
+    });
+}
+
+#[test]
+fn chunked_read_at() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("chunked_read_at");
+    let data = pattern(MIB, 3);
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+
+        let mut object = ChunkedObject::open(&key).await.unwrap().with_window(64 * 1024);
+        assert_eq!(object.len(), MIB as u64);
+
+        // spans two windows, then reads back into an evicted or cached one
+        for offset in [60_000, 900_000, 0, 61_000, MIB as u64 - 10] {
+            let len = std::cmp::min(10_000, MIB as u64 - offset);
+            let got = object.read_at(offset, len as usize).await.unwrap();
+            assert!(got == data[offset as usize..(offset + len) as usize]);
+        }
+
+        assert!(object.read_at(MIB as u64 - 10, 11).await.is_err());
+    });
+}
+
+#[test]
+fn session_from_client() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("session_from_client");
+    let mut expected = pattern(12 * MIB, 5);
+    let (bucket, client) = new_client();
+    let session = Session::from_client(client, &bucket);
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(expected.clone()), expected.len() as i64).await.unwrap();
+        session.modify(&key, Part::new(6 * MIB as i64, vec![7; 1024])).await.unwrap();
+    });
+
+    expected[6 * MIB..6 * MIB + 1024].fill(7);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn session_in_region() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("session_in_region");
+    let (bucket, client) = new_client();
+    let session = Session::from_client(client, &bucket).in_region("eu-west-1");
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(vec![3; 100]), 100).await.unwrap();
+        assert_eq!(session.stat(&key).await.unwrap().unwrap().len, 100);
+    });
+    assert_eq!(session.client().config().region().map(|r| r.as_ref()), Some("eu-west-1"));
+}
+
+#[test]
+fn verify_credentials() {
+    if !setup() {
+        return;
+    }
+    let (bucket, client) = new_client();
+    let conf = client.config()
+        .to_builder()
+        .credentials_provider(Credentials::new("wrong", "wrong", None, None, "Static"))
+        .build();
+    let good = Session::from_client(client, &bucket);
+    let bad = Session::from_client(aws_sdk_s3::Client::from_conf(conf), &bucket);
+
+    TEST_RT.block_on(async {
+        good.verify_credentials().await.unwrap();
+
+        let err = bad.verify_credentials().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::AuthFailed)), "{:?}", err);
+    });
+}
+
+#[test]
+fn atomic_edit_removes_temp_object() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("atomic_edit");
+    let mut expected = pattern(6 * MIB, 8);
+    let session = Session::new(config_builder().atomic_edits(true).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(expected.clone()), expected.len() as i64).await.unwrap();
+        session.modify(&key, Part::new(100, vec![4; 100])).await.unwrap();
+    });
+
+    expected[100..200].fill(4);
+    assert!(read_all(&key, expected.len()) == expected);
+    assert_eq!(list_keys(&key), vec![key]);
+}
+
+#[test]
+fn atomic_edit_temp_key_strategy() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("atomic_edit_strategy");
+    let prefix = format!("{}-tmp/", key);
+    let strategy = TempKeyStrategy::Custom(Arc::new({
+        let prefix = prefix.clone();
+        move |key: &str| format!("{}{}-{}", prefix, key.replace('/', "_"), unique_key("t").replace('/', "_"))
+    }));
+    let session = Session::new(config_builder().atomic_edits(true).temp_key_strategy(strategy).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(vec![1; 1000]), 1000).await.unwrap();
+        session.modify(&key, Part::new(100, vec![4; 100])).await.unwrap();
+    });
+
+    assert!(list_keys(&prefix).is_empty());
+    assert_eq!(list_keys(&key), vec![key]);
+}
+
+#[test]
+fn session_with_credentials_provider() {
+    if !setup() {
+        return;
+    }
+    let env = |name: &str| std::env::var(name).unwrap_or_else(|_| "minioadmin".to_string());
+    let provider = Credentials::new(env("EDIT_S3_IT_ACCESS_KEY"), env("EDIT_S3_IT_SECRET_KEY"), None, None, "Provided");
+
+    // the config itself carries no keys
+    let config = edit_s3_file::S3Config { access_key: None, secret_key: None, ..config_builder().build().unwrap() };
+    let session = Session::with_credentials_provider(config, Arc::new(provider)).unwrap();
+
+    TEST_RT.block_on(async {
+        session.verify_credentials().await.unwrap();
+    });
+}
+
+#[test]
+fn session_with_default_chain() {
+    if !setup() {
+        return;
+    }
+    let env = |name: &str| std::env::var(name).unwrap_or_else(|_| "minioadmin".to_string());
+    std::env::set_var("AWS_ACCESS_KEY_ID", env("EDIT_S3_IT_ACCESS_KEY"));
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", env("EDIT_S3_IT_SECRET_KEY"));
+
+    // without keys in the config, they come from the environment
+    let config = edit_s3_file::S3Config { access_key: None, secret_key: None, ..config_builder().build().unwrap() };
+    let session = Session::new(config).unwrap();
+
+    TEST_RT.block_on(async {
+        session.verify_credentials().await.unwrap();
+    });
+}
+
+#[test]
+fn read_respects_max_read_bytes() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("read_respects_max_read_bytes");
+    let session = Session::new(config_builder().max_read_bytes(100).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(vec![1; 101]), 101).await.unwrap();
+        assert!(session.read(&key).await.is_err());
+
+        session.replace(&key, ByteStream::from(vec![1; 100]), 100).await.unwrap();
+        assert_eq!(session.read(&key).await.unwrap().len(), 100);
+    });
+}
+
+#[test]
+fn small_read_buffer_reads_whole() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("small_read_buffer");
+    let data = pattern(MIB, 9);
+    let session = Session::new(config_builder().read_buffer_size(1000).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(data.clone()), data.len() as i64).await.unwrap();
+        assert!(session.read(&key).await.unwrap() == data);
+
+        let ranges = session.read_ranges(&key, &[(10, 5000), (500_000, 500_001)]).await.unwrap();
+        assert!(ranges[0] == data[10..5000]);
+        assert!(ranges[1] == data[500_000..500_001]);
+    });
+}
+
+#[test]
+fn preserve_timestamps_records_original() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("preserve_timestamps");
+    let session = Session::new(config_builder().preserve_timestamps(true).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(vec![1; 1000]), 1000).await.unwrap();
+        let before = session.stat(&key).await.unwrap().unwrap().last_modified.unwrap();
+        let expected = DateTime::from(before).fmt(DateTimeFormat::HttpDate).unwrap();
+
+        for i in 0..2 {
+            session.modify(&key, Part::new(i, vec![2])).await.unwrap();
+            let stat = session.stat(&key).await.unwrap().unwrap();
+            assert_eq!(stat.metadata.get(ORIGINAL_LAST_MODIFIED_KEY), Some(&expected));
+        }
+    });
+}
+
+async fn put_with_metadata(key: &str) {
+    let (bucket, client) = new_client();
+
+    client.put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from(vec![1; 1000]))
+        .metadata("owner", "it")
+        .content_type("application/x-plot")
+        .send()
+        .await
+        .unwrap();
+}
+
+#[test]
+fn metadata_directive_copy_keeps_source() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("metadata_directive_copy");
+    let session = Session::new(config_builder().metadata_directive(MetadataDirective::Copy).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put_with_metadata(&key).await;
+        session.modify(&key, Part::new(10, vec![2])).await.unwrap();
+
+        let stat = session.stat(&key).await.unwrap().unwrap();
+        assert_eq!(stat.metadata.get("owner").map(String::as_str), Some("it"));
+        assert_eq!(stat.content_type.as_deref(), Some("application/x-plot"));
+    });
+}
+
+#[test]
+fn metadata_directive_replace_drops_source() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("metadata_directive_replace");
+    let metadata = [("stage".to_string(), "edited".to_string())].into_iter().collect();
+    let session = Session::new(config_builder().replace_metadata(metadata).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put_with_metadata(&key).await;
+        session.modify(&key, Part::new(10, vec![2])).await.unwrap();
+
+        let stat = session.stat(&key).await.unwrap().unwrap();
+        assert_eq!(stat.metadata.get("stage").map(String::as_str), Some("edited"));
+        assert!(!stat.metadata.contains_key("owner"));
+        assert_ne!(stat.content_type.as_deref(), Some("application/x-plot"));
+    });
+}
+
+#[test]
+fn storage_class_kept_or_overridden() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("storage_class");
+    let (bucket, client) = new_client();
+
+    TEST_RT.block_on(async {
+        client.put_object()
+            .bucket(&bucket)
+            .key(&key)
+            .body(ByteStream::from(vec![1; 1000]))
+            .storage_class(StorageClass::ReducedRedundancy)
+            .send()
+            .await
+            .unwrap();
+
+        let storage_class = || async {
+            let out = client.head_object().bucket(&bucket).key(&key).send().await.unwrap();
+            out.storage_class().cloned().unwrap_or(StorageClass::Standard)
+        };
+
+        let session = Session::new(config_builder().build().unwrap()).unwrap();
+        session.modify(&key, Part::new(10, vec![2])).await.unwrap();
+        assert_eq!(storage_class().await, StorageClass::ReducedRedundancy);
+
+        let session = Session::new(config_builder().storage_class("STANDARD").build().unwrap()).unwrap();
+        session.modify(&key, Part::new(10, vec![3])).await.unwrap();
+        assert_eq!(storage_class().await, StorageClass::Standard);
+    });
+}
+
+#[test]
+fn multipart_etag_matches_server() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("multipart_etag_matches_server");
+    let parts = [pattern(5 * MIB, 17), pattern(1000, 18)];
+    let (bucket, client) = new_client();
+
+    let etag = TEST_RT.block_on(async {
+        let upload = client.create_multipart_upload().bucket(&bucket).key(&key).send().await.unwrap();
+        let upload_id = upload.upload_id().unwrap();
+        let mut completed = CompletedMultipartUpload::builder();
+
+        for (i, part) in parts.iter().enumerate() {
+            let number = i as i32 + 1;
+            let out = client.upload_part()
+                .bucket(&bucket)
+                .key(&key)
+                .upload_id(upload_id)
+                .part_number(number)
+                .body(ByteStream::from(part.clone()))
+                .send()
+                .await
+                .unwrap();
+            completed = completed.parts(CompletedPart::builder().part_number(number).e_tag(out.e_tag().unwrap()).build());
+        }
+
+        client.complete_multipart_upload()
+            .bucket(&bucket)
+            .key(&key)
+            .upload_id(upload_id)
+            .multipart_upload(completed.build())
+            .send()
+            .await
+            .unwrap()
+            .e_tag()
+            .unwrap()
+            .to_string()
+    });
+
+    assert_eq!(etag.trim_matches('"'), compute_multipart_etag(&[&parts[0], &parts[1]]));
+}
+
+#[test]
+fn replace_unsized_multipart() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("replace_unsized_multipart");
+    let data = pattern(12 * MIB + 1, 20);
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        let len = session.replace_unsized(&key, ByteStream::from(data.clone())).await.unwrap();
+        assert_eq!(len, data.len() as i64);
+    });
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn replace_rejects_long_body() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("replace_rejects_long_body");
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        let err = session.replace(&key, ByteStream::from(pattern(12 * MIB, 21)), 11 * MIB as i64).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::BodyLengthMismatch { .. })), "{:?}", err);
+        assert!(session.stat(&key).await.unwrap().is_none());
+    });
+}
+
+#[test]
+fn force_single_put_edit() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("force_single_put_edit");
+    let mut expected = pattern(12 * MIB, 24);
+    let session = Session::new(config_builder().force_single_put(true).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(expected.clone()), expected.len() as i64).await.unwrap();
+
+        let parts = vec![Part::new(MIB as i64, vec![5; 100]), Part::new(expected.len() as i64, vec![6; 10])];
+        let stats = session.modify_many(&key, parts).await.unwrap();
+        assert_eq!(stats.ranges, vec![(RangeKind::Uploaded, 0, expected.len() as i64 + 10)]);
+    });
+
+    expected[MIB..MIB + 100].fill(5);
+    expected.extend_from_slice(&[6; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn preserve_acl_keeps_grants() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("preserve_acl_keeps_grants");
+    let session = Session::new(config_builder().preserve_acl(true).build().unwrap()).unwrap();
+    let (bucket, client) = new_client();
+
+    TEST_RT.block_on(async {
+        put(&key, &pattern(1000, 26)).await;
+        let before = client.get_object_acl().bucket(&bucket).key(&key).send().await.unwrap();
+
+        session.modify(&key, Part::new(10, vec![1; 10])).await.unwrap();
+
+        let after = client.get_object_acl().bucket(&bucket).key(&key).send().await.unwrap();
+        assert_eq!(before.grants(), after.grants());
+    });
+}
+
+#[test]
+fn preserve_tags_keeps_tags() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("preserve_tags_keeps_tags");
+    let session = Session::new(config_builder().preserve_tags(true).build().unwrap()).unwrap();
+    let (bucket, client) = new_client();
+
+    TEST_RT.block_on(async {
+        put(&key, &pattern(12 * MIB, 58)).await;
+        let tag = Tag::builder().key("owner").value("it").build().unwrap();
+        let tagging = Tagging::builder().tag_set(tag.clone()).build().unwrap();
+        client.put_object_tagging().bucket(&bucket).key(&key).tagging(tagging).send().await.unwrap();
+
+        session.modify(&key, Part::new(7 * MIB as i64, vec![1; 100])).await.unwrap();
+
+        let after = client.get_object_tagging().bucket(&bucket).key(&key).send().await.unwrap();
+        assert_eq!(after.tag_set(), [tag]);
+    });
+}
+
+#[test]
+fn upload_token_resumes_upload() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("upload_token_resumes_upload");
+    let session = Session::new(config_builder().build().unwrap()).unwrap().with_upload_token("retry-1");
+
+    TEST_RT.block_on(async {
+        // a first attempt that crashed before completing
+        let mut first = session.begin_multipart(&key).await.unwrap();
+        first.upload_bytes(vec![1; 10]).await.unwrap();
+        let upload_id = first.upload_id().to_string();
+        drop(first);
+
+        let mut retry = session.begin_multipart(&key).await.unwrap();
+        assert_eq!(retry.upload_id(), upload_id);
+        retry.upload_bytes(vec![2; 10]).await.unwrap();
+        retry.complete().await.unwrap();
+
+        assert!(session.read(&key).await.unwrap() == [2; 10]);
+        assert!(session.stat(&format!("{}.upload-retry-1", key)).await.unwrap().is_none());
+
+        // the completed upload isn't resumed again
+        let next = session.begin_multipart(&key).await.unwrap();
+        assert_ne!(next.upload_id(), upload_id);
+        next.abort().await.unwrap();
+    });
+}
+
+#[test]
+fn resumed_upload_keeps_matching_parts() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("resumed_upload_keeps_matching_parts");
+    let session = Session::new(config_builder().build().unwrap()).unwrap().with_upload_token("retry-1");
+    let first_part = pattern(5 * MIB, 41);
+
+    TEST_RT.block_on(async {
+        let mut first = session.begin_multipart(&key).await.unwrap();
+        first.upload_bytes(first_part.clone()).await.unwrap();
+        first.upload_bytes(vec![1; 10]).await.unwrap();
+        drop(first);
+
+        // the same first part is kept, the changed second one uploaded again
+        let mut retry = session.begin_multipart(&key).await.unwrap();
+        retry.upload_bytes(first_part.clone()).await.unwrap();
+        retry.upload_bytes(vec![2; 10]).await.unwrap();
+        retry.complete().await.unwrap();
+
+        let mut expected = first_part;
+        expected.extend_from_slice(&[2; 10]);
+        assert!(session.read(&key).await.unwrap() == expected);
+    });
+}
+
+#[test]
+fn multipart_builder_steps() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("multipart_builder_steps");
+    let data = pattern(12 * MIB, 27);
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+
+        let mut upload = session.begin_multipart(&key).await.unwrap();
+        upload.upload_bytes(vec![1; 10]).await.unwrap();
+        upload.abort().await.unwrap();
+
+        let mut upload = session.begin_multipart(&key).await.unwrap();
+        assert_eq!(upload.copy_range(6 * MIB as i64, 6 * MIB as i64).await.unwrap(), 1);
+        assert_eq!(upload.upload_bytes(vec![2; 100]).await.unwrap(), 2);
+        upload.complete().await.unwrap();
+    });
+
+    let mut expected = data[6 * MIB..].to_vec();
+    expected.extend_from_slice(&[2; 100]);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn detect_capabilities_cached() {
+    if !setup() {
+        return;
+    }
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        let caps = session.detect_capabilities().await.unwrap();
+        // every server the tests run against copies parts
+        assert!(caps.upload_part_copy);
+        assert_eq!(session.clone().detect_capabilities().await.unwrap(), caps);
+    });
+}
+
+#[test]
+fn pad_to_part_off_rejects_small_edits() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("pad_to_part_off");
+    let mut expected = pattern(12 * MIB, 32);
+    let session = Session::new(config_builder().pad_to_part(false).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put(&key, &expected).await;
+
+        for index in [0, 6 * MIB as i64, 12 * MIB as i64 - 10] {
+            assert!(session.modify(&key, Part::new(index, vec![1; 10])).await.is_err(), "{}", index);
+        }
+        session.modify(&key, Part::new(0, vec![2; 6 * MIB])).await.unwrap();
+    });
+
+    expected[..6 * MIB].fill(2);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn transaction_overwrite_and_truncate() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("transaction_overwrite_and_truncate");
+    let data = pattern(12 * MIB, 33);
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+
+        let mut tx = session.begin(&key);
+        tx.write_at(MIB as i64, vec![1; 100]);
+        tx.write_at(MIB as i64 + 50, vec![2; 100]);
+        tx.write_at(9 * MIB as i64, vec![3; 10]);
+        tx.truncate(8 * MIB as i64);
+        tx.write_at(8 * MIB as i64, vec![4; 10]);
+        tx.commit().await.unwrap();
+
+        let mut tx = session.begin(&key);
+        tx.truncate(10);
+        tx.write_at(20, vec![5; 10]);
+        assert!(tx.commit().await.is_err());
+    });
+
+    let mut expected = data[..8 * MIB].to_vec();
+    expected[MIB..MIB + 50].fill(1);
+    expected[MIB + 50..MIB + 150].fill(2);
+    expected.extend_from_slice(&[4; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn if_none_match_creates_once() {
+    if !setup() {
+        return;
+    }
+    let src = unique_key("if_none_match_src");
+    let dst = unique_key("if_none_match_dst");
+    let session = Session::new(config_builder().if_none_match(true).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&src, ByteStream::from(pattern(1000, 34)), 1000).await.unwrap();
+        let err = session.replace(&src, ByteStream::from(pattern(1000, 35)), 1000).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::AlreadyExists { .. })), "{:?}", err);
+
+        session.modify_into(&src, &dst, Part::new(0, vec![1; 10])).await.unwrap();
+        let err = session.modify_into(&src, &dst, Part::new(0, vec![2; 10])).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::AlreadyExists { .. })), "{:?}", err);
+    });
+
+    let mut expected = pattern(1000, 34);
+    expected[..10].fill(1);
+    assert!(read_all(&dst, expected.len()) == expected);
+}
+
+#[test]
+fn modify_older_version() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_older_version");
+    let (bucket, client) = new_client();
+
+    TEST_RT.block_on(async {
+        let versioning = client.get_bucket_versioning().bucket(&bucket).send().await.unwrap();
+        if versioning.status().is_none() {
+            eprintln!("{} is not versioned, skipping", bucket);
+            return;
+        }
+
+        put(&key, &pattern(1000, 36)).await;
+        put(&key, &pattern(1000, 37)).await;
+
+        let versions = edit_s3_file::list_object_versions_async(&key).await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(versions[0].is_latest && !versions[1].is_latest);
+
+        let old = &versions[1].version_id;
+        let data = edit_s3_file::read_version_range_async(&key, old, 100, 200).await.unwrap();
+        assert!(data == pattern(1000, 36)[100..200]);
+
+        let stats = edit_s3_file::modify_version_async(&key, old, vec![Part::new(0, vec![1; 10])]).await.unwrap();
+        let versions = edit_s3_file::list_object_versions_async(&key).await.unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(stats.version_id.as_ref(), Some(&versions[0].version_id));
+    });
+
+    let mut expected = pattern(1000, 36);
+    expected[..10].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn modify_assumed_len() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_assumed_len");
+    let mut expected = pattern(12 * MIB, 38);
+    let len = expected.len() as i64;
+
+    TEST_RT.block_on(async {
+        put(&key, &expected).await;
+
+        let parts = vec![
+            Part::new(MIB as i64, vec![1; 100]).with_assumed_len(len),
+            Part::new(len, vec![2; 10]).with_assumed_len(len),
+        ];
+        edit_s3_file::modify_many_async(&key, parts).await.unwrap();
+    });
+
+    expected[MIB..MIB + 100].fill(1);
+    expected.extend_from_slice(&[2; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn preserve_part_layout_edit() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("preserve_part_layout_edit");
+    let mut expected = pattern(12 * MIB + 1000, 39);
+    let len = expected.len() as i64;
+    let session = Session::new(config_builder().preserve_part_layout(true).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        let mut upload = session.begin_multipart(&key).await.unwrap();
+        upload.upload_bytes(expected[..6 * MIB].to_vec()).await.unwrap();
+        upload.upload_bytes(expected[6 * MIB..12 * MIB].to_vec()).await.unwrap();
+        upload.upload_bytes(expected[12 * MIB..].to_vec()).await.unwrap();
+        upload.complete().await.unwrap();
+
+        let Some(before) = session.get_object_parts(&key).await.unwrap() else {
+            eprintln!("server doesn't report object parts, skipping");
+            return;
+        };
+
+        let stats = session.modify(&key, Part::new(7 * MIB as i64, vec![1; 100])).await.unwrap();
+        assert_eq!(stats.ranges, vec![
+            (RangeKind::Copied, 0, 6 * MIB as i64),
+            (RangeKind::Uploaded, 6 * MIB as i64, 12 * MIB as i64),
+            (RangeKind::Copied, 12 * MIB as i64, len),
+        ]);
+
+        let after = session.get_object_parts(&key).await.unwrap().unwrap();
+        assert_eq!(before.parts, after.parts);
+    });
+
+    expected[7 * MIB..7 * MIB + 100].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn fetch_add_concurrent_increments() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("fetch_add_concurrent_increments");
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        let mut data = pattern(100, 40);
+        data[16..24].copy_from_slice(&7u64.to_le_bytes());
+        put(&key, &data).await;
+
+        let tasks = (0..8)
+            .map(|_| {
+                let (session, key) = (session.clone(), key.clone());
+                tokio::spawn(async move {
+                    let mut seen = Vec::new();
+                    for _ in 0..4 {
+                        seen.push(session.fetch_add_u64_le(&key, 16, 1).await.unwrap());
+                    }
+                    seen
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = Vec::new();
+        for task in tasks {
+            seen.extend(task.await.unwrap());
+        }
+
+        // every increment saw a distinct value, none was lost
+        seen.sort();
+        assert_eq!(seen, (8..40).collect::<Vec<u64>>());
+
+        let out = session.read(&key).await.unwrap();
+        assert_eq!(u64::from_le_bytes(out[16..24].try_into().unwrap()), 39);
+    });
+}
+
+#[test]
+fn modify_batch_continues_past_failures() {
+    if !setup() {
+        return;
+    }
+    let keys = (0..4).map(|i| unique_key(&format!("modify_batch_{}", i))).collect::<Vec<_>>();
+    let missing = unique_key("modify_batch_missing");
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        for key in &keys {
+            put(key, &pattern(MIB, 42)).await;
+        }
+
+        let edits = keys.iter()
+            .chain([&missing])
+            .map(|key| (key.clone(), vec![Part::new(10, b"patched".to_vec())]))
+            .collect();
+        let results = session.modify_batch(edits, 2).await.unwrap();
+
+        assert_eq!(results.len(), 5);
+        assert!(results[&missing].is_err());
+
+        for key in &keys {
+            assert!(results[key].is_ok(), "{}: {:?}", key, results[key]);
+            assert_eq!(session.read_at(key, 10, 7).await.unwrap(), b"patched");
+        }
+
+        let dup = vec![(keys[0].clone(), Vec::new()), (keys[0].clone(), Vec::new())];
+        assert!(session.modify_batch(dup, 2).await.is_err());
+    });
+}
+
+#[test]
+fn modify_through_backend_trait() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_through_backend_trait");
+    let mut expected = pattern(12 * MIB, 43);
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    let stats = TEST_RT.block_on(async {
+        put(&key, &expected).await;
+
+        let parts = vec![Part::new(100, vec![1; 100]), Part::new(12 * MIB as i64 - 10, vec![2; 20])];
+        modify_with_backend(&session, &key, parts, 5 * MIB as i64).await.unwrap()
+    });
+
+    expected[100..200].fill(1);
+    expected[12 * MIB - 10..].fill(2);
+    expected.extend_from_slice(&[2; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    assert!(stats.bytes_copied() > 0);
+    assert_eq!(stats.ranges.last().unwrap().2, expected.len() as i64);
+}
+
+#[test]
+fn edit_lock_held_by_another() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("edit_lock_held_by_another");
+    let lock_key = format!("{}.lock", key);
+    let session = Session::new(config_builder().edit_lock(true).lock_wait_secs(1).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put(&key, &pattern(MIB, 44)).await;
+        put(&lock_key, b"other").await;
+
+        let err = session.modify(&key, Part::new(0, b"locked".to_vec())).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(EditError::Locked { .. })), "{:?}", err);
+        assert!(err.downcast_ref::<EditError>().unwrap().is_retryable());
+
+        session.delete_object(&lock_key).await.unwrap();
+        session.modify(&key, Part::new(0, b"unlocked".to_vec())).await.unwrap();
+
+        assert_eq!(session.read_at(&key, 0, 8).await.unwrap(), b"unlocked");
+        assert!(session.stat(&lock_key).await.unwrap().is_none(), "lock not released");
+    });
+}
+
+#[test]
+fn edit_transaction_rolls_back() {
+    if !setup() {
+        return;
+    }
+    let first = unique_key("edit_transaction_first");
+    let second = unique_key("edit_transaction_second");
+    let (first_data, second_data) = (pattern(MIB, 45), pattern(MIB, 46));
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put(&first, &first_data).await;
+        put(&second, &second_data).await;
+
+        // the second edit expects the wrong length, after the first was written
+        let mut tx = session.edit_transaction();
+        tx.modify_many(&first, vec![Part::new(0, b"edited".to_vec())])
+            .modify_many(&second, vec![Part::with_expected_object_len(0, b"edited".to_vec(), 1)]);
+        assert!(tx.commit().await.is_err());
+
+        assert!(session.read(&first).await.unwrap() == first_data);
+        assert!(session.read(&second).await.unwrap() == second_data);
+
+        let mut tx = session.edit_transaction();
+        tx.modify_many(&first, vec![Part::new(0, b"edited".to_vec())])
+            .modify_many(&second, vec![Part::new(0, b"edited".to_vec())]);
+        assert_eq!(tx.commit().await.unwrap().len(), 2);
+
+        assert_eq!(session.read_at(&first, 0, 6).await.unwrap(), b"edited");
+        assert_eq!(session.read_at(&second, 0, 6).await.unwrap(), b"edited");
+    });
+
+    // no backups are left behind
+    assert_eq!(list_keys(&first), vec![first.clone()]);
+    assert_eq!(list_keys(&second), vec![second.clone()]);
+}
+
+#[test]
+fn cancelled_edit_leaves_object() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("cancelled_edit_leaves_object");
+    let data = pattern(12 * MIB, 47);
+    let token = CancellationToken::new();
+    let session = Session::new(config_builder().build().unwrap()).unwrap().with_cancellation(token.clone());
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+        token.cancel();
+
+        let err = session.modify(&key, Part::new(MIB as i64, vec![1; 100])).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(EditError::Cancelled { .. })), "{:?}", err);
+        assert!(session.list_stale_uploads(Some(&key), Duration::ZERO).await.unwrap().is_empty());
+    });
+
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn awkward_keys_copied() {
+    if !setup() {
+        return;
+    }
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+    let data = pattern(12 * MIB, 49);
+
+    for suffix in [" space", "plus+sign", "percent%25", "ünïcödé/日本", "query?versionId=1", "hash#frag", "amp&eq=", "star*(paren)'"] {
+        let key = format!("{}/{}", unique_key("awkward_keys"), suffix);
+        let mut expected = data.clone();
+        expected[6 * MIB..6 * MIB + 100].copy_from_slice(&[1; 100]);
+
+        let stats = TEST_RT.block_on(async {
+            put(&key, &data).await;
+            session.modify(&key, Part::new(6 * MIB as i64, vec![1; 100])).await.unwrap()
+        });
+
+        assert!(stats.ranges.iter().any(|(kind, _, _)| *kind == RangeKind::Copied), "{}", key);
+        assert!(read_all(&key, expected.len()) == expected, "{}", key);
+    }
+}
+
+#[test]
+fn presigned_edit_covers_object() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("presigned_edit");
+    let mut data = pattern(12 * MIB, 48);
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+    let edits = [10..110, (12 * MIB) as i64..(12 * MIB + 100) as i64];
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+        let edit = session.presign_edit(&key, &edits, Duration::from_secs(600)).await.unwrap();
+        assert_eq!(edit.object_len, 12 * MIB as i64);
+        assert_eq!(edit.new_len, 12 * MIB as i64 + 100);
+
+        let mut offset = 0;
+        for (part, part_num) in edit.parts.iter().zip(1..) {
+            let (number, start, end) = match part {
+                PresignedPart::Copy { part_number, start, end } => (part_number, start, end),
+                PresignedPart::Upload { part_number, start, end, request, read } => {
+                    assert_eq!(read.is_some(), *start < edit.object_len);
+                    assert!(request.url.contains(&edit.upload_id));
+                    (part_number, start, end)
+                }
+            };
+            assert_eq!((*number, *start), (part_num, offset));
+            offset = *end;
+        }
+        assert_eq!(offset, edit.new_len);
+        assert!(matches!(edit.parts.first(), Some(PresignedPart::Upload { .. })));
+        assert!(edit.parts.iter().any(|part| matches!(part, PresignedPart::Copy { .. })));
+
+        let json = serde_json::to_value(&edit).unwrap();
+        assert_eq!(json["parts"][0]["kind"], "upload");
+
+        // the worker's side: the original bytes of each uploaded part with the edits over them
+        data.resize(12 * MIB + 100, 0);
+        for range in &edits {
+            data[range.start as usize..range.end as usize].fill(7);
+        }
+
+        let (bucket, client) = new_client();
+        let mut uploaded = Vec::new();
+        for part in &edit.parts {
+            if let PresignedPart::Upload { part_number, start, end, .. } = *part {
+                let out = client.upload_part()
+                    .bucket(&bucket)
+                    .key(&key)
+                    .upload_id(&edit.upload_id)
+                    .part_number(part_number)
+                    .body(ByteStream::from(data[start as usize..end as usize].to_vec()))
+                    .send()
+                    .await
+                    .unwrap();
+                uploaded.push((part_number, out.e_tag.unwrap()));
+            }
+        }
+
+        let stats = session.complete_presigned_edit(&edit, &uploaded).await.unwrap();
+        assert_eq!(stats.upload_id.as_deref(), Some(edit.upload_id.as_str()));
+        assert_eq!(stats.ranges.len(), edit.parts.len());
+    });
+
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn presigned_edit_aborts_missing_part() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("presigned_edit_missing");
+    let data = pattern(12 * MIB, 49);
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+        let edit = session.presign_edit(&key, &[10..110, 200..300], Duration::from_secs(600)).await.unwrap();
+
+        assert!(session.complete_presigned_edit(&edit, &[]).await.is_err());
+        assert!(session.abort_stale_uploads(Some(&key), Duration::ZERO).await.unwrap().is_empty());
+    });
+
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn small_edit_reads_min_part_size() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("small_edit_reads_min_part_size");
+    let mut expected = pattern(20 * MIB, 41);
+
+    let stats = TEST_RT.block_on(async {
+        put(&key, &expected).await;
+        edit_s3_file::modify_async(&key, Part::new(MIB as i64, vec![1; 1024])).await.unwrap()
+    });
+
+    assert_eq!(stats.bytes_read, 5 * MIB as u64 - 1024);
+
+    expected[MIB..MIB + 1024].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn replace_with_stream_checksum() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("replace_with_stream_checksum");
+    let data = pattern(3 * MIB, 42);
+    let session = Session::new(config_builder().stream_checksums(true).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        session.replace(&key, ByteStream::from(data.clone()), data.len() as i64).await.unwrap();
+    });
+
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn pattern_range_exact_repetitions() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("pattern_range_exact_repetitions");
+    let mut expected = pattern(12 * MIB, 43);
+
+    TEST_RT.block_on(async {
+        put(&key, &expected).await;
+        edit_s3_file::pattern_range_async(&key, MIB as i64, 6 * MIB as i64, b"abcd").await.unwrap();
+    });
+
+    for (i, b) in expected[MIB..7 * MIB].iter_mut().enumerate() {
+        *b = b"abcd"[i % 4];
+    }
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn pattern_range_partial_last_repetition() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("pattern_range_partial_last_repetition");
+    let mut expected = pattern(1000, 44);
+
+    TEST_RT.block_on(async {
+        put(&key, &expected).await;
+        // runs 100 bytes past the end, 200 bytes being 28 repetitions and 4 bytes
+        edit_s3_file::pattern_range_async(&key, 900, 200, b"0123456").await.unwrap();
+    });
+
+    expected.resize(1100, 0);
+    for (i, b) in expected[900..].iter_mut().enumerate() {
+        *b = b"0123456"[i % 7];
+    }
+    assert_eq!(&expected[1096..], b"0123");
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn modify_progress_stream() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_progress_stream");
+    let mut expected = pattern(12 * MIB, 45);
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        use futures_util::StreamExt;
+
+        put(&key, &expected).await;
+
+        let (events, edit) = session.modify_many_with_progress(&key, vec![Part::new(7 * MIB as i64, vec![1; 100])]);
+        let (stats, events) = futures_util::future::join(edit, events.collect::<Vec<_>>()).await;
+        stats.unwrap();
+
+        // the plan comes first, parts go up concurrently, in any order, and completing comes last
+        assert_eq!(events.len(), 4);
+        let ops = vec![PartOp::Copy(0..7 * MIB as i64), PartOp::Upload(7 * MIB as i64..12 * MIB as i64)];
+        assert_eq!(events[0], ProgressEvent::Planned { key: key.clone(), ops });
+        assert!(events.contains(&ProgressEvent::PartCopied { part_number: 1, len: 7 * MIB as i64 }));
+        assert!(events.contains(&ProgressEvent::PartUploaded { part_number: 2, len: 5 * MIB as i64 }));
+        assert_eq!(events[3], ProgressEvent::Completed { key: key.clone() });
+    });
+
+    expected[7 * MIB..7 * MIB + 100].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn stat_cache_invalidated_on_write() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("stat_cache_invalidated_on_write");
+    let session = Session::new(config_builder().stat_cache_ttl_secs(600).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        put(&key, &[1; 100]).await;
+        assert_eq!(session.stat(&key).await.unwrap().unwrap().len, 100);
+
+        // written behind the session's back, the cached stat is still served
+        put(&key, &[1; 200]).await;
+        assert_eq!(session.stat(&key).await.unwrap().unwrap().len, 100);
+
+        // a write through the session drops it
+        session.modify(&key, Part::new(200, vec![2; 10])).await.unwrap();
+        assert_eq!(session.stat(&key).await.unwrap().unwrap().len, 210);
+
+        session.delete_object(&key).await.unwrap();
+        assert!(session.stat(&key).await.unwrap().is_none());
+    });
+}
+
+#[test]
+fn multipart_merges_external_parts() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("multipart_merges_external_parts");
+    let external = [pattern(5 * MIB, 46), pattern(5 * MIB, 47)];
+    let session = Session::new(config_builder().build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        let mut upload = session.begin_multipart(&key).await.unwrap();
+        upload.start_parts_at(3).unwrap();
+        assert_eq!(upload.upload_bytes(vec![3; 100]).await.unwrap(), 3);
+
+        // parts 1 and 2 uploaded by another process into the same upload
+        for (part_number, data) in (1..).zip(&external) {
+            let etag = session.client()
+                .upload_part()
+                .bucket(session.bucket())
+                .key(&key)
+                .upload_id(upload.upload_id())
+                .part_number(part_number)
+                .body(ByteStream::from(data.clone()))
+                .send()
+                .await
+                .unwrap()
+                .e_tag
+                .unwrap();
+            upload.add_uploaded_part(part_number, etag).unwrap();
+        }
+
+        for part_number in [0, 1, 3, 10001] {
+            let err = upload.add_uploaded_part(part_number, "\"etag\"").unwrap_err();
+            assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::InvalidPartNumber { part_number: n }) if *n == part_number));
+        }
+        assert!(upload.start_parts_at(2).is_err());
+
+        upload.complete().await.unwrap();
+    });
+
+    let mut expected = external.concat();
+    expected.extend_from_slice(&[3; 100]);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn adaptive_part_size_edit() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("adaptive_part_size_edit");
+    let mut expected = pattern(31 * MIB, 48);
+    let config = config_builder()
+        .part_size(PartSize::Fixed(5 * MIB as i64))
+        .adaptive_part_size(true)
+        .build()
+        .unwrap();
+    let session = Session::new(config).unwrap();
+
+    let stats = TEST_RT.block_on(async {
+        put(&key, &expected).await;
+
+        let parts = vec![Part::new(10 * MIB as i64, vec![1; 7 * MIB]), Part::new(25 * MIB as i64, vec![2; 100])];
+        session.modify_many(&key, parts).await.unwrap()
+    });
+
+    // the 10MB in front copied in one part rather than two of the 5MB part size, the large
+    // edit uploaded as one 7MB part, and the small one padded to 5MB
+    let m = MIB as i64;
+    assert_eq!(stats.ranges, vec![
+        (RangeKind::Copied, 0, 10 * m),
+        (RangeKind::Uploaded, 10 * m, 17 * m),
+        (RangeKind::Copied, 17 * m, 25 * m),
+        (RangeKind::Uploaded, 25 * m, 30 * m),
+        (RangeKind::Copied, 30 * m, 31 * m),
+    ]);
+
+    expected[10 * MIB..17 * MIB].fill(1);
+    expected[25 * MIB..25 * MIB + 100].fill(2);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn failed_upload_aborted() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("failed_upload_aborted");
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+
+    TEST_RT.block_on(async {
+        // the first part is uploaded before the body turns out too long
+        session.replace(&key, ByteStream::from(pattern(12 * MIB, 49)), 11 * MIB as i64).await.unwrap_err();
+        assert!(session.list_stale_uploads(Some(&key), Duration::ZERO).await.unwrap().is_empty());
+
+        // resumable uploads are kept for the retry
+        let resumable = session.with_upload_token("failed_upload_aborted");
+        resumable.replace(&key, ByteStream::from(pattern(12 * MIB, 49)), 11 * MIB as i64).await.unwrap_err();
+
+        let uploads = session.list_stale_uploads(Some(&key), Duration::ZERO).await.unwrap();
+        assert_eq!(uploads.len(), 1);
+        session.abort_stale_uploads(Some(&key), Duration::ZERO).await.unwrap();
+    });
+}
+
+#[test]
+fn concurrent_copies_edit() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("concurrent_copies_edit");
+    let mut expected = pattern(30 * MIB, 50);
+    let config = config_builder()
+        .part_size(PartSize::Fixed(5 * MIB as i64))
+        .max_concurrency(4)
+        .build()
+        .unwrap();
+    let session = Session::new(config).unwrap();
+
+    let stats = TEST_RT.block_on(async {
+        put(&key, &expected).await;
+        session.modify(&key, Part::new(15 * MIB as i64, vec![1; 100])).await.unwrap()
+    });
+
+    // five copies sent at once around the one upload, assembled in part order
+    let m = MIB as i64;
+    assert_eq!(stats.ranges, vec![
+        (RangeKind::Copied, 0, 5 * m),
+        (RangeKind::Copied, 5 * m, 10 * m),
+        (RangeKind::Copied, 10 * m, 15 * m),
+        (RangeKind::Uploaded, 15 * m, 20 * m),
+        (RangeKind::Copied, 20 * m, 25 * m),
+        (RangeKind::Copied, 25 * m, 30 * m),
+    ]);
+
+    expected[15 * MIB..15 * MIB + 100].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn source_changed_mid_edit_conflicts() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("source_changed_mid_edit_conflicts");
+    let config = config_builder()
+        .part_size(PartSize::Fixed(5 * MIB as i64))
+        .max_concurrency(1)
+        .build()
+        .unwrap();
+
+    // another writer replaces the object once the first part is copied
+    let overwrite_key = key.clone();
+    let overwritten = std::sync::Once::new();
+    let session = Session::new(config).unwrap().with_progress(move |event| {
+        if let ProgressEvent::PartCopied { .. } = event {
+            let key = overwrite_key.clone();
+
+            overwritten.call_once(|| std::thread::spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+                let (bucket, client) = new_client();
+                rt.block_on(client.put_object().bucket(bucket).key(key).body(ByteStream::from(vec![2; 100])).send()).unwrap();
+            }).join().unwrap());
+        }
+    });
+
+    let err = TEST_RT.block_on(async {
+        put(&key, &pattern(20 * MIB, 53)).await;
+        session.modify(&key, Part::new(17 * MIB as i64, vec![1; 100])).await.unwrap_err()
+    });
+
+    assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::Conflict { .. })), "{:?}", err);
+    assert!(read_all(&key, 100) == vec![2; 100]);
+}
+
+// vetoes edits of objects over `max_len` bytes and uploads of other than `completed_len`
+struct SizePolicy {
+    max_len: i64,
+    completed_len: i64,
+}
+
+impl EditHooks for SizePolicy {
+    fn before_plan(&self, key: &str, obj: &HeadObjectOutput) -> anyhow::Result<()> {
+        anyhow::ensure!(obj.content_length().unwrap() <= self.max_len, "{} is too large to edit", key);
+        Ok(())
+    }
+
+    fn before_complete(&self, report: &EditReport) -> anyhow::Result<()> {
+        assert!(report.expected_etag.is_some());
+        anyhow::ensure!(report.len == Some(self.completed_len), "{} would be {:?} bytes", report.key, report.len);
+        Ok(())
+    }
+}
+
+#[test]
+fn hooks_veto_edits() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("hooks_veto_edits");
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+    let data = pattern(12 * MIB, 56);
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+
+        let vetoed = |err: anyhow::Error| matches!(err.downcast_ref::<EditError>(), Some(EditError::Vetoed { .. }));
+
+        let too_large = session.with_hooks(SizePolicy { max_len: MIB as i64, completed_len: 12 * MIB as i64 });
+        let err = too_large.modify(&key, Part::new(7 * MIB as i64, vec![1; 100])).await.unwrap_err();
+        assert!(vetoed(err));
+
+        let wrong_len = session.with_hooks(SizePolicy { max_len: 12 * MIB as i64, completed_len: MIB as i64 });
+        let err = wrong_len.modify(&key, Part::new(7 * MIB as i64, vec![1; 100])).await.unwrap_err();
+        assert!(vetoed(err));
+        assert!(session.list_stale_uploads(Some(&key), Duration::ZERO).await.unwrap().is_empty());
+
+        let allowed = session.with_hooks(SizePolicy { max_len: 12 * MIB as i64, completed_len: 12 * MIB as i64 });
+        allowed.modify(&key, Part::new(7 * MIB as i64, vec![1; 100])).await.unwrap();
+    });
+
+    let mut expected = data;
+    expected[7 * MIB..7 * MIB + 100].fill(1);
+    assert!(read_all(&key, 12 * MIB) == expected);
+}
+
+#[test]
+fn unreachable_endpoint_fails_over() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("unreachable_endpoint_fails_over");
+    let endpoint = config_builder().build().unwrap().endpoint;
+
+    // nothing listens on port 1, so every request goes on to the working endpoint
+    let config = config_builder()
+        .endpoint("http://127.0.0.1:1")
+        .failover_endpoints(vec![endpoint])
+        .part_size(PartSize::Fixed(5 * MIB as i64))
+        .build()
+        .unwrap();
+    let session = Session::new(config).unwrap();
+    let data = pattern(12 * MIB, 57);
+
+    TEST_RT.block_on(async {
+        put(&key, &data).await;
+        session.modify(&key, Part::new(7 * MIB as i64, vec![1; 100])).await.unwrap();
+        assert_eq!(session.read_at(&key, 7 * MIB as i64, 100).await.unwrap(), vec![1; 100]);
+    });
+
+    let mut expected = data;
+    expected[7 * MIB..7 * MIB + 100].fill(1);
+    assert!(read_all(&key, 12 * MIB) == expected);
+}
+
+#[test]
+fn plan_then_execute() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("plan_then_execute");
+    let session = Session::new(config_builder().part_size(PartSize::Fixed(5 * MIB as i64)).build().unwrap()).unwrap();
+    let expected = pattern(12 * MIB, 55);
+
+    TEST_RT.block_on(async {
+        put(&key, &pattern(12 * MIB, 54)).await;
+
+        let plan = session.plan_modify(&key, vec![Part::new(7 * MIB as i64, vec![1; 100])]).await.unwrap();
+        assert_eq!((plan.object_len(), plan.new_len()), (12 * MIB as i64, 12 * MIB as i64));
+        assert_eq!(plan.cost().copy_requests + plan.cost().upload_requests, plan.ops().len() as u64);
+
+        let stats = plan.execute().await.unwrap();
+        assert_eq!(stats.ranges.len(), 2);
+
+        // a plan made before the object was replaced isn't carried out
+        let plan = session.plan_modify(&key, vec![Part::new(0, vec![2; 10])]).await.unwrap();
+        put(&key, &expected).await;
+        let err = plan.execute().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::Conflict { .. })), "{:?}", err);
+    });
+
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn verified_edit() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("verified_edit");
+    let session = Session::new(config_builder().verify_edits(true).build().unwrap()).unwrap();
+    let mut expected = pattern(12 * MIB, 56);
+
+    TEST_RT.block_on(async {
+        put(&key, &expected).await;
+        session.modify_many(&key, vec![Part::new(0, vec![1; 10]), Part::new(12 * MIB as i64, vec![2; 10])]).await.unwrap();
+    });
+
+    expected[..10].fill(1);
+    expected.extend_from_slice(&[2; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn checksummed_parts_edit() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("checksummed_parts_edit");
+    let session = Session::new(config_builder().checksum_algorithm(PartChecksum::Sha256).build().unwrap()).unwrap();
+    let mut expected = pattern(12 * MIB, 57);
+
+    TEST_RT.block_on(async {
+        put(&key, &expected).await;
+        session.modify(&key, Part::new(7 * MIB as i64, vec![1; 100])).await.unwrap();
+    });
+
+    expected[7 * MIB..7 * MIB + 100].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
@@ -0,0 +1,64 @@
+//! `requester_pays` against a mock server keeping the headers of each request, checking
+//! `x-amz-request-payer` is sent and signed, and overridden by `Session::with_requester_pays`.
+
+mod common;
+
+use common::mock_s3::{head, MockS3};
+use edit_s3_file::{S3Config, Session};
+
+/// Mock S3 answering every request as a `HeadObject` of a 10 byte object.
+fn start() -> MockS3 {
+    MockS3::start(|_| head("ETag: \"etag\"\r\n", 10))
+}
+
+/// `x-amz-request-payer` of the last request `mock` got, and whether its signature covers it.
+fn last_payer(mock: &MockS3) -> (Option<String>, bool) {
+    let requests = mock.requests();
+    let request = requests.last().unwrap();
+
+    let signed = request.header("authorization")
+        .and_then(|auth| auth.split("SignedHeaders=").nth(1))
+        .is_some_and(|signed| signed.split([';', ',']).any(|name| name == "x-amz-request-payer"));
+    (request.header("x-amz-request-payer").map(String::from), signed)
+}
+
+fn session(endpoint: &str, requester_pays: bool) -> Session {
+    let config = S3Config::builder()
+        .endpoint(endpoint)
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .requester_pays(requester_pays)
+        .build()
+        .unwrap();
+    Session::new(config).unwrap()
+}
+
+#[test]
+fn request_payer_is_sent_and_signed() {
+    let mock = start();
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+
+    rt.block_on(session(&mock.endpoint, true).stat("key")).unwrap();
+    assert_eq!(last_payer(&mock), (Some("requester".to_string()), true));
+
+    rt.block_on(session(&mock.endpoint, false).stat("key")).unwrap();
+    assert_eq!(last_payer(&mock), (None, false));
+}
+
+#[test]
+fn with_requester_pays_overrides_setting() {
+    let mock = start();
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+
+    rt.block_on(session(&mock.endpoint, false).with_requester_pays(true).stat("key")).unwrap();
+    assert_eq!(last_payer(&mock), (Some("requester".to_string()), true));
+
+    let paying = session(&mock.endpoint, true);
+    rt.block_on(paying.with_requester_pays(false).stat("key")).unwrap();
+    assert_eq!(last_payer(&mock), (None, false));
+
+    // the session overridden is left as it was
+    rt.block_on(paying.stat("key")).unwrap();
+    assert_eq!(last_payer(&mock), (Some("requester".to_string()), true));
+}
@@ -0,0 +1,924 @@
+//! End-to-end tests against a real S3-compatible endpoint (MinIO, localstack, ...).
+//!
+//! Skipped unless `EDIT_S3_IT_ENDPOINT` is set, e.g. for a local MinIO:
+//!
+//! ```text
+//! docker run -p 9000:9000 minio/minio server /data
+//! mc mb local/edit-s3-file-it
+//! EDIT_S3_IT_ENDPOINT=http://127.0.0.1:9000 cargo test
+//! ```
+//!
+//! `EDIT_S3_IT_BUCKET` (default `edit-s3-file-it`, must exist), `EDIT_S3_IT_ACCESS_KEY` and
+//! `EDIT_S3_IT_SECRET_KEY` (default `minioadmin`) can be overridden.
+
+#![cfg(feature = "blocking")]
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::head_object::HeadObjectError;
+use aws_sdk_s3::operation::put_object::PutObjectError;
+use aws_sdk_s3::primitives::ByteStream;
+use edit_s3_file::{EditError, Part, PartSource, RangeKind};
+use sha2::{Digest, Sha256};
+
+use common::{pattern, put_direct, read_all, setup, unique_key, MIB};
+
+mod common;
+
+fn put(key: &str, data: &[u8]) {
+    edit_s3_file::replace(key, ByteStream::from(data.to_vec()), data.len() as i64).unwrap();
+}
+
+fn check_modify_many(name: &str, obj_len: usize, edits: &[(usize, usize)]) {
+    let key = unique_key(name);
+    let mut expected = pattern(obj_len, 0);
+    put(&key, &expected);
+
+    let parts = edits.iter()
+        .enumerate()
+        .map(|(i, &(index, len))| {
+            let edit = pattern(len, 0x5a ^ i as u8);
+            expected[index..index + len].copy_from_slice(&edit);
+            Part::new(index as i64, edit)
+        })
+        .collect();
+    let stats = edit_s3_file::modify_many(&key, parts).unwrap();
+
+    assert!(read_all(&key, obj_len) == expected, "{} content mismatch", name);
+
+    let mut offset = 0;
+    for &(_, start, end) in &stats.ranges {
+        assert_eq!(start, offset, "{} ranges not contiguous", name);
+        offset = end;
+    }
+    assert_eq!(offset, obj_len as i64, "{} ranges don't cover the object", name);
+
+    for &(index, len) in edits {
+        let (index, end) = (index as i64, (index + len) as i64);
+        assert!(
+            stats.ranges.iter().all(|&(kind, start, stop)| kind == RangeKind::Uploaded || stop <= index || start >= end),
+            "{} edit {}-{} was copied", name, index, end
+        );
+    }
+}
+
+fn check_modify(name: &str, obj_len: usize, index: usize, edit_len: usize) {
+    check_modify_many(name, obj_len, &[(index, edit_len)]);
+}
+
+#[test]
+fn modify_at_start() {
+    if !setup() {
+        return;
+    }
+    check_modify("modify_at_start", 12 * MIB, 0, 6 * MIB);
+}
+
+#[test]
+fn modify_at_end() {
+    if !setup() {
+        return;
+    }
+    check_modify("modify_at_end", 12 * MIB, 6 * MIB, 6 * MIB);
+}
+
+#[test]
+fn modify_whole_object() {
+    if !setup() {
+        return;
+    }
+    check_modify("modify_whole_object", 6 * MIB, 0, 6 * MIB);
+}
+
+#[test]
+fn modify_small_at_start() {
+    if !setup() {
+        return;
+    }
+    check_modify("modify_small_at_start", 12 * MIB, 0, 10);
+}
+
+#[test]
+fn modify_small_in_middle() {
+    if !setup() {
+        return;
+    }
+    check_modify("modify_small_in_middle", 12 * MIB, 6 * MIB, 1024);
+}
+
+#[test]
+fn modify_small_near_end() {
+    if !setup() {
+        return;
+    }
+    check_modify("modify_small_near_end", 12 * MIB, 12 * MIB - 10, 5);
+}
+
+#[test]
+fn modify_many_scattered() {
+    if !setup() {
+        return;
+    }
+    check_modify_many("modify_many_scattered", 30 * MIB, &[(20 * MIB, 100), (10, 1000), (8 * MIB, 3 * MIB)]);
+}
+
+#[test]
+fn apply_patch_edits() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("apply_patch");
+    let mut expected = pattern(6 * MIB, 23);
+    put(&key, &expected);
+
+    let edits = [(5 * MIB, pattern(100, 24)), (10, pattern(1000, 25)), (6 * MIB, pattern(10, 26))];
+    expected.resize(6 * MIB + 10, 0);
+    for (offset, data) in &edits {
+        expected[*offset..*offset + data.len()].copy_from_slice(data);
+    }
+
+    let patch = edit_s3_file::encode_patch(edits.iter().map(|(offset, data)| (*offset as i64, data.as_slice())));
+    edit_s3_file::apply_patch(&key, &patch).unwrap();
+    assert!(read_all(&key, expected.len()) == expected);
+
+    assert!(edit_s3_file::apply_patch(&key, &patch[..patch.len() - 1]).is_err());
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn modify_fields() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_fields");
+    let mut expected = pattern(1000, 22);
+    put(&key, &expected);
+
+    edit_s3_file::modify_many(&key, vec![Part::le(16, 0x0102_0304_0506_0708u64), Part::be(100, 1.5f32)]).unwrap();
+    expected[16..24].copy_from_slice(&0x0102_0304_0506_0708u64.to_le_bytes());
+    expected[100..104].copy_from_slice(&1.5f32.to_be_bytes());
+
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn modify_if_checksum_gates_edit() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_if_checksum");
+    let mut expected = pattern(1000, 25);
+    put(&key, &expected);
+    let current: [u8; 32] = Sha256::digest(&expected[100..110]).into();
+
+    let err = edit_s3_file::modify_if_checksum(&key, Part::new(101, vec![1; 10]), current).unwrap_err();
+    match err.downcast_ref::<EditError>() {
+        Some(EditError::PreconditionFailed { start: 101, end: 111, actual_sha256, .. }) => {
+            assert_eq!(actual_sha256[..], Sha256::digest(&expected[101..111])[..]);
+        }
+        _ => panic!("unexpected {:?}", err),
+    }
+    assert!(read_all(&key, expected.len()) == expected);
+
+    edit_s3_file::modify_if_checksum(&key, Part::new(100, vec![1; 10]), current).unwrap();
+    expected[100..110].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    // appending replaces nothing
+    edit_s3_file::modify_if_checksum(&key, Part::new(1000, vec![2; 10]), Sha256::digest([]).into()).unwrap();
+}
+
+#[test]
+fn modify_many_rejects_overlap() {
+    if !setup() {
+        return;
+    }
+    let parts = vec![Part::new(0, vec![1; 10]), Part::new(5, vec![2; 10])];
+    assert!(edit_s3_file::modify_many("it/never-created", parts).is_err());
+}
+
+#[test]
+fn modify_into_new_object() {
+    if !setup() {
+        return;
+    }
+    let src = unique_key("modify_into_src");
+    let dst = unique_key("modify_into_dst");
+    let data = pattern(12 * MIB, 7);
+    put(&src, &data);
+
+    edit_s3_file::modify_into(&src, &dst, Part::new(MIB as i64, vec![9; 100])).unwrap();
+
+    let mut expected = data.clone();
+    expected[MIB..MIB + 100].fill(9);
+    assert!(read_all(&dst, expected.len()) == expected);
+    assert!(read_all(&src, data.len()) == data);
+}
+
+#[test]
+fn modify_across_buckets() {
+    if !setup() {
+        return;
+    }
+    // the source bucket named explicitly and as an `s3://` URI; a second bucket isn't set up
+    let bucket = std::env::var("EDIT_S3_IT_BUCKET").unwrap_or_else(|_| "edit-s3-file-it".to_string());
+    let src = unique_key("modify_across_src");
+    let dst = unique_key("modify_across_dst");
+    let data = pattern(12 * MIB, 35);
+    put(&src, &data);
+
+    edit_s3_file::modify_many_across(&bucket, &src, &dst, vec![Part::new(MIB as i64, vec![9; 100])]).unwrap();
+    let mut expected = data.clone();
+    expected[MIB..MIB + 100].fill(9);
+    assert!(read_all(&dst, expected.len()) == expected);
+
+    let src_uri = format!("s3://{}/{}", bucket, src);
+    edit_s3_file::modify_into(&src_uri, &dst, Part::new(0, vec![8; 10])).unwrap();
+    expected = data.clone();
+    expected[..10].fill(8);
+    assert!(read_all(&dst, expected.len()) == expected);
+    assert!(read_all(&src, data.len()) == data);
+}
+
+#[test]
+fn modify_at_len_appends() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_at_len_appends");
+    let mut expected = pattern(6 * MIB, 9);
+    put(&key, &expected);
+
+    let tail = pattern(1000, 10);
+    let stats = edit_s3_file::modify(&key, Part::new(expected.len() as i64, tail.clone())).unwrap();
+    expected.extend_from_slice(&tail);
+
+    assert_eq!(stats.ranges.last().map(|r| r.2), Some(expected.len() as i64));
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn modify_past_len_rejected() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_past_len_rejected");
+    put(&key, &pattern(1000, 11));
+    assert!(edit_s3_file::modify(&key, Part::new(1001, vec![1; 10])).is_err());
+}
+
+#[test]
+fn modify_rejects_changed_len() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_rejects_changed_len");
+    let data = pattern(1000, 6);
+    put(&key, &data);
+
+    let part = Part::with_expected_object_len(10, vec![1; 10], 999);
+    assert!(edit_s3_file::modify(&key, part).is_err());
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn set_contents_shrinks() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("set_contents_shrinks");
+    put(&key, &pattern(100 * MIB, 13));
+
+    let data = pattern(MIB, 14);
+    let stats = edit_s3_file::set_contents(&key, data.clone()).unwrap();
+
+    assert!(stats.ranges.iter().all(|r| r.0 == RangeKind::Uploaded));
+    assert_eq!(edit_s3_file::stat(&key).unwrap().unwrap().len, MIB as i64);
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn read_whole_and_missing() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("read_whole_and_missing");
+    let data = pattern(MIB + 3, 16);
+    put(&key, &data);
+    assert!(edit_s3_file::read(&key).unwrap() == data);
+
+    let err = edit_s3_file::read(&unique_key("missing")).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::ObjectNotFound { .. })), "{:?}", err);
+}
+
+#[test]
+fn read_ranges_in_order() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("read_ranges_in_order");
+    let data = pattern(2 * MIB, 15);
+    put(&key, &data);
+
+    let ranges = [(MIB as i64, MIB as i64 + 10), (0, 100), (5, 5), (2 * MIB as i64 - 7, 2 * MIB as i64)];
+    let out = edit_s3_file::read_ranges(&key, &ranges).unwrap();
+
+    for (&(start, end), got) in ranges.iter().zip(&out) {
+        assert!(got[..] == data[start as usize..end as usize], "{}-{}", start, end);
+    }
+    assert!(edit_s3_file::read_ranges(&key, &[(0, 2 * MIB as i64 + 1)]).is_err());
+}
+
+#[test]
+fn read_at_then_modify() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("read_at_then_modify");
+    let mut data = pattern(MIB, 16);
+    put(&key, &data);
+
+    let mut header = edit_s3_file::read_at(&key, 100, 16).unwrap();
+    assert!(header[..] == data[100..116]);
+
+    header[0] ^= 0xff;
+    edit_s3_file::modify(&key, Part::new(100, header.clone())).unwrap();
+    data[100..116].copy_from_slice(&header);
+    assert!(edit_s3_file::read_at(&key, 0, MIB as i64).unwrap() == data);
+
+    let err = edit_s3_file::read_at(&key, MIB as i64 - 1, 2).unwrap_err();
+    assert!(matches!(err.downcast_ref(), Some(EditError::RangeOutOfBounds { .. })), "{}", err);
+}
+
+#[test]
+fn update_range_in_place() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("update_range_in_place");
+    let mut data = pattern(MIB, 17);
+    put(&key, &data);
+
+    edit_s3_file::update_range(&key, 4096, 64, |buf| buf.iter_mut().for_each(|b| *b = !*b)).unwrap();
+    data[4096..4160].iter_mut().for_each(|b| *b = !*b);
+    assert!(read_all(&key, MIB) == data);
+
+    // the tail past the end reads as zeros and is appended
+    edit_s3_file::update_range(&key, MIB as i64 - 4, 8, |buf| {
+        assert!(buf[4..] == [0; 4]);
+        buf[4..].copy_from_slice(b"tail");
+    }).unwrap();
+    data.extend_from_slice(b"tail");
+    assert!(read_all(&key, MIB + 4) == data);
+}
+
+#[test]
+fn replace_round_trip() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("replace_round_trip");
+    let data = pattern(3 * MIB + 17, 1);
+    put(&key, &data);
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn s3_uri_keys() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("s3_uri_keys");
+    let bucket = std::env::var("EDIT_S3_IT_BUCKET").unwrap_or_else(|_| "edit-s3-file-it".to_string());
+    let uri = format!("s3://{}/{}", bucket, key);
+    let mut expected = pattern(1000, 23);
+    put(&uri, &expected);
+
+    edit_s3_file::modify(&uri, Part::new(10, vec![1; 10])).unwrap();
+    expected[10..20].fill(1);
+
+    assert!(edit_s3_file::read(&uri).unwrap() == expected);
+    assert!(edit_s3_file::read(&key).unwrap() == expected);
+    assert!(edit_s3_file::stat(&format!("s3://{}/{}", bucket, unique_key("missing"))).unwrap().is_none());
+    assert!(edit_s3_file::read(&format!("s3://{}/", bucket)).is_err());
+}
+
+#[test]
+fn replace_rejects_short_body() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("replace_rejects_short_body");
+    let err = edit_s3_file::replace(&key, ByteStream::from(vec![0; 10]), 11).unwrap_err();
+    assert!(
+        matches!(err.downcast_ref::<EditError>(), Some(EditError::BodyLengthMismatch { declared: 11, read: 10 })),
+        "{:?}", err
+    );
+}
+
+#[test]
+fn replace_unsized_small() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("replace_unsized_small");
+    let data = pattern(MIB + 5, 19);
+    assert_eq!(edit_s3_file::replace_unsized(&key, ByteStream::from(data.clone())).unwrap(), data.len() as i64);
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn list_stale_uploads() {
+    if !setup() {
+        return;
+    }
+    edit_s3_file::list_stale_uploads(Some("it/"), Duration::from_secs(24 * 3600)).unwrap();
+}
+
+#[test]
+fn stat_existing_and_missing() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("stat_existing_and_missing");
+    put(&key, &pattern(1234, 3));
+
+    let stat = edit_s3_file::stat(&key).unwrap().unwrap();
+    assert_eq!(stat.len, 1234);
+    assert!(stat.etag.is_some());
+    assert!(edit_s3_file::stat(&unique_key("missing")).unwrap().is_none());
+}
+
+#[test]
+fn modify_reports_etag() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_reports_etag");
+    put(&key, &pattern(12 * MIB, 36));
+
+    let stats = edit_s3_file::modify(&key, Part::new(MIB as i64, vec![1; 100])).unwrap();
+    let stat = edit_s3_file::stat(&key).unwrap().unwrap();
+    assert!(stats.etag.is_some());
+    assert_eq!(stats.etag, stat.etag);
+}
+
+#[test]
+fn modify_stats_totals() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_stats_totals");
+    put(&key, &pattern(12 * MIB, 37));
+
+    let stats = edit_s3_file::modify(&key, Part::new(MIB as i64, vec![1; 100])).unwrap();
+    assert!(stats.upload_id.is_some());
+    assert_eq!(stats.bytes_uploaded() + stats.bytes_copied(), 12 * MIB as u64);
+    assert!(stats.bytes_copied() > 0);
+    // the edit's part is padded with original bytes
+    assert_eq!(stats.bytes_read, stats.bytes_uploaded() - 100);
+    // a local server has no reason to throttle
+    assert_eq!(stats.part_retries, 0);
+}
+
+#[test]
+fn get_object_parts_of_multipart_object() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("get_object_parts");
+    put(&key, &pattern(12 * MIB, 12));
+    edit_s3_file::modify(&key, Part::new(6 * MIB as i64, vec![1; 10])).unwrap();
+
+    // servers without the attribute report nothing instead of failing
+    if let Some(parts) = edit_s3_file::get_object_parts(&key).unwrap() {
+        assert!(parts.total_parts >= 1);
+        assert!(parts.parts.is_empty() || parts.parts.iter().map(|p| p.1).sum::<i64>() == 12 * MIB as i64);
+    }
+}
+
+#[test]
+fn update_metadata_keeps_bytes() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("update_metadata_keeps_bytes");
+    let data = pattern(4096, 4);
+    put(&key, &data);
+
+    let metadata = [("owner".to_string(), "it".to_string())].into_iter().collect();
+    edit_s3_file::update_metadata(&key, metadata, Some("application/x-plot".to_string())).unwrap();
+
+    let stat = edit_s3_file::stat(&key).unwrap().unwrap();
+    assert_eq!(stat.content_type.as_deref(), Some("application/x-plot"));
+    assert_eq!(stat.metadata.get("owner").map(String::as_str), Some("it"));
+    assert!(read_all(&key, data.len()) == data);
+}
+
+#[test]
+fn append_and_write_past_end() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("append_and_write_past_end");
+    let mut expected = pattern(1000, 30);
+    put(&key, &expected);
+
+    edit_s3_file::append(&key, vec![1; 10]).unwrap();
+    expected.extend_from_slice(&[1; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    // 90 zeros between the old end and the write
+    edit_s3_file::write_at(&key, 1100, vec![2; 10]).unwrap();
+    expected.extend_from_slice(&[0; 90]);
+    expected.extend_from_slice(&[2; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    edit_s3_file::write_at(&key, 5, vec![3; 10]).unwrap();
+    expected[5..15].fill(3);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn truncate_and_remove_range() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("truncate_and_remove_range");
+    let mut expected = pattern(12 * MIB, 31);
+    put(&key, &expected);
+
+    // across the boundary of the first two parts
+    edit_s3_file::remove_range(&key, (5 * MIB - 10) as i64, 20).unwrap();
+    expected.drain(5 * MIB - 10..5 * MIB + 10);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    edit_s3_file::truncate(&key, (11 * MIB) as i64).unwrap();
+    expected.truncate(11 * MIB);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    assert!(edit_s3_file::truncate(&key, (11 * MIB + 1) as i64).is_err());
+    let err = edit_s3_file::remove_range(&key, (11 * MIB - 5) as i64, 10).unwrap_err();
+    assert!(matches!(err.downcast_ref(), Some(EditError::RangeOutOfBounds { start, end, .. }) if *end - *start == 10), "{}", err);
+
+    edit_s3_file::remove_range(&key, 0, (11 * MIB) as i64).unwrap();
+    assert!(read_all(&key, 0).is_empty());
+}
+
+#[test]
+fn insert_shifts_the_rest() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("insert_shifts_the_rest");
+    let mut expected = pattern(12 * MIB, 32);
+    put(&key, &expected);
+
+    edit_s3_file::insert(&key, (6 * MIB) as i64, vec![1; 100]).unwrap();
+    expected.splice(6 * MIB..6 * MIB, [1; 100]);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    edit_s3_file::insert(&key, 0, vec![2; 10]).unwrap();
+    expected.splice(0..0, [2; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    let end = expected.len() as i64;
+    edit_s3_file::insert(&key, end, vec![3; 10]).unwrap();
+    expected.extend_from_slice(&[3; 10]);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    assert!(edit_s3_file::insert(&key, end + 11, vec![4]).is_err());
+}
+
+#[test]
+fn sync_from_file_uploads_changes() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("sync_from_file");
+    let original = pattern(12 * MIB, 35);
+    put(&key, &original);
+
+    let mut local = original.clone();
+    local[6 * MIB + 10..6 * MIB + 20].copy_from_slice(&[9; 10]);
+    local.extend_from_slice(&pattern(1000, 36));
+    let path = std::env::temp_dir().join(key.replace('/', "_"));
+    std::fs::write(&path, &local).unwrap();
+
+    let stats = edit_s3_file::sync_from_file(&key, &path).unwrap();
+    assert!(read_all(&key, local.len()) == local);
+    assert!(stats.bytes_uploaded() < 12 * MIB as u64, "{} bytes uploaded", stats.bytes_uploaded());
+
+    // already in sync
+    let stats = edit_s3_file::sync_from_file(&key, &path).unwrap();
+    assert!(stats.ranges.is_empty());
+
+    // shorter file
+    std::fs::write(&path, &original[..5 * MIB]).unwrap();
+    edit_s3_file::sync_from_file(&key, &path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(read_all(&key, 5 * MIB) == original[..5 * MIB]);
+}
+
+#[test]
+fn modify_from_file_and_reader() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_from_file_and_reader");
+    let mut expected = pattern(12 * MIB, 33);
+    put(&key, &expected);
+
+    // spanning two parts
+    let patch = pattern(6 * MIB, 34);
+    let path = std::env::temp_dir().join(key.replace('/', "_"));
+    std::fs::write(&path, &patch).unwrap();
+
+    edit_s3_file::modify_from(&key, (3 * MIB) as i64, PartSource::file(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    expected[3 * MIB..9 * MIB].copy_from_slice(&patch);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    // past the end
+    let source = PartSource::blocking_reader(std::io::Cursor::new(vec![1; 100]), 100);
+    edit_s3_file::modify_from(&key, (12 * MIB - 50) as i64, source).unwrap();
+    expected.truncate(12 * MIB - 50);
+    expected.extend_from_slice(&[1; 100]);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    // a reader shorter than it claims fails without writing
+    let source = PartSource::blocking_reader(std::io::Cursor::new(vec![2; 10]), 20);
+    assert!(edit_s3_file::modify_from(&key, 0, source).is_err());
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn modify_rejects_changed_total_len() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_rejects_changed_total_len");
+    let mut expected = pattern(1000, 28);
+    put(&key, &expected);
+
+    let err = edit_s3_file::modify(&key, Part::new(995, vec![1; 10]).with_expected_total_len(1000)).unwrap_err();
+    assert!(
+        matches!(err.downcast_ref::<EditError>(), Some(EditError::LengthMismatch { expected: 1000, actual: 1005 })),
+        "{:?}", err
+    );
+    assert!(read_all(&key, expected.len()) == expected);
+
+    edit_s3_file::modify(&key, Part::new(990, vec![1; 10]).with_expected_total_len(1000)).unwrap();
+    expected[990..].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn modify_rejects_wrong_prefix() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_rejects_wrong_prefix");
+    let mut expected = b"PNG\0".to_vec();
+    expected.extend_from_slice(&pattern(1000, 29));
+    put(&key, &expected);
+
+    let err = edit_s3_file::modify(&key, Part::new(100, vec![1; 10]).with_expected_prefix(*b"GIF8")).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::FingerprintMismatch { .. })), "{:?}", err);
+    assert!(read_all(&key, expected.len()) == expected);
+
+    edit_s3_file::modify(&key, Part::new(100, vec![1; 10]).with_expected_prefix(*b"PNG\0")).unwrap();
+    expected[100..110].fill(1);
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn delete_if_match_checks_etag() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("delete_if_match");
+    put(&key, &pattern(1000, 29));
+    let etag = edit_s3_file::stat(&key).unwrap().unwrap().etag.unwrap();
+
+    edit_s3_file::modify(&key, Part::new(0, vec![1; 10])).unwrap();
+    assert!(!edit_s3_file::delete_if_match(&key, &etag).unwrap());
+    assert!(edit_s3_file::stat(&key).unwrap().is_some());
+
+    let etag = edit_s3_file::stat(&key).unwrap().unwrap().etag.unwrap();
+    assert!(edit_s3_file::delete_if_match(&key, &etag).unwrap());
+    assert!(edit_s3_file::stat(&key).unwrap().is_none());
+}
+
+#[test]
+fn delete_object_idempotent() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("delete_object_idempotent");
+    put(&key, &pattern(100, 30));
+
+    edit_s3_file::delete_object(&key).unwrap();
+    assert!(edit_s3_file::stat(&key).unwrap().is_none());
+    edit_s3_file::delete_object(&key).unwrap();
+}
+
+#[test]
+fn list_objects_under_prefix() {
+    if !setup() {
+        return;
+    }
+    let prefix = format!("{}/", unique_key("list_objects"));
+    for i in 0..3 {
+        put(&format!("{}{}", prefix, i), &pattern(10 + i, 31));
+    }
+
+    let objects = edit_s3_file::list_objects(&prefix).unwrap();
+    let listed = objects.iter().map(|o| (o.key.clone(), o.size)).collect::<Vec<_>>();
+    let expected = (0..3).map(|i| (format!("{}{}", prefix, i), 10 + i as i64)).collect::<Vec<_>>();
+    assert_eq!(listed, expected);
+    assert!(objects.iter().all(|o| o.etag.is_some()));
+}
+
+#[test]
+fn modify_from_many_threads() {
+    if !setup() {
+        return;
+    }
+    let threads = (0..8u8)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let key = unique_key(&format!("modify_from_many_threads-{}", i));
+                let mut expected = pattern(1000, i);
+                put(&key, &expected);
+
+                edit_s3_file::modify(&key, Part::new(i as i64, vec![i; 10])).unwrap();
+                expected[i as usize..i as usize + 10].fill(i);
+                (key, expected)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        let (key, expected) = thread.join().unwrap();
+        assert!(read_all(&key, expected.len()) == expected);
+    }
+}
+
+#[test]
+fn failed_request_carries_request_id() {
+    if !setup() {
+        return;
+    }
+    let err = edit_s3_file::modify(&unique_key("missing"), Part::new(0, vec![1; 10])).unwrap_err();
+    match err.downcast_ref::<EditError>() {
+        Some(EditError::S3 { request_id, .. }) => assert!(request_id.is_some()),
+        _ => panic!("unexpected {:?}", err),
+    }
+}
+
+#[test]
+fn failed_request_carries_sdk_error() {
+    if !setup() {
+        return;
+    }
+    let err = edit_s3_file::modify(&unique_key("missing"), Part::new(0, vec![1; 10])).unwrap_err();
+
+    let edit_err = err.downcast_ref::<EditError>().unwrap();
+    assert!(matches!(edit_err, EditError::ObjectNotFound { .. }), "{:?}", err);
+    assert!(edit_err.sdk_error::<PutObjectError>().is_none());
+
+    // the length comes from a HEAD, no body is opened
+    let sdk_err = edit_s3_file::sdk_error::<HeadObjectError>(&err).unwrap();
+    assert!(sdk_err.as_service_error().is_some_and(|e| e.is_not_found()), "{:?}", err);
+    assert!(edit_s3_file::sdk_error::<GetObjectError>(&err).is_none());
+}
+
+#[test]
+fn splice_between_objects() {
+    if !setup() {
+        return;
+    }
+    let src = unique_key("splice_src");
+    let dst = unique_key("splice_dst");
+    let src_data = pattern(20 * MIB, 36);
+    let mut expected = pattern(12 * MIB, 37);
+    put(&src, &src_data);
+    put(&dst, &expected);
+
+    // large enough for server-side copies, then small and padded, then appended
+    edit_s3_file::splice(&src, 3 * MIB as i64, 6 * MIB as i64, &dst, MIB as i64).unwrap();
+    expected[MIB..7 * MIB].copy_from_slice(&src_data[3 * MIB..9 * MIB]);
+    edit_s3_file::splice(&src, 10, 100, &dst, 9 * MIB as i64).unwrap();
+    expected[9 * MIB..9 * MIB + 100].copy_from_slice(&src_data[10..110]);
+    edit_s3_file::splice(&src, 0, 1000, &dst, expected.len() as i64).unwrap();
+    expected.extend_from_slice(&src_data[..1000]);
+
+    assert!(read_all(&dst, expected.len()) == expected);
+    assert!(edit_s3_file::splice(&src, 20 * MIB as i64 - 10, 11, &dst, 0).is_err());
+}
+
+#[test]
+fn modify_cas_retries_conflict() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("modify_cas_retries_conflict");
+    put(&key, &[1; 100]);
+
+    let mut calls = 0;
+    let stats = edit_s3_file::modify_cas(&key, 0..8, |current| {
+        calls += 1;
+
+        // a concurrent writer changes the counter after the first read
+        if calls == 1 {
+            let key = key.clone();
+            std::thread::spawn(move || put_direct(&key, &[2; 100])).join().unwrap();
+        }
+        let counter = u64::from_le_bytes(current.try_into().unwrap());
+        Part::le(0, counter + 1)
+    }, 3).unwrap();
+
+    assert_eq!(calls, 2);
+    assert!(!stats.ranges.is_empty());
+
+    let mut expected = vec![2; 100];
+    expected[..8].copy_from_slice(&(u64::from_le_bytes([2; 8]) + 1).to_le_bytes());
+    assert!(read_all(&key, expected.len()) == expected);
+
+    let err = edit_s3_file::modify_cas(&key, 0..8, |_| {
+        let key = key.clone();
+        std::thread::spawn(move || put_direct(&key, &[3; 100])).join().unwrap();
+        Part::new(0, vec![4; 8])
+    }, 1).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EditError>(), Some(EditError::Conflict { .. })), "{:?}", err);
+}
+
+#[test]
+fn region_writer_applies_on_flush() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("region_writer_applies_on_flush");
+    let mut expected = pattern(6 * MIB, 21);
+    put(&key, &expected);
+
+    let mut writer = edit_s3_file::RegionWriter::new(&key, 1000, 20).unwrap();
+    write!(writer, "hello ").unwrap();
+    assert!(read_all(&key, expected.len()) == expected);
+
+    writer.flush().unwrap();
+    writer.write_all(b"world").unwrap();
+    assert!(writer.write_all(&[0; 10]).is_err());
+    drop(writer);
+
+    expected[1000..1011].copy_from_slice(b"hello world");
+    assert!(read_all(&key, expected.len()) == expected);
+}
+
+#[test]
+fn s3_file_reads_its_writes() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("s3_file_reads_its_writes");
+    let mut expected = pattern(6 * MIB, 22);
+    put(&key, &expected);
+
+    let mut file = edit_s3_file::S3File::open(&key).unwrap();
+    file.seek(SeekFrom::Start(1000)).unwrap();
+    file.write_all(b"hello").unwrap();
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(b"tail").unwrap();
+    assert!(read_all(&key, expected.len()) == expected);
+
+    expected[1000..1005].copy_from_slice(b"hello");
+    expected.extend_from_slice(b"tail");
+
+    // reads see the writes before they're flushed
+    let mut read = Vec::new();
+    file.rewind().unwrap();
+    file.read_to_end(&mut read).unwrap();
+    assert!(read == expected);
+
+    file.flush().unwrap();
+    assert!(read_all(&key, expected.len()) == expected);
+
+    file.seek(SeekFrom::Start(6 * MIB as u64 + 10)).unwrap();
+    assert!(file.write_all(b"hole").is_err());
+}
+
+#[test]
+fn would_use_multipart_follows_config() {
+    if !setup() {
+        return;
+    }
+    let key = unique_key("would_use_multipart_follows_config");
+    put(&key, &[1; 100]);
+
+    assert!(edit_s3_file::would_use_multipart(&key, &Part::new(10, vec![2; 10])).unwrap());
+    assert!(edit_s3_file::would_use_multipart(&key, &Part::new(200, vec![2; 10])).is_err());
+    assert!(edit_s3_file::would_use_multipart(&unique_key("missing"), &Part::new(0, vec![2])).is_err());
+}
@@ -0,0 +1,86 @@
+//! `sync_from_file` against a mock server whose object stored SHA-256 part checksums,
+//! checking only the parts they don't vouch for are downloaded and the changes uploaded.
+
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use common::mock_s3::{head, initiated, not_found, ok, zeros, MockS3, Request};
+use edit_s3_file::{S3Config, Session, MIN_PART_SIZE, SYNC_BLOCK_SIZE};
+use sha2::{Digest, Sha256};
+
+const PART: usize = MIN_PART_SIZE as usize;
+
+/// Answers of a mock S3 serving one object of two `PART` byte parts of zeros, uploaded with
+/// SHA-256 checksums.
+fn respond(request: &Request) -> Vec<u8> {
+    let copy = request.header("x-amz-copy-source").is_some();
+
+    match request.method.as_str() {
+        "HEAD" => head("ETag: \"etag-2\"\r\n", 2 * PART),
+        "GET" if request.target.contains("attributes") => {
+            let checksum = STANDARD.encode(Sha256::digest(vec![0; PART]));
+            let part = |number: usize| format!(
+                "<Part><PartNumber>{}</PartNumber><Size>{}</Size><ChecksumSHA256>{}</ChecksumSHA256></Part>",
+                number, PART, checksum,
+            );
+            ok("", &format!(
+                "<GetObjectAttributesResponse><ObjectParts><TotalPartsCount>2</TotalPartsCount>\
+                <IsTruncated>false</IsTruncated>{}{}</ObjectParts></GetObjectAttributesResponse>",
+                part(1), part(2),
+            ))
+        }
+        "GET" => {
+            let (start, end) = request.range().unwrap();
+            zeros("ETag: \"etag-2\"\r\n", start, end - start, 2 * PART)
+        }
+        "POST" if request.target.contains("uploads") => initiated(),
+        "PUT" if copy => ok("", "<CopyPartResult><ETag>\"copied\"</ETag></CopyPartResult>"),
+        "PUT" => ok("ETag: \"uploaded\"\r\n", ""),
+        "POST" => ok("", "<CompleteMultipartUploadResult><ETag>\"new\"</ETag></CompleteMultipartUploadResult>"),
+        _ => not_found(),
+    }
+}
+
+#[test]
+fn parts_with_matching_checksums_are_not_downloaded() {
+    let mock = MockS3::start(respond);
+
+    let path = std::env::temp_dir().join(format!("edit_s3_file_sync_{}", std::process::id()));
+    let mut file = vec![0; 2 * PART];
+    let changed = PART + 3 * SYNC_BLOCK_SIZE + 10;
+    file[changed] = 1;
+    std::fs::write(&path, &file).unwrap();
+
+    let config = S3Config::builder()
+        .endpoint(&mock.endpoint)
+        .bucket("bucket")
+        .credentials("ak", "sk")
+        .force_path_style(true)
+        .build()
+        .unwrap();
+    let session = Session::new(config).unwrap();
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let stats = rt.block_on(session.sync_from_file("key", &path)).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(stats.etag.as_deref(), Some("\"new\""));
+
+    // the first part's checksum matched, so only the second was read to find the change
+    let requests = mock.requests();
+    let reads = requests.iter()
+        .filter(|r| r.method == "GET" && !r.target.contains("attributes"))
+        .filter_map(Request::range)
+        .collect::<Vec<_>>();
+    assert!(!reads.is_empty());
+    for (start, end) in &reads {
+        assert!(*start >= PART, "{}-{}", start, end);
+    }
+
+    let uploaded = requests.iter()
+        .filter(|r| r.method == "PUT" && r.header("x-amz-copy-source").is_none())
+        .map(|r| &r.body)
+        .collect::<Vec<_>>();
+    assert!(uploaded.iter().map(|part| part.len()).sum::<usize>() < 2 * PART);
+    assert!(uploaded.iter().any(|part| part.contains(&1)));
+}
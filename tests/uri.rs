@@ -0,0 +1,28 @@
+use edit_s3_file::parse_s3_uri;
+
+#[test]
+fn plain_keys_pass_through() {
+    assert_eq!(parse_s3_uri("dir/plot.bin").unwrap(), None);
+    assert_eq!(parse_s3_uri("").unwrap(), None);
+}
+
+#[test]
+fn bucket_and_key() {
+    assert_eq!(parse_s3_uri("s3://data/dir/plot.bin").unwrap(), Some(("data", "dir/plot.bin")));
+    assert_eq!(parse_s3_uri("s3://data/a").unwrap(), Some(("data", "a")));
+}
+
+#[test]
+fn access_point_arn() {
+    let arn = "arn:aws:s3:us-west-2:123456789012:accesspoint/plots";
+    let uri = format!("s3://{}/dir/plot.bin", arn);
+    assert_eq!(parse_s3_uri(&uri).unwrap(), Some((arn, "dir/plot.bin")));
+    assert!(parse_s3_uri(&format!("s3://{}", arn)).is_err());
+}
+
+#[test]
+fn malformed() {
+    for uri in ["s3://", "s3://data", "s3://data/", "s3:///key"] {
+        assert!(parse_s3_uri(uri).is_err(), "{}", uri);
+    }
+}